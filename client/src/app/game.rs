@@ -217,6 +217,9 @@ impl Component for Game {
                     height: dim.height as u32,
                 }
             })
+            .resource(render::ui::node_preview::CommandCallback::new(
+                link.callback(Msg::Command),
+            ))
             .uses(crate::setup_ecs)
             .build(); // TODO setup depending on gamemode
 
@@ -259,6 +262,13 @@ impl Component for Game {
             Msg::MouseDown(event) => self.on_mouse_click(event.button(), true),
             Msg::MouseUp(event) => self.on_mouse_click(event.button(), false),
             Msg::Wheel(event) => self.on_wheel(event.delta_y()),
+            Msg::Command(command) => {
+                self.legion
+                    .publish(traffloat::command::CommandRequestEvent::new(
+                        command,
+                        traffloat::command::Origin::Player,
+                    ))
+            }
             Msg::TouchMove(event) => {
                 if let Some(touch) = event.target_touches().item(0) {
                     self.on_mouse_move(touch.client_x(), touch.client_y());
@@ -357,6 +367,8 @@ pub enum Msg {
     MouseUp(MouseEvent),
     /// Scrolls the wheel.
     Wheel(WheelEvent),
+    /// Issues a player command.
+    Command(traffloat::command::Command),
     /// Starts touching the screen.
     TouchDown(TouchEvent),
     /// Stops touching the screen.
@@ -1,7 +1,5 @@
 //! Renders nodes, edges and vehicles.
 
-use std::f64::consts::PI;
-
 use legion::world::SubWorld;
 use legion::{component, Entity};
 use web_sys::WebGlRenderingContext;
@@ -10,10 +8,9 @@ use super::{CursorType, RenderFlag};
 use crate::camera::Camera;
 use crate::input;
 use traffloat::graph;
-use traffloat::lerp;
 use traffloat::shape::{Shape, Texture};
 use traffloat::space::{Matrix, Position};
-use traffloat::sun::{LightStats, Sun, MONTH_COUNT};
+use traffloat::sun::{LightStats, Sun};
 
 pub mod mesh;
 
@@ -119,7 +116,6 @@ fn draw(
     scene.gl.enable(WebGlRenderingContext::CULL_FACE);
     scene.gl.enable(WebGlRenderingContext::BLEND);
 
-    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
     for (entity, &position, shape, light) in <(Entity, &Position, &Shape, &LightStats)>::query()
         .filter(component::<graph::NodeId>())
         .iter(world)
@@ -128,13 +124,7 @@ fn draw(
 
         let unit_to_real = shape.transform(position);
 
-        let base_month = sun.yaw() / PI / 2. * MONTH_COUNT as f64;
-        #[allow(clippy::indexing_slicing)]
-        let brightness = {
-            let prev = light.brightness()[base_month.floor() as usize % MONTH_COUNT];
-            let next = light.brightness()[base_month.ceil() as usize % MONTH_COUNT];
-            lerp(prev.0, next.0, base_month.fract())
-        };
+        let brightness = light.current(sun).0;
         let selected =
             hover_target.entity() == Some(*entity) || focus_target.entity() == Some(*entity);
 
@@ -1,11 +1,15 @@
 //! Renders node info preview.
 
+use derive_new::new;
 use legion::world::SubWorld;
 use legion::{Entity, EntityStore};
 use yew::prelude::*;
 
 use super::{Update, UpdaterRef};
 use crate::input;
+use traffloat::command;
+use traffloat::def::{reaction, GameDefinition};
+use traffloat::factory;
 use traffloat::graph;
 
 /// Displays basic info about a node at a corner of the screen.
@@ -14,6 +18,20 @@ pub struct Comp {
     link: ComponentLink<Self>,
 }
 
+impl Comp {
+    fn set_throttle(&self, index: usize, setting: factory::Setting) {
+        if let Some(control) = self.props.reactions.get(index) {
+            self.props
+                .command_callback
+                .emit(command::Command::ConfigureReaction {
+                    node: self.props.node,
+                    reaction: control.reaction,
+                    setting,
+                });
+        }
+    }
+}
+
 impl Component for Comp {
     type Message = Msg;
     type Properties = Props;
@@ -23,7 +41,22 @@ impl Component for Comp {
     }
 
     fn update(&mut self, msg: Msg) -> ShouldRender {
-        match msg {}
+        match msg {
+            Msg::SetRate(index, rate) => {
+                if let Some(control) = self.props.reactions.get(index) {
+                    let setting = factory::Setting::new(control.setting.enabled(), rate);
+                    self.set_throttle(index, setting);
+                }
+            }
+            Msg::Toggle(index) => {
+                if let Some(control) = self.props.reactions.get(index) {
+                    let setting =
+                        factory::Setting::new(!control.setting.enabled(), control.setting.rate());
+                    self.set_throttle(index, setting);
+                }
+            }
+        }
+        false
     }
 
     fn change(&mut self, props: Props) -> ShouldRender {
@@ -36,7 +69,7 @@ impl Component for Comp {
             position: absolute;
             bottom: 0;
             left: 0;
-            width: 5em; height: 5em;
+            min-width: 5em; min-height: 5em;
             color: black;
             pointer-events: auto;
             background-color: white;
@@ -45,41 +78,129 @@ impl Component for Comp {
         html! {
             <div style=style>
                 <p>{ &self.props.node_name }</p>
+                { for self.props.reactions.iter().enumerate().map(|(index, control)| html! {
+                    <div>
+                        <label>
+                            <input
+                                type="checkbox"
+                                checked=control.setting.enabled()
+                                onclick=self.link.callback(move |_| Msg::Toggle(index))
+                            />
+                            { &control.name }
+                        </label>
+                        <input
+                            type="range"
+                            min="0"
+                            max="100"
+                            value=format!("{}", (control.setting.rate() * 100.).round())
+                            disabled=!control.setting.enabled()
+                            onchange=self.link.batch_callback(move |data| match data {
+                                ChangeData::Value(value) => value
+                                    .parse::<f64>()
+                                    .ok()
+                                    .map(|percent| Msg::SetRate(index, percent / 100.)),
+                                _ => None,
+                            })
+                        />
+                        { format!("{:.0}%", control.setting.rate() * 100.) }
+                    </div>
+                }) }
             </div>
         }
     }
 }
 
 /// Events for [`Comp`].
-pub enum Msg {}
+pub enum Msg {
+    /// Sets the rate of the reaction at the index.
+    SetRate(usize, f64),
+    /// Toggles the reaction at the index.
+    Toggle(usize),
+}
 
 /// Yew properties for [`Comp`].
-#[derive(Clone, Properties)]
+#[derive(Clone, PartialEq, Properties)]
 pub struct Props {
     /// Entity ID of the node.
     pub entity: Entity,
+    /// Node ID of the node.
+    pub node: graph::NodeId,
     /// Name of the targeted node.
     pub node_name: String,
+    /// Configurable reactions of the node.
+    pub reactions: Vec<ReactionControl>,
+    /// Callback to issue player commands.
+    pub command_callback: Callback<command::Command>,
+}
+
+/// A configurable reaction displayed in the node preview.
+#[derive(Clone, PartialEq)]
+pub struct ReactionControl {
+    /// The reaction type.
+    pub reaction: reaction::TypeId,
+    /// The name of the reaction.
+    pub name: String,
+    /// The current throttle setting.
+    pub setting: factory::Setting,
+}
+
+/// Resource storing the callback to issue player commands.
+#[derive(new)]
+pub struct CommandCallback {
+    callback: Callback<command::Command>,
 }
 
 #[codegen::system]
+#[read_component(graph::NodeId)]
 #[read_component(graph::NodeName)]
+#[read_component(graph::NodeBuilding)]
 #[read_component(graph::EdgeId)]
+#[read_component(factory::Factory)]
+#[read_component(factory::Throttle)]
 #[thread_local]
 fn draw(
     #[resource] hover_target: &input::mouse::HoverTarget,
     #[resource] focus_target: &input::FocusTarget,
     world: &mut SubWorld,
     #[resource] updater_ref: &UpdaterRef,
+    #[resource] def: &GameDefinition,
+    #[resource] command_callback: &CommandCallback,
 ) {
     let info = if let Some(entity) = focus_target.entity().or_else(|| hover_target.entity()) {
         let entity_entry = world
             .entry_ref(entity)
             .expect("Target entity does not exist"); // TODO what if user is hovering over node while deleting it?
-        if let Ok(node_name) = entity_entry.get_component::<graph::NodeName>() {
+        if let (Ok(&node), Ok(node_name)) = (
+            entity_entry.get_component::<graph::NodeId>(),
+            entity_entry.get_component::<graph::NodeName>(),
+        ) {
+            let mut reactions = Vec::new();
+            if let (Ok(building), Ok(factory), Ok(throttle)) = (
+                entity_entry.get_component::<graph::NodeBuilding>(),
+                entity_entry.get_component::<factory::Factory>(),
+                entity_entry.get_component::<factory::Throttle>(),
+            ) {
+                let policies = def.get_building(building.building()).reactions();
+                for (index, &reaction) in factory.reactions().iter().enumerate() {
+                    let configurable = policies
+                        .iter()
+                        .any(|&(id, policy)| id == reaction && policy.configurable());
+                    if configurable {
+                        reactions.push(ReactionControl {
+                            reaction,
+                            name: def.get_reaction(reaction).name().to_string(),
+                            setting: throttle.get(index),
+                        });
+                    }
+                }
+            }
+
             Some(Props {
                 entity,
+                node,
                 node_name: node_name.name().to_string(),
+                reactions,
+                command_callback: command_callback.callback.clone(),
             })
         } else {
             None
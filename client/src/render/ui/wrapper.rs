@@ -34,7 +34,7 @@ impl Component for Wrapper {
             Update::SetNodePreview(props) => {
                 match (&self.node_preview_info, &props) {
                     (None, None) => return false,
-                    (Some(old), Some(new)) if old.entity == new.entity => return false,
+                    (Some(old), Some(new)) if old == new => return false,
                     _ => (),
                 }
                 self.node_preview_info = props;
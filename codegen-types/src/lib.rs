@@ -125,6 +125,15 @@ impl SetupEcs {
             .get_or_insert_with(shrev::EventChannel::<T>::new);
         self
     }
+    /// Publish an event to be read in the first tick
+    ///
+    /// Only systems added before this call receive the event.
+    pub fn event<T: shrev::Event>(mut self, event: T) -> Self {
+        self.resources
+            .get_mut_or_insert_with(shrev::EventChannel::<T>::new)
+            .single_write(event);
+        self
+    }
     /// Declare a subscribed event
     pub fn subscribe<T: shrev::Event>(&mut self) -> shrev::ReaderId<T> {
         let mut channel = self
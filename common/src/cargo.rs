@@ -1,24 +1,42 @@
 //! Management of cargo in buildings
 
+use std::collections::BTreeMap;
+
 use legion::world::SubWorld;
 use legion::Entity;
 use smallvec::SmallVec;
 
 use crate::clock::{SimulationEvent, SIMULATION_PERIOD};
-use crate::def;
+use crate::def::{self, GameDefinition};
+use crate::graph::NodeBuilding;
 use crate::time::Time;
 use crate::units::CargoSize;
 use crate::util;
 use crate::SetupEcs;
 
 /// A component attached to entities that house cargo.
-#[derive(getset::Getters)]
+#[derive(Default, getset::Getters)]
 pub struct StorageList {
     /// The list of cargos stored in the entity.
     #[getset(get = "pub")]
     storages: SmallVec<[(def::cargo::TypeId, Entity); 4]>,
 }
 
+impl StorageList {
+    /// Returns the storage entity of a cargo type.
+    pub fn find(&self, ty: def::cargo::TypeId) -> Option<Entity> {
+        self.storages
+            .iter()
+            .find(|&&(cargo, _)| cargo == ty)
+            .map(|&(_, storage)| storage)
+    }
+
+    /// Adds a storage entity of a cargo type.
+    pub fn push(&mut self, ty: def::cargo::TypeId, storage: Entity) {
+        self.storages.push((ty, storage));
+    }
+}
+
 /// A component attached to storage entities.
 #[derive(getset::CopyGetters)]
 pub struct Storage {
@@ -47,6 +65,155 @@ pub struct NextStorageSize {
     size: CargoSize,
 }
 
+/// Return type of [`create_storage_components`].
+pub type StorageComponents = (Storage, StorageSize, NextStorageSize);
+
+/// Creates the components for a storage entity containing `size` of cargo.
+pub fn create_storage_components(
+    cargo: def::cargo::TypeId,
+    capacity: CargoSize,
+    size: CargoSize,
+) -> StorageComponents {
+    (
+        Storage { cargo, capacity },
+        StorageSize { size },
+        NextStorageSize { size },
+    )
+}
+
+/// Returns the storage entity of a cargo type in a node entity.
+fn find_storage(world: &SubWorld, node: Entity, ty: def::cargo::TypeId) -> Option<Entity> {
+    use legion::EntityStore;
+
+    world
+        .entry_ref(node)
+        .ok()?
+        .get_component::<StorageList>()
+        .ok()?
+        .find(ty)
+}
+
+/// Removes up to `size` of a cargo type from a node entity.
+///
+/// Returns the amount actually removed.
+/// The calling system must read [`StorageList`] and write [`NextStorageSize`].
+pub fn take(
+    world: &mut SubWorld,
+    node: Entity,
+    ty: def::cargo::TypeId,
+    size: CargoSize,
+) -> CargoSize {
+    use legion::EntityStore;
+
+    let storage = match find_storage(world, node, ty) {
+        Some(storage) => storage,
+        None => return CargoSize::default(),
+    };
+    let mut entry = world
+        .entry_mut(storage)
+        .expect("Storage entity does not exist");
+    let next = entry
+        .get_component_mut::<NextStorageSize>()
+        .expect("Storage entity does not have NextStorageSize");
+    let taken = if next.size < size { next.size } else { size };
+    if taken > CargoSize::default() {
+        next.size -= taken;
+        taken
+    } else {
+        CargoSize::default()
+    }
+}
+
+/// Adds up to `size` to `stored` without exceeding `capacity`.
+///
+/// Returns the amount actually added.
+fn fill(stored: &mut CargoSize, capacity: CargoSize, size: CargoSize) -> CargoSize {
+    let space = capacity - *stored;
+    let given = if space < size { space } else { size };
+    if given > CargoSize::default() {
+        *stored += given;
+        given
+    } else {
+        CargoSize::default()
+    }
+}
+
+/// Cargo delivered to nodes by a system.
+///
+/// Cargo of types that a node does not store yet is kept here
+/// until [`finish`](Delivery::finish) creates the storage entities,
+/// so that multiple deliveries in the same tick share one new storage.
+#[derive(Debug, Default)]
+pub struct Delivery {
+    created: BTreeMap<(Entity, def::cargo::TypeId), (CargoSize, CargoSize)>,
+}
+
+impl Delivery {
+    /// Adds up to `size` of a cargo type to a node entity.
+    ///
+    /// Returns the amount actually added, limited by the storage capacity.
+    /// The calling system must read [`NodeBuilding`], [`StorageList`] and [`Storage`]
+    /// and write [`NextStorageSize`].
+    pub fn store(
+        &mut self,
+        world: &mut SubWorld,
+        def: &GameDefinition,
+        node: Entity,
+        ty: def::cargo::TypeId,
+        size: CargoSize,
+    ) -> CargoSize {
+        use legion::EntityStore;
+
+        if let Some(storage) = find_storage(world, node, ty) {
+            let mut entry = world
+                .entry_mut(storage)
+                .expect("Storage entity does not exist");
+            let capacity = entry
+                .get_component::<Storage>()
+                .expect("Storage entity does not have Storage")
+                .capacity;
+            let next = entry
+                .get_component_mut::<NextStorageSize>()
+                .expect("Storage entity does not have NextStorageSize");
+            return fill(&mut next.size, capacity, size);
+        }
+
+        let building = match world.entry_ref(node) {
+            Ok(entry) => entry
+                .get_component::<NodeBuilding>()
+                .expect("Node entity does not have NodeBuilding")
+                .building(),
+            Err(_) => return CargoSize::default(),
+        };
+        let capacity = def.get_building(building).storage().cargo();
+        let (_, stored) = self
+            .created
+            .entry((node, ty))
+            .or_insert((capacity, CargoSize::default()));
+        fill(stored, capacity, size)
+    }
+
+    /// Creates the storages for cargo types that the receiving nodes did not store.
+    ///
+    /// The calling system must write [`StorageList`].
+    pub fn finish(self, world: &mut SubWorld, cmd_buf: &mut legion::systems::CommandBuffer) {
+        use legion::EntityStore;
+
+        let mut new_lists = BTreeMap::<Entity, StorageList>::new();
+        for ((node, ty), (capacity, size)) in self.created {
+            let storage = cmd_buf.push(create_storage_components(ty, capacity, size));
+            let mut entry = world.entry_mut(node).expect("Node entity does not exist");
+            match entry.get_component_mut::<StorageList>() {
+                Ok(list) => list.push(ty, storage),
+                Err(_) => new_lists.entry(node).or_default().push(ty, storage),
+            }
+        }
+        for (node, list) in new_lists {
+            cmd_buf.add_component(node, list);
+        }
+    }
+}
+
 /// Interpolates the current graphical size of a storage.
 pub fn lerp(current: &StorageSize, next: NextStorageSize, time: Time) -> CargoSize {
     CargoSize(util::lerp(
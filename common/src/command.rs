@@ -0,0 +1,908 @@
+//! Commands issued by players.
+//!
+//! Every change requested by a player is published as a [`CommandRequestEvent`],
+//! both locally in singleplayer and on the server in multiplayer,
+//! so that all changes to the world can be replayed or undone in the same order.
+//! The [`Origin`] of each request is set by the receiving side, not by the sender,
+//! and host-only commands such as [`PlaceNode`](Command::PlaceNode)
+//! are rejected from players.
+//! Commands refer to nodes, inhabitants and vehicles by their stable IDs
+//! and can be serialized to be sent over the network or recorded.
+//!
+//! The [`validate`](validate_setup) system checks each command against the current world
+//! and the commands accepted earlier in the same tick.
+//! Valid commands are applied, or forwarded to the request event of the responsible module,
+//! and published as a [`CommandAcceptEvent`],
+//! which some modules subscribe to in order to apply their own settings.
+//! Invalid commands are published as a [`CommandRejectEvent`] with the reason.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use arcstr::ArcStr;
+use derive_new::new;
+use legion::world::SubWorld;
+use legion::Entity;
+use serde::{Deserialize, Serialize};
+
+use crate::construction::{self, ConstructRequestEvent, DemolishEvent, Site, Worker};
+use crate::def::{self, building, reaction, tech, vehicle, GameDefinition};
+use crate::factory::{self, Factory, Operator, Setting, ThrottleRejection, ThrottleRequestEvent};
+use crate::graph::{
+    self, EdgeAddEvent, EdgeId, EdgeSize, NodeAddEvent, NodeBuilding, NodeId, NodeName,
+};
+use crate::inhabitant::{Destination, InhabitantId};
+use crate::leisure::Visitor;
+use crate::logistics::{self, Carrier, TransferRejection, TransferRequestEvent};
+use crate::research::{Locked, Research};
+use crate::schedule::{
+    self, AssignRouteRequestEvent, DefineRouteRequestEvent, RemoveRouteRequestEvent,
+    RouteRejection, RouteStop, Routes, Scheduled,
+};
+use crate::security::{Police, Prisoner};
+use crate::space::{Matrix, Position};
+use crate::units::CargoSize;
+use crate::vehicle::{Vehicle, VehicleId};
+use crate::SetupEcs;
+
+/// A change to the world requested by a player.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Command {
+    /// Places a construction site of a building.
+    BuildNode {
+        /// The building type to construct
+        building: building::TypeId,
+        /// The position of the new node
+        position: Position,
+        /// The rotation of the new node
+        rotation: Matrix,
+    },
+    /// Places an operational node with the given ID without construction or research,
+    /// such as the initial nodes of a scenario.
+    ///
+    /// This command is only accepted from the [host](Origin::Host).
+    PlaceNode {
+        /// The ID of the new node
+        node: NodeId,
+        /// The building type of the node
+        building: building::TypeId,
+        /// The position of the new node
+        position: Position,
+        /// The rotation of the new node
+        rotation: Matrix,
+    },
+    /// Builds a corridor between two nodes.
+    BuildCorridor {
+        /// The "source" node
+        from: NodeId,
+        /// The "dest" node
+        to: NodeId,
+        /// The radius of the corridor
+        radius: f64,
+    },
+    /// Demolishes a node.
+    Demolish {
+        /// The node to demolish
+        node: NodeId,
+    },
+    /// Changes the rate of a reaction in a factory.
+    ConfigureReaction {
+        /// The factory node
+        node: NodeId,
+        /// The reaction to throttle
+        reaction: reaction::TypeId,
+        /// The new setting
+        setting: Setting,
+    },
+    /// Assigns an inhabitant to a job.
+    AssignJob {
+        /// The inhabitant
+        inhabitant: InhabitantId,
+        /// The new job, or `None` to make the inhabitant off-duty
+        job: Option<Job>,
+    },
+    /// Renames a node.
+    RenameNode {
+        /// The node to rename
+        node: NodeId,
+        /// The new name
+        name: ArcStr,
+    },
+    /// Defines a vehicle route, replacing any route with the same name.
+    DefineRoute {
+        /// The name of the route
+        name: String,
+        /// The stops of the route
+        stops: Vec<RouteStop>,
+    },
+    /// Removes a vehicle route.
+    RemoveRoute {
+        /// The name of the route
+        name: String,
+    },
+    /// Schedules a vehicle on a route.
+    SetRoute {
+        /// The vehicle
+        vehicle: VehicleId,
+        /// The name of the route
+        route: String,
+    },
+    /// Makes a vehicle a logistics carrier.
+    SetCarrier {
+        /// The vehicle
+        vehicle: VehicleId,
+    },
+    /// Moves cargo between two nodes once.
+    TransferCargo {
+        /// The node to load cargo from
+        from: NodeId,
+        /// The node to unload cargo to
+        to: NodeId,
+        /// The cargo type
+        cargo: def::cargo::TypeId,
+        /// The amount of cargo
+        size: CargoSize,
+    },
+    /// Sets the logistics priority of a node.
+    SetPriority {
+        /// The node to configure
+        node: NodeId,
+        /// The new priority
+        priority: i32,
+    },
+    /// Sets the amount of a cargo type that a node keeps in stock.
+    SetKeep {
+        /// The node to configure
+        node: NodeId,
+        /// The cargo type
+        cargo: def::cargo::TypeId,
+        /// The amount to keep in stock
+        size: CargoSize,
+    },
+}
+
+/// A job assigned to an inhabitant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Job {
+    /// Works on a construction site.
+    Construction(NodeId),
+    /// Operates a factory.
+    Operator(NodeId),
+    /// Serves as a police officer.
+    Police,
+}
+
+/// The issuer of a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Origin {
+    /// The host setting up the scenario.
+    Host,
+    /// A player, either local or connected to the server.
+    Player,
+}
+
+/// The reason for rejecting a command.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CommandRejection {
+    /// The command is only accepted from the host.
+    HostOnly,
+    /// The building type does not exist.
+    NoSuchBuilding(building::TypeId),
+    /// The reaction type does not exist.
+    NoSuchReaction(reaction::TypeId),
+    /// The cargo type does not exist.
+    NoSuchCargo(def::cargo::TypeId),
+    /// The node does not exist.
+    NoSuchNode(NodeId),
+    /// Another node already has the ID.
+    DuplicateNode(NodeId),
+    /// The inhabitant does not exist or is in prison.
+    NoSuchInhabitant(InhabitantId),
+    /// The vehicle does not exist.
+    NoSuchVehicle(VehicleId),
+    /// The building or vehicle type has not been unlocked by research.
+    Locked(Locked),
+    /// The node is a core, which cannot be demolished.
+    Core(NodeId),
+    /// The corridor connects a node to itself, duplicates another corridor
+    /// or does not have a positive finite radius.
+    InvalidCorridor,
+    /// The new name is empty.
+    EmptyName,
+    /// The node does not offer the job.
+    InvalidJob(Job),
+    /// The reaction cannot be configured.
+    Throttle(ThrottleRejection),
+    /// The route command is invalid.
+    Route(RouteRejection),
+    /// The cargo transfer is invalid.
+    Transfer(TransferRejection),
+}
+
+/// Requests to execute a command.
+#[derive(Debug, new, getset::Getters)]
+pub struct CommandRequestEvent {
+    /// The requested command
+    #[getset(get = "pub")]
+    command: Command,
+    /// The issuer of the command
+    #[getset(get = "pub")]
+    origin: Origin,
+}
+
+/// Indicates that a command has been accepted.
+#[derive(Debug, new, getset::Getters)]
+pub struct CommandAcceptEvent {
+    /// The accepted command
+    #[getset(get = "pub")]
+    command: Command,
+}
+
+/// Indicates that a command has been rejected.
+#[derive(Debug, new, getset::Getters)]
+pub struct CommandRejectEvent {
+    /// The rejected command
+    #[getset(get = "pub")]
+    command: Command,
+    /// The reason for rejection
+    #[getset(get = "pub")]
+    reason: CommandRejection,
+}
+
+/// The nodes, corridors and routes that commands are checked against.
+///
+/// It includes the changes of commands accepted earlier in the same tick,
+/// which are not visible in the world until the command buffer is flushed.
+#[derive(Debug, Default)]
+struct Snapshot {
+    /// The entities of the nodes
+    nodes: BTreeMap<NodeId, Entity>,
+    /// The endpoints of the corridors
+    corridors: BTreeSet<(NodeId, NodeId)>,
+    /// The names of the defined routes
+    routes: BTreeSet<String>,
+    /// The nodes of core buildings
+    cores: BTreeSet<NodeId>,
+}
+
+impl Snapshot {
+    /// Returns the entity of a node.
+    fn node(&self, node: NodeId) -> Result<Entity, CommandRejection> {
+        self.nodes
+            .get(&node)
+            .copied()
+            .ok_or(CommandRejection::NoSuchNode(node))
+    }
+
+    /// Checks whether a corridor connects two nodes in either direction.
+    fn has_corridor(&self, from: NodeId, to: NodeId) -> bool {
+        self.corridors.contains(&(from, to)) || self.corridors.contains(&(to, from))
+    }
+}
+
+/// Returns the entity of an inhabitant who is not in prison.
+fn find_inhabitant(world: &SubWorld, inhabitant: InhabitantId) -> Result<Entity, CommandRejection> {
+    use legion::IntoQuery;
+
+    <(Entity, &InhabitantId)>::query()
+        .filter(!legion::component::<Prisoner>())
+        .iter(world)
+        .find(|&(_, &id)| id == inhabitant)
+        .map(|(&entity, _)| entity)
+        .ok_or(CommandRejection::NoSuchInhabitant(inhabitant))
+}
+
+/// Returns the entity and the type of a vehicle.
+fn find_vehicle(
+    world: &SubWorld,
+    vehicle: VehicleId,
+) -> Result<(Entity, vehicle::TypeId), CommandRejection> {
+    use legion::IntoQuery;
+
+    <(Entity, &VehicleId, &Vehicle)>::query()
+        .iter(world)
+        .find(|&(_, &id, _)| id == vehicle)
+        .map(|(&entity, _, state)| (entity, state.ty()))
+        .ok_or(CommandRejection::NoSuchVehicle(vehicle))
+}
+
+/// Checks whether an entity has a component.
+fn has<T: legion::storage::Component>(world: &SubWorld, entity: Entity) -> bool {
+    use legion::EntityStore;
+
+    matches!(world.entry_ref(entity), Ok(entry) if entry.get_component::<T>().is_ok())
+}
+
+/// Checks that the definition IDs in a command exist,
+/// so that commands from the network cannot index definitions out of bounds.
+fn check_ids(def: &GameDefinition, command: &Command) -> Result<(), CommandRejection> {
+    let check_cargo = |cargo: def::cargo::TypeId| {
+        if cargo.0 < def.cargo().len() {
+            Ok(())
+        } else {
+            Err(CommandRejection::NoSuchCargo(cargo))
+        }
+    };
+
+    match *command {
+        Command::BuildNode { building, .. } | Command::PlaceNode { building, .. } => {
+            if building.0 >= def.building().len() {
+                return Err(CommandRejection::NoSuchBuilding(building));
+            }
+            Ok(())
+        }
+        Command::ConfigureReaction { reaction, .. } => {
+            if reaction.0 >= def.reaction().len() {
+                return Err(CommandRejection::NoSuchReaction(reaction));
+            }
+            Ok(())
+        }
+        Command::SetKeep { cargo, .. } | Command::TransferCargo { cargo, .. } => check_cargo(cargo),
+        Command::DefineRoute { ref stops, .. } => stops
+            .iter()
+            .flat_map(|stop| stop.rules().keys())
+            .try_for_each(|&cargo| check_cargo(cargo)),
+        _ => Ok(()),
+    }
+}
+
+/// Checks the parts of a command that only depend on the snapshot.
+fn check_snapshot(
+    def: &GameDefinition,
+    research: &Research,
+    snapshot: &Snapshot,
+    origin: Origin,
+    command: &Command,
+) -> Result<(), CommandRejection> {
+    check_ids(def, command)?;
+
+    match *command {
+        Command::BuildNode { building, .. } => research
+            .require(def, tech::Unlock::Building(building))
+            .map_err(CommandRejection::Locked),
+        Command::PlaceNode { node, .. } => {
+            if origin != Origin::Host {
+                return Err(CommandRejection::HostOnly);
+            }
+            if snapshot.nodes.contains_key(&node) {
+                return Err(CommandRejection::DuplicateNode(node));
+            }
+            Ok(())
+        }
+        Command::BuildCorridor { from, to, radius } => {
+            snapshot.node(from)?;
+            snapshot.node(to)?;
+            #[allow(clippy::neg_cmp_op_on_partial_ord)] // also rejects NaN
+            let invalid_radius = !(radius > 0.) || !radius.is_finite();
+            if from == to || snapshot.has_corridor(from, to) || invalid_radius {
+                return Err(CommandRejection::InvalidCorridor);
+            }
+            Ok(())
+        }
+        Command::Demolish { node } => {
+            snapshot.node(node)?;
+            if snapshot.cores.contains(&node) {
+                return Err(CommandRejection::Core(node));
+            }
+            Ok(())
+        }
+        Command::SetPriority { node, .. } | Command::SetKeep { node, .. } => {
+            snapshot.node(node).map(|_| ())
+        }
+        Command::RenameNode { node, ref name } => {
+            snapshot.node(node)?;
+            if name.trim().is_empty() {
+                return Err(CommandRejection::EmptyName);
+            }
+            Ok(())
+        }
+        Command::RemoveRoute { ref name }
+        | Command::SetRoute {
+            route: ref name, ..
+        } => {
+            if !snapshot.routes.contains(name) {
+                return Err(CommandRejection::Route(RouteRejection::UnknownRoute(
+                    name.clone(),
+                )));
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks whether a command can be applied to the current world.
+fn check(
+    world: &SubWorld,
+    def: &GameDefinition,
+    research: &Research,
+    snapshot: &Snapshot,
+    origin: Origin,
+    command: &Command,
+) -> Result<(), CommandRejection> {
+    use legion::IntoQuery;
+
+    check_snapshot(def, research, snapshot, origin, command)?;
+
+    match *command {
+        Command::ConfigureReaction {
+            node,
+            reaction,
+            setting,
+        } => {
+            let request = ThrottleRequestEvent::new(node, reaction, setting);
+            let mut query = <(&NodeId, &NodeBuilding, &Factory, Option<&Site>)>::query();
+            let (_, building, factory, site) = query
+                .iter(world)
+                .find(|&(&id, _, _, _)| id == node)
+                .ok_or(CommandRejection::Throttle(ThrottleRejection::NoSuchFactory))?;
+            factory::check_throttle(def, research, &request, building, factory, site)
+                .map(|_| ())
+                .map_err(CommandRejection::Throttle)
+        }
+        Command::AssignJob { inhabitant, job } => {
+            find_inhabitant(world, inhabitant)?;
+            let valid = match job {
+                Some(Job::Construction(node)) => has::<Site>(world, snapshot.node(node)?),
+                Some(Job::Operator(node)) => {
+                    let entity = snapshot.node(node)?;
+                    !has::<Site>(world, entity)
+                        && <(Entity, &Factory)>::query()
+                            .iter(world)
+                            .any(|(&other, factory)| {
+                                other == entity && !factory.reactions().is_empty()
+                            })
+                }
+                Some(Job::Police) | None => true,
+            };
+            match job {
+                Some(job) if !valid => Err(CommandRejection::InvalidJob(job)),
+                _ => Ok(()),
+            }
+        }
+        Command::DefineRoute {
+            ref name,
+            ref stops,
+        } => schedule::check_stops(world, def, name, stops).map_err(CommandRejection::Route),
+        Command::SetRoute { vehicle, .. } | Command::SetCarrier { vehicle } => {
+            let (_, ty) = find_vehicle(world, vehicle)?;
+            research
+                .require(def, tech::Unlock::Vehicle(ty))
+                .map_err(CommandRejection::Locked)
+        }
+        Command::TransferCargo {
+            from,
+            to,
+            cargo,
+            size,
+        } => logistics::check_transfer(world, &TransferRequestEvent::new(from, to, cargo, size))
+            .map_err(CommandRejection::Transfer),
+        _ => Ok(()),
+    }
+}
+
+#[codegen::system]
+#[read_component(NodeId)]
+#[read_component(NodeBuilding)]
+#[read_component(Site)]
+#[read_component(Factory)]
+#[read_component(EdgeId)]
+#[read_component(InhabitantId)]
+#[read_component(Prisoner)]
+#[read_component(VehicleId)]
+#[read_component(Carrier)]
+#[write_component(Vehicle)]
+fn validate(
+    world: &mut SubWorld,
+    cmd_buf: &mut legion::systems::CommandBuffer,
+    #[resource] def: &GameDefinition,
+    #[resource] research: &Research,
+    #[resource] routes: &Routes,
+    #[subscriber] requests: impl Iterator<Item = CommandRequestEvent>,
+    #[publisher] accept_pub: impl FnMut(CommandAcceptEvent),
+    #[publisher] reject_pub: impl FnMut(CommandRejectEvent),
+    #[publisher] construct_pub: impl FnMut(ConstructRequestEvent),
+    #[publisher] demolish_pub: impl FnMut(DemolishEvent),
+    #[publisher] throttle_pub: impl FnMut(ThrottleRequestEvent),
+    #[publisher] node_add_pub: impl FnMut(NodeAddEvent),
+    #[publisher] edge_add_pub: impl FnMut(EdgeAddEvent),
+    #[publisher] define_route_pub: impl FnMut(DefineRouteRequestEvent),
+    #[publisher] remove_route_pub: impl FnMut(RemoveRouteRequestEvent),
+    #[publisher] assign_route_pub: impl FnMut(AssignRouteRequestEvent),
+    #[publisher] transfer_pub: impl FnMut(TransferRequestEvent),
+) {
+    use legion::{EntityStore, IntoQuery};
+
+    let mut requests = requests.peekable();
+    if requests.peek().is_none() {
+        return;
+    }
+
+    let mut snapshot = Snapshot {
+        nodes: <(Entity, &NodeId)>::query()
+            .iter(world)
+            .map(|(&entity, &node)| (node, entity))
+            .collect(),
+        corridors: <&EdgeId>::query()
+            .iter(world)
+            .map(|edge| (edge.from(), edge.to()))
+            .collect(),
+        routes: routes.iter().map(|(name, _)| name.to_string()).collect(),
+        cores: <(&NodeId, &NodeBuilding)>::query()
+            .iter(world)
+            .filter(|(_, building)| construction::is_core(def.get_building(building.building())))
+            .map(|(&node, _)| node)
+            .collect(),
+    };
+
+    for request in requests {
+        let checked = check(
+            world,
+            def,
+            research,
+            &snapshot,
+            request.origin,
+            &request.command,
+        );
+        if let Err(reason) = checked {
+            reject_pub(CommandRejectEvent {
+                command: request.command.clone(),
+                reason,
+            });
+            continue;
+        }
+
+        match request.command.clone() {
+            Command::BuildNode {
+                building,
+                position,
+                rotation,
+            } => construct_pub(ConstructRequestEvent::new(building, position, rotation)),
+            Command::PlaceNode {
+                node,
+                building,
+                position,
+                rotation,
+            } => {
+                let mut components =
+                    graph::create_node_components(def, building, position, rotation);
+                components.0 = node;
+                let entity = cmd_buf.push(components);
+                snapshot.nodes.insert(node, entity);
+                if construction::is_core(def.get_building(building)) {
+                    snapshot.cores.insert(node);
+                }
+                node_add_pub(NodeAddEvent::new(node));
+            }
+            Command::BuildCorridor { from, to, radius } => {
+                let mut edge = EdgeId::new(from, to);
+                edge.set_from_entity(snapshot.node(from).ok());
+                edge.set_to_entity(snapshot.node(to).ok());
+                let size = EdgeSize::new(radius);
+                cmd_buf.push((edge, size));
+                snapshot.corridors.insert((from, to));
+                edge_add_pub(EdgeAddEvent::new(edge, size));
+            }
+            Command::Demolish { node } => demolish_pub(DemolishEvent::new(node)),
+            Command::ConfigureReaction {
+                node,
+                reaction,
+                setting,
+            } => throttle_pub(ThrottleRequestEvent::new(node, reaction, setting)),
+            Command::AssignJob { inhabitant, job } => {
+                let inhabitant = find_inhabitant(world, inhabitant).expect("Checked");
+                cmd_buf.remove_component::<Worker>(inhabitant);
+                cmd_buf.remove_component::<Operator>(inhabitant);
+                cmd_buf.remove_component::<Police>(inhabitant);
+                cmd_buf.remove_component::<Visitor>(inhabitant);
+                cmd_buf.remove_component::<Destination>(inhabitant);
+                match job {
+                    Some(Job::Construction(node)) => {
+                        let site = snapshot.node(node).expect("Checked");
+                        cmd_buf.add_component(inhabitant, Worker::new(site));
+                        cmd_buf.add_component(inhabitant, Destination::new(node));
+                    }
+                    Some(Job::Operator(node)) => {
+                        let factory = snapshot.node(node).expect("Checked");
+                        cmd_buf.add_component(inhabitant, Operator::new(factory));
+                        cmd_buf.add_component(inhabitant, Destination::new(node));
+                    }
+                    Some(Job::Police) => cmd_buf.add_component(inhabitant, Police::default()),
+                    None => {}
+                }
+            }
+            Command::RenameNode { node, name } => {
+                // the node may have been placed earlier in the same tick
+                let entity = snapshot.node(node).expect("Checked");
+                cmd_buf.add_component(entity, NodeName::new(name));
+            }
+            Command::DefineRoute { name, stops } => {
+                snapshot.routes.insert(name.clone());
+                define_route_pub(DefineRouteRequestEvent::new(name, stops));
+            }
+            Command::RemoveRoute { name } => {
+                snapshot.routes.remove(&name);
+                remove_route_pub(RemoveRouteRequestEvent::new(name));
+            }
+            Command::SetRoute { vehicle, route } => {
+                assign_route_pub(AssignRouteRequestEvent::new(vehicle, route));
+            }
+            Command::SetCarrier { vehicle } => {
+                let (vehicle, _) = find_vehicle(world, vehicle).expect("Checked");
+                if !has::<Carrier>(world, vehicle) {
+                    let mut entry = world.entry_mut(vehicle).expect("Checked");
+                    let state = entry.get_component_mut::<Vehicle>().expect("Checked");
+                    let node = state.node();
+                    state.set_stops(vec![node]);
+                    cmd_buf.remove_component::<Scheduled>(vehicle);
+                    cmd_buf.add_component(vehicle, Carrier::default());
+                }
+            }
+            Command::TransferCargo {
+                from,
+                to,
+                cargo,
+                size,
+            } => transfer_pub(TransferRequestEvent::new(from, to, cargo, size)),
+            // applied by the logistics module
+            Command::SetPriority { .. } | Command::SetKeep { .. } => {}
+        }
+
+        accept_pub(CommandAcceptEvent {
+            command: request.command.clone(),
+        });
+    }
+}
+
+/// Initializes ECS
+pub fn setup_ecs(setup: SetupEcs) -> SetupEcs {
+    setup.uses(validate_setup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn snapshot(nodes: &[NodeId]) -> Snapshot {
+        let mut world = legion::World::default();
+        Snapshot {
+            nodes: nodes
+                .iter()
+                .map(|&node| (node, world.push((node,))))
+                .collect(),
+            ..Snapshot::default()
+        }
+    }
+
+    fn corridor(from: NodeId, to: NodeId, radius: f64) -> Command {
+        Command::BuildCorridor { from, to, radius }
+    }
+
+    #[test]
+    fn corridors_within_tick() {
+        let mut def = GameDefinition::default();
+        let building = testing::building(
+            &mut def,
+            Vec::new(),
+            testing::storage(0., 0., 0.),
+            Vec::new(),
+        );
+        let research = Research::default();
+        let nodes = [NodeId::new(1), NodeId::new(2), NodeId::new(3)];
+        let mut snapshot = snapshot(&nodes[..2]);
+        let check = |snapshot: &Snapshot, command| {
+            check_snapshot(&def, &research, snapshot, Origin::Host, &command)
+        };
+
+        assert!(check(&snapshot, corridor(nodes[0], nodes[1], 0.1)).is_ok());
+        assert!(matches!(
+            check(&snapshot, corridor(nodes[0], nodes[0], 0.1)),
+            Err(CommandRejection::InvalidCorridor)
+        ));
+        for &radius in &[0., -1., f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                check(&snapshot, corridor(nodes[0], nodes[1], radius)),
+                Err(CommandRejection::InvalidCorridor)
+            ));
+        }
+        assert!(matches!(
+            check(&snapshot, corridor(nodes[0], nodes[2], 0.1)),
+            Err(CommandRejection::NoSuchNode(node)) if node == nodes[2]
+        ));
+
+        // a corridor accepted earlier in the same tick
+        snapshot.corridors.insert((nodes[0], nodes[1]));
+        assert!(matches!(
+            check(&snapshot, corridor(nodes[1], nodes[0], 0.1)),
+            Err(CommandRejection::InvalidCorridor)
+        ));
+
+        // a node placed earlier in the same tick
+        let place = Command::PlaceNode {
+            node: nodes[2],
+            building,
+            position: Position::new(0., 0., 0.),
+            rotation: Matrix::identity(),
+        };
+        assert!(check(&snapshot, place.clone()).is_ok());
+        snapshot
+            .nodes
+            .insert(nodes[2], legion::World::default().push((nodes[2],)));
+        assert!(matches!(
+            check(&snapshot, place),
+            Err(CommandRejection::DuplicateNode(node)) if node == nodes[2]
+        ));
+        assert!(check(&snapshot, corridor(nodes[2], nodes[0], 0.1)).is_ok());
+    }
+
+    #[test]
+    fn routes_within_tick() {
+        let def = GameDefinition::default();
+        let research = Research::default();
+        let mut snapshot = Snapshot::default();
+        let set_route = Command::SetRoute {
+            vehicle: VehicleId::new(1),
+            route: String::from("loop"),
+        };
+        let remove = Command::RemoveRoute {
+            name: String::from("loop"),
+        };
+
+        for command in &[&set_route, &remove] {
+            assert!(matches!(
+                check_snapshot(&def, &research, &snapshot, Origin::Player, command),
+                Err(CommandRejection::Route(RouteRejection::UnknownRoute(ref name))) if name == "loop"
+            ));
+        }
+
+        // a route defined earlier in the same tick
+        snapshot.routes.insert(String::from("loop"));
+        assert!(check_snapshot(&def, &research, &snapshot, Origin::Player, &set_route).is_ok());
+        assert!(check_snapshot(&def, &research, &snapshot, Origin::Player, &remove).is_ok());
+    }
+
+    #[test]
+    fn locked_buildings_and_names() {
+        let mut def = GameDefinition::default();
+        let building = testing::building(
+            &mut def,
+            Vec::new(),
+            testing::storage(0., 0., 0.),
+            Vec::new(),
+        );
+        let tech = testing::tech(
+            &mut def,
+            Vec::new(),
+            Vec::new(),
+            vec![tech::Unlock::Building(building)],
+        );
+        let node = NodeId::new(1);
+        let snapshot = snapshot(&[node]);
+        let build = Command::BuildNode {
+            building,
+            position: Position::new(0., 0., 0.),
+            rotation: Matrix::identity(),
+        };
+
+        let research = Research::default();
+        assert!(matches!(
+            check_snapshot(&def, &research, &snapshot, Origin::Player, &build),
+            Err(CommandRejection::Locked(ref locked)) if locked.techs() == &vec![tech]
+        ));
+        let rename = |name| Command::RenameNode {
+            node,
+            name: ArcStr::from(name),
+        };
+        assert!(matches!(
+            check_snapshot(&def, &research, &snapshot, Origin::Player, &rename(" ")),
+            Err(CommandRejection::EmptyName)
+        ));
+        assert!(
+            check_snapshot(&def, &research, &snapshot, Origin::Player, &rename("Core")).is_ok()
+        );
+    }
+
+    #[test]
+    fn host_only_and_unknown_ids() {
+        let mut def = GameDefinition::default();
+        let core = testing::building(
+            &mut def,
+            Vec::new(),
+            testing::storage(0., 0., 0.),
+            vec![building::ExtraFeature::Core],
+        );
+        let research = Research::default();
+        let node = NodeId::new(1);
+        let mut snapshot = snapshot(&[node]);
+        let check = |snapshot: &Snapshot, origin, command: &Command| {
+            check_snapshot(&def, &research, snapshot, origin, command)
+        };
+
+        let place = |building| Command::PlaceNode {
+            node: NodeId::new(2),
+            building,
+            position: Position::new(0., 0., 0.),
+            rotation: Matrix::identity(),
+        };
+        assert!(check(&snapshot, Origin::Host, &place(core)).is_ok());
+        assert!(matches!(
+            check(&snapshot, Origin::Player, &place(core)),
+            Err(CommandRejection::HostOnly)
+        ));
+        assert!(matches!(
+            check(&snapshot, Origin::Host, &place(building::TypeId(1))),
+            Err(CommandRejection::NoSuchBuilding(building::TypeId(1)))
+        ));
+        let build = Command::BuildNode {
+            building: building::TypeId(1),
+            position: Position::new(0., 0., 0.),
+            rotation: Matrix::identity(),
+        };
+        assert!(matches!(
+            check(&snapshot, Origin::Player, &build),
+            Err(CommandRejection::NoSuchBuilding(building::TypeId(1)))
+        ));
+
+        let configure = Command::ConfigureReaction {
+            node,
+            reaction: reaction::TypeId(0),
+            setting: Setting::default(),
+        };
+        assert!(matches!(
+            check(&snapshot, Origin::Player, &configure),
+            Err(CommandRejection::NoSuchReaction(reaction::TypeId(0)))
+        ));
+        let keep = Command::SetKeep {
+            node,
+            cargo: def::cargo::TypeId(0),
+            size: CargoSize(1.),
+        };
+        assert!(matches!(
+            check(&snapshot, Origin::Player, &keep),
+            Err(CommandRejection::NoSuchCargo(def::cargo::TypeId(0)))
+        ));
+        let transfer = Command::TransferCargo {
+            from: node,
+            to: NodeId::new(2),
+            cargo: def::cargo::TypeId(0),
+            size: CargoSize(1.),
+        };
+        assert!(matches!(
+            check(&snapshot, Origin::Player, &transfer),
+            Err(CommandRejection::NoSuchCargo(def::cargo::TypeId(0)))
+        ));
+
+        let demolish = Command::Demolish { node };
+        assert!(check(&snapshot, Origin::Player, &demolish).is_ok());
+        snapshot.cores.insert(node);
+        assert!(matches!(
+            check(&snapshot, Origin::Player, &demolish),
+            Err(CommandRejection::Core(id)) if id == node
+        ));
+    }
+
+    #[test]
+    fn serde_roundtrip() {
+        let command = Command::DefineRoute {
+            name: String::from("loop"),
+            stops: vec![RouteStop::new(
+                NodeId::new(1),
+                vec![(def::cargo::TypeId(0), schedule::Rule::Load)]
+                    .into_iter()
+                    .collect(),
+            )],
+        };
+        let json = serde_json::to_string(&command).expect("Cannot serialize command");
+        let parsed: Command = serde_json::from_str(&json).expect("Cannot deserialize command");
+        assert_eq!(
+            serde_json::to_string(&parsed).expect("Cannot serialize command"),
+            json
+        );
+
+        let reason = CommandRejection::NoSuchVehicle(VehicleId::new(3));
+        let json = serde_json::to_string(&reason).expect("Cannot serialize rejection");
+        let parsed: CommandRejection =
+            serde_json::from_str(&json).expect("Cannot deserialize rejection");
+        assert!(matches!(parsed, CommandRejection::NoSuchVehicle(id) if id == VehicleId::new(3)));
+    }
+}
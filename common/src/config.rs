@@ -5,21 +5,75 @@
 //! Configurations are stored as resources in the Legion.
 //! They are referenced using IDs.
 
-use std::f64::consts::PI;
-
-use super::time;
 use crate::SetupEcs;
 
 /// Scalar configuration values
 pub struct Scalar {
-    /// The angle the sun moves per tick
-    pub sun_speed: time::Rate<f64>,
+    /// The fraction of construction cargo refunded when a building is demolished
+    pub demolition_refund: f64,
+    /// The happiness lost by inhabitants in a node per dm\u{b3} of cargo dumped into it
+    pub dump_penalty: f64,
+    /// The number of entries kept in the colony-wide bottleneck report
+    pub bottleneck_count: usize,
+    /// The temperature that nodes dissipate heat towards, in kelvins
+    pub ambient_temperature: f64,
+    /// The heat required to raise the temperature of a node by one kelvin, in kJ
+    pub heat_capacity: f64,
+    /// The fraction of heat above the ambient temperature dissipated per second
+    pub heat_dissipation: f64,
+    /// The temperature above which nodes lose hitpoints, in kelvins
+    pub overheat_temperature: f64,
+    /// The hitpoints lost per second for each kelvin above the overheat temperature
+    pub overheat_damage: f64,
+    /// The fraction of the difference in liquid fill levels between adjacent nodes
+    /// equalized per second, divided by the viscosity of the liquid
+    pub pipe_flow: f64,
+    /// The happiness lost by inhabitants per second for each full waste storage in their node
+    pub waste_penalty: f64,
+    /// The amount of each inhaled gas consumed by an inhabitant per second, in mol
+    pub respiration_rate: f64,
+    /// The health lost by an inhabitant per second when it cannot breathe at all
+    pub suffocation_damage: f64,
+    /// The health of newborn inhabitants
+    pub initial_health: f64,
+    /// The age at which inhabitants die, in years
+    pub max_age: f64,
+    /// The trained skill points gained by a newborn inhabitant per second,
+    /// decreasing linearly to zero at the maximum age
+    pub skill_growth: f64,
+    /// The health lost per second by an inhabitant at the maximum age,
+    /// proportional to the square of the fraction of the maximum age reached
+    pub aging_damage: f64,
+    /// The health lost by inhabitants per second for each full waste storage in their node
+    pub waste_damage: f64,
+    /// The health lost by the victim of an inhabitant theft
+    pub theft_damage: f64,
+    /// The happiness of newborn inhabitants
+    pub initial_happiness: f64,
 }
 
 impl Default for Scalar {
     fn default() -> Self {
         Self {
-            sun_speed: time::Rate(PI * 2. / 300. / 10.), // 5 minutes = 1 year
+            demolition_refund: 0.5,
+            dump_penalty: 0.01,
+            bottleneck_count: 10,
+            ambient_temperature: 293.,
+            heat_capacity: 100.,
+            heat_dissipation: 0.01,
+            overheat_temperature: 400.,
+            overheat_damage: 0.1,
+            pipe_flow: 0.1,
+            waste_penalty: 0.05,
+            respiration_rate: 0.01,
+            suffocation_damage: 5.,
+            initial_health: 100.,
+            max_age: 80.,
+            skill_growth: 0.01,
+            aging_damage: 1.,
+            waste_damage: 0.2,
+            theft_damage: 5.,
+            initial_happiness: 50.,
         }
     }
 }
@@ -0,0 +1,599 @@
+//! Construction and demolition of buildings.
+//!
+//! A newly placed node starts as a construction site.
+//! The site consumes the cargo delivered to the node and the energy supplied to it,
+//! and advances with the work of the workers assigned to it.
+//! The node becomes operational when the [`Site`] component is removed.
+//!
+//! A demolished node refunds part of its construction cargo
+//! into the storages of the nodes connected to it, nearest first.
+//! The refund that does not fit is reported in a [`RefundLossEvent`].
+//! [Core][building::ExtraFeature::Core] buildings cannot be demolished.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use derive_new::new;
+use legion::world::SubWorld;
+use legion::Entity;
+
+use crate::cargo;
+use crate::clock::{SimulationEvent, SIMULATION_PERIOD};
+use crate::config;
+use crate::def::{self, building, tech, GameDefinition};
+use crate::graph::{self, EdgeId, NodeAddEvent, NodeBuilding, NodeId, NodeRemoveEvent};
+use crate::inhabitant::DeathEvent;
+use crate::research::{Locked, Research};
+use crate::skill::Skills;
+use crate::space::{Matrix, Position};
+use crate::units::{CargoSize, ElectricEnergy};
+use crate::SetupEcs;
+
+/// A component attached to nodes under construction.
+#[derive(Debug, Clone, getset::CopyGetters)]
+pub struct Site {
+    /// The building type under construction
+    #[getset(get_copy = "pub")]
+    building: building::TypeId,
+    /// The cargo consumed so far
+    consumed: BTreeMap<def::cargo::TypeId, CargoSize>,
+    /// The energy consumed so far
+    #[getset(get_copy = "pub")]
+    energy: ElectricEnergy,
+    /// The fraction of construction work done, between 0 and 1
+    #[getset(get_copy = "pub")]
+    progress: f64,
+}
+
+impl Site {
+    /// Creates an empty construction site.
+    pub fn new(building: building::TypeId) -> Self {
+        Self {
+            building,
+            consumed: BTreeMap::new(),
+            energy: ElectricEnergy::default(),
+            progress: 0.,
+        }
+    }
+
+    /// The amount of a cargo type consumed so far.
+    pub fn consumed(&self, ty: def::cargo::TypeId) -> CargoSize {
+        self.consumed.get(&ty).copied().unwrap_or_default()
+    }
+
+    /// The energy still required by the site.
+    pub fn remaining_energy(&self, def: &GameDefinition) -> ElectricEnergy {
+        let required = def.get_building(self.building).construction().energy();
+        if self.energy < required {
+            required - self.energy
+        } else {
+            ElectricEnergy::default()
+        }
+    }
+
+    /// Supplies energy to the site.
+    ///
+    /// Returns the amount of energy actually consumed.
+    pub fn supply_energy(
+        &mut self,
+        def: &GameDefinition,
+        energy: ElectricEnergy,
+    ) -> ElectricEnergy {
+        let remaining = self.remaining_energy(def);
+        let used = if energy < remaining {
+            energy
+        } else {
+            remaining
+        };
+        self.energy += used;
+        used
+    }
+
+    /// The fraction of materials consumed, between 0 and 1.
+    ///
+    /// Construction work cannot progress beyond this fraction.
+    pub fn materials(&self, def: &GameDefinition) -> f64 {
+        self.materials_of(def.get_building(self.building).construction())
+    }
+
+    fn materials_of(&self, construction: &building::Construction) -> f64 {
+        let energy = fraction(self.energy.value(), construction.energy().value());
+        construction
+            .cargo()
+            .iter()
+            .map(|&(ty, size)| fraction(self.consumed(ty).value(), size.value()))
+            .fold(energy, f64::min)
+    }
+
+    /// Advances construction with `work` workers at a multiplier of 1 for one simulation period.
+    ///
+    /// Returns whether the construction is complete.
+    fn advance(&mut self, construction: &building::Construction, work: f64) -> bool {
+        let time = construction.time().as_secs();
+        let progress = if time > 0. {
+            self.progress + work * SIMULATION_PERIOD.as_secs() / time
+        } else {
+            1.
+        };
+        self.progress = progress.min(self.materials_of(construction));
+        self.progress >= 1.
+    }
+}
+
+fn fraction(done: f64, required: f64) -> f64 {
+    if required > 0. {
+        (done / required).min(1.)
+    } else {
+        1.
+    }
+}
+
+/// A component attached to inhabitants assigned to a construction site.
+#[derive(Debug, Clone, Copy, new, getset::CopyGetters)]
+pub struct Worker {
+    /// The node entity of the construction site
+    #[getset(get_copy = "pub")]
+    site: Entity,
+}
+
+/// Requests to place a new construction site.
+#[derive(Debug, new, getset::CopyGetters)]
+pub struct ConstructRequestEvent {
+    /// The building type to construct
+    #[getset(get_copy = "pub")]
+    building: building::TypeId,
+    /// The position of the new node
+    #[getset(get_copy = "pub")]
+    position: Position,
+    /// The rotation of the new node
+    #[getset(get_copy = "pub")]
+    rotation: Matrix,
+}
+
+/// Indicates that a construction request has been rejected.
+#[derive(Debug, new, getset::CopyGetters, getset::Getters)]
+pub struct ConstructRejectEvent {
+    /// The requested building type
+    #[getset(get_copy = "pub")]
+    building: building::TypeId,
+    /// The reason for rejection
+    #[getset(get = "pub")]
+    reason: ConstructRejection,
+}
+
+/// The reason for rejecting a construction request.
+#[derive(Debug, Clone)]
+pub enum ConstructRejection {
+    /// The building type does not exist.
+    NoSuchBuilding,
+    /// The building has not been unlocked by research.
+    Locked(Locked),
+}
+
+/// Indicates that a construction site has become an operational node.
+#[derive(Debug, new, getset::CopyGetters)]
+pub struct ConstructCompleteEvent {
+    /// The constructed node
+    #[getset(get_copy = "pub")]
+    node: NodeId,
+}
+
+/// Requests to demolish a node and refund part of its construction cost.
+#[derive(Debug, new, getset::CopyGetters)]
+pub struct DemolishEvent {
+    /// The node to demolish
+    #[getset(get_copy = "pub")]
+    node: NodeId,
+}
+
+/// Indicates that a demolition request has been rejected
+/// because the node is a [core][building::ExtraFeature::Core].
+#[derive(Debug, new, getset::CopyGetters)]
+pub struct DemolishRejectEvent {
+    /// The requested node
+    #[getset(get_copy = "pub")]
+    node: NodeId,
+}
+
+/// Indicates that part of a demolition refund did not fit in the connected storages.
+#[derive(Debug, new, getset::CopyGetters)]
+pub struct RefundLossEvent {
+    /// The demolished node
+    #[getset(get_copy = "pub")]
+    node: NodeId,
+    /// The type of cargo lost
+    #[getset(get_copy = "pub")]
+    cargo: def::cargo::TypeId,
+    /// The amount of cargo lost
+    #[getset(get_copy = "pub")]
+    size: CargoSize,
+}
+
+#[codegen::system]
+fn start_construction(
+    cmd_buf: &mut legion::systems::CommandBuffer,
+    #[resource] def: &GameDefinition,
+    #[resource] research: &Research,
+    #[subscriber] requests: impl Iterator<Item = ConstructRequestEvent>,
+    #[publisher] node_add_pub: impl FnMut(NodeAddEvent),
+    #[publisher] reject_pub: impl FnMut(ConstructRejectEvent),
+) {
+    for request in requests {
+        let checked = if request.building.0 < def.building().len() {
+            research
+                .require(def, tech::Unlock::Building(request.building))
+                .map_err(ConstructRejection::Locked)
+        } else {
+            Err(ConstructRejection::NoSuchBuilding)
+        };
+        if let Err(reason) = checked {
+            reject_pub(ConstructRejectEvent {
+                building: request.building,
+                reason,
+            });
+            continue;
+        }
+
+        let components = graph::create_node_components(
+            def,
+            request.building,
+            request.position,
+            request.rotation,
+        );
+        let node = components.0;
+        let entity = cmd_buf.push(components);
+        cmd_buf.add_component(entity, Site::new(request.building));
+        node_add_pub(NodeAddEvent::new(node));
+    }
+}
+
+#[codegen::system]
+#[write_component(Site)]
+#[read_component(NodeId)]
+#[read_component(cargo::StorageList)]
+#[write_component(cargo::NextStorageSize)]
+#[read_component(Worker)]
+#[read_component(Skills)]
+fn build(
+    world: &mut SubWorld,
+    cmd_buf: &mut legion::systems::CommandBuffer,
+    #[resource] def: &GameDefinition,
+    #[subscriber] sim_sub: impl Iterator<Item = SimulationEvent>,
+    #[subscriber] death_sub: impl Iterator<Item = DeathEvent>,
+    #[publisher] complete_pub: impl FnMut(ConstructCompleteEvent),
+) {
+    use legion::{EntityStore, IntoQuery};
+
+    // dead workers no longer contribute work
+    let dead: BTreeSet<Entity> = death_sub.map(|death| death.inhabitant()).collect();
+
+    if sim_sub.next().is_none() {
+        return;
+    }
+
+    let mut work = BTreeMap::<Entity, f64>::new();
+    for (entity, worker, skills) in <(Entity, &Worker, &Skills)>::query().iter(world) {
+        if dead.contains(entity) {
+            continue;
+        }
+        let building = match <&Site>::query().get(world, worker.site) {
+            Ok(site) => site.building,
+            Err(_) => continue, // the site is completed or demolished
+        };
+        let construction = def.get_building(building).construction();
+        let multiplier = construction
+            .multipliers()
+            .get(construction.levels(), skills.get(construction.skill()));
+        *work.entry(worker.site).or_default() += multiplier;
+    }
+
+    let mut storages = Vec::new();
+    for (&entity, site, list) in <(Entity, &Site, &cargo::StorageList)>::query().iter(world) {
+        let construction = def.get_building(site.building).construction();
+        for &(ty, storage) in list.storages() {
+            let required = construction
+                .cargo()
+                .iter()
+                .find(|&&(cost, _)| cost == ty)
+                .map(|&(_, size)| size);
+            if let Some(required) = required {
+                if site.consumed(ty) < required {
+                    storages.push((entity, ty, storage, required));
+                }
+            }
+        }
+    }
+    for (entity, ty, storage, required) in storages {
+        let consumed = match <&Site>::query().get(world, entity) {
+            Ok(site) => site.consumed(ty),
+            Err(_) => continue,
+        };
+        let mut entry = match world.entry_mut(storage) {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        let next = entry
+            .get_component_mut::<cargo::NextStorageSize>()
+            .expect("Storage entity does not have NextStorageSize");
+        let remaining = required - consumed;
+        let taken = if next.size() < remaining {
+            next.size()
+        } else {
+            remaining
+        };
+        *next.size_mut() -= taken;
+
+        let mut query = <&mut Site>::query();
+        let site = query.get_mut(world, entity).expect("Site was just queried");
+        *site.consumed.entry(ty).or_default() += taken;
+    }
+
+    for (&entity, &node, site) in <(Entity, &NodeId, &mut Site)>::query().iter_mut(world) {
+        let construction = def.get_building(site.building).construction();
+        let workers = work.get(&entity).copied().unwrap_or_default();
+        if site.advance(construction, workers) {
+            cmd_buf.remove_component::<Site>(entity);
+            complete_pub(ConstructCompleteEvent { node });
+        }
+    }
+}
+
+/// Whether a building type is a core, which cannot be demolished.
+pub(crate) fn is_core(building: &building::Type) -> bool {
+    building
+        .features()
+        .iter()
+        .any(|feature| matches!(feature, building::ExtraFeature::Core))
+}
+
+/// The cargo refunded by demolishing a node of the given construction.
+///
+/// `site` is the construction site of the node, if it is not completed yet.
+fn refunds(
+    construction: &building::Construction,
+    site: Option<&Site>,
+    ratio: f64,
+) -> Vec<(def::cargo::TypeId, CargoSize)> {
+    construction
+        .cargo()
+        .iter()
+        .map(|&(ty, size)| {
+            let spent = match site {
+                Some(site) => site.consumed(ty),
+                None => size,
+            };
+            (ty, spent * ratio)
+        })
+        .collect()
+}
+
+/// Distributes `refund` into storages with the given free spaces in order.
+///
+/// Returns the amount given to each storage and the amount that does not fit.
+fn distribute(
+    mut refund: CargoSize,
+    spaces: impl IntoIterator<Item = CargoSize>,
+) -> (Vec<CargoSize>, CargoSize) {
+    let given = spaces
+        .into_iter()
+        .map(|space| {
+            let given = if space < refund { space } else { refund };
+            if given > CargoSize::default() {
+                refund -= given;
+                given
+            } else {
+                CargoSize::default()
+            }
+        })
+        .collect();
+    (given, refund)
+}
+
+#[codegen::system]
+#[read_component(NodeId)]
+#[read_component(NodeBuilding)]
+#[read_component(EdgeId)]
+#[read_component(Site)]
+#[read_component(cargo::StorageList)]
+#[read_component(cargo::Storage)]
+#[write_component(cargo::NextStorageSize)]
+fn demolish(
+    world: &mut SubWorld,
+    #[resource] def: &GameDefinition,
+    #[resource] config: &config::Scalar,
+    #[subscriber] requests: impl Iterator<Item = DemolishEvent>,
+    #[publisher] reject_pub: impl FnMut(DemolishRejectEvent),
+    #[publisher] loss_pub: impl FnMut(RefundLossEvent),
+    #[publisher] node_remove_pub: impl FnMut(NodeRemoveEvent),
+) {
+    use legion::{EntityStore, IntoQuery};
+
+    let mut requests = requests.peekable();
+    if requests.peek().is_none() {
+        return;
+    }
+    let adjacency = graph::adjacency(<&EdgeId>::query().iter(world));
+
+    for request in requests {
+        let mut refund = Vec::new();
+        let mut core = false;
+        for (&node, building, site) in
+            <(&NodeId, &NodeBuilding, Option<&Site>)>::query().iter(world)
+        {
+            if node == request.node {
+                let building = def.get_building(building.building());
+                core = is_core(building);
+                refund = refunds(building.construction(), site, config.demolition_refund);
+            }
+        }
+        if core {
+            reject_pub(DemolishRejectEvent { node: request.node });
+            continue;
+        }
+
+        // storages of connected nodes, nearest first
+        let distances = graph::distances(&adjacency, request.node);
+        let mut storages = Vec::new();
+        for (&node, list) in <(&NodeId, &cargo::StorageList)>::query().iter(world) {
+            match distances.get(&node) {
+                Some(&distance) if distance > 0 => {
+                    for &(ty, storage) in list.storages() {
+                        storages.push((distance, node, ty, storage));
+                    }
+                }
+                _ => continue, // the demolished node itself or not connected
+            }
+        }
+        storages.sort_by_key(|&(distance, node, _, _)| (distance, node));
+
+        for (ty, size) in refund {
+            let mut query = <(&cargo::Storage, &cargo::NextStorageSize)>::query();
+            let mut targets = Vec::new();
+            for &(_, _, storage_ty, storage) in &storages {
+                if storage_ty != ty {
+                    continue;
+                }
+                let (storage_def, next) = match query.get(world, storage) {
+                    Ok(components) => components,
+                    Err(_) => continue,
+                };
+                targets.push((storage, storage_def.capacity() - next.size()));
+            }
+
+            let (given, lost) = distribute(size, targets.iter().map(|&(_, space)| space));
+            for (&(storage, _), given) in targets.iter().zip(given) {
+                let mut entry = match world.entry_mut(storage) {
+                    Ok(entry) => entry,
+                    Err(_) => continue,
+                };
+                let next = entry
+                    .get_component_mut::<cargo::NextStorageSize>()
+                    .expect("Storage entity does not have NextStorageSize");
+                *next.size_mut() += given;
+            }
+            if lost > CargoSize::default() {
+                loss_pub(RefundLossEvent {
+                    node: request.node,
+                    cargo: ty,
+                    size: lost,
+                });
+            }
+        }
+
+        node_remove_pub(NodeRemoveEvent::new(request.node));
+    }
+}
+
+/// Initializes ECS
+pub fn setup_ecs(setup: SetupEcs) -> SetupEcs {
+    setup
+        .uses(start_construction_setup)
+        .uses(build_setup)
+        .uses(demolish_setup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use crate::time::Time;
+    use crate::units;
+
+    fn construction(cargo: Vec<(def::cargo::TypeId, CargoSize)>) -> building::Construction {
+        building::Construction::builder()
+            .cargo(cargo)
+            .energy(ElectricEnergy(0.))
+            .time(Time(1000))
+            .skill(def::skill::TypeId(0))
+            .levels(units::Skill(0.)..units::Skill(1.))
+            .multipliers(testing::multipliers(1., 1.))
+            .build()
+    }
+
+    #[test]
+    fn progress_limited_by_materials() {
+        let steel = def::cargo::TypeId(0);
+        let construction = construction(vec![(steel, CargoSize(10.))]);
+        let mut site = Site::new(building::TypeId(0));
+        site.consumed.insert(steel, CargoSize(5.));
+
+        let expected = [(0.2, false), (0.4, false), (0.5, false), (0.5, false)];
+        for &(progress, complete) in &expected {
+            assert_eq!(site.advance(&construction, 2.), complete);
+            assert!((site.progress() - progress).abs() < 1e-9);
+        }
+
+        site.consumed.insert(steel, CargoSize(10.));
+        let expected = [(0.7, false), (0.9, false), (1., true)];
+        for &(progress, complete) in &expected {
+            assert_eq!(site.advance(&construction, 2.), complete);
+            assert!((site.progress() - progress).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn no_progress_without_workers() {
+        let construction = construction(Vec::new());
+        let mut site = Site::new(building::TypeId(0));
+        assert!(!site.advance(&construction, 0.));
+        assert!(site.progress() <= 0.);
+    }
+
+    #[test]
+    fn refund_spent_cargo() {
+        let steel = def::cargo::TypeId(0);
+        let glass = def::cargo::TypeId(1);
+        let construction = construction(vec![(steel, CargoSize(10.)), (glass, CargoSize(6.))]);
+
+        let complete = refunds(&construction, None, 0.5);
+        assert_eq!(
+            complete,
+            vec![(steel, CargoSize(5.)), (glass, CargoSize(3.))]
+        );
+
+        let mut site = Site::new(building::TypeId(0));
+        site.consumed.insert(steel, CargoSize(4.));
+        let partial = refunds(&construction, Some(&site), 0.5);
+        assert_eq!(
+            partial,
+            vec![(steel, CargoSize(2.)), (glass, CargoSize(0.))]
+        );
+    }
+
+    #[test]
+    fn distribute_nearest_first() {
+        let spaces = vec![CargoSize(3.), CargoSize(0.), CargoSize(5.), CargoSize(4.)];
+        let (given, lost) = distribute(CargoSize(10.), spaces);
+        assert_eq!(
+            given,
+            vec![CargoSize(3.), CargoSize(0.), CargoSize(5.), CargoSize(2.)]
+        );
+        assert_eq!(lost, CargoSize(0.));
+
+        let (given, lost) = distribute(CargoSize(10.), vec![CargoSize(3.), CargoSize(4.)]);
+        assert_eq!(given, vec![CargoSize(3.), CargoSize(4.)]);
+        assert_eq!(lost, CargoSize(3.));
+
+        let (given, lost) = distribute(CargoSize(10.), Vec::new());
+        assert!(given.is_empty());
+        assert_eq!(lost, CargoSize(10.));
+    }
+
+    #[test]
+    fn core_buildings() {
+        let mut def = GameDefinition::default();
+        let core = testing::building(
+            &mut def,
+            Vec::new(),
+            testing::storage(0., 0., 0.),
+            vec![building::ExtraFeature::Core],
+        );
+        let hut = testing::building(
+            &mut def,
+            Vec::new(),
+            testing::storage(0., 0., 0.),
+            vec![building::ExtraFeature::ProvidesHousing(2)],
+        );
+        assert!(is_core(def.get_building(core)));
+        assert!(!is_core(def.get_building(hut)));
+    }
+}
@@ -0,0 +1,159 @@
+//! Crimes committed by inhabitants.
+//!
+//! Each simulation tick, inhabitants whose happiness is in the
+//! [trigger range][crime::Type::trigger_happiness_range] of a crime type
+//! commit it with its probability per second,
+//! after which their happiness and skills change accordingly.
+//! Prisoners do not commit crimes.
+//!
+//! Only [`InhabitantTheft`][crime::Action::InhabitantTheft] is simulated so far.
+//! It steals a random cargo type carried by another inhabitant in the same vehicle,
+//! or in the same node if the offender is not in a vehicle,
+//! and [injures][InjuryEvent] the victim.
+
+use std::collections::BTreeMap;
+
+use derive_new::new;
+use legion::world::SubWorld;
+use legion::Entity;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::clock::{SimulationEvent, SIMULATION_PERIOD};
+use crate::config;
+use crate::def::{crime, GameDefinition};
+use crate::graph::NodeId;
+use crate::happiness::Happiness;
+use crate::inhabitant::{DeathCause, InjuryEvent, Inventory, Location};
+use crate::security::Prisoner;
+use crate::skill::Skills;
+use crate::units;
+use crate::vehicle::Passenger;
+use crate::SetupEcs;
+
+/// Indicates that a crime has been committed.
+#[derive(Debug, new, getset::CopyGetters)]
+pub struct CrimeEvent {
+    /// The inhabitant committing the crime
+    #[getset(get_copy = "pub")]
+    offender: Entity,
+    /// The crime type
+    #[getset(get_copy = "pub")]
+    crime: crime::TypeId,
+    /// The node where the crime was committed
+    #[getset(get_copy = "pub")]
+    node: NodeId,
+}
+
+/// The inhabitants that can be victims of the same offender.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Scene {
+    /// Inhabitants in a node, not in a vehicle
+    Node(NodeId),
+    /// Passengers of a vehicle
+    Vehicle(Entity),
+}
+
+#[codegen::system]
+#[read_component(Location)]
+#[read_component(Passenger)]
+#[read_component(Prisoner)]
+#[write_component(Happiness)]
+#[write_component(Skills)]
+#[write_component(Inventory)]
+fn commit(
+    world: &mut SubWorld,
+    #[resource] def: &GameDefinition,
+    #[resource] config: &config::Scalar,
+    #[subscriber] sim_sub: impl Iterator<Item = SimulationEvent>,
+    #[publisher] crime_pub: impl FnMut(CrimeEvent),
+    #[publisher] injury_pub: impl FnMut(InjuryEvent),
+) {
+    use legion::{EntityStore, IntoQuery};
+
+    if sim_sub.next().is_none() {
+        return;
+    }
+
+    let secs = SIMULATION_PERIOD.as_secs();
+    let mut rng = rand::thread_rng();
+
+    let mut scenes = BTreeMap::<Scene, Vec<Entity>>::new();
+    let mut offenders = Vec::new();
+    for (&entity, location, happiness, passenger) in
+        <(Entity, &Location, &Happiness, Option<&Passenger>)>::query()
+            .filter(!legion::component::<Prisoner>())
+            .iter(world)
+    {
+        let scene = match passenger {
+            Some(passenger) => Scene::Vehicle(passenger.vehicle()),
+            None => Scene::Node(location.node()),
+        };
+        scenes.entry(scene).or_default().push(entity);
+        offenders.push((entity, location.node(), scene, happiness.value()));
+    }
+
+    for (offender, node, scene, happiness) in offenders {
+        for (index, crime) in def.crime().iter().enumerate() {
+            let max = match *crime.action() {
+                crime::Action::InhabitantTheft(max) => max,
+                _ => continue, // TODO simulate other crime actions
+            };
+            if !crime.trigger_happiness_range().contains(&happiness)
+                || !rng.gen_bool((crime.probability() * secs).min(1.))
+            {
+                continue;
+            }
+
+            let victims: Vec<Entity> = scenes
+                .get(&scene)
+                .into_iter()
+                .flatten()
+                .copied()
+                .filter(|&victim| victim != offender)
+                .collect();
+            let victim = match victims.choose(&mut rng) {
+                Some(&victim) => victim,
+                None => continue,
+            };
+            let loot = world.entry_mut(victim).ok().and_then(|mut entry| {
+                let inventory = entry.get_component_mut::<Inventory>().ok()?;
+                let carried: Vec<_> = inventory.iter().map(|(ty, _)| ty).collect();
+                let &ty = carried.choose(&mut rng)?;
+                Some((ty, inventory.take(ty, max)))
+            });
+            injury_pub(InjuryEvent::new(
+                victim,
+                units::Skill(config.theft_damage),
+                DeathCause::Crime,
+            ));
+
+            let mut entry = world
+                .entry_mut(offender)
+                .expect("Offender entity does not exist");
+            if let (Some((ty, size)), Ok(inventory)) =
+                (loot, entry.get_component_mut::<Inventory>())
+            {
+                inventory.add(ty, size);
+            }
+            if let Ok(happiness) = entry.get_component_mut::<Happiness>() {
+                happiness.add(crime.happiness_change());
+            }
+            if let Ok(skills) = entry.get_component_mut::<Skills>() {
+                for &(ty, delta) in crime.skill_change() {
+                    skills.add(ty, delta);
+                }
+            }
+            crime_pub(CrimeEvent {
+                offender,
+                crime: crime::TypeId(index),
+                node,
+            });
+        }
+    }
+}
+
+/// Initializes ECS
+pub fn setup_ecs(setup: SetupEcs) -> SetupEcs {
+    setup.uses(commit_setup)
+}
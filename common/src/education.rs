@@ -0,0 +1,260 @@
+//! Students in education buildings.
+//!
+//! Buildings with the [`Education`][building::ExtraFeature::Education] feature
+//! provide seats for students.
+//! Students travel to the school and, while they are in it,
+//! receive the [skill changes][crate::def::reaction::Put::Skill] of the reactions in the building,
+//! but cannot be trained beyond the skill of the operator teaching them.
+//! Students graduate and become idle again when they reach the skill of their teacher.
+//!
+//! The [`EnrolmentPolicy`] fills free seats with idle inhabitants automatically.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+use derive_new::new;
+use legion::world::SubWorld;
+use legion::Entity;
+
+use crate::clock::SimulationEvent;
+use crate::construction::{Site, Worker};
+use crate::def::{building, reaction, skill, GameDefinition};
+use crate::factory::Operator;
+use crate::graph::{NodeBuilding, NodeId};
+use crate::happiness::Happiness;
+use crate::inhabitant::{Destination, Location};
+use crate::leisure::Visitor;
+use crate::security::{Police, Prisoner};
+use crate::skill::Skills;
+use crate::units;
+use crate::SetupEcs;
+
+/// A component attached to inhabitants attending an education building.
+#[derive(Debug, Clone, Copy, new, getset::CopyGetters)]
+pub struct Student {
+    /// The node entity of the education building
+    #[getset(get_copy = "pub")]
+    school: Entity,
+}
+
+/// A resource storing the policy of enrolling idle inhabitants into education buildings.
+#[derive(Debug, Clone, Copy, getset::CopyGetters, getset::Setters)]
+pub struct EnrolmentPolicy {
+    /// Whether free seats are filled automatically
+    #[getset(get_copy = "pub", set = "pub")]
+    automatic: bool,
+    /// Only inhabitants younger than this age are enrolled
+    #[getset(get_copy = "pub", set = "pub")]
+    max_age: units::Skill,
+}
+
+impl Default for EnrolmentPolicy {
+    fn default() -> Self {
+        Self {
+            automatic: true,
+            max_age: units::Skill(30.),
+        }
+    }
+}
+
+/// Returns the number of seats in a building type.
+fn seats(building: &building::Type) -> u32 {
+    building
+        .features()
+        .iter()
+        .map(|feature| match *feature {
+            building::ExtraFeature::Education(seats) => seats,
+            _ => 0,
+        })
+        .sum()
+}
+
+#[codegen::system]
+#[read_component(NodeId)]
+#[read_component(NodeBuilding)]
+#[read_component(Site)]
+#[read_component(Location)]
+#[read_component(Skills)]
+#[read_component(Worker)]
+#[read_component(Operator)]
+#[read_component(Student)]
+#[read_component(Police)]
+#[read_component(Prisoner)]
+#[read_component(Happiness)]
+#[read_component(Visitor)]
+fn enrol(
+    world: &mut SubWorld,
+    cmd_buf: &mut legion::systems::CommandBuffer,
+    #[resource] def: &GameDefinition,
+    #[resource] policy: &EnrolmentPolicy,
+    #[subscriber] sim_sub: impl Iterator<Item = SimulationEvent>,
+) {
+    use legion::IntoQuery;
+
+    if sim_sub.next().is_none() || !policy.automatic {
+        return;
+    }
+
+    let age = def.find_skill_kind(skill::Kind::Age);
+
+    let mut idle = Vec::new();
+    for (&entity, skills, happiness, worker, operator, student) in <(
+        Entity,
+        &Skills,
+        Option<&Happiness>,
+        Option<&Worker>,
+        Option<&Operator>,
+        Option<&Student>,
+    )>::query()
+    .filter(
+        legion::component::<Location>()
+            & !legion::component::<Police>()
+            & !legion::component::<Prisoner>()
+            & !legion::component::<Visitor>(),
+    )
+    .iter(world)
+    {
+        if worker.is_some() || operator.is_some() || student.is_some() {
+            continue;
+        }
+        // outlaws refuse to study
+        if matches!(happiness, Some(happiness) if happiness.is_outlaw()) {
+            continue;
+        }
+        if let Some(age) = age {
+            if skills.get(age) >= policy.max_age {
+                continue;
+            }
+        }
+        idle.push((entity, skills.clone()));
+    }
+    if idle.is_empty() {
+        return;
+    }
+
+    let mut attending = BTreeMap::<Entity, u32>::new();
+    for student in <&Student>::query().iter(world) {
+        *attending.entry(student.school).or_default() += 1;
+    }
+
+    for (&entity, &node, building, site) in
+        <(Entity, &NodeId, &NodeBuilding, Option<&Site>)>::query().iter(world)
+    {
+        if site.is_some() {
+            continue;
+        }
+        let building = def.get_building(building.building());
+        let free = seats(building).saturating_sub(attending.get(&entity).copied().unwrap_or(0));
+        if free == 0 {
+            continue;
+        }
+
+        let taught: Vec<skill::TypeId> = building
+            .reactions()
+            .iter()
+            .flat_map(|&(reaction, _)| def.get_reaction(reaction).puts().iter())
+            .filter_map(|put| match *put {
+                reaction::Put::Skill { ty, base } if base.0 .0 > 0. => Some(ty),
+                _ => None,
+            })
+            .collect();
+        if taught.is_empty() {
+            continue;
+        }
+
+        for inhabitant in fill_seats(&mut idle, &taught, free) {
+            cmd_buf.add_component(inhabitant, Student::new(entity));
+            cmd_buf.add_component(inhabitant, Destination::new(node));
+        }
+        if idle.is_empty() {
+            return;
+        }
+    }
+}
+
+/// Removes up to `free` inhabitants from `idle` to fill the seats of a school.
+///
+/// The inhabitants with the lowest levels of the `taught` skills are enrolled first.
+fn fill_seats(
+    idle: &mut Vec<(Entity, Skills)>,
+    taught: &[skill::TypeId],
+    free: u32,
+) -> Vec<Entity> {
+    let level = |skills: &Skills| -> f64 { taught.iter().map(|&ty| skills.get(ty).0).sum() };
+    idle.sort_by(|a, b| {
+        level(&b.1)
+            .partial_cmp(&level(&a.1))
+            .unwrap_or(Ordering::Equal)
+    });
+    let mut enrolled = Vec::new();
+    for _ in 0..free {
+        match idle.pop() {
+            Some((inhabitant, _)) => enrolled.push(inhabitant),
+            None => break,
+        }
+    }
+    enrolled
+}
+
+/// Initializes ECS
+pub fn setup_ecs(setup: SetupEcs) -> SetupEcs {
+    setup.resource(EnrolmentPolicy::default()).uses(enrol_setup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn skills(ty: skill::TypeId, level: f64) -> Skills {
+        let mut skills = Skills::default();
+        skills.set(ty, units::Skill(level));
+        skills
+    }
+
+    #[test]
+    fn fill_lowest_skills_first() {
+        let mut world = legion::World::default();
+        let taught = skill::TypeId(0);
+        let other = skill::TypeId(1);
+        let novice = world.push(((),));
+        let learner = world.push(((),));
+        let expert = world.push(((),));
+        let mut idle = vec![
+            (expert, skills(taught, 8.)),
+            (novice, skills(other, 9.)),
+            (learner, skills(taught, 3.)),
+        ];
+
+        let enrolled = fill_seats(&mut idle, &[taught], 2);
+        assert_eq!(enrolled, vec![novice, learner]);
+        assert_eq!(idle.len(), 1);
+
+        let enrolled = fill_seats(&mut idle, &[taught], 2);
+        assert_eq!(enrolled, vec![expert]);
+        assert!(idle.is_empty());
+
+        assert!(fill_seats(&mut idle, &[taught], 2).is_empty());
+    }
+
+    #[test]
+    fn count_education_seats() {
+        let mut def = GameDefinition::default();
+        let school = crate::testing::building(
+            &mut def,
+            Vec::new(),
+            crate::testing::storage(0., 0., 0.),
+            vec![
+                building::ExtraFeature::Education(3),
+                building::ExtraFeature::Education(2),
+            ],
+        );
+        let hut = crate::testing::building(
+            &mut def,
+            Vec::new(),
+            crate::testing::storage(0., 0., 0.),
+            vec![building::ExtraFeature::ProvidesHousing(4)],
+        );
+        assert_eq!(seats(def.get_building(school)), 5);
+        assert_eq!(seats(def.get_building(hut)), 0);
+    }
+}
@@ -0,0 +1,318 @@
+//! Electricity grid simulation.
+//!
+//! Nodes connected by corridors form a grid.
+//! Each simulation tick, the power generated by factories in a grid
+//! is first used by the factories consuming power, then by construction sites.
+//! Surplus power charges the [`Battery`] of the nodes in the grid,
+//! and deficits are covered by discharging them,
+//! both limited by the charge rate of each battery.
+//! If the demand still cannot be covered,
+//! factories consuming power in the grid run at the [satisfied fraction][Grid::satisfaction]
+//! of their rate in the next tick.
+//!
+//! Power cables are not simulated yet, so all corridors conduct electricity without loss.
+
+use std::collections::BTreeMap;
+
+use derive_new::new;
+use legion::world::SubWorld;
+
+use crate::clock::{SimulationEvent, SIMULATION_PERIOD};
+use crate::construction::Site;
+use crate::def::GameDefinition;
+use crate::factory::Power;
+use crate::graph::{EdgeId, NodeId};
+use crate::units::{ElectricEnergy, ElectricPower};
+use crate::SetupEcs;
+
+/// A component storing the electric energy stored in a node.
+#[derive(Debug, Clone, Copy, new, getset::CopyGetters)]
+pub struct Battery {
+    /// The energy currently stored
+    #[getset(get_copy = "pub")]
+    #[new(default)]
+    stored: ElectricEnergy,
+    /// The maximum energy stored
+    #[getset(get_copy = "pub")]
+    capacity: ElectricEnergy,
+    /// The maximum power at which the battery is charged or discharged
+    #[getset(get_copy = "pub")]
+    rate: ElectricPower,
+}
+
+impl Battery {
+    /// The energy that can be charged into the battery in one simulation tick.
+    fn charge_limit(&self) -> ElectricEnergy {
+        min(energy(self.rate), self.capacity - self.stored)
+    }
+
+    /// The energy that can be discharged from the battery in one simulation tick.
+    fn discharge_limit(&self) -> ElectricEnergy {
+        min(energy(self.rate), self.stored)
+    }
+}
+
+/// Statistics of a grid in the last simulation tick.
+#[derive(Debug, Clone, Default, getset::CopyGetters, getset::Getters)]
+pub struct Grid {
+    /// The nodes in the grid
+    #[getset(get = "pub")]
+    nodes: Vec<NodeId>,
+    /// The power generated by factories
+    #[getset(get_copy = "pub")]
+    supply: ElectricPower,
+    /// The power consumed by factories
+    #[getset(get_copy = "pub")]
+    demand: ElectricPower,
+    /// The power demanded by factories but not supplied by generation or storage
+    #[getset(get_copy = "pub")]
+    unmet: ElectricPower,
+    /// The net power charged into batteries, negative if discharged
+    #[getset(get_copy = "pub")]
+    charge: ElectricPower,
+    /// The total energy stored in batteries
+    #[getset(get_copy = "pub")]
+    stored: ElectricEnergy,
+    /// The total energy capacity of batteries
+    #[getset(get_copy = "pub")]
+    capacity: ElectricEnergy,
+}
+
+impl Grid {
+    /// The power available to the grid, including the power discharged from batteries.
+    pub fn available(&self) -> ElectricPower {
+        if self.charge.0 < 0. {
+            self.supply - self.charge
+        } else {
+            self.supply
+        }
+    }
+
+    /// The fraction of the power demanded by factories that was supplied.
+    pub fn satisfaction(&self) -> f64 {
+        if self.demand.0 > 0. {
+            (1. - self.unmet.0 / self.demand.0).clamp(0., 1.)
+        } else {
+            1.
+        }
+    }
+
+    /// The time for which the stored energy can cover the current net consumption,
+    /// in seconds.
+    ///
+    /// Returns `None` if the grid is not consuming more than it generates.
+    pub fn endurance(&self) -> Option<f64> {
+        let deficit = self.demand - self.supply;
+        if deficit.0 > 0. {
+            Some(self.stored.0 / deficit.0)
+        } else {
+            None
+        }
+    }
+}
+
+/// A resource storing the statistics of each grid in the last simulation tick.
+#[derive(Debug, Default, getset::Getters)]
+pub struct Grids {
+    /// The grids in the colony
+    #[getset(get = "pub")]
+    grids: Vec<Grid>,
+}
+
+impl Grids {
+    /// Returns the grid containing a node.
+    pub fn find(&self, node: NodeId) -> Option<&Grid> {
+        self.grids.iter().find(|grid| grid.nodes.contains(&node))
+    }
+}
+
+/// Converts power to the energy transferred in one simulation tick.
+fn energy(power: ElectricPower) -> ElectricEnergy {
+    ElectricEnergy(power.0 * SIMULATION_PERIOD.as_secs())
+}
+
+/// Converts the energy transferred in one simulation tick to power.
+fn power(energy: ElectricEnergy) -> ElectricPower {
+    ElectricPower(energy.0 / SIMULATION_PERIOD.as_secs())
+}
+
+fn min(a: ElectricEnergy, b: ElectricEnergy) -> ElectricEnergy {
+    if a < b {
+        a
+    } else {
+        b
+    }
+}
+
+/// Finds the representative node of the disjoint set containing `node`.
+fn find_root(parents: &mut BTreeMap<NodeId, NodeId>, node: NodeId) -> NodeId {
+    let mut root = node;
+    while let Some(&parent) = parents.get(&root) {
+        if parent == root {
+            break;
+        }
+        root = parent;
+    }
+    parents.insert(node, root);
+    root
+}
+
+#[codegen::system]
+#[read_component(NodeId)]
+#[read_component(EdgeId)]
+#[read_component(Power)]
+#[write_component(Battery)]
+#[write_component(Site)]
+fn solve_grid(
+    world: &mut SubWorld,
+    #[resource] def: &GameDefinition,
+    #[resource] grids: &mut Grids,
+    #[subscriber] sim_sub: impl Iterator<Item = SimulationEvent>,
+) {
+    use legion::IntoQuery;
+
+    if sim_sub.next().is_none() {
+        return;
+    }
+
+    // TODO restrict the throughput and loss of power cables in each corridor
+    let mut parents = BTreeMap::new();
+    for &node in <&NodeId>::query().iter(world) {
+        parents.insert(node, node);
+    }
+    for edge in <&EdgeId>::query().iter(world) {
+        let from = find_root(&mut parents, edge.from());
+        let to = find_root(&mut parents, edge.to());
+        parents.insert(from, to);
+    }
+
+    let mut by_root = BTreeMap::<NodeId, Grid>::new();
+    let nodes: Vec<NodeId> = parents.keys().copied().collect();
+    for node in nodes {
+        let root = find_root(&mut parents, node);
+        by_root.entry(root).or_default().nodes.push(node);
+    }
+
+    for (&node, power) in <(&NodeId, &Power)>::query().iter(world) {
+        let root = find_root(&mut parents, node);
+        let grid = by_root.get_mut(&root).expect("All nodes have a grid");
+        if power.net().0 > 0. {
+            grid.supply += power.net();
+        } else {
+            grid.demand -= power.net();
+        }
+    }
+
+    // Energy generated in this tick that has not been used yet
+    let mut available = BTreeMap::<NodeId, ElectricEnergy>::new();
+    // Energy demanded by factories in this tick that has not been supplied yet
+    let mut deficit = BTreeMap::<NodeId, ElectricEnergy>::new();
+    for (&root, grid) in &by_root {
+        let supply = energy(grid.supply);
+        let demand = energy(grid.demand);
+        if supply < demand {
+            deficit.insert(root, demand - supply);
+        } else {
+            available.insert(root, supply - demand);
+        }
+    }
+
+    let mut site_demand = BTreeMap::<NodeId, ElectricEnergy>::new();
+    for (&node, site) in <(&NodeId, &mut Site)>::query().iter_mut(world) {
+        let root = find_root(&mut parents, node);
+        let available = available.entry(root).or_default();
+        *available -= site.supply_energy(def, *available);
+        *site_demand.entry(root).or_default() += site.remaining_energy(def);
+    }
+
+    // Discharge batteries for factories first, then construction sites
+    let mut discharged = BTreeMap::<NodeId, ElectricEnergy>::new();
+    for (&node, battery) in <(&NodeId, &mut Battery)>::query().iter_mut(world) {
+        let root = find_root(&mut parents, node);
+        let pool = discharged.entry(root).or_default();
+        let wanted = deficit.get(&root).copied().unwrap_or_default()
+            + site_demand.get(&root).copied().unwrap_or_default()
+            - *pool;
+        let amount = min(battery.discharge_limit(), wanted);
+        if amount.0 > 0. {
+            battery.stored -= amount;
+            *pool += amount;
+        }
+    }
+    for (root, grid) in &mut by_root {
+        let pool = discharged.entry(*root).or_default();
+        grid.charge -= power(*pool);
+        let deficit = deficit.get(root).copied().unwrap_or_default();
+        let covered = min(*pool, deficit);
+        *pool -= covered;
+        grid.unmet = power(deficit - covered);
+    }
+    for (&node, site) in <(&NodeId, &mut Site)>::query().iter_mut(world) {
+        let root = find_root(&mut parents, node);
+        let pool = discharged.entry(root).or_default();
+        *pool -= site.supply_energy(def, *pool);
+    }
+
+    // Charge batteries with the surplus
+    for (&node, battery) in <(&NodeId, &mut Battery)>::query().iter_mut(world) {
+        let root = find_root(&mut parents, node);
+        let grid = by_root.get_mut(&root).expect("All nodes have a grid");
+        let available = available.entry(root).or_default();
+        let amount = min(battery.charge_limit(), *available);
+        if amount.0 > 0. {
+            battery.stored += amount;
+            *available -= amount;
+            grid.charge += power(amount);
+        }
+        grid.stored += battery.stored;
+        grid.capacity += battery.capacity;
+    }
+
+    grids.grids = by_root.into_iter().map(|(_, grid)| grid).collect();
+}
+
+/// Initializes ECS
+pub fn setup_ecs(setup: SetupEcs) -> SetupEcs {
+    setup.resource(Grids::default()).uses(solve_grid_setup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn battery_rate_limits() {
+        let mut battery = Battery::new(ElectricEnergy(100.), ElectricPower(30.));
+        assert_eq!(battery.charge_limit(), ElectricEnergy(30.));
+        assert_eq!(battery.discharge_limit(), ElectricEnergy(0.));
+
+        battery.stored = ElectricEnergy(80.);
+        assert_eq!(battery.charge_limit(), ElectricEnergy(20.));
+        assert_eq!(battery.discharge_limit(), ElectricEnergy(30.));
+
+        battery.stored = ElectricEnergy(10.);
+        assert_eq!(battery.discharge_limit(), ElectricEnergy(10.));
+    }
+
+    #[test]
+    fn grid_stats() {
+        let mut grid = Grid {
+            supply: ElectricPower(20.),
+            demand: ElectricPower(50.),
+            unmet: ElectricPower(10.),
+            charge: ElectricPower(-20.),
+            stored: ElectricEnergy(300.),
+            ..Grid::default()
+        };
+        assert_eq!(grid.endurance(), Some(10.));
+        assert_eq!(grid.available(), ElectricPower(40.));
+        assert!((grid.satisfaction() - 0.8).abs() < 1e-9);
+
+        grid.supply = ElectricPower(60.);
+        grid.unmet = ElectricPower(0.);
+        grid.charge = ElectricPower(10.);
+        assert_eq!(grid.endurance(), None);
+        assert_eq!(grid.available(), ElectricPower(60.));
+        assert!((grid.satisfaction() - 1.).abs() < 1e-9);
+    }
+}
@@ -1,18 +1,1361 @@
 //! Manages factory building logic.
 
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+use derive_new::new;
+use legion::world::SubWorld;
+use legion::Entity;
+use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
 
-use crate::def;
+use crate::cargo;
+use crate::clock::{SimulationEvent, SIMULATION_PERIOD};
+use crate::config;
+use crate::construction::Site;
+use crate::def::{self, building, reaction, skill, tech, GameDefinition};
+use crate::education::Student;
+use crate::electricity::{Grid, Grids};
+use crate::gas::GasPool;
+use crate::graph::{NodeBuilding, NodeId};
+use crate::happiness::Happiness;
+use crate::inhabitant::{DeathEvent, Location};
+use crate::liquid::LiquidPool;
+use crate::research::{Locked, Research};
+use crate::skill::Skills;
+use crate::sun::{LightStats, Sun};
+use crate::thermal::Thermal;
+use crate::units::{self, CargoSize};
 use crate::SetupEcs;
 
 /// A component attached to buildings that can perform reactions.
-#[derive(getset::Getters)]
+#[derive(new, getset::Getters)]
 pub struct Factory {
     /// List of reactions supported by this factory.
     #[getset(get = "pub")]
     reactions: SmallVec<[def::reaction::TypeId; 2]>,
 }
+
+/// A component attached to inhabitants assigned to operate a factory.
+///
+/// Operators serve as [skill catalysts][reaction::CatalystRange::Skill] of the reactions
+/// and receive the [skill changes][reaction::Put::Skill] of the reactions they are not catalysts of.
+#[derive(Debug, Clone, Copy, new, getset::CopyGetters)]
+pub struct Operator {
+    /// The node entity of the factory
+    #[getset(get_copy = "pub")]
+    factory: Entity,
+}
+
+/// The player-configured rate of a reaction.
+#[derive(Debug, Clone, Copy, PartialEq, new, getset::CopyGetters, Serialize, Deserialize)]
+pub struct Setting {
+    /// Whether the reaction is enabled
+    #[getset(get_copy = "pub")]
+    enabled: bool,
+    /// The fraction of the full rate, between 0 and 1
+    #[getset(get_copy = "pub")]
+    rate: f64,
+}
+
+impl Setting {
+    /// The multiplier applied on the reaction rate.
+    pub fn multiplier(self) -> f64 {
+        if self.enabled {
+            self.rate
+        } else {
+            0.
+        }
+    }
+}
+
+impl Default for Setting {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            rate: 1.,
+        }
+    }
+}
+
+/// A component storing the player-configured rate of each reaction in a factory.
+///
+/// Settings are in the same order as [`Factory::reactions`].
+#[derive(Debug, Clone, getset::Getters)]
+pub struct Throttle {
+    /// The settings of each reaction
+    #[getset(get = "pub")]
+    settings: SmallVec<[Setting; 2]>,
+}
+
+impl Throttle {
+    /// Creates a throttle with all reactions enabled at full rate.
+    pub fn new(count: usize) -> Self {
+        Self {
+            settings: (0..count).map(|_| Setting::default()).collect(),
+        }
+    }
+
+    /// Returns the setting of the reaction at `index`.
+    pub fn get(&self, index: usize) -> Setting {
+        self.settings.get(index).copied().unwrap_or_default()
+    }
+}
+
+/// Requests to change the rate of a reaction in a factory.
+#[derive(Debug, new, getset::CopyGetters)]
+pub struct ThrottleRequestEvent {
+    /// The factory node
+    #[getset(get_copy = "pub")]
+    node: NodeId,
+    /// The reaction to throttle
+    #[getset(get_copy = "pub")]
+    reaction: reaction::TypeId,
+    /// The new setting
+    #[getset(get_copy = "pub")]
+    setting: Setting,
+}
+
+/// The reason for rejecting a throttle request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ThrottleRejection {
+    /// The node does not exist or is not a factory.
+    NoSuchFactory,
+    /// The factory does not support the reaction.
+    NoSuchReaction,
+    /// The building does not allow players to configure the reaction.
+    NotConfigurable,
+    /// The node is still under construction.
+    UnderConstruction,
+    /// The reaction has not been unlocked by research.
+    Locked(Locked),
+}
+
+/// Indicates that a throttle request has been rejected.
+#[derive(Debug, new, getset::CopyGetters, getset::Getters)]
+pub struct ThrottleRejectEvent {
+    /// The factory node
+    #[getset(get_copy = "pub")]
+    node: NodeId,
+    /// The requested reaction
+    #[getset(get_copy = "pub")]
+    reaction: reaction::TypeId,
+    /// The reason for rejection
+    #[getset(get = "pub")]
+    reason: ThrottleRejection,
+}
+
+#[codegen::system]
+#[read_component(NodeId)]
+#[read_component(NodeBuilding)]
+#[read_component(Factory)]
+#[write_component(Throttle)]
+#[read_component(Site)]
+fn set_throttle(
+    world: &mut SubWorld,
+    #[resource] def: &GameDefinition,
+    #[resource] research: &Research,
+    #[subscriber] requests: impl Iterator<Item = ThrottleRequestEvent>,
+    #[publisher] reject_pub: impl FnMut(ThrottleRejectEvent),
+) {
+    use legion::IntoQuery;
+
+    for request in requests {
+        let mut result = Err(ThrottleRejection::NoSuchFactory);
+        for (&node, building, factory, throttle, site) in <(
+            &NodeId,
+            &NodeBuilding,
+            &Factory,
+            &mut Throttle,
+            Option<&Site>,
+        )>::query()
+        .iter_mut(world)
+        {
+            if node != request.node {
+                continue;
+            }
+            result = apply_throttle(def, research, request, building, factory, throttle, site);
+            break;
+        }
+
+        if let Err(reason) = result {
+            reject_pub(ThrottleRejectEvent {
+                node: request.node,
+                reaction: request.reaction,
+                reason,
+            });
+        }
+    }
+}
+
+fn apply_throttle(
+    def: &GameDefinition,
+    research: &Research,
+    request: &ThrottleRequestEvent,
+    building: &NodeBuilding,
+    factory: &Factory,
+    throttle: &mut Throttle,
+    site: Option<&Site>,
+) -> Result<(), ThrottleRejection> {
+    let index = check_throttle(def, research, request, building, factory, site)?;
+    let setting = throttle
+        .settings
+        .get_mut(index)
+        .expect("Throttle has fewer settings than Factory reactions");
+    *setting = Setting {
+        enabled: request.setting.enabled,
+        rate: request.setting.rate.clamp(0., 1.),
+    };
+    Ok(())
+}
+
+/// Checks whether a throttle request can be applied to a factory.
+///
+/// Returns the index of the requested reaction in [`Factory::reactions`].
+pub(crate) fn check_throttle(
+    def: &GameDefinition,
+    research: &Research,
+    request: &ThrottleRequestEvent,
+    building: &NodeBuilding,
+    factory: &Factory,
+    site: Option<&Site>,
+) -> Result<usize, ThrottleRejection> {
+    let index = factory
+        .reactions
+        .iter()
+        .position(|&reaction| reaction == request.reaction)
+        .ok_or(ThrottleRejection::NoSuchReaction)?;
+    let configurable = def
+        .get_building(building.building())
+        .reactions()
+        .iter()
+        .any(|&(reaction, policy)| reaction == request.reaction && policy.configurable());
+    if !configurable {
+        return Err(ThrottleRejection::NotConfigurable);
+    }
+    if site.is_some() {
+        return Err(ThrottleRejection::UnderConstruction);
+    }
+    research
+        .require(def, tech::Unlock::Reaction(request.reaction))
+        .map_err(ThrottleRejection::Locked)?;
+    Ok(index)
+}
+
+/// A component storing why each reaction in a factory ran below its base rate
+/// in the last simulation tick.
+///
+/// Reports are in the same order as [`Factory::reactions`].
+/// Reports are not updated while the node is under construction.
+#[derive(Debug, Clone, Default, getset::Getters)]
+pub struct Diagnostics {
+    /// The report of each reaction
+    #[getset(get = "pub")]
+    reactions: SmallVec<[ReactionReport; 2]>,
+}
+
+impl Diagnostics {
+    /// Returns the report of the reaction at `index`.
+    pub fn get(&self, index: usize) -> Option<&ReactionReport> {
+        self.reactions.get(index)
+    }
+}
+
+/// The rate of a reaction in the last simulation tick and the factors limiting it.
+#[derive(Debug, Clone, Default, getset::CopyGetters, getset::Getters)]
+pub struct ReactionReport {
+    /// The player-configured multiplier, from [`Setting::multiplier`]
+    #[getset(get_copy = "pub")]
+    throttle: f64,
+    /// The effect of each catalyst, in the same order as [`reaction::Type::catalysts`]
+    #[getset(get = "pub")]
+    catalysts: SmallVec<[CatalystReport; 2]>,
+    /// The index of the last input in [`reaction::Type::puts`] that was insufficient
+    #[getset(get_copy = "pub")]
+    limiting_input: Option<usize>,
+    /// The indices of the outputs in [`reaction::Type::puts`] without enough storage space
+    #[getset(get = "pub")]
+    overflows: SmallVec<[usize; 1]>,
+    /// The effective multiplier on the base rate
+    #[getset(get_copy = "pub")]
+    rate: f64,
+}
+
+impl ReactionReport {
+    /// The combined multiplier from all catalysts.
+    pub fn catalyst_multiplier(&self) -> f64 {
+        self.catalysts
+            .iter()
+            .map(|catalyst| catalyst.multiplier)
+            .product()
+    }
+
+    /// The fraction of the player-configured rate that was not achieved.
+    pub fn loss(&self) -> f64 {
+        (self.throttle - self.rate).max(0.)
+    }
+
+    /// The factor that reduced the rate the most, if the rate is below the throttle.
+    pub fn cause(&self) -> Option<Cause> {
+        if self.loss() <= 0. {
+            return None;
+        }
+
+        let catalyst = self
+            .catalysts
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                a.multiplier
+                    .partial_cmp(&b.multiplier)
+                    .unwrap_or(Ordering::Equal)
+            })
+            .filter(|(_, catalyst)| catalyst.multiplier < 1.)
+            .map(|(index, catalyst)| (Cause::Catalyst(index), catalyst.multiplier));
+
+        let catalysed = self.throttle * self.catalyst_multiplier();
+        let flow_factor = if catalysed > 0. {
+            self.rate / catalysed
+        } else {
+            1.
+        };
+        let flow = self
+            .overflows
+            .first()
+            .map(|&index| Cause::Output(index))
+            .or_else(|| self.limiting_input.map(Cause::Input))
+            .filter(|_| flow_factor < 1.)
+            .map(|cause| (cause, flow_factor));
+
+        match (catalyst, flow) {
+            (Some((catalyst, a)), Some((flow, b))) => Some(if a <= b { catalyst } else { flow }),
+            (Some((cause, _)), None) | (None, Some((cause, _))) => Some(cause),
+            (None, None) => None,
+        }
+    }
+}
+
+/// The effect of a catalyst on a reaction.
+#[derive(Debug, Clone, Copy, getset::CopyGetters)]
+pub struct CatalystReport {
+    /// The multiplier applied on the reaction rate
+    #[getset(get_copy = "pub")]
+    multiplier: f64,
+    /// The zone of the catalyst level relative to its lerp endpoints
+    #[getset(get_copy = "pub")]
+    zone: reaction::Zone,
+}
+
+/// The factor that reduced the rate of a reaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cause {
+    /// The catalyst at the index in [`reaction::Type::catalysts`] has a multiplier below 1.
+    Catalyst(usize),
+    /// The input at the index in [`reaction::Type::puts`] is insufficient.
+    Input(usize),
+    /// The output at the index in [`reaction::Type::puts`] has insufficient storage space.
+    Output(usize),
+}
+
+/// A reaction running below its player-configured rate.
+#[derive(Debug, Clone, Copy, getset::CopyGetters)]
+pub struct Bottleneck {
+    /// The factory node
+    #[getset(get_copy = "pub")]
+    node: NodeId,
+    /// The reaction type
+    #[getset(get_copy = "pub")]
+    reaction: reaction::TypeId,
+    /// The fraction of the player-configured rate that was not achieved
+    #[getset(get_copy = "pub")]
+    loss: f64,
+    /// The factor that reduced the rate the most
+    #[getset(get_copy = "pub")]
+    cause: Cause,
+}
+
+/// A resource listing the worst bottlenecks in the colony in the last simulation tick.
+///
+/// Entries are sorted by descending loss,
+/// and at most [`config::Scalar::bottleneck_count`] entries are kept.
+#[derive(Debug, Default, getset::Getters)]
+pub struct Bottlenecks {
+    /// The bottleneck entries
+    #[getset(get = "pub")]
+    entries: Vec<Bottleneck>,
+}
+
+/// A component storing the electric power generated by a factory in the last simulation tick.
+///
+/// Consumption by reactions is counted as negative generation.
+#[derive(Debug, Clone, Copy, Default, getset::CopyGetters)]
+pub struct Power {
+    /// The net power generated
+    #[getset(get_copy = "pub")]
+    net: units::ElectricPower,
+}
+
+/// The catalyst levels available to a factory.
+struct Stock {
+    temperature: units::Temperature,
+    cargo: BTreeMap<def::cargo::TypeId, SmallVec<[Entity; 1]>>,
+    cargo_sizes: BTreeMap<Entity, (CargoSize, CargoSize)>,
+    brightness: units::Brightness,
+    liquid: LiquidPool,
+    gas: GasPool,
+    /// The power available to the grid of the factory in the last simulation tick
+    power: units::ElectricPower,
+    /// The fraction of the power demand of the grid supplied in the last simulation tick
+    satisfaction: f64,
+    operators: Vec<(Entity, Skills)>,
+    students: Vec<(Entity, Skills)>,
+}
+
+impl Stock {
+    /// The most skilled operator in a skill type and their level.
+    fn best_operator(&self, ty: skill::TypeId) -> Option<(Entity, units::Skill)> {
+        self.operators
+            .iter()
+            .map(|(entity, skills)| (*entity, skills.get(ty)))
+            .fold(None, |best, (entity, level)| match best {
+                Some((_, best_level)) if best_level >= level => best,
+                _ => Some((entity, level)),
+            })
+    }
+
+    fn cargo(&self, ty: def::cargo::TypeId) -> CargoSize {
+        self.storages(ty).map(|(size, _)| size).sum()
+    }
+
+    fn cargo_space(&self, ty: def::cargo::TypeId) -> CargoSize {
+        self.storages(ty)
+            .map(|(size, capacity)| capacity - size)
+            .sum()
+    }
+
+    fn storages(
+        &self,
+        ty: def::cargo::TypeId,
+    ) -> impl Iterator<Item = (CargoSize, CargoSize)> + '_ {
+        self.cargo
+            .get(&ty)
+            .into_iter()
+            .flatten()
+            .filter_map(move |storage| self.cargo_sizes.get(storage).copied())
+    }
+
+    /// Adds `delta` to the storages of a cargo type, limited by their sizes and capacities.
+    ///
+    /// Returns the part of `delta` that could not be stored.
+    fn put_cargo(&mut self, ty: def::cargo::TypeId, mut delta: CargoSize) -> CargoSize {
+        let storages = match self.cargo.get(&ty) {
+            Some(storages) => storages,
+            None => return delta,
+        };
+        for storage in storages {
+            let (size, capacity) = match self.cargo_sizes.get_mut(storage) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            let mut next = *size + delta;
+            if next < CargoSize::default() {
+                next = CargoSize::default();
+            } else if next > *capacity {
+                next = *capacity;
+            }
+            delta -= next - *size;
+            *size = next;
+        }
+        delta
+    }
+}
+
+/// Computes the effect of each catalyst of a reaction.
+fn catalyst_reports(reaction: &reaction::Type, stock: &Stock) -> SmallVec<[CatalystReport; 2]> {
+    fn report<U: units::Unit>(
+        multipliers: reaction::Multipliers,
+        levels: &Range<U>,
+        level: U,
+    ) -> CatalystReport {
+        CatalystReport {
+            multiplier: multipliers.get(levels, level),
+            zone: reaction::Zone::of(levels, level),
+        }
+    }
+
+    reaction
+        .catalysts()
+        .iter()
+        .map(|catalyst| {
+            let multipliers = catalyst.multipliers();
+            match catalyst.range() {
+                reaction::CatalystRange::Cargo { ty, levels } => {
+                    report(multipliers, levels, stock.cargo(*ty))
+                }
+                reaction::CatalystRange::Liquid { ty, levels } => {
+                    report(multipliers, levels, stock.liquid.get(*ty))
+                }
+                reaction::CatalystRange::Gas { ty, levels } => {
+                    report(multipliers, levels, stock.gas.get(*ty))
+                }
+                reaction::CatalystRange::Electricity { levels } => {
+                    report(multipliers, levels, stock.power)
+                }
+                reaction::CatalystRange::Light { levels } => {
+                    report(multipliers, levels, stock.brightness)
+                }
+                reaction::CatalystRange::Heat { levels } => {
+                    report(multipliers, levels, stock.temperature)
+                }
+                reaction::CatalystRange::Skill { ty, levels } => report(
+                    multipliers,
+                    levels,
+                    stock
+                        .best_operator(*ty)
+                        .map(|(_, level)| level)
+                        .unwrap_or_default(),
+                ),
+            }
+        })
+        .collect()
+}
+
+/// The reaction rate after applying the flow policies of the building.
+struct Limit {
+    rate: f64,
+    /// The rate at which electricity is requested from the grid
+    powered: f64,
+    limiting_input: Option<usize>,
+    overflows: SmallVec<[usize; 1]>,
+}
+
+/// Limits the reaction rate according to the flow policies of the building.
+fn limit_rate(
+    reaction: &reaction::Type,
+    policy: building::ReactionPolicy,
+    stock: &Stock,
+    rate: f64,
+) -> Limit {
+    let mut limit = Limit {
+        rate,
+        powered: rate,
+        limiting_input: None,
+        overflows: SmallVec::new(),
+    };
+    for (index, put) in reaction.puts().iter().enumerate() {
+        let (delta, available, space) = match *put {
+            reaction::Put::Cargo { ty, base } => (
+                (base * SIMULATION_PERIOD).value(),
+                stock.cargo(ty).value(),
+                stock.cargo_space(ty).value(),
+            ),
+            reaction::Put::Liquid { ty, base } => (
+                (base * SIMULATION_PERIOD).value(),
+                stock.liquid.get(ty).value(),
+                (stock.liquid.capacity() - stock.liquid.total()).value(),
+            ),
+            reaction::Put::Gas { ty, base } => (
+                (base * SIMULATION_PERIOD).value(),
+                stock.gas.get(ty).value(),
+                (stock.gas.capacity() - stock.gas.total()).value(),
+            ),
+            _ => continue,
+        };
+        let delta = delta * limit.rate;
+        if put.is_input() {
+            if available < -delta {
+                limit.limiting_input = Some(index);
+                match policy.on_underflow() {
+                    building::FlowPolicy::Halt => limit.rate = 0.,
+                    _ => limit.rate *= available / -delta,
+                }
+            }
+        } else if space < delta {
+            limit.overflows.push(index);
+            match policy.on_overflow() {
+                building::FlowPolicy::Halt => limit.rate = 0.,
+                building::FlowPolicy::Dump if matches!(put, reaction::Put::Cargo { .. }) => {}
+                building::FlowPolicy::Vent if !matches!(put, reaction::Put::Cargo { .. }) => {}
+                _ => limit.rate *= space / delta,
+            }
+        }
+    }
+
+    // the grid only supplies part of the power demand
+    limit.powered = limit.rate;
+    let consumer = reaction
+        .puts()
+        .iter()
+        .position(|put| matches!(put, reaction::Put::Electricity { .. }) && put.is_input());
+    if let Some(index) = consumer {
+        if stock.satisfaction < 1. {
+            limit.limiting_input = Some(index);
+            match policy.on_underflow() {
+                building::FlowPolicy::Halt => limit.rate = 0.,
+                _ => limit.rate *= stock.satisfaction.max(0.),
+            }
+        }
+    }
+    limit
+}
+
+/// Lists the reactions of a factory with their indices and policies in the order they run.
+///
+/// Higher priority reactions take shared inputs and output capacity first.
+fn reaction_order(
+    factory: &Factory,
+    policies: &[(reaction::TypeId, building::ReactionPolicy)],
+) -> SmallVec<[(usize, reaction::TypeId, building::ReactionPolicy); 2]> {
+    let mut order: SmallVec<[_; 2]> = factory
+        .reactions()
+        .iter()
+        .enumerate()
+        .map(|(index, &reaction_id)| {
+            let policy = policies
+                .iter()
+                .find(|&&(id, _)| id == reaction_id)
+                .map(|&(_, policy)| policy)
+                .unwrap_or_default();
+            (index, reaction_id, policy)
+        })
+        .collect();
+    order.sort_by_key(|&(_, _, policy)| {
+        Reverse(
+            policy
+                .on_underflow()
+                .priority()
+                .max(policy.on_overflow().priority()),
+        )
+    });
+    order
+}
+
+/// Indicates that overflowing cargo has been dumped into a node.
+#[derive(Debug, new, getset::CopyGetters)]
+pub struct CargoDumpEvent {
+    /// The node where the cargo is dumped
+    #[getset(get_copy = "pub")]
+    node: NodeId,
+    /// The type of cargo dumped
+    #[getset(get_copy = "pub")]
+    cargo: def::cargo::TypeId,
+    /// The amount of cargo dumped
+    #[getset(get_copy = "pub")]
+    size: CargoSize,
+    /// The happiness lost by each inhabitant in the node
+    #[getset(get_copy = "pub")]
+    penalty: units::Happiness,
+}
+
+/// Applies a skill change of `delta` on `level`,
+/// without training beyond the skill `cap` of the teacher.
+///
+/// Returns the new level and whether the attendee has reached the cap.
+fn train(
+    level: units::Skill,
+    delta: units::Skill,
+    cap: Option<units::Skill>,
+) -> (units::Skill, bool) {
+    let mut next = level + delta;
+    if let Some(cap) = cap {
+        if delta.0 > 0. && next > cap {
+            next = if level > cap { level } else { cap };
+        }
+    }
+    if next.0 < 0. {
+        next = units::Skill(0.);
+    }
+    let graduated = matches!(cap, Some(cap) if next >= cap);
+    (next, graduated)
+}
+
+#[codegen::system]
+#[read_component(NodeId)]
+#[read_component(Factory)]
+#[read_component(Throttle)]
+#[read_component(NodeBuilding)]
+#[read_component(LightStats)]
+#[read_component(Site)]
+#[read_component(cargo::StorageList)]
+#[read_component(cargo::Storage)]
+#[write_component(cargo::NextStorageSize)]
+#[write_component(Diagnostics)]
+#[write_component(Power)]
+#[write_component(Thermal)]
+#[write_component(LiquidPool)]
+#[write_component(GasPool)]
+#[read_component(Operator)]
+#[read_component(Student)]
+#[write_component(Skills)]
+#[read_component(Location)]
+#[write_component(Happiness)]
+fn react(
+    world: &mut SubWorld,
+    cmd_buf: &mut legion::systems::CommandBuffer,
+    #[resource] def: &GameDefinition,
+    #[resource] sun: &Sun,
+    #[resource] grids: &Grids,
+    #[resource] research: &mut Research,
+    #[resource] config: &config::Scalar,
+    #[resource] bottlenecks: &mut Bottlenecks,
+    #[subscriber] sim_sub: impl Iterator<Item = SimulationEvent>,
+    #[subscriber] death_sub: impl Iterator<Item = DeathEvent>,
+    #[publisher] dump_pub: impl FnMut(CargoDumpEvent),
+) {
+    use legion::{EntityStore, IntoQuery};
+
+    // dead operators and students no longer serve as catalysts
+    let dead: BTreeSet<Entity> = death_sub.map(|death| death.inhabitant()).collect();
+
+    if sim_sub.next().is_none() {
+        return;
+    }
+
+    let mut operators = BTreeMap::<Entity, Vec<(Entity, Skills)>>::new();
+    for (&entity, operator, skills) in <(Entity, &Operator, &Skills)>::query().iter(world) {
+        if dead.contains(&entity) {
+            continue;
+        }
+        operators
+            .entry(operator.factory)
+            .or_default()
+            .push((entity, skills.clone()));
+    }
+    // students still travelling to the school are excluded in the factory loop
+    let mut students = BTreeMap::<Entity, Vec<(Entity, Skills, NodeId)>>::new();
+    for (&entity, student, skills, location) in
+        <(Entity, &Student, &Skills, &Location)>::query().iter(world)
+    {
+        if dead.contains(&entity) {
+            continue;
+        }
+        students.entry(student.school()).or_default().push((
+            entity,
+            skills.clone(),
+            location.node(),
+        ));
+    }
+
+    let node_grids: BTreeMap<NodeId, &Grid> = grids
+        .grids()
+        .iter()
+        .flat_map(|grid| grid.nodes().iter().map(move |&node| (node, grid)))
+        .collect();
+
+    let mut updates = Vec::new();
+    let mut diagnostics = Vec::new();
+    let mut entries = Vec::new();
+    let mut training = Vec::new();
+    let mut cheer = BTreeMap::<NodeId, units::Happiness>::new();
+    for (&entity, &node, factory, throttle, building, list, site) in <(
+        Entity,
+        &NodeId,
+        &Factory,
+        &Throttle,
+        &NodeBuilding,
+        Option<&cargo::StorageList>,
+        Option<&Site>,
+    )>::query()
+    .iter(world)
+    {
+        if site.is_some() {
+            continue;
+        }
+
+        // node environment is read separately to keep the query tuple small
+        let node_entry = world
+            .entry_ref(entity)
+            .expect("Factory entity does not exist");
+        let mut stock = Stock {
+            temperature: node_entry
+                .get_component::<Thermal>()
+                .expect("Factory entity does not have Thermal")
+                .temperature(config),
+            cargo: BTreeMap::new(),
+            cargo_sizes: BTreeMap::new(),
+            brightness: node_entry
+                .get_component::<LightStats>()
+                .expect("Factory entity does not have LightStats")
+                .current(sun),
+            liquid: node_entry
+                .get_component::<LiquidPool>()
+                .expect("Factory entity does not have LiquidPool")
+                .clone(),
+            gas: node_entry
+                .get_component::<GasPool>()
+                .expect("Factory entity does not have GasPool")
+                .clone(),
+            power: node_grids
+                .get(&node)
+                .map(|grid| grid.available())
+                .unwrap_or_default(),
+            satisfaction: node_grids.get(&node).map_or(1., |grid| grid.satisfaction()),
+            operators: operators.remove(&entity).unwrap_or_default(),
+            students: students
+                .remove(&entity)
+                .into_iter()
+                .flatten()
+                .filter(|&(_, _, location)| location == node)
+                .map(|(student, skills, _)| (student, skills))
+                .collect(),
+        };
+        for &(ty, storage) in list.into_iter().flat_map(|list| list.storages()) {
+            let entry = world
+                .entry_ref(storage)
+                .expect("Storage entity does not exist");
+            let capacity = entry
+                .get_component::<cargo::Storage>()
+                .expect("Storage entity does not have Storage")
+                .capacity();
+            let size = entry
+                .get_component::<cargo::NextStorageSize>()
+                .expect("Storage entity does not have NextStorageSize")
+                .size();
+            stock.cargo.entry(ty).or_default().push(storage);
+            stock.cargo_sizes.insert(storage, (size, capacity));
+        }
+
+        let order = reaction_order(factory, def.get_building(building.building()).reactions());
+
+        let mut reports: SmallVec<[_; 2]> = factory
+            .reactions()
+            .iter()
+            .map(|_| ReactionReport::default())
+            .collect();
+        let mut power = units::ElectricPower::default();
+        let mut heat = units::Heat::default();
+        for (index, reaction_id, policy) in order {
+            let reaction = def.get_reaction(reaction_id);
+            let catalysts = catalyst_reports(reaction, &stock);
+            let throttle = throttle.get(index).multiplier();
+            let rate = throttle
+                * catalysts
+                    .iter()
+                    .map(|catalyst| catalyst.multiplier)
+                    .product::<f64>();
+            let limit = limit_rate(reaction, policy, &stock, rate);
+            let (rate, powered) = (limit.rate, limit.powered);
+            let report = ReactionReport {
+                throttle,
+                catalysts,
+                limiting_input: limit.limiting_input,
+                overflows: limit.overflows,
+                rate,
+            };
+            if let Some(cause) = report.cause() {
+                entries.push(Bottleneck {
+                    node,
+                    reaction: reaction_id,
+                    loss: report.loss(),
+                    cause,
+                });
+            }
+            if let Some(slot) = reports.get_mut(index) {
+                *slot = report;
+            }
+
+            // power is requested even if the grid cannot supply it,
+            // so that the shortage is still reported in the next tick
+            for put in reaction.puts() {
+                if let reaction::Put::Electricity { base } = *put {
+                    if put.is_input() {
+                        power += base.0 * powered;
+                    }
+                }
+            }
+            if rate <= 0. {
+                continue;
+            }
+
+            // the operators serving as skill catalysts and their levels
+            let teachers: SmallVec<[_; 1]> = reaction
+                .catalysts()
+                .iter()
+                .filter_map(|catalyst| match catalyst.range() {
+                    reaction::CatalystRange::Skill { ty, .. } => stock
+                        .best_operator(*ty)
+                        .map(|(teacher, level)| (teacher, *ty, level)),
+                    _ => None,
+                })
+                .collect();
+
+            for put in reaction.puts() {
+                match *put {
+                    reaction::Put::Cargo { ty, base } => {
+                        let excess = stock.put_cargo(ty, base * SIMULATION_PERIOD * rate);
+                        if excess > CargoSize::default()
+                            && policy.on_overflow() == building::FlowPolicy::Dump
+                        {
+                            dump_pub(CargoDumpEvent {
+                                node,
+                                cargo: ty,
+                                size: excess,
+                                penalty: units::Happiness(excess.value() * config.dump_penalty),
+                            });
+                        }
+                    }
+                    reaction::Put::Research { base } => {
+                        research.add_points(base * SIMULATION_PERIOD * rate);
+                    }
+                    reaction::Put::Electricity { base } => {
+                        // consumption is counted at the requested rate above
+                        if put.is_output() {
+                            power += base.0 * rate;
+                        }
+                    }
+                    reaction::Put::Heat { base } => {
+                        heat += base * SIMULATION_PERIOD * rate;
+                    }
+                    reaction::Put::Liquid { ty, base } => {
+                        // overflowing liquid is vented to space
+                        stock.liquid.add(ty, base * SIMULATION_PERIOD * rate);
+                    }
+                    reaction::Put::Gas { ty, base } => {
+                        // overflowing gas is vented to space
+                        stock.gas.add(ty, base * SIMULATION_PERIOD * rate);
+                    }
+                    reaction::Put::Skill { ty, base } => {
+                        // attendees cannot be trained beyond the teacher
+                        let cap = teachers
+                            .iter()
+                            .find(|&&(_, skill, _)| skill == ty)
+                            .map(|&(_, _, level)| level);
+                        let delta = base * SIMULATION_PERIOD * rate;
+                        for (attendee, _) in stock.operators.iter().chain(&stock.students) {
+                            if teachers.iter().all(|&(teacher, _, _)| teacher != *attendee) {
+                                training.push((*attendee, ty, delta, cap));
+                            }
+                        }
+                    }
+                    reaction::Put::Happiness { base } => {
+                        *cheer.entry(node).or_default() += base * SIMULATION_PERIOD * rate;
+                    }
+                }
+            }
+        }
+
+        updates.extend(stock.cargo_sizes);
+        diagnostics.push((entity, reports, power, heat, stock.liquid, stock.gas));
+    }
+
+    for (entity, reports, power, heat, liquid, gas) in diagnostics {
+        let mut entry = world
+            .entry_mut(entity)
+            .expect("Factory entity does not exist");
+        entry
+            .get_component_mut::<Diagnostics>()
+            .expect("Factory entity does not have Diagnostics")
+            .reactions = reports;
+        entry
+            .get_component_mut::<Power>()
+            .expect("Factory entity does not have Power")
+            .net = power;
+        entry
+            .get_component_mut::<Thermal>()
+            .expect("Factory entity does not have Thermal")
+            .add_heat(heat);
+        *entry
+            .get_component_mut::<LiquidPool>()
+            .expect("Factory entity does not have LiquidPool") = liquid;
+        *entry
+            .get_component_mut::<GasPool>()
+            .expect("Factory entity does not have GasPool") = gas;
+    }
+
+    for (attendee, ty, delta, cap) in training {
+        let mut entry = match world.entry_mut(attendee) {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        let skills = entry
+            .get_component_mut::<Skills>()
+            .expect("Attendee entity does not have Skills");
+        let (next, graduated) = train(skills.get(ty), delta, cap);
+        skills.set(ty, next);
+
+        if graduated && entry.get_component::<Student>().is_ok() {
+            cmd_buf.remove_component::<Student>(attendee);
+        }
+    }
+
+    for (location, happiness) in <(&Location, &mut Happiness)>::query().iter_mut(world) {
+        if let Some(&delta) = cheer.get(&location.node()) {
+            happiness.add(delta);
+        }
+    }
+
+    entries.sort_by(|a, b| b.loss.partial_cmp(&a.loss).unwrap_or(Ordering::Equal));
+    entries.truncate(config.bottleneck_count);
+    bottlenecks.entries = entries;
+
+    for (storage, (size, _)) in updates {
+        let mut entry = world
+            .entry_mut(storage)
+            .expect("Storage entity does not exist");
+        *entry
+            .get_component_mut::<cargo::NextStorageSize>()
+            .expect("Storage entity does not have NextStorageSize")
+            .size_mut() = size;
+    }
+}
+
 /// Initializes ECS
 pub fn setup_ecs(setup: SetupEcs) -> SetupEcs {
     setup
+        .resource(Bottlenecks::default())
+        .uses(set_throttle_setup)
+        .uses(react_setup)
+}
+
+#[cfg(test)]
+mod tests {
+    use arcstr::literal;
+    use smallvec::smallvec;
+
+    use super::*;
+    use crate::testing;
+    use crate::time::Rate;
+
+    fn stock(world: &mut legion::World, cargo: &[(def::cargo::TypeId, f64, f64)]) -> Stock {
+        let mut stock = Stock {
+            temperature: units::Temperature(300.),
+            cargo: BTreeMap::new(),
+            cargo_sizes: BTreeMap::new(),
+            brightness: units::Brightness(0.),
+            liquid: LiquidPool::new(units::LiquidVolume(100.)),
+            gas: GasPool::new(units::GasVolume(100.)),
+            power: units::ElectricPower(0.),
+            satisfaction: 1.,
+            operators: Vec::new(),
+            students: Vec::new(),
+        };
+        for &(ty, size, capacity) in cargo {
+            let storage = world.push((ty,));
+            stock.cargo.entry(ty).or_default().push(storage);
+            stock
+                .cargo_sizes
+                .insert(storage, (CargoSize(size), CargoSize(capacity)));
+        }
+        stock
+    }
+
+    #[test]
+    fn catalyst_zones() {
+        let mut def = GameDefinition::default();
+        let water = def::liquid::TypeId(0);
+        let multipliers = reaction::Multipliers::builder()
+            .underflow(0.1)
+            .min(0.2)
+            .max(0.8)
+            .overflow(0.9)
+            .build();
+        let reaction = testing::reaction(
+            &mut def,
+            vec![
+                reaction::Catalyst::builder()
+                    .range(reaction::CatalystRange::Liquid {
+                        ty: water,
+                        levels: units::LiquidVolume(10.)..units::LiquidVolume(20.),
+                    })
+                    .multipliers(multipliers)
+                    .build(),
+                reaction::Catalyst::builder()
+                    .range(reaction::CatalystRange::Electricity {
+                        levels: units::ElectricPower(0.)..units::ElectricPower(100.),
+                    })
+                    .multipliers(testing::multipliers(0., 1.))
+                    .build(),
+            ],
+            Vec::new(),
+        );
+        let reaction = def.get_reaction(reaction);
+
+        let mut stock = stock(&mut legion::World::default(), &[]);
+        stock.power = units::ElectricPower(50.);
+        let expected = [
+            (5., reaction::Zone::Underflow, 0.1),
+            (5., reaction::Zone::Min, 0.2),
+            (5., reaction::Zone::Lerp, 0.5),
+            (5., reaction::Zone::Max, 0.8),
+            (5., reaction::Zone::Overflow, 0.9),
+        ];
+        for &(delta, zone, multiplier) in &expected {
+            stock.liquid.add(water, units::LiquidVolume(delta));
+            let reports = catalyst_reports(reaction, &stock);
+            assert_eq!(reports.len(), 2);
+            let liquid = reports.first().expect("Reports are not empty");
+            assert_eq!(liquid.zone(), zone);
+            assert!((liquid.multiplier() - multiplier).abs() < 1e-9);
+            let power = reports.last().expect("Reports are not empty");
+            assert_eq!(power.zone(), reaction::Zone::Lerp);
+            assert!((power.multiplier() - 0.5).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn limit_liquid_and_cargo() {
+        let mut def = GameDefinition::default();
+        let water = def::liquid::TypeId(0);
+        let rock = def::cargo::TypeId(0);
+        let reaction = testing::reaction(
+            &mut def,
+            Vec::new(),
+            vec![
+                reaction::Put::Liquid {
+                    ty: water,
+                    base: Rate(units::LiquidVolume(-20.)),
+                },
+                reaction::Put::Cargo {
+                    ty: rock,
+                    base: Rate(CargoSize(10.)),
+                },
+            ],
+        );
+        let reaction = def.get_reaction(reaction);
+        let policy = building::ReactionPolicy::default();
+
+        let mut world = legion::World::default();
+        let mut stock = stock(&mut world, &[(rock, 0., 100.)]);
+        let limit = limit_rate(reaction, policy, &stock, 1.);
+        assert!(limit.rate <= 0.);
+        assert_eq!(limit.limiting_input, Some(0));
+
+        stock.liquid.add(water, units::LiquidVolume(10.));
+        let limit = limit_rate(reaction, policy, &stock, 1.);
+        assert!((limit.rate - 0.5).abs() < 1e-9);
+        assert!(limit.overflows.is_empty());
+
+        let mut stock = self::stock(&mut world, &[(rock, 98., 100.)]);
+        stock.liquid.add(water, units::LiquidVolume(100.));
+        let limit = limit_rate(reaction, policy, &stock, 1.);
+        assert!((limit.rate - 0.2).abs() < 1e-9);
+        assert_eq!(limit.limiting_input, None);
+        assert_eq!(&limit.overflows[..], &[1]);
+    }
+
+    #[test]
+    fn flow_policies() {
+        let mut def = GameDefinition::default();
+        let water = def::liquid::TypeId(0);
+        let rock = def::cargo::TypeId(0);
+        let consume = testing::reaction(
+            &mut def,
+            Vec::new(),
+            vec![reaction::Put::Liquid {
+                ty: water,
+                base: Rate(units::LiquidVolume(-20.)),
+            }],
+        );
+        let produce = testing::reaction(
+            &mut def,
+            Vec::new(),
+            vec![reaction::Put::Liquid {
+                ty: water,
+                base: Rate(units::LiquidVolume(20.)),
+            }],
+        );
+        let mine = testing::reaction(
+            &mut def,
+            Vec::new(),
+            vec![reaction::Put::Cargo {
+                ty: rock,
+                base: Rate(CargoSize(10.)),
+            }],
+        );
+        let policy = |on_underflow, on_overflow| {
+            building::ReactionPolicy::builder()
+                .on_underflow(on_underflow)
+                .on_overflow(on_overflow)
+                .build()
+        };
+        let mut world = legion::World::default();
+        let mut liquid = stock(&mut world, &[]);
+        liquid.liquid.add(water, units::LiquidVolume(10.));
+        let mut full = stock(&mut world, &[]);
+        full.liquid.add(water, units::LiquidVolume(90.));
+        let cargo = stock(&mut world, &[(rock, 98., 100.)]);
+
+        use building::FlowPolicy::{Dump, Halt, ReduceRate, Vent};
+        let cases = [
+            (consume, &liquid, ReduceRate, ReduceRate, 0.5),
+            (consume, &liquid, Halt, ReduceRate, 0.),
+            (consume, &liquid, Vent, Vent, 0.5),
+            (produce, &full, ReduceRate, ReduceRate, 0.5),
+            (produce, &full, ReduceRate, Halt, 0.),
+            (produce, &full, ReduceRate, Vent, 1.),
+            (produce, &full, ReduceRate, Dump, 0.5),
+            (mine, &cargo, ReduceRate, Vent, 0.2),
+            (mine, &cargo, ReduceRate, Dump, 1.),
+            (mine, &cargo, ReduceRate, Halt, 0.),
+        ];
+        for &(reaction, stock, on_underflow, on_overflow, rate) in &cases {
+            let limit = limit_rate(
+                def.get_reaction(reaction),
+                policy(on_underflow, on_overflow),
+                stock,
+                1.,
+            );
+            assert!(
+                (limit.rate - rate).abs() < 1e-9,
+                "{:?} {:?} {:?}: {} != {}",
+                reaction,
+                on_underflow,
+                on_overflow,
+                limit.rate,
+                rate
+            );
+        }
+    }
+
+    #[test]
+    fn priority_order() {
+        let reactions: SmallVec<[_; 2]> = (0..4).map(reaction::TypeId).collect();
+        let policy = |on_underflow, on_overflow| {
+            building::ReactionPolicy::builder()
+                .on_underflow(on_underflow)
+                .on_overflow(on_overflow)
+                .build()
+        };
+        let policies = vec![
+            (reactions[0], building::ReactionPolicy::default()),
+            (
+                reactions[1],
+                policy(
+                    building::FlowPolicy::Priority(1),
+                    building::FlowPolicy::Halt,
+                ),
+            ),
+            (
+                reactions[2],
+                policy(
+                    building::FlowPolicy::ReduceRate,
+                    building::FlowPolicy::Priority(2),
+                ),
+            ),
+        ];
+        let factory = Factory::new(reactions.clone());
+
+        let order: Vec<_> = reaction_order(&factory, &policies)
+            .into_iter()
+            .map(|(index, reaction, _)| (index, reaction))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                (2, reactions[2]),
+                (1, reactions[1]),
+                (0, reactions[0]),
+                (3, reactions[3]),
+            ]
+        );
+    }
+
+    #[test]
+    fn limit_by_grid_satisfaction() {
+        let mut def = GameDefinition::default();
+        let reaction = testing::reaction(
+            &mut def,
+            Vec::new(),
+            vec![reaction::Put::Electricity {
+                base: Rate(units::ElectricPower(-10.)),
+            }],
+        );
+        let reaction = def.get_reaction(reaction);
+        let halt = building::ReactionPolicy::builder()
+            .on_underflow(building::FlowPolicy::Halt)
+            .build();
+
+        let mut stock = stock(&mut legion::World::default(), &[]);
+        let limit = limit_rate(reaction, building::ReactionPolicy::default(), &stock, 0.8);
+        assert!((limit.rate - 0.8).abs() < 1e-9);
+        assert_eq!(limit.limiting_input, None);
+
+        stock.satisfaction = 0.25;
+        let limit = limit_rate(reaction, building::ReactionPolicy::default(), &stock, 0.8);
+        assert!((limit.rate - 0.2).abs() < 1e-9);
+        assert!((limit.powered - 0.8).abs() < 1e-9);
+        assert_eq!(limit.limiting_input, Some(0));
+
+        let limit = limit_rate(reaction, halt, &stock, 0.8);
+        assert!(limit.rate <= 0.);
+        assert!((limit.powered - 0.8).abs() < 1e-9);
+    }
+
+    #[test]
+    fn check_throttle_rejections() {
+        let mut def = GameDefinition::default();
+        let locked = testing::reaction(&mut def, Vec::new(), Vec::new());
+        let fixed = testing::reaction(&mut def, Vec::new(), Vec::new());
+        let free = testing::reaction(&mut def, Vec::new(), Vec::new());
+        let configurable = building::ReactionPolicy::builder()
+            .configurable(true)
+            .build();
+        let building = testing::building(
+            &mut def,
+            vec![
+                (locked, configurable),
+                (fixed, building::ReactionPolicy::default()),
+                (free, configurable),
+            ],
+            testing::storage(0., 0., 0.),
+            Vec::new(),
+        );
+        def.add_tech(
+            tech::Type::builder()
+                .id(literal!("test:tech"))
+                .name(literal!(""))
+                .description(literal!(""))
+                .prerequisites(SmallVec::new())
+                .costs(SmallVec::new())
+                .unlocks(smallvec![tech::Unlock::Reaction(locked)])
+                .build(),
+        );
+
+        let research = Research::default();
+        let node = NodeBuilding::new(building);
+        let factory = Factory::new(smallvec![locked, fixed, free]);
+        let check = |reaction, site: Option<&Site>| {
+            let request = ThrottleRequestEvent::new(NodeId::new(0), reaction, Setting::default());
+            check_throttle(&def, &research, &request, &node, &factory, site)
+        };
+
+        assert!(matches!(check(free, None), Ok(2)));
+        assert!(matches!(
+            check(reaction::TypeId(3), None),
+            Err(ThrottleRejection::NoSuchReaction)
+        ));
+        assert!(matches!(
+            check(fixed, None),
+            Err(ThrottleRejection::NotConfigurable)
+        ));
+        assert!(matches!(
+            check(free, Some(&Site::new(building))),
+            Err(ThrottleRejection::UnderConstruction)
+        ));
+        assert!(matches!(
+            check(locked, None),
+            Err(ThrottleRejection::Locked(_))
+        ));
+    }
+
+    #[test]
+    fn train_up_to_teacher() {
+        let expected = [
+            (2., 1., Some(5.), 3., false),
+            (4., 2., Some(5.), 5., true),
+            (6., 2., Some(5.), 6., true),
+            (4., 2., None, 6., false),
+            (1., -2., Some(5.), 0., false),
+        ];
+        for &(level, delta, cap, next, graduated) in &expected {
+            let (level, done) = train(
+                units::Skill(level),
+                units::Skill(delta),
+                cap.map(units::Skill),
+            );
+            assert!((level.0 - next).abs() < 1e-9);
+            assert_eq!(done, graduated);
+        }
+    }
 }
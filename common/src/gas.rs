@@ -0,0 +1,19 @@
+//! Gas stored in nodes.
+//!
+//! Each node stores gases up to the gas storage of its building,
+//! shared by all gas types.
+//! Gas is produced and consumed by [reactions][crate::def::reaction::Put::Gas]
+//! and breathed by inhabitants.
+//!
+//! Gas diffusion through corridors is not simulated yet.
+
+use crate::def::gas;
+use crate::pool::{Fluid, Pool};
+use crate::units::GasVolume;
+
+impl Fluid for gas::TypeId {
+    type Volume = GasVolume;
+}
+
+/// A component storing the gases in a node.
+pub type GasPool = Pool<gas::TypeId>;
@@ -1,20 +1,28 @@
 //! Basic node and edge management
 
-use std::collections::BTreeMap;
+use std::collections::{btree_map, BTreeMap, VecDeque};
 use std::num::NonZeroUsize;
 
 use arcstr::ArcStr;
 use derive_new::new;
 use legion::Entity;
+use serde::{Deserialize, Serialize};
 
 use crate::def::{building, GameDefinition};
+use crate::electricity::Battery;
+use crate::factory::{Diagnostics, Factory, Power, Throttle};
+use crate::gas::GasPool;
+use crate::liquid::LiquidPool;
 use crate::shape::{self, Shape};
 use crate::space::{Matrix, Position, Vector};
 use crate::sun::LightStats;
+use crate::thermal::Thermal;
+use crate::units;
+use crate::waste;
 use crate::SetupEcs;
 
 /// Component storing an identifier for a node
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, new)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, new, Serialize, Deserialize)]
 pub struct NodeId {
     inner: u32,
 }
@@ -28,6 +36,33 @@ pub struct NodeName {
     name: ArcStr,
 }
 
+/// Component storing the building type of a node
+#[derive(Debug, Clone, Copy, new, getset::CopyGetters)]
+pub struct NodeBuilding {
+    /// The building type
+    #[getset(get_copy = "pub")]
+    building: building::TypeId,
+}
+
+/// Component storing the remaining hitpoints of a node
+#[derive(Debug, Clone, Copy, new, getset::CopyGetters)]
+pub struct NodeHitpoint {
+    /// The remaining hitpoints
+    #[getset(get_copy = "pub")]
+    hitpoint: units::Hitpoint,
+}
+
+impl NodeHitpoint {
+    /// Reduces the hitpoints of the node, stopping at zero.
+    ///
+    /// Returns true if the hitpoints have just been depleted.
+    pub fn damage(&mut self, damage: units::Hitpoint) -> bool {
+        let alive = self.hitpoint.0 > 0.;
+        self.hitpoint = units::Hitpoint((self.hitpoint.0 - damage.0).max(0.));
+        alive && self.hitpoint.0 <= 0.
+    }
+}
+
 /// Component storing the endpoints of an edge
 #[derive(Debug, Clone, Copy, PartialEq, Eq, new, getset::CopyGetters, getset::Setters)]
 pub struct EdgeId {
@@ -82,11 +117,14 @@ pub struct PostNodeRemoveEvent {
 }
 
 /// Indicates that an edge is added
-#[derive(Debug, new, getset::Getters)]
+#[derive(Debug, new, getset::Getters, getset::CopyGetters)]
 pub struct EdgeAddEvent {
     /// The added edge
     #[getset(get = "pub")]
     edge: EdgeId,
+    /// The size of the added edge
+    #[getset(get_copy = "pub")]
+    size: EdgeSize,
 }
 
 /// Indicates that an edge is flagged for removal
@@ -137,13 +175,97 @@ fn delete_nodes(
     }
 }
 
+/// Collects the neighbours of each node from the edges.
+///
+/// Corridors can be passed in both directions.
+pub fn adjacency<'t>(edges: impl IntoIterator<Item = &'t EdgeId>) -> BTreeMap<NodeId, Vec<NodeId>> {
+    let mut adjacency = BTreeMap::<NodeId, Vec<NodeId>>::new();
+    for edge in edges {
+        adjacency.entry(edge.from).or_default().push(edge.to);
+        adjacency.entry(edge.to).or_default().push(edge.from);
+    }
+    adjacency
+}
+
+/// Finds the shortest path from `start` to the nearest node satisfying `goal`.
+///
+/// The returned path excludes `start`, so it is empty if `start` satisfies `goal`.
+pub fn route(
+    adjacency: &BTreeMap<NodeId, Vec<NodeId>>,
+    start: NodeId,
+    goal: impl Fn(NodeId) -> bool,
+) -> Option<Vec<NodeId>> {
+    let mut parents = BTreeMap::new();
+    parents.insert(start, start);
+    let mut queue = VecDeque::new();
+    queue.push_back(start);
+
+    while let Some(node) = queue.pop_front() {
+        if goal(node) {
+            let mut path = Vec::new();
+            let mut current = node;
+            while current != start {
+                path.push(current);
+                current = *parents.get(&current).expect("Visited node has no parent");
+            }
+            path.reverse();
+            return Some(path);
+        }
+        for &next in adjacency.get(&node).into_iter().flatten() {
+            if let btree_map::Entry::Vacant(entry) = parents.entry(next) {
+                entry.insert(node);
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+/// Computes the number of corridors between `start` and every node reachable from it.
+pub fn distances(
+    adjacency: &BTreeMap<NodeId, Vec<NodeId>>,
+    start: NodeId,
+) -> BTreeMap<NodeId, usize> {
+    let mut distances = BTreeMap::new();
+    distances.insert(start, 0);
+    let mut queue = VecDeque::new();
+    queue.push_back((start, 0));
+
+    while let Some((node, distance)) = queue.pop_front() {
+        for &next in adjacency.get(&node).into_iter().flatten() {
+            if let btree_map::Entry::Vacant(entry) = distances.entry(next) {
+                entry.insert(distance + 1);
+                queue.push_back((next, distance + 1));
+            }
+        }
+    }
+    distances
+}
+
 /// Initializes ECS
 pub fn setup_ecs(setup: SetupEcs) -> SetupEcs {
     setup.uses(delete_nodes_setup)
 }
 
 /// Return type of [`create_node_components`].
-pub type NodeComponents = (NodeId, NodeName, Position, Shape, LightStats);
+pub type NodeComponents = (
+    NodeId,
+    NodeName,
+    NodeBuilding,
+    NodeHitpoint,
+    Position,
+    Shape,
+    LightStats,
+    Factory,
+    Throttle,
+    Diagnostics,
+    Power,
+    Battery,
+    Thermal,
+    waste::Overflow,
+    GasPool,
+    LiquidPool,
+);
 
 /// Creates the components for a node entity.
 pub fn create_node_components(
@@ -157,6 +279,8 @@ pub fn create_node_components(
     (
         NodeId::new(rand::random()),
         NodeName::new(building.name().clone()),
+        NodeBuilding::new(id),
+        NodeHitpoint::new(*building.hitpoint()),
         position,
         Shape::builder()
             .unit(shape::Unit::Cube)
@@ -167,6 +291,24 @@ pub fn create_node_components(
             ))
             .build(),
         LightStats::default(),
+        Factory::new(
+            building
+                .reactions()
+                .iter()
+                .map(|&(reaction, _)| reaction)
+                .collect(),
+        ),
+        Throttle::new(building.reactions().len()),
+        Diagnostics::default(),
+        Power::default(),
+        Battery::new(
+            building.storage().electricity(),
+            building.storage().charge_rate(),
+        ),
+        Thermal::default(),
+        waste::Overflow::default(),
+        GasPool::new(building.storage().gas()),
+        LiquidPool::new(building.storage().liquid()),
     )
 }
 
@@ -193,6 +335,12 @@ pub fn edge_tf(
         .get_component()
         .expect("to node does not have Position");
 
+    edge_tf_between(from, to, size, from_unit)
+}
+
+/// Computes the transformation matrix from or to the unit cylinder
+/// for an edge between the given endpoint positions
+pub fn edge_tf_between(from: Position, to: Position, size: &EdgeSize, from_unit: bool) -> Matrix {
     let dir = to - from;
     let rot = match nalgebra::Rotation3::rotation_between(&Vector::new(0., 0., 1.), &dir) {
         Some(rot) => rot.to_homogeneous(),
@@ -212,3 +360,36 @@ pub fn edge_tf(
             ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn route_shortest_path() {
+        let nodes: Vec<NodeId> = (0..5).map(NodeId::new).collect();
+        let edges: Vec<EdgeId> = [(0, 1), (1, 2), (2, 3), (0, 4), (4, 3)]
+            .iter()
+            .map(|&(from, to)| EdgeId::new(nodes[from], nodes[to]))
+            .collect();
+        let adjacency = adjacency(&edges);
+
+        assert_eq!(
+            route(&adjacency, nodes[0], |node| node == nodes[3]),
+            Some(vec![nodes[4], nodes[3]])
+        );
+        assert_eq!(
+            route(&adjacency, nodes[2], |node| node == nodes[2]),
+            Some(vec![])
+        );
+        assert_eq!(
+            route(&adjacency, nodes[0], |node| node == NodeId::new(9)),
+            None
+        );
+
+        let distances = distances(&adjacency, nodes[0]);
+        assert_eq!(distances[&nodes[2]], 2);
+        assert_eq!(distances[&nodes[3]], 2);
+        assert_eq!(distances.get(&NodeId::new(9)), None);
+    }
+}
@@ -0,0 +1,81 @@
+//! Happiness of inhabitants.
+//!
+//! The [happiness changes][crate::def::reaction::Put::Happiness] of reactions
+//! apply to all inhabitants in the node.
+//! Inhabitants lose happiness when cargo is [dumped][CargoDumpEvent] into their node
+//! and when their node has [full waste storage][waste::Overflow].
+//! Inhabitants with negative happiness are *outlaws*,
+//! who are arrested by [police][crate::security::Police].
+
+use std::collections::BTreeMap;
+
+use derive_new::new;
+use legion::world::SubWorld;
+
+use crate::clock::{SimulationEvent, SIMULATION_PERIOD};
+use crate::factory::CargoDumpEvent;
+use crate::graph::NodeId;
+use crate::inhabitant::Location;
+use crate::units;
+use crate::waste;
+use crate::SetupEcs;
+
+/// A component storing the happiness of an inhabitant.
+#[derive(Debug, Clone, Copy, new, getset::CopyGetters)]
+pub struct Happiness {
+    /// The current happiness
+    #[getset(get_copy = "pub")]
+    value: units::Happiness,
+}
+
+impl Happiness {
+    /// Changes the happiness by `delta`.
+    pub fn add(&mut self, delta: units::Happiness) {
+        self.value += delta;
+    }
+
+    /// Whether the inhabitant is an outlaw.
+    pub fn is_outlaw(&self) -> bool {
+        self.value.0 < 0.
+    }
+}
+
+#[codegen::system]
+#[read_component(NodeId)]
+#[read_component(waste::Overflow)]
+#[read_component(Location)]
+#[write_component(Happiness)]
+fn penalize(
+    world: &mut SubWorld,
+    #[subscriber] sim_sub: impl Iterator<Item = SimulationEvent>,
+    #[subscriber] dump_sub: impl Iterator<Item = CargoDumpEvent>,
+) {
+    use legion::IntoQuery;
+
+    let mut penalties = BTreeMap::<NodeId, units::Happiness>::new();
+    for dump in dump_sub {
+        *penalties.entry(dump.node()).or_default() += dump.penalty();
+    }
+    if sim_sub.next().is_some() {
+        for (&node, overflow) in <(&NodeId, &waste::Overflow)>::query().iter(world) {
+            if overflow.penalty().0 > 0. {
+                *penalties.entry(node).or_default() +=
+                    overflow.penalty() * SIMULATION_PERIOD.as_secs();
+            }
+        }
+    }
+    if penalties.is_empty() {
+        return;
+    }
+
+    for (location, happiness) in <(&Location, &mut Happiness)>::query().iter_mut(world) {
+        if let Some(&penalty) = penalties.get(&location.node()) {
+            happiness.add(units::Happiness(-penalty.0));
+        }
+    }
+}
+
+/// Initializes ECS
+pub fn setup_ecs(setup: SetupEcs) -> SetupEcs {
+    setup.uses(penalize_setup)
+}
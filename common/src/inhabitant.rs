@@ -0,0 +1,540 @@
+//! Breathing, aging, health and death of inhabitants.
+//!
+//! Each simulation tick, inhabitants consume the [inhaled][gas::Respiration::Inhaled] gases
+//! from the [`GasPool`] of the node they are in
+//! and release the same amount of [exhaled][gas::Respiration::Exhaled] gases.
+//! If the node does not have enough gas,
+//! inhabitants use the bottles of the gas in their [`Inventory`].
+//!
+//! The [age][skill::Kind::Age] of inhabitants increases with game time,
+//! and their [trained][skill::Kind::Trained] skills grow slowly, faster when they are young.
+//! [Health][skill::Kind::Health] is lost when inhabitants cannot breathe,
+//! when their node has full waste storage, when they are hurt by crimes
+//! and increasingly as they get old.
+//! Inhabitants die when their health drops to zero or they reach the maximum age.
+//!
+//! Inhabitants with a [`Destination`] walk through the corridors by one node every simulation tick,
+//! unless they are carried by a [vehicle][crate::vehicle::Passenger].
+
+use std::collections::{btree_map, BTreeMap};
+
+use derive_new::new;
+use legion::world::SubWorld;
+use legion::Entity;
+use serde::{Deserialize, Serialize};
+
+use crate::clock::{SimulationEvent, SIMULATION_PERIOD};
+use crate::config;
+use crate::def::{cargo, gas, skill, GameDefinition};
+use crate::gas::GasPool;
+use crate::graph::{self, EdgeId, Graph, NodeId};
+use crate::happiness::Happiness;
+use crate::skill::Skills;
+use crate::units::{self, CargoSize, GasVolume};
+use crate::vehicle::Passenger;
+use crate::waste;
+use crate::SetupEcs;
+use safety::Safety;
+
+/// Component storing an identifier for an inhabitant
+///
+/// Unlike the entity, the identifier is stable across clients and replays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, new, Serialize, Deserialize)]
+pub struct InhabitantId {
+    inner: u32,
+}
+
+/// A component storing the node an inhabitant is in.
+#[derive(Debug, Clone, Copy, new, getset::CopyGetters)]
+pub struct Location {
+    /// The node containing the inhabitant
+    #[getset(get_copy = "pub")]
+    node: NodeId,
+}
+
+/// A component attached to inhabitants travelling to another node.
+///
+/// The component is removed when the inhabitant arrives at the node
+/// or when the node is no longer reachable.
+#[derive(Debug, Clone, Copy, new, getset::CopyGetters)]
+pub struct Destination {
+    /// The node to travel to
+    #[getset(get_copy = "pub")]
+    node: NodeId,
+}
+
+/// A component storing the cause of the most recent health loss of an inhabitant.
+#[derive(Debug, Clone, Copy, Default, getset::CopyGetters)]
+pub struct Injury {
+    /// The cause of the most recent health loss
+    #[getset(get_copy = "pub")]
+    cause: Option<DeathCause>,
+}
+
+impl Injury {
+    /// Reduces the health of an inhabitant and records the cause.
+    pub fn apply(
+        &mut self,
+        skills: &mut Skills,
+        health: skill::TypeId,
+        damage: units::Skill,
+        cause: DeathCause,
+    ) {
+        if damage.0 > 0. {
+            skills.add(health, units::Skill(-damage.0));
+            self.cause = Some(cause);
+        }
+    }
+}
+
+/// A component storing the cargo carried by an inhabitant or a scheduled vehicle.
+#[derive(Debug, Clone, Default)]
+pub struct Inventory {
+    cargo: BTreeMap<cargo::TypeId, CargoSize>,
+}
+
+impl Inventory {
+    /// Returns the amount of a cargo type carried.
+    pub fn get(&self, ty: cargo::TypeId) -> CargoSize {
+        self.cargo.get(&ty).copied().unwrap_or_default()
+    }
+
+    /// Adds cargo to the inventory.
+    pub fn add(&mut self, ty: cargo::TypeId, size: CargoSize) {
+        *self.cargo.entry(ty).or_default() += size;
+    }
+
+    /// Removes up to `size` of a cargo type from the inventory.
+    ///
+    /// Returns the amount actually removed.
+    pub fn take(&mut self, ty: cargo::TypeId, size: CargoSize) -> CargoSize {
+        let stored = match self.cargo.get_mut(&ty) {
+            Some(stored) => stored,
+            None => return CargoSize::default(),
+        };
+        let taken = if *stored < size { *stored } else { size };
+        *stored -= taken;
+        if stored.0 <= 0. {
+            self.cargo.remove(&ty);
+        }
+        taken
+    }
+
+    /// Iterates over the carried cargo.
+    pub fn iter(&self) -> impl Iterator<Item = (cargo::TypeId, CargoSize)> + '_ {
+        self.cargo.iter().map(|(&ty, &size)| (ty, size))
+    }
+}
+
+/// The cause of death of an inhabitant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DeathCause {
+    /// The inhabitant could not breathe.
+    Suffocation,
+    /// The inhabitant stayed in a node with full waste storage.
+    Waste,
+    /// The inhabitant was hurt by a crime.
+    Crime,
+    /// The inhabitant reached the maximum age or lost health due to old age.
+    Aging,
+}
+
+/// Requests to reduce the health of an inhabitant.
+#[derive(Debug, new, getset::CopyGetters)]
+pub struct InjuryEvent {
+    /// The injured inhabitant
+    #[getset(get_copy = "pub")]
+    inhabitant: Entity,
+    /// The health lost
+    #[getset(get_copy = "pub")]
+    damage: units::Skill,
+    /// The cause of the injury
+    #[getset(get_copy = "pub")]
+    cause: DeathCause,
+}
+
+/// Indicates that an inhabitant has died.
+///
+/// Systems referring to the inhabitant entity, such as housing and vehicles,
+/// should release it when this event is received.
+#[derive(Debug, new, getset::CopyGetters)]
+pub struct DeathEvent {
+    /// The inhabitant entity, which is removed in the same tick
+    #[getset(get_copy = "pub")]
+    inhabitant: Entity,
+    /// The node where the inhabitant died
+    #[getset(get_copy = "pub")]
+    node: NodeId,
+    /// The cause of death
+    #[getset(get_copy = "pub")]
+    cause: DeathCause,
+}
+
+/// A resource storing the number of deaths by each cause.
+#[derive(Debug, Default)]
+pub struct Deaths {
+    counts: BTreeMap<DeathCause, u32>,
+}
+
+impl Deaths {
+    /// Returns the number of deaths by a cause.
+    pub fn get(&self, cause: DeathCause) -> u32 {
+        self.counts.get(&cause).copied().unwrap_or_default()
+    }
+
+    /// Returns the total number of deaths.
+    pub fn total(&self) -> u32 {
+        self.counts.values().sum()
+    }
+
+    /// Iterates over the causes with at least one death.
+    pub fn iter(&self) -> impl Iterator<Item = (DeathCause, u32)> + '_ {
+        self.counts.iter().map(|(&cause, &count)| (cause, count))
+    }
+}
+
+/// Return type of [`create_components`].
+pub type InhabitantComponents = (InhabitantId, Location, Skills, Inventory, Injury, Happiness);
+
+/// Creates the components for a newborn inhabitant in a node.
+pub fn create_components(
+    def: &GameDefinition,
+    config: &config::Scalar,
+    node: NodeId,
+) -> InhabitantComponents {
+    let mut skills = Skills::default();
+    if let Some(health) = def.find_skill_kind(skill::Kind::Health) {
+        skills.set(health, units::Skill(config.initial_health));
+    }
+    (
+        InhabitantId::new(rand::random()),
+        Location::new(node),
+        skills,
+        Inventory::default(),
+        Injury::default(),
+        Happiness::new(units::Happiness(config.initial_happiness)),
+    )
+}
+
+#[codegen::system]
+#[read_component(Location)]
+#[write_component(Skills)]
+#[write_component(Injury)]
+#[write_component(Inventory)]
+#[write_component(GasPool)]
+fn breathe(
+    world: &mut SubWorld,
+    #[resource] def: &GameDefinition,
+    #[resource] graph: &Graph,
+    #[resource] config: &config::Scalar,
+    #[subscriber] sim_sub: impl Iterator<Item = SimulationEvent>,
+) {
+    use legion::{EntityStore, IntoQuery};
+
+    if sim_sub.next().is_none() {
+        return;
+    }
+
+    let mut inhaled = Vec::new();
+    let mut exhaled = Vec::new();
+    for (index, ty) in def.gas().iter().enumerate() {
+        let id = gas::TypeId(index);
+        match ty.respiration() {
+            gas::Respiration::Inert => {}
+            gas::Respiration::Inhaled => inhaled.push((id, def.gas_bottle(id))),
+            gas::Respiration::Exhaled => exhaled.push(id),
+        }
+    }
+    if inhaled.is_empty() {
+        return;
+    }
+    let health = def.find_skill_kind(skill::Kind::Health);
+
+    let need = GasVolume(config.respiration_rate * SIMULATION_PERIOD.as_secs());
+    let inhabitants: Vec<(Entity, NodeId)> = <(Entity, &Location)>::query()
+        .iter(world)
+        .map(|(&entity, location)| (entity, location.node))
+        .collect();
+
+    let mut pools = BTreeMap::new();
+    for (entity, node) in inhabitants {
+        let node_entity = graph.get_node(node);
+        if let Some(node_entity) = node_entity {
+            if let btree_map::Entry::Vacant(vacant) = pools.entry(node_entity) {
+                let pool = world
+                    .entry_ref(node_entity)
+                    .ok()
+                    .and_then(|entry| entry.get_component::<GasPool>().ok().cloned());
+                if let Some(pool) = pool {
+                    vacant.insert(pool);
+                }
+            }
+        }
+        let mut pool = node_entity.and_then(|node_entity| pools.get_mut(&node_entity));
+
+        let mut entry = world
+            .entry_mut(entity)
+            .expect("Inhabitant entity does not exist");
+
+        // the fraction of the required gases breathed in this tick
+        let mut breathed = 0.;
+        for &(ty, bottle) in &inhaled {
+            let mut unmet = need;
+            if let Some(pool) = pool.as_mut() {
+                unmet = GasVolume(-pool.add(ty, GasVolume(-need.0)).0);
+            }
+            if unmet.0 > 0. {
+                if let (Some((bottle, volume)), Ok(inventory)) =
+                    (bottle, entry.get_component_mut::<Inventory>())
+                {
+                    let taken = inventory.take(bottle, CargoSize(unmet.0 / volume.0));
+                    unmet -= volume * taken.0;
+                }
+            }
+            breathed += 1. - unmet.0 / need.0;
+        }
+        let breathed = breathed / inhaled.len().small_float::<f64>();
+
+        if let Some(pool) = pool.as_mut() {
+            for &ty in &exhaled {
+                pool.add(ty, need * breathed);
+            }
+        }
+
+        if let Some(health) = health {
+            let damage = units::Skill(
+                config.suffocation_damage * SIMULATION_PERIOD.as_secs() * (1. - breathed),
+            );
+            let mut skills = entry
+                .get_component::<Skills>()
+                .expect("Inhabitant entity does not have Skills")
+                .clone();
+            entry
+                .get_component_mut::<Injury>()
+                .expect("Inhabitant entity does not have Injury")
+                .apply(&mut skills, health, damage, DeathCause::Suffocation);
+            *entry
+                .get_component_mut::<Skills>()
+                .expect("Inhabitant entity does not have Skills") = skills;
+        }
+    }
+
+    for (node_entity, pool) in pools {
+        let mut entry = world
+            .entry_mut(node_entity)
+            .expect("Node entity does not exist");
+        *entry
+            .get_component_mut::<GasPool>()
+            .expect("Node entity does not have GasPool") = pool;
+    }
+}
+
+#[codegen::system]
+#[read_component(Location)]
+#[write_component(Skills)]
+#[write_component(Injury)]
+#[read_component(waste::Overflow)]
+fn live(
+    world: &mut SubWorld,
+    cmd_buf: &mut legion::systems::CommandBuffer,
+    #[resource] def: &GameDefinition,
+    #[resource] graph: &Graph,
+    #[resource] config: &config::Scalar,
+    #[resource] deaths: &mut Deaths,
+    #[subscriber] sim_sub: impl Iterator<Item = SimulationEvent>,
+    #[subscriber] injury_sub: impl Iterator<Item = InjuryEvent>,
+    #[publisher] death_pub: impl FnMut(DeathEvent),
+) {
+    use legion::{EntityStore, IntoQuery};
+
+    let age = def.find_skill_kind(skill::Kind::Age);
+    let health = def.find_skill_kind(skill::Kind::Health);
+
+    for injury in injury_sub {
+        let health = match health {
+            Some(health) => health,
+            None => continue,
+        };
+        let mut entry = match world.entry_mut(injury.inhabitant) {
+            Ok(entry) => entry,
+            Err(_) => continue, // the inhabitant has already died
+        };
+        let mut skills = match entry.get_component::<Skills>() {
+            Ok(skills) => skills.clone(),
+            Err(_) => continue,
+        };
+        if let Ok(record) = entry.get_component_mut::<Injury>() {
+            record.apply(&mut skills, health, injury.damage, injury.cause);
+        }
+        if let Ok(slot) = entry.get_component_mut::<Skills>() {
+            *slot = skills;
+        }
+    }
+
+    if sim_sub.next().is_none() {
+        return;
+    }
+
+    let secs = SIMULATION_PERIOD.as_secs();
+    let years = secs / def.orbit().year_length().as_secs();
+    let trained: Vec<skill::TypeId> = def
+        .skill()
+        .iter()
+        .enumerate()
+        .filter(|(_, skill)| skill.kind() == skill::Kind::Trained)
+        .map(|(index, _)| skill::TypeId(index))
+        .collect();
+
+    let inhabitants: Vec<(Entity, NodeId)> = <(Entity, &Location)>::query()
+        .iter(world)
+        .map(|(&entity, location)| (entity, location.node))
+        .collect();
+    for (entity, node) in inhabitants {
+        let full_waste = graph
+            .get_node(node)
+            .and_then(|node_entity| world.entry_ref(node_entity).ok())
+            .and_then(|entry| {
+                entry
+                    .get_component::<waste::Overflow>()
+                    .ok()
+                    .map(|overflow| overflow.full().len())
+            })
+            .unwrap_or_default();
+
+        let mut entry = world
+            .entry_mut(entity)
+            .expect("Inhabitant entity does not exist");
+        let mut skills = match entry.get_component::<Skills>() {
+            Ok(skills) => skills.clone(),
+            Err(_) => continue,
+        };
+        let mut injury = entry
+            .get_component::<Injury>()
+            .ok()
+            .copied()
+            .unwrap_or_default();
+
+        // the fraction of the maximum age reached
+        let mut maturity = 0.;
+        if let Some(age) = age {
+            skills.add(age, units::Skill(years));
+            maturity = skills.get(age).0 / config.max_age;
+        }
+        let growth = config.skill_growth * secs * (1. - maturity).max(0.);
+        for &ty in &trained {
+            skills.add(ty, units::Skill(growth));
+        }
+
+        let mut dead = None;
+        if let Some(health) = health {
+            let aging = config.aging_damage * secs * maturity * maturity;
+            injury.apply(&mut skills, health, units::Skill(aging), DeathCause::Aging);
+            let waste = config.waste_damage * secs * full_waste.small_float::<f64>();
+            injury.apply(&mut skills, health, units::Skill(waste), DeathCause::Waste);
+            if skills.get(health).0 <= 0. {
+                dead = Some(injury.cause.unwrap_or(DeathCause::Aging));
+            }
+        }
+        if dead.is_none() && maturity >= 1. {
+            dead = Some(DeathCause::Aging);
+        }
+
+        if let Ok(slot) = entry.get_component_mut::<Skills>() {
+            *slot = skills;
+        }
+        if let Ok(slot) = entry.get_component_mut::<Injury>() {
+            *slot = injury;
+        }
+
+        if let Some(cause) = dead {
+            *deaths.counts.entry(cause).or_default() += 1;
+            death_pub(DeathEvent {
+                inhabitant: entity,
+                node,
+                cause,
+            });
+            cmd_buf.remove(entity);
+        }
+    }
+}
+
+#[codegen::system]
+#[read_component(EdgeId)]
+#[read_component(Destination)]
+#[read_component(Passenger)]
+#[write_component(Location)]
+fn walk(
+    world: &mut SubWorld,
+    cmd_buf: &mut legion::systems::CommandBuffer,
+    #[subscriber] sim_sub: impl Iterator<Item = SimulationEvent>,
+) {
+    use legion::IntoQuery;
+
+    if sim_sub.next().is_none() {
+        return;
+    }
+
+    let adjacency = graph::adjacency(<&EdgeId>::query().iter(world));
+    for (&entity, location, destination) in <(Entity, &mut Location, &Destination)>::query()
+        .filter(!legion::component::<Passenger>())
+        .iter_mut(world)
+    {
+        match graph::route(&adjacency, location.node, |node| node == destination.node) {
+            Some(path) => {
+                if let Some(&next) = path.first() {
+                    location.node = next;
+                }
+                if location.node == destination.node {
+                    cmd_buf.remove_component::<Destination>(entity);
+                }
+            }
+            None => cmd_buf.remove_component::<Destination>(entity),
+        }
+    }
+}
+
+/// Initializes ECS
+pub fn setup_ecs(setup: SetupEcs) -> SetupEcs {
+    setup
+        .resource(Deaths::default())
+        .uses(breathe_setup)
+        .uses(live_setup)
+        .uses(walk_setup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn injury_records_cause() {
+        let health = skill::TypeId(0);
+        let mut skills = Skills::default();
+        skills.set(health, units::Skill(10.));
+        let mut injury = Injury::default();
+
+        injury.apply(&mut skills, health, units::Skill(0.), DeathCause::Waste);
+        assert_eq!(injury.cause(), None);
+
+        injury.apply(
+            &mut skills,
+            health,
+            units::Skill(4.),
+            DeathCause::Suffocation,
+        );
+        assert_eq!(injury.cause(), Some(DeathCause::Suffocation));
+        assert_eq!(skills.get(health), units::Skill(6.));
+    }
+
+    #[test]
+    fn inventory_take() {
+        let bottle = cargo::TypeId(0);
+        let mut inventory = Inventory::default();
+        inventory.add(bottle, CargoSize(3.));
+
+        assert_eq!(inventory.take(bottle, CargoSize(2.)), CargoSize(2.));
+        assert_eq!(inventory.take(bottle, CargoSize(2.)), CargoSize(1.));
+        assert_eq!(inventory.get(bottle), CargoSize(0.));
+        assert_eq!(inventory.iter().count(), 0);
+    }
+}
@@ -0,0 +1,292 @@
+//! Entertainment of off-duty inhabitants.
+//!
+//! Inhabitants without assigned jobs and with happiness below the [`LeisurePolicy`] threshold
+//! visit the nearest reachable building with the
+//! [`Entertainment`][building::ExtraFeature::Entertainment] feature that still has free capacity,
+//! travelling there as a [`Destination`].
+//! Visitors gain happiness at the rate of the building until they are satisfied.
+//! Outlaws do not use entertainment facilities.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+
+use derive_new::new;
+use legion::world::SubWorld;
+use legion::Entity;
+
+use crate::clock::{SimulationEvent, SIMULATION_PERIOD};
+use crate::construction::{Site, Worker};
+use crate::def::{building, GameDefinition};
+use crate::education::Student;
+use crate::factory::Operator;
+use crate::graph::{self, EdgeId, NodeBuilding, NodeId};
+use crate::happiness::Happiness;
+use crate::inhabitant::{DeathEvent, Destination, Location};
+use crate::security::{Police, Prisoner};
+use crate::time::Rate;
+use crate::units;
+use crate::SetupEcs;
+
+/// A component attached to inhabitants visiting an entertainment building.
+#[derive(Debug, Clone, Copy, new, getset::CopyGetters)]
+pub struct Visitor {
+    /// The node entity of the entertainment building
+    #[getset(get_copy = "pub")]
+    venue: Entity,
+    /// The node of the entertainment building
+    #[getset(get_copy = "pub")]
+    node: NodeId,
+}
+
+/// A resource storing the policy of sending off-duty inhabitants to entertainment buildings.
+#[derive(Debug, Clone, Copy, getset::CopyGetters, getset::Setters)]
+pub struct LeisurePolicy {
+    /// Off-duty inhabitants with happiness below this value visit entertainment buildings
+    #[getset(get_copy = "pub", set = "pub")]
+    threshold: units::Happiness,
+    /// Visitors leave when their happiness reaches this value
+    #[getset(get_copy = "pub", set = "pub")]
+    satisfaction: units::Happiness,
+}
+
+impl Default for LeisurePolicy {
+    fn default() -> Self {
+        Self {
+            threshold: units::Happiness(30.),
+            satisfaction: units::Happiness(80.),
+        }
+    }
+}
+
+/// Returns the visitor capacity and the happiness rate of a building type.
+fn entertainment(building: &building::Type) -> Option<(u32, Rate<units::Happiness>)> {
+    building
+        .features()
+        .iter()
+        .find_map(|feature| match *feature {
+            building::ExtraFeature::Entertainment { capacity, rate } => Some((capacity, rate)),
+            _ => None,
+        })
+}
+
+#[codegen::system]
+#[read_component(EdgeId)]
+#[read_component(NodeId)]
+#[read_component(NodeBuilding)]
+#[read_component(Site)]
+#[read_component(Worker)]
+#[read_component(Operator)]
+#[read_component(Student)]
+#[read_component(Police)]
+#[read_component(Prisoner)]
+#[read_component(Visitor)]
+#[read_component(Destination)]
+#[read_component(Location)]
+#[write_component(Happiness)]
+fn visit(
+    world: &mut SubWorld,
+    cmd_buf: &mut legion::systems::CommandBuffer,
+    #[resource] def: &GameDefinition,
+    #[resource] policy: &LeisurePolicy,
+    #[subscriber] sim_sub: impl Iterator<Item = SimulationEvent>,
+    #[subscriber] death_sub: impl Iterator<Item = DeathEvent>,
+) {
+    use legion::IntoQuery;
+
+    // dead visitors no longer occupy their venues, even before their entities are removed
+    let dead: BTreeSet<Entity> = death_sub.map(|death| death.inhabitant()).collect();
+
+    if sim_sub.next().is_none() {
+        return;
+    }
+
+    let adjacency = graph::adjacency(<&EdgeId>::query().iter(world));
+
+    let mut visitors = BTreeMap::<Entity, u32>::new();
+    for (entity, visitor) in <(Entity, &Visitor)>::query().iter(world) {
+        if dead.contains(entity) {
+            continue;
+        }
+        *visitors.entry(visitor.venue).or_default() += 1;
+    }
+
+    let mut rates = BTreeMap::new();
+    // venues with free capacity, indexed by node
+    let mut venues = BTreeMap::new();
+    for (&entity, &node, building, site) in
+        <(Entity, &NodeId, &NodeBuilding, Option<&Site>)>::query().iter(world)
+    {
+        if site.is_some() {
+            continue;
+        }
+        if let Some((capacity, rate)) = entertainment(def.get_building(building.building())) {
+            rates.insert(entity, rate);
+            let free = capacity.saturating_sub(visitors.get(&entity).copied().unwrap_or(0));
+            if free > 0 {
+                venues.insert(node, (entity, free));
+            }
+        }
+    }
+
+    for (&entity, location, happiness, visitor, destination) in <(
+        Entity,
+        &Location,
+        &mut Happiness,
+        &Visitor,
+        Option<&Destination>,
+    )>::query()
+    .iter_mut(world)
+    {
+        let rate = match rates.get(&visitor.venue) {
+            Some(&rate) if !happiness.is_outlaw() => rate,
+            _ => {
+                // the venue has been demolished or the visitor has become an outlaw
+                cmd_buf.remove_component::<Visitor>(entity);
+                cmd_buf.remove_component::<Destination>(entity);
+                continue;
+            }
+        };
+
+        if location.node() == visitor.node {
+            if entertain(happiness, rate, policy) {
+                cmd_buf.remove_component::<Visitor>(entity);
+            }
+        } else if destination.is_none() {
+            // the venue is no longer reachable
+            cmd_buf.remove_component::<Visitor>(entity);
+        }
+    }
+
+    if venues.is_empty() {
+        return;
+    }
+
+    let mut idle: Vec<(Entity, NodeId, units::Happiness)> =
+        <(Entity, &Location, &Happiness)>::query()
+            .filter(
+                !legion::component::<Worker>()
+                    & !legion::component::<Operator>()
+                    & !legion::component::<Student>()
+                    & !legion::component::<Police>()
+                    & !legion::component::<Prisoner>()
+                    & !legion::component::<Visitor>(),
+            )
+            .iter(world)
+            .filter(|(entity, _, happiness)| {
+                !dead.contains(entity) && wants_leisure(happiness, policy)
+            })
+            .map(|(&entity, location, happiness)| (entity, location.node(), happiness.value()))
+            .collect();
+    // the unhappiest inhabitants are sent first
+    idle.sort_by(|a, b| a.2.partial_cmp(&b.2).unwrap_or(Ordering::Equal));
+
+    for (inhabitant, node, _) in idle {
+        let (venue_node, venue) = match reserve_venue(&adjacency, node, &mut venues) {
+            Some(venue) => venue,
+            None => continue,
+        };
+        cmd_buf.add_component(inhabitant, Visitor::new(venue, venue_node));
+        cmd_buf.add_component(inhabitant, Destination::new(venue_node));
+        if venues.is_empty() {
+            return;
+        }
+    }
+}
+
+/// Whether an off-duty inhabitant wants to visit an entertainment building.
+///
+/// Outlaws are refused by entertainment buildings.
+fn wants_leisure(happiness: &Happiness, policy: &LeisurePolicy) -> bool {
+    !happiness.is_outlaw() && happiness.value() < policy.threshold
+}
+
+/// Entertains a visitor in the venue for one simulation period.
+///
+/// Returns whether the visitor is satisfied.
+fn entertain(
+    happiness: &mut Happiness,
+    rate: Rate<units::Happiness>,
+    policy: &LeisurePolicy,
+) -> bool {
+    happiness.add(rate * SIMULATION_PERIOD);
+    happiness.value() >= policy.satisfaction
+}
+
+/// Reserves a place in the nearest venue with free capacity from `node`.
+///
+/// `venues` maps the venue nodes to their entities and free capacity.
+/// Returns the node and entity of the venue.
+fn reserve_venue(
+    adjacency: &BTreeMap<NodeId, Vec<NodeId>>,
+    node: NodeId,
+    venues: &mut BTreeMap<NodeId, (Entity, u32)>,
+) -> Option<(NodeId, Entity)> {
+    let path = graph::route(adjacency, node, |node| venues.contains_key(&node))?;
+    let venue = path.last().copied().unwrap_or(node);
+    let (entity, free) = venues.get_mut(&venue).expect("Route ends at a venue");
+    let entity = *entity;
+    *free -= 1;
+    if *free == 0 {
+        venues.remove(&venue);
+    }
+    Some((venue, entity))
+}
+
+/// Initializes ECS
+pub fn setup_ecs(setup: SetupEcs) -> SetupEcs {
+    setup.resource(LeisurePolicy::default()).uses(visit_setup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outlaws_refused() {
+        let policy = LeisurePolicy::default();
+        let happiness = |value| Happiness::new(units::Happiness(value));
+
+        assert!(wants_leisure(&happiness(10.), &policy));
+        assert!(!wants_leisure(&happiness(50.), &policy));
+        assert!(!wants_leisure(&happiness(-10.), &policy));
+    }
+
+    #[test]
+    fn entertain_until_satisfied() {
+        let policy = LeisurePolicy::default();
+        let mut happiness = Happiness::new(units::Happiness(50.));
+        let rate = Rate(units::Happiness(20.));
+
+        assert!(!entertain(&mut happiness, rate, &policy));
+        assert!((happiness.value().0 - 70.).abs() < 1e-9);
+        assert!(entertain(&mut happiness, rate, &policy));
+    }
+
+    #[test]
+    fn reserve_nearest_free_venue() {
+        let nodes: Vec<NodeId> = (0..4).map(NodeId::new).collect();
+        let edges = vec![
+            EdgeId::new(nodes[0], nodes[1]),
+            EdgeId::new(nodes[1], nodes[2]),
+            EdgeId::new(nodes[2], nodes[3]),
+        ];
+        let adjacency = graph::adjacency(&edges);
+
+        let mut world = legion::World::default();
+        let near = world.push(((),));
+        let far = world.push(((),));
+        let mut venues = vec![(nodes[1], (near, 1)), (nodes[3], (far, 1))]
+            .into_iter()
+            .collect();
+
+        assert_eq!(
+            reserve_venue(&adjacency, nodes[0], &mut venues),
+            Some((nodes[1], near))
+        );
+        assert_eq!(
+            reserve_venue(&adjacency, nodes[0], &mut venues),
+            Some((nodes[3], far))
+        );
+        assert_eq!(reserve_venue(&adjacency, nodes[0], &mut venues), None);
+    }
+}
@@ -35,12 +35,33 @@ pub use traffloat_types::{def, space, time, units};
 
 pub mod cargo;
 pub mod clock;
+pub mod command;
 pub mod config;
+pub mod construction;
+pub mod crime;
+pub mod education;
+pub mod electricity;
 pub mod factory;
+pub mod gas;
 pub mod graph;
+pub mod happiness;
+pub mod inhabitant;
+pub mod leisure;
+pub mod liquid;
+pub mod logistics;
+pub mod pool;
+pub mod research;
+pub mod schedule;
+pub mod security;
 pub mod shape;
+pub mod skill;
 pub mod sun;
+#[cfg(test)]
+mod testing;
+pub mod thermal;
 mod util;
+pub mod vehicle;
+pub mod waste;
 pub use util::*;
 
 pub use codegen::{Legion, SetupEcs};
@@ -50,9 +71,25 @@ pub fn setup_ecs(setup: SetupEcs) -> SetupEcs {
     setup
         .resource(codegen::Perf::default())
         .uses(clock::setup_ecs)
+        .uses(command::setup_ecs)
         .uses(factory::setup_ecs)
         .uses(shape::setup_ecs)
         .uses(graph::setup_ecs)
         .uses(cargo::setup_ecs)
         .uses(sun::setup_ecs)
+        .uses(research::setup_ecs)
+        .uses(construction::setup_ecs)
+        .uses(electricity::setup_ecs)
+        .uses(liquid::setup_ecs)
+        .uses(thermal::setup_ecs)
+        .uses(waste::setup_ecs)
+        .uses(inhabitant::setup_ecs)
+        .uses(education::setup_ecs)
+        .uses(happiness::setup_ecs)
+        .uses(security::setup_ecs)
+        .uses(leisure::setup_ecs)
+        .uses(vehicle::setup_ecs)
+        .uses(logistics::setup_ecs)
+        .uses(schedule::setup_ecs)
+        .uses(crime::setup_ecs)
 }
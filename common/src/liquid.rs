@@ -0,0 +1,175 @@
+//! Liquid stored in nodes.
+//!
+//! Each node stores liquids up to the liquid storage of its building,
+//! shared by all liquid types.
+//! Liquid is produced and consumed by [reactions][crate::def::reaction::Put::Liquid],
+//! and can be carried between nodes in [bottles][crate::def::cargo::Bottle].
+//!
+//! Liquids flow through corridors between adjacent nodes,
+//! so coolant produced in one node can supply cooling reactions in the nodes around it.
+//! Each simulation tick, each liquid type flows towards the node
+//! where it fills a smaller fraction of the liquid storage,
+//! slower for more viscous liquids.
+//! Pumping force is not simulated yet, so all corridors carry liquids equally.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use legion::world::SubWorld;
+
+use crate::clock::{SimulationEvent, SIMULATION_PERIOD};
+use crate::config;
+use crate::def::{liquid, GameDefinition};
+use crate::graph::{EdgeId, NodeId};
+use crate::pool::{Fluid, Pool};
+use crate::units::LiquidVolume;
+use crate::SetupEcs;
+
+impl Fluid for liquid::TypeId {
+    type Volume = LiquidVolume;
+}
+
+/// A component storing the liquids in a node.
+pub type LiquidPool = Pool<liquid::TypeId>;
+
+#[codegen::system]
+#[read_component(NodeId)]
+#[read_component(EdgeId)]
+#[write_component(LiquidPool)]
+fn flow(
+    world: &mut SubWorld,
+    #[resource] def: &GameDefinition,
+    #[resource] config: &config::Scalar,
+    #[subscriber] sim_sub: impl Iterator<Item = SimulationEvent>,
+) {
+    use legion::IntoQuery;
+
+    if sim_sub.next().is_none() {
+        return;
+    }
+
+    let edges: Vec<(NodeId, NodeId)> = <&EdgeId>::query()
+        .iter(world)
+        .map(|edge| (edge.from(), edge.to()))
+        .collect();
+    if edges.is_empty() {
+        return;
+    }
+
+    let mut pools: BTreeMap<NodeId, LiquidPool> = <(&NodeId, &LiquidPool)>::query()
+        .iter(world)
+        .map(|(&node, pool)| (node, pool.clone()))
+        .collect();
+    let fraction = config.pipe_flow * SIMULATION_PERIOD.as_secs();
+    for (from, to) in edges {
+        let mut from_pool = match pools.remove(&from) {
+            Some(pool) => pool,
+            None => continue,
+        };
+        if let Some(to_pool) = pools.get_mut(&to) {
+            exchange(
+                |ty| def.get_liquid(ty).viscosity().value(),
+                fraction,
+                &mut from_pool,
+                to_pool,
+            );
+        }
+        pools.insert(from, from_pool);
+    }
+
+    for (node, pool) in <(&NodeId, &mut LiquidPool)>::query().iter_mut(world) {
+        if let Some(next) = pools.remove(node) {
+            *pool = next;
+        }
+    }
+}
+
+/// Moves each liquid type between two adjacent pools
+/// towards equal fractions of their capacities.
+///
+/// `fraction` is the part of the difference equalized for liquids of unit viscosity.
+fn exchange(
+    viscosity: impl Fn(liquid::TypeId) -> f64,
+    fraction: f64,
+    a: &mut LiquidPool,
+    b: &mut LiquidPool,
+) {
+    let (cap_a, cap_b) = (a.capacity().value(), b.capacity().value());
+    if cap_a <= 0. || cap_b <= 0. {
+        return;
+    }
+
+    let types: BTreeSet<liquid::TypeId> = a.iter().chain(b.iter()).map(|(ty, _)| ty).collect();
+    for ty in types {
+        let (level_a, level_b) = (a.get(ty).value(), b.get(ty).value());
+        // the volume moved from `a` to `b` to equalize the fractions
+        let target = (level_a * cap_b - level_b * cap_a) / (cap_a + cap_b);
+        let rate = (fraction / viscosity(ty)).min(1.);
+        if rate <= 0. {
+            continue;
+        }
+        let volume = target * rate;
+        if volume > 0. {
+            pour(ty, LiquidVolume(volume), a, b);
+        } else if volume < 0. {
+            pour(ty, LiquidVolume(-volume), b, a);
+        }
+    }
+}
+
+/// Moves up to `volume` of a liquid type from `from` to `to`.
+fn pour(ty: liquid::TypeId, volume: LiquidVolume, from: &mut LiquidPool, to: &mut LiquidPool) {
+    let missing = from.add(ty, LiquidVolume(-volume.value()));
+    let taken = LiquidVolume(volume.value() + missing.value());
+    let overflow = to.add(ty, taken);
+    from.add(ty, overflow);
+}
+
+/// Initializes ECS
+pub fn setup_ecs(setup: SetupEcs) -> SetupEcs {
+    setup.uses(flow_setup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flow_towards_lower_fraction() {
+        let coolant = liquid::TypeId(0);
+        let urine = liquid::TypeId(1);
+        let viscosity = |ty| if ty == urine { 5. } else { 1. };
+
+        let mut a = LiquidPool::new(LiquidVolume(100.));
+        let mut b = LiquidPool::new(LiquidVolume(300.));
+        a.add(coolant, LiquidVolume(80.));
+        b.add(urine, LiquidVolume(100.));
+
+        // fully equalized coolant fills 20% of both pools
+        exchange(viscosity, 1., &mut a, &mut b);
+        assert!((a.get(coolant).value() - 20.).abs() < 1e-9);
+        assert!((b.get(coolant).value() - 60.).abs() < 1e-9);
+        // viscous urine only moves a fifth of the way
+        assert!((a.get(urine).value() - 5.).abs() < 1e-9);
+        assert!((b.get(urine).value() - 95.).abs() < 1e-9);
+
+        // liquid is neither created nor destroyed
+        let total = a.total().value() + b.total().value();
+        assert!((total - 180.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn flow_limited_by_capacity() {
+        let coolant = liquid::TypeId(0);
+        let water = liquid::TypeId(1);
+
+        let mut a = LiquidPool::new(LiquidVolume(100.));
+        let mut b = LiquidPool::new(LiquidVolume(100.));
+        a.add(coolant, LiquidVolume(100.));
+        b.add(water, LiquidVolume(90.));
+
+        exchange(|_| 1., 1., &mut a, &mut b);
+        assert!(b.total().value() <= 100.);
+        let total = a.total().value() + b.total().value();
+        assert!((total - 190.).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,908 @@
+//! Automatic cargo transport by vehicles.
+//!
+//! The logistics planner computes the cargo required by each node
+//! from the [cargo inputs][reaction::Put::Cargo] of its reactions
+//! over the [planning horizon][Logistics::horizon]
+//! and the [keep-in-stock thresholds][NodeLogistics::keep] set by the player.
+//! Nodes storing less than required have a demand,
+//! and nodes storing more than required have a supply.
+//!
+//! Each simulation tick, idle [carriers][Carrier] are assigned trips greedily:
+//! demands of nodes with higher [priority][NodeLogistics::priority] are served first,
+//! from the nearest node with supply,
+//! by the idle carrier nearest to that node,
+//! carrying no more than the [capacity][crate::def::vehicle::Type::capacity] of the vehicle.
+//! Carriers load and unload cargo when they [stop][StopEvent] at the nodes of their trips.
+//!
+//! Players can also [request][TransferRequestEvent] to move an amount of cargo
+//! between two nodes once.
+//! Requested transfers are assigned to the idle carriers nearest to the source
+//! before any demand, split into multiple trips if they exceed the vehicle capacity.
+//! Priorities and keep-in-stock thresholds are set by accepted [player commands][Command].
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::BTreeMap;
+
+use derive_new::new;
+use legion::world::SubWorld;
+use legion::Entity;
+use serde::{Deserialize, Serialize};
+
+use crate::cargo;
+use crate::clock::SimulationEvent;
+use crate::command::{Command, CommandAcceptEvent};
+use crate::construction::Site;
+use crate::def::{self, reaction, GameDefinition};
+use crate::factory::Factory;
+use crate::graph::{self, EdgeId, Graph, NodeBuilding, NodeId};
+use crate::time::Time;
+use crate::units::CargoSize;
+use crate::vehicle::{StopEvent, Vehicle};
+use crate::SetupEcs;
+
+/// The logistics settings of a node.
+#[derive(Debug, Clone, Default, getset::CopyGetters, getset::Setters)]
+pub struct NodeLogistics {
+    /// Demands of nodes with higher priority are served first
+    #[getset(get_copy = "pub", set = "pub")]
+    priority: i32,
+    /// The amount of each cargo type to keep in stock
+    keep: BTreeMap<def::cargo::TypeId, CargoSize>,
+}
+
+impl NodeLogistics {
+    /// Returns the amount of a cargo type to keep in stock.
+    pub fn keep(&self, ty: def::cargo::TypeId) -> CargoSize {
+        self.keep.get(&ty).copied().unwrap_or_default()
+    }
+
+    /// Sets the amount of a cargo type to keep in stock.
+    pub fn set_keep(&mut self, ty: def::cargo::TypeId, size: CargoSize) {
+        if size > CargoSize::default() {
+            self.keep.insert(ty, size);
+        } else {
+            self.keep.remove(&ty);
+        }
+    }
+}
+
+/// A resource storing the logistics settings.
+#[derive(Debug, getset::CopyGetters, getset::Getters, getset::Setters)]
+pub struct Logistics {
+    /// The duration of reaction inputs requested by each node
+    #[getset(get_copy = "pub", set = "pub")]
+    horizon: Time,
+    /// The settings of each node
+    nodes: BTreeMap<NodeId, NodeLogistics>,
+    /// Requested transfers not yet assigned to carriers
+    #[getset(get = "pub")]
+    transfers: Vec<Trip>,
+}
+
+impl Default for Logistics {
+    fn default() -> Self {
+        Self {
+            horizon: Time(6000), // 1 minute
+            nodes: BTreeMap::new(),
+            transfers: Vec::new(),
+        }
+    }
+}
+
+impl Logistics {
+    /// Returns the settings of a node.
+    pub fn get(&self, node: NodeId) -> Option<&NodeLogistics> {
+        self.nodes.get(&node)
+    }
+
+    /// Returns the settings of a node, creating the default settings if absent.
+    pub fn get_mut(&mut self, node: NodeId) -> &mut NodeLogistics {
+        self.nodes.entry(node).or_default()
+    }
+}
+
+/// A component attached to vehicles whose trips are assigned by the logistics planner.
+#[derive(Debug, Clone, Copy, Default, getset::CopyGetters)]
+pub struct Carrier {
+    /// The current trip
+    #[getset(get_copy = "pub")]
+    trip: Option<Trip>,
+    /// The cargo carried
+    #[getset(get_copy = "pub")]
+    load: Option<(def::cargo::TypeId, CargoSize)>,
+}
+
+/// A cargo delivery assigned to a carrier.
+#[derive(Debug, Clone, Copy, PartialEq, new, getset::CopyGetters)]
+pub struct Trip {
+    /// The node to load cargo from
+    #[getset(get_copy = "pub")]
+    from: NodeId,
+    /// The node to unload cargo to
+    #[getset(get_copy = "pub")]
+    to: NodeId,
+    /// The cargo type
+    #[getset(get_copy = "pub")]
+    cargo: def::cargo::TypeId,
+    /// The amount of cargo
+    #[getset(get_copy = "pub")]
+    size: CargoSize,
+}
+
+/// Requests to move cargo between two nodes once.
+#[derive(Debug, new, getset::CopyGetters)]
+pub struct TransferRequestEvent {
+    /// The node to load cargo from
+    #[getset(get_copy = "pub")]
+    from: NodeId,
+    /// The node to unload cargo to
+    #[getset(get_copy = "pub")]
+    to: NodeId,
+    /// The cargo type
+    #[getset(get_copy = "pub")]
+    cargo: def::cargo::TypeId,
+    /// The amount of cargo
+    #[getset(get_copy = "pub")]
+    size: CargoSize,
+}
+
+/// Indicates that a transfer request has been rejected.
+#[derive(Debug, new, getset::CopyGetters)]
+pub struct TransferRejectEvent {
+    /// The requested source node
+    #[getset(get_copy = "pub")]
+    from: NodeId,
+    /// The requested destination node
+    #[getset(get_copy = "pub")]
+    to: NodeId,
+    /// The reason for rejection
+    #[getset(get_copy = "pub")]
+    reason: TransferRejection,
+}
+
+/// The reason for rejecting a transfer request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransferRejection {
+    /// The source and destination are the same node.
+    SameNode,
+    /// The requested amount of cargo is not positive and finite.
+    EmptyTransfer,
+    /// The node does not exist.
+    UnknownNode(NodeId),
+    /// The destination cannot be reached from the source.
+    Unreachable,
+}
+
+/// The cargo requested by a node.
+#[derive(Debug, Clone, Copy)]
+struct Demand {
+    node: NodeId,
+    priority: i32,
+    cargo: def::cargo::TypeId,
+    size: CargoSize,
+}
+
+/// A carrier waiting for a trip.
+#[derive(Debug, Clone, Copy)]
+struct Idle {
+    entity: Entity,
+    node: NodeId,
+    capacity: CargoSize,
+    load: Option<(def::cargo::TypeId, CargoSize)>,
+}
+
+/// The logistics balance of a cargo type in a node.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Settlement {
+    /// The node requests more cargo.
+    Demand(CargoSize),
+    /// The node can give away some cargo.
+    Supply(CargoSize),
+    /// The node neither requests nor gives away cargo.
+    Balanced,
+}
+
+/// Computes the balance of a cargo type in a node.
+///
+/// `stock` is the amount stored, `capacity` the storage capacity
+/// and `need` the amount required by reactions and keep-in-stock thresholds.
+/// `inbound` and `outbound` are the amounts to be delivered and loaded by assigned trips.
+fn settle(
+    stock: CargoSize,
+    capacity: CargoSize,
+    need: CargoSize,
+    inbound: CargoSize,
+    outbound: CargoSize,
+) -> Settlement {
+    let incoming = stock + inbound;
+    let outgoing = need + outbound;
+    let target = min(need, capacity);
+    if incoming < target {
+        Settlement::Demand(target - incoming)
+    } else if stock > outgoing {
+        Settlement::Supply(stock - outgoing)
+    } else {
+        Settlement::Balanced
+    }
+}
+
+fn min(a: CargoSize, b: CargoSize) -> CargoSize {
+    if a < b {
+        a
+    } else {
+        b
+    }
+}
+
+/// Returns the nearest reachable item from `start`.
+fn nearest<T>(
+    adjacency: &BTreeMap<NodeId, Vec<NodeId>>,
+    start: NodeId,
+    items: impl IntoIterator<Item = (NodeId, T)>,
+) -> Option<T> {
+    let distances = graph::distances(adjacency, start);
+    items
+        .into_iter()
+        .filter_map(|(node, item)| distances.get(&node).map(|&distance| (distance, item)))
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, item)| item)
+}
+
+/// Assigns trips to idle carriers.
+///
+/// Requested `transfers` are served before demands,
+/// and are removed once fully assigned.
+/// `supplies` is reduced by the assigned trips.
+fn assign(
+    adjacency: &BTreeMap<NodeId, Vec<NodeId>>,
+    transfers: &mut Vec<Trip>,
+    mut demands: Vec<Demand>,
+    supplies: &mut BTreeMap<(NodeId, def::cargo::TypeId), CargoSize>,
+    mut idle: Vec<Idle>,
+) -> Vec<(Entity, Trip)> {
+    let mut trips = Vec::new();
+    demands.sort_by(|a, b| {
+        (Reverse(a.priority), b.size)
+            .partial_cmp(&(Reverse(b.priority), a.size))
+            .unwrap_or(Ordering::Equal)
+    });
+
+    // carriers with leftover cargo deliver it to the nearest demand first
+    idle.retain(|carrier| {
+        let (ty, size) = match carrier.load {
+            Some(load) => load,
+            None => return true,
+        };
+        let candidates = demands
+            .iter_mut()
+            .filter(|demand| demand.cargo == ty && demand.size > CargoSize::default())
+            .map(|demand| (demand.node, demand));
+        if let Some(demand) = nearest(adjacency, carrier.node, candidates) {
+            demand.size -= min(demand.size, size);
+            trips.push((
+                carrier.entity,
+                Trip::new(carrier.node, demand.node, ty, size),
+            ));
+        }
+        false
+    });
+
+    for transfer in transfers.iter_mut() {
+        while transfer.size > CargoSize::default() {
+            let carriers = idle
+                .iter()
+                .enumerate()
+                .filter(|(_, carrier)| carrier.capacity > CargoSize::default())
+                .map(|(index, carrier)| (carrier.node, index));
+            let index = match nearest(adjacency, transfer.from, carriers) {
+                Some(index) => index,
+                None => break,
+            };
+            let carrier = idle.swap_remove(index);
+            let size = min(transfer.size, carrier.capacity);
+            transfer.size -= size;
+            if let Some(supply) = supplies.get_mut(&(transfer.from, transfer.cargo)) {
+                *supply -= min(*supply, size);
+            }
+            trips.push((carrier.entity, Trip { size, ..*transfer }));
+        }
+    }
+    transfers.retain(|transfer| transfer.size > CargoSize::default());
+
+    for demand in &mut demands {
+        while demand.size > CargoSize::default() && !idle.is_empty() {
+            let candidates = supplies
+                .iter()
+                .filter(|&(&(node, ty), &size)| {
+                    ty == demand.cargo && node != demand.node && size > CargoSize::default()
+                })
+                .map(|(&(node, _), _)| (node, node));
+            let from = match nearest(adjacency, demand.node, candidates) {
+                Some(from) => from,
+                None => break,
+            };
+            let carriers = idle
+                .iter()
+                .enumerate()
+                .filter(|(_, carrier)| carrier.capacity > CargoSize::default())
+                .map(|(index, carrier)| (carrier.node, index));
+            let index = match nearest(adjacency, from, carriers) {
+                Some(index) => index,
+                None => break,
+            };
+            let carrier = idle.swap_remove(index);
+
+            let supply = supplies
+                .get_mut(&(from, demand.cargo))
+                .expect("Supply was just found");
+            let size = min(min(demand.size, *supply), carrier.capacity);
+            *supply -= size;
+            demand.size -= size;
+            trips.push((
+                carrier.entity,
+                Trip::new(from, demand.node, demand.cargo, size),
+            ));
+        }
+    }
+
+    trips
+}
+
+#[codegen::system]
+#[read_component(EdgeId)]
+#[read_component(NodeId)]
+#[read_component(NodeBuilding)]
+#[read_component(Site)]
+#[read_component(Factory)]
+#[read_component(cargo::StorageList)]
+#[read_component(cargo::Storage)]
+#[read_component(cargo::NextStorageSize)]
+#[write_component(Vehicle)]
+#[write_component(Carrier)]
+fn plan(
+    world: &mut SubWorld,
+    #[resource] def: &GameDefinition,
+    #[resource] logistics: &mut Logistics,
+    #[subscriber] sim_sub: impl Iterator<Item = SimulationEvent>,
+) {
+    use legion::{EntityStore, IntoQuery};
+
+    if sim_sub.next().is_none() {
+        return;
+    }
+
+    let mut idle = Vec::new();
+    // cargo to be delivered to and loaded from each node by assigned trips
+    let mut inbound = BTreeMap::<(NodeId, def::cargo::TypeId), CargoSize>::new();
+    let mut outbound = BTreeMap::<(NodeId, def::cargo::TypeId), CargoSize>::new();
+    for (&entity, vehicle, carrier) in <(Entity, &Vehicle, &Carrier)>::query().iter(world) {
+        match carrier.trip {
+            Some(trip) => {
+                *inbound.entry((trip.to, trip.cargo)).or_default() += trip.size;
+                if carrier.load.is_none() {
+                    *outbound.entry((trip.from, trip.cargo)).or_default() += trip.size;
+                }
+            }
+            // only parked carriers can be assigned
+            None if vehicle.progress() == 0. => idle.push(Idle {
+                entity,
+                node: vehicle.node(),
+                capacity: def.get_vehicle(vehicle.ty()).capacity(),
+                load: carrier.load,
+            }),
+            None => {}
+        }
+    }
+    if idle.is_empty() {
+        return;
+    }
+
+    let mut demands = Vec::new();
+    let mut supplies = BTreeMap::new();
+    for (&node, building, factory, list, site) in <(
+        &NodeId,
+        &NodeBuilding,
+        Option<&Factory>,
+        Option<&cargo::StorageList>,
+        Option<&Site>,
+    )>::query()
+    .iter(world)
+    {
+        let settings = logistics.get(node);
+
+        // the stock, capacity and required amount of each cargo type
+        let mut balances = BTreeMap::<def::cargo::TypeId, (CargoSize, CargoSize, CargoSize)>::new();
+        let default_capacity = def.get_building(building.building()).storage().cargo();
+        for &(ty, storage) in list.into_iter().flat_map(|list| list.storages()) {
+            let entry = world
+                .entry_ref(storage)
+                .expect("Storage entity does not exist");
+            let capacity = entry
+                .get_component::<cargo::Storage>()
+                .expect("Storage entity does not have Storage")
+                .capacity();
+            let size = entry
+                .get_component::<cargo::NextStorageSize>()
+                .expect("Storage entity does not have NextStorageSize")
+                .size();
+            balances.insert(ty, (size, capacity, CargoSize::default()));
+        }
+        if let (Some(factory), None) = (factory, site) {
+            for &reaction in factory.reactions() {
+                for put in def.get_reaction(reaction).puts() {
+                    if let reaction::Put::Cargo { ty, base } = *put {
+                        if base.0 < CargoSize::default() {
+                            let balance = balances.entry(ty).or_insert((
+                                CargoSize::default(),
+                                default_capacity,
+                                CargoSize::default(),
+                            ));
+                            balance.2 += CargoSize(-base.0 .0 * logistics.horizon.as_secs());
+                        }
+                    }
+                }
+            }
+        }
+        for (&ty, &keep) in settings.into_iter().flat_map(|settings| &settings.keep) {
+            let balance = balances.entry(ty).or_insert((
+                CargoSize::default(),
+                default_capacity,
+                CargoSize::default(),
+            ));
+            balance.2 += keep;
+        }
+
+        for (ty, (stock, capacity, need)) in balances {
+            let key = (node, ty);
+            let inbound = inbound.get(&key).copied().unwrap_or_default();
+            let outbound = outbound.get(&key).copied().unwrap_or_default();
+            match settle(stock, capacity, need, inbound, outbound) {
+                Settlement::Demand(size) => demands.push(Demand {
+                    node,
+                    priority: settings.map_or(0, |settings| settings.priority),
+                    cargo: ty,
+                    size,
+                }),
+                Settlement::Supply(size) => {
+                    supplies.insert(key, size);
+                }
+                Settlement::Balanced => {}
+            }
+        }
+    }
+
+    let adjacency = graph::adjacency(<&EdgeId>::query().iter(world));
+    let trips = assign(
+        &adjacency,
+        &mut logistics.transfers,
+        demands,
+        &mut supplies,
+        idle,
+    );
+    for (entity, trip) in trips {
+        let mut entry = world
+            .entry_mut(entity)
+            .expect("Carrier entity does not exist");
+        entry
+            .get_component_mut::<Carrier>()
+            .expect("Carrier entity does not have Carrier")
+            .trip = Some(trip);
+        entry
+            .get_component_mut::<Vehicle>()
+            .expect("Carrier entity does not have Vehicle")
+            .set_stops(vec![trip.from, trip.to]);
+    }
+}
+
+#[codegen::system]
+#[read_component(NodeBuilding)]
+#[read_component(cargo::Storage)]
+#[write_component(cargo::StorageList)]
+#[write_component(cargo::NextStorageSize)]
+#[write_component(Vehicle)]
+#[write_component(Carrier)]
+fn transfer(
+    world: &mut SubWorld,
+    cmd_buf: &mut legion::systems::CommandBuffer,
+    #[resource] def: &GameDefinition,
+    #[resource] graph: &Graph,
+    #[subscriber] stop_sub: impl Iterator<Item = StopEvent>,
+) {
+    use legion::EntityStore;
+
+    let mut delivery = cargo::Delivery::default();
+
+    for stop in stop_sub {
+        let mut carrier = match world
+            .entry_ref(stop.vehicle())
+            .ok()
+            .and_then(|entry| entry.get_component::<Carrier>().ok().copied())
+        {
+            Some(carrier) => carrier,
+            None => continue,
+        };
+        let trip = match carrier.trip {
+            Some(trip) => trip,
+            None => continue,
+        };
+        let node = match graph.get_node(stop.node()) {
+            Some(node) => node,
+            None => continue,
+        };
+
+        match carrier.load {
+            Some((ty, size)) if stop.node() == trip.to => {
+                let stored = delivery.store(world, def, node, ty, size);
+                // cargo that does not fit stays in the carrier for another trip
+                carrier.load = Some((ty, size - stored)).filter(|&(_, left)| left.0 > 0.);
+                carrier.trip = None;
+            }
+            None if stop.node() == trip.from => {
+                let taken = cargo::take(world, node, trip.cargo, trip.size);
+                if taken.0 > 0. {
+                    carrier.load = Some((trip.cargo, taken));
+                } else {
+                    // the supply has been consumed by others
+                    carrier.trip = None;
+                }
+            }
+            _ => continue,
+        }
+
+        let mut entry = world
+            .entry_mut(stop.vehicle())
+            .expect("Carrier entity does not exist");
+        *entry
+            .get_component_mut::<Carrier>()
+            .expect("Carrier entity does not have Carrier") = carrier;
+        if carrier.trip.is_none() {
+            entry
+                .get_component_mut::<Vehicle>()
+                .expect("Carrier entity does not have Vehicle")
+                .set_stops(vec![stop.node()]);
+        }
+    }
+
+    delivery.finish(world, cmd_buf);
+}
+
+/// Checks the endpoints and the size of a transfer request.
+fn check_trip(from: NodeId, to: NodeId, size: CargoSize) -> Result<(), TransferRejection> {
+    if from == to {
+        return Err(TransferRejection::SameNode);
+    }
+    #[allow(clippy::neg_cmp_op_on_partial_ord)] // also rejects NaN
+    let empty = !(size.0 > 0.);
+    if empty || !size.0.is_finite() {
+        return Err(TransferRejection::EmptyTransfer);
+    }
+    Ok(())
+}
+
+/// Checks whether a transfer request is valid.
+pub(crate) fn check_transfer(
+    world: &SubWorld,
+    request: &TransferRequestEvent,
+) -> Result<(), TransferRejection> {
+    use legion::IntoQuery;
+
+    check_trip(request.from, request.to, request.size)?;
+    for &node in &[request.from, request.to] {
+        if !<&NodeId>::query().iter(world).any(|&other| other == node) {
+            return Err(TransferRejection::UnknownNode(node));
+        }
+    }
+    let adjacency = graph::adjacency(<&EdgeId>::query().iter(world));
+    let to = request.to;
+    if graph::route(&adjacency, request.from, |node| node == to).is_none() {
+        return Err(TransferRejection::Unreachable);
+    }
+    Ok(())
+}
+
+#[codegen::system]
+#[read_component(NodeId)]
+#[read_component(EdgeId)]
+fn request_transfer(
+    world: &mut SubWorld,
+    #[resource] logistics: &mut Logistics,
+    #[subscriber] requests: impl Iterator<Item = TransferRequestEvent>,
+    #[publisher] reject_pub: impl FnMut(TransferRejectEvent),
+) {
+    for request in requests {
+        match check_transfer(world, request) {
+            Ok(()) => logistics.transfers.push(Trip::new(
+                request.from,
+                request.to,
+                request.cargo,
+                request.size,
+            )),
+            Err(reason) => reject_pub(TransferRejectEvent {
+                from: request.from,
+                to: request.to,
+                reason,
+            }),
+        }
+    }
+}
+
+#[codegen::system]
+fn configure(
+    #[resource] logistics: &mut Logistics,
+    #[subscriber] commands: impl Iterator<Item = CommandAcceptEvent>,
+) {
+    for event in commands {
+        match *event.command() {
+            Command::SetPriority { node, priority } => {
+                logistics.get_mut(node).set_priority(priority);
+            }
+            Command::SetKeep { node, cargo, size } => {
+                logistics.get_mut(node).set_keep(cargo, size);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Initializes ECS
+pub fn setup_ecs(setup: SetupEcs) -> SetupEcs {
+    setup
+        .resource(Logistics::default())
+        .uses(configure_setup)
+        .uses(request_transfer_setup)
+        .uses(plan_setup)
+        .uses(transfer_setup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assign_nearest_supply() {
+        let nodes: Vec<NodeId> = (0..4).map(NodeId::new).collect();
+        let edges = vec![
+            EdgeId::new(nodes[0], nodes[1]),
+            EdgeId::new(nodes[1], nodes[2]),
+            EdgeId::new(nodes[2], nodes[3]),
+        ];
+        let adjacency = graph::adjacency(&edges);
+
+        let mut world = legion::World::default();
+        let carrier = world.push((nodes[0],));
+
+        let ty = def::cargo::TypeId(0);
+        let mut supplies = vec![
+            ((nodes[0], ty), CargoSize(100.)),
+            ((nodes[2], ty), CargoSize(100.)),
+        ]
+        .into_iter()
+        .collect();
+        let demands = vec![Demand {
+            node: nodes[3],
+            priority: 0,
+            cargo: ty,
+            size: CargoSize(50.),
+        }];
+        let idle = vec![Idle {
+            entity: carrier,
+            node: nodes[0],
+            capacity: CargoSize(30.),
+            load: None,
+        }];
+
+        let trips = assign(&adjacency, &mut Vec::new(), demands, &mut supplies, idle);
+        assert_eq!(
+            trips,
+            vec![(carrier, Trip::new(nodes[2], nodes[3], ty, CargoSize(30.)))]
+        );
+        assert_eq!(supplies[&(nodes[2], ty)], CargoSize(70.));
+    }
+
+    #[test]
+    fn assign_transfer_split() {
+        let nodes: Vec<NodeId> = (0..2).map(NodeId::new).collect();
+        let edges = vec![EdgeId::new(nodes[0], nodes[1])];
+        let adjacency = graph::adjacency(&edges);
+
+        let mut world = legion::World::default();
+        let carriers: Vec<Entity> = (0..2).map(|_| world.push((nodes[1],))).collect();
+
+        let ty = def::cargo::TypeId(0);
+        let mut transfers = vec![Trip::new(nodes[0], nodes[1], ty, CargoSize(50.))];
+        let idle = vec![Idle {
+            entity: carriers[0],
+            node: nodes[1],
+            capacity: CargoSize(30.),
+            load: None,
+        }];
+
+        let trips = assign(
+            &adjacency,
+            &mut transfers,
+            Vec::new(),
+            &mut BTreeMap::new(),
+            idle,
+        );
+        assert_eq!(
+            trips,
+            vec![(
+                carriers[0],
+                Trip::new(nodes[0], nodes[1], ty, CargoSize(30.))
+            )]
+        );
+        // the remaining cargo waits for another carrier
+        assert_eq!(
+            transfers,
+            vec![Trip::new(nodes[0], nodes[1], ty, CargoSize(20.))]
+        );
+    }
+
+    #[test]
+    fn assign_by_priority() {
+        let nodes: Vec<NodeId> = (0..3).map(NodeId::new).collect();
+        let edges = vec![
+            EdgeId::new(nodes[0], nodes[1]),
+            EdgeId::new(nodes[1], nodes[2]),
+        ];
+        let adjacency = graph::adjacency(&edges);
+
+        let mut world = legion::World::default();
+        let carrier = world.push((nodes[1],));
+
+        let ty = def::cargo::TypeId(0);
+        let mut supplies = vec![((nodes[1], ty), CargoSize(100.))]
+            .into_iter()
+            .collect();
+        let demand = |node, priority| Demand {
+            node,
+            priority,
+            cargo: ty,
+            size: CargoSize(10.),
+        };
+        let idle = vec![Idle {
+            entity: carrier,
+            node: nodes[1],
+            capacity: CargoSize(30.),
+            load: None,
+        }];
+
+        let trips = assign(
+            &adjacency,
+            &mut Vec::new(),
+            vec![demand(nodes[0], 0), demand(nodes[2], 1)],
+            &mut supplies,
+            idle,
+        );
+        assert_eq!(
+            trips,
+            vec![(carrier, Trip::new(nodes[1], nodes[2], ty, CargoSize(10.)))]
+        );
+    }
+
+    #[test]
+    fn deliver_leftover_load() {
+        let nodes: Vec<NodeId> = (0..3).map(NodeId::new).collect();
+        let edges = vec![
+            EdgeId::new(nodes[0], nodes[1]),
+            EdgeId::new(nodes[1], nodes[2]),
+        ];
+        let adjacency = graph::adjacency(&edges);
+
+        let mut world = legion::World::default();
+        let carrier = world.push((nodes[0],));
+
+        let ty = def::cargo::TypeId(0);
+        let demands = vec![
+            Demand {
+                node: nodes[2],
+                priority: 0,
+                cargo: ty,
+                size: CargoSize(10.),
+            },
+            Demand {
+                node: nodes[1],
+                priority: 0,
+                cargo: ty,
+                size: CargoSize(10.),
+            },
+        ];
+        let idle = vec![Idle {
+            entity: carrier,
+            node: nodes[0],
+            capacity: CargoSize(30.),
+            load: Some((ty, CargoSize(20.))),
+        }];
+
+        // the whole load goes to the nearest demand without loading more cargo
+        let trips = assign(
+            &adjacency,
+            &mut Vec::new(),
+            demands,
+            &mut BTreeMap::new(),
+            idle,
+        );
+        assert_eq!(
+            trips,
+            vec![(carrier, Trip::new(nodes[0], nodes[1], ty, CargoSize(20.)))]
+        );
+    }
+
+    #[test]
+    fn settle_demand_and_supply() {
+        let balance = |stock, capacity, need, inbound, outbound| {
+            settle(
+                CargoSize(stock),
+                CargoSize(capacity),
+                CargoSize(need),
+                CargoSize(inbound),
+                CargoSize(outbound),
+            )
+        };
+
+        assert_eq!(
+            balance(10., 100., 50., 0., 0.),
+            Settlement::Demand(CargoSize(40.))
+        );
+        // the demand does not exceed the storage capacity
+        assert_eq!(
+            balance(10., 30., 50., 0., 0.),
+            Settlement::Demand(CargoSize(20.))
+        );
+        // cargo already on its way is not requested again
+        assert_eq!(
+            balance(10., 100., 50., 15., 0.),
+            Settlement::Demand(CargoSize(25.))
+        );
+        assert_eq!(balance(10., 100., 50., 40., 0.), Settlement::Balanced);
+        assert_eq!(
+            balance(80., 100., 50., 0., 0.),
+            Settlement::Supply(CargoSize(30.))
+        );
+        assert_eq!(
+            balance(80., 100., 50., 0., 10.),
+            Settlement::Supply(CargoSize(20.))
+        );
+        assert_eq!(balance(50., 100., 50., 0., 0.), Settlement::Balanced);
+    }
+
+    #[test]
+    fn keep_in_stock() {
+        let ty = def::cargo::TypeId(0);
+        let mut logistics = Logistics::default();
+        assert!(logistics.get(NodeId::new(0)).is_none());
+
+        let settings = logistics.get_mut(NodeId::new(0));
+        settings.set_priority(2);
+        settings.set_keep(ty, CargoSize(40.));
+        let settings = logistics
+            .get(NodeId::new(0))
+            .expect("Settings were just set");
+        assert_eq!(settings.priority(), 2);
+        assert_eq!(settings.keep(ty), CargoSize(40.));
+
+        logistics
+            .get_mut(NodeId::new(0))
+            .set_keep(ty, CargoSize(0.));
+        let settings = logistics
+            .get(NodeId::new(0))
+            .expect("Settings were just set");
+        assert!(settings.keep.is_empty());
+    }
+
+    #[test]
+    fn reject_invalid_trips() {
+        let nodes: Vec<NodeId> = (0..2).map(NodeId::new).collect();
+        assert_eq!(check_trip(nodes[0], nodes[1], CargoSize(10.)), Ok(()));
+        assert_eq!(
+            check_trip(nodes[0], nodes[0], CargoSize(10.)),
+            Err(TransferRejection::SameNode)
+        );
+        for &size in &[0., -1., f64::NAN, f64::INFINITY] {
+            assert_eq!(
+                check_trip(nodes[0], nodes[1], CargoSize(size)),
+                Err(TransferRejection::EmptyTransfer)
+            );
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Fluid storage shared by all fluid kinds.
+//!
+//! A [`Pool`] stores several types of one fluid kind up to a common capacity.
+//! Liquids are stored in a [`LiquidPool`][crate::liquid::LiquidPool]
+//! and gases in a [`GasPool`][crate::gas::GasPool].
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use derive_new::new;
+
+use crate::units::Unit;
+
+/// A fluid type identifier that can be stored in a [`Pool`].
+pub trait Fluid: fmt::Debug + Copy + Ord + Send + Sync + 'static {
+    /// The unit measuring an amount of this fluid.
+    type Volume: Unit + From<f64> + Send + Sync + 'static;
+}
+
+/// A component storing the fluids of one kind in a node.
+#[derive(Debug, Clone, new, getset::CopyGetters)]
+pub struct Pool<T: Fluid> {
+    /// The amount of each fluid type
+    #[new(default)]
+    levels: BTreeMap<T, T::Volume>,
+    /// The maximum total amount of fluid
+    #[getset(get_copy = "pub")]
+    capacity: T::Volume,
+}
+
+impl<T: Fluid> Pool<T> {
+    /// Returns the amount of a fluid type.
+    pub fn get(&self, ty: T) -> T::Volume {
+        self.levels.get(&ty).copied().unwrap_or_default()
+    }
+
+    /// The total amount of all fluid types.
+    pub fn total(&self) -> T::Volume {
+        T::Volume::from(
+            self.levels
+                .values()
+                .map(|&level| level.value())
+                .sum::<f64>(),
+        )
+    }
+
+    /// Adds `delta` of a fluid type, limited by zero and the remaining capacity.
+    ///
+    /// Returns the part of `delta` that could not be added or removed.
+    pub fn add(&mut self, ty: T, delta: T::Volume) -> T::Volume {
+        let space = self.capacity.value() - self.total().value();
+        let level = self.levels.entry(ty).or_default();
+        let before = level.value();
+        let mut next = before + delta.value();
+        if next < 0. {
+            next = 0.;
+        } else if next - before > space {
+            next = before + space;
+        }
+        *level.value_mut() = next;
+        T::Volume::from(delta.value() - (next - before))
+    }
+
+    /// Iterates over the stored fluid levels.
+    pub fn iter(&self) -> impl Iterator<Item = (T, T::Volume)> + '_ {
+        self.levels.iter().map(|(&ty, &level)| (ty, level))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::def::{gas, liquid};
+    use crate::gas::GasPool;
+    use crate::liquid::LiquidPool;
+    use crate::units::{GasVolume, LiquidVolume};
+
+    #[test]
+    fn add_within_bounds() {
+        let water = liquid::TypeId(0);
+        let coolant = liquid::TypeId(1);
+        let mut pool = LiquidPool::new(LiquidVolume(10.));
+
+        assert_eq!(pool.add(water, LiquidVolume(7.)), LiquidVolume(0.));
+        assert_eq!(pool.add(coolant, LiquidVolume(5.)), LiquidVolume(2.));
+        assert_eq!(pool.get(coolant), LiquidVolume(3.));
+        assert_eq!(pool.add(water, LiquidVolume(-9.)), LiquidVolume(-2.));
+        assert_eq!(pool.get(water), LiquidVolume(0.));
+        assert_eq!(pool.total(), LiquidVolume(3.));
+
+        let oxygen = gas::TypeId(0);
+        let mut pool = GasPool::new(GasVolume(4.));
+        assert_eq!(pool.add(oxygen, GasVolume(6.)), GasVolume(2.));
+        assert_eq!(pool.total(), GasVolume(4.));
+    }
+}
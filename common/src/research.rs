@@ -0,0 +1,334 @@
+//! Research of technologies.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use derive_new::new;
+use legion::world::SubWorld;
+use serde::{Deserialize, Serialize};
+
+use crate::cargo;
+use crate::def::{self, tech, GameDefinition};
+use crate::skill::Skills;
+use crate::units::{CargoSize, ResearchPoint, Skill};
+use crate::SetupEcs;
+
+/// Tracks the research progress of the colony.
+#[derive(Debug, Default)]
+pub struct Research {
+    unlocked: BTreeSet<tech::TypeId>,
+    points: ResearchPoint,
+}
+
+impl Research {
+    /// Checks whether a technology has been researched.
+    pub fn is_researched(&self, tech: tech::TypeId) -> bool {
+        self.unlocked.contains(&tech)
+    }
+
+    /// Iterates over the researched technologies.
+    pub fn researched(&self) -> impl Iterator<Item = tech::TypeId> + '_ {
+        self.unlocked.iter().copied()
+    }
+
+    /// The research points accumulated and not yet consumed.
+    pub fn points(&self) -> ResearchPoint {
+        self.points
+    }
+
+    /// Accumulates research points produced by the colony.
+    pub fn add_points(&mut self, points: ResearchPoint) {
+        self.points += points;
+    }
+
+    /// Checks whether some content is available.
+    ///
+    /// Content not unlocked by any technology is always available.
+    pub fn is_unlocked(&self, def: &GameDefinition, unlock: tech::Unlock) -> bool {
+        self.require(def, unlock).is_ok()
+    }
+
+    /// Returns the technologies that unlock some content
+    /// if none of them have been researched.
+    pub fn require(&self, def: &GameDefinition, unlock: tech::Unlock) -> Result<(), Locked> {
+        let techs: Vec<tech::TypeId> = def
+            .tech()
+            .iter()
+            .enumerate()
+            .filter(|(_, ty)| ty.unlocks().contains(&unlock))
+            .map(|(id, _)| tech::TypeId(id))
+            .collect();
+        if techs.is_empty() || techs.iter().any(|&tech| self.is_researched(tech)) {
+            Ok(())
+        } else {
+            Err(Locked { techs })
+        }
+    }
+
+    /// Checks whether a technology can be researched with the given resources.
+    ///
+    /// Returns the definition of the technology.
+    fn check<'t>(
+        &self,
+        def: &'t GameDefinition,
+        id: tech::TypeId,
+        cargo: &BTreeMap<def::cargo::TypeId, CargoSize>,
+        skills: &BTreeMap<def::skill::TypeId, Skill>,
+    ) -> Result<&'t tech::Type, Rejection> {
+        // the ID may come from the network
+        let tech = def.tech().get(id.0).ok_or(Rejection::NoSuchTech)?;
+        if self.is_researched(id) {
+            return Err(Rejection::AlreadyResearched);
+        }
+
+        for &prereq in tech.prerequisites() {
+            if !self.is_researched(prereq) {
+                return Err(Rejection::MissingPrerequisite(prereq));
+            }
+        }
+
+        for cost in tech.costs() {
+            match *cost {
+                tech::Cost::Cargo { ty, size } => {
+                    if cargo.get(&ty).copied().unwrap_or_default() < size {
+                        return Err(Rejection::InsufficientCargo(ty));
+                    }
+                }
+                tech::Cost::Skill { ty, level } => {
+                    if skills.get(&ty).copied().unwrap_or_default() < level {
+                        return Err(Rejection::InsufficientSkill(ty));
+                    }
+                }
+                tech::Cost::Research(points) => {
+                    if self.points < points {
+                        return Err(Rejection::InsufficientResearch);
+                    }
+                }
+            }
+        }
+
+        Ok(tech)
+    }
+}
+
+/// Indicates that some content has not been unlocked yet.
+#[derive(Debug, Clone, PartialEq, Eq, getset::Getters, Serialize, Deserialize)]
+pub struct Locked {
+    /// The technologies that unlock the content.
+    #[getset(get = "pub")]
+    techs: Vec<tech::TypeId>,
+}
+
+/// The reason for rejecting a research request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// The technology does not exist.
+    NoSuchTech,
+    /// The technology has already been researched.
+    AlreadyResearched,
+    /// A prerequisite technology has not been researched.
+    MissingPrerequisite(tech::TypeId),
+    /// The colony does not store enough of a cargo type.
+    InsufficientCargo(def::cargo::TypeId),
+    /// No inhabitant has the required skill level.
+    InsufficientSkill(def::skill::TypeId),
+    /// The colony has not accumulated enough research points.
+    InsufficientResearch,
+}
+
+/// Requests to research a technology.
+#[derive(Debug, new, getset::CopyGetters)]
+pub struct ResearchRequestEvent {
+    /// The technology to research
+    #[getset(get_copy = "pub")]
+    tech: tech::TypeId,
+}
+
+/// Indicates that a technology has been researched.
+#[derive(Debug, new, getset::CopyGetters)]
+pub struct ResearchEvent {
+    /// The researched technology
+    #[getset(get_copy = "pub")]
+    tech: tech::TypeId,
+}
+
+/// Indicates that a research request has been rejected.
+#[derive(Debug, new, getset::CopyGetters)]
+pub struct ResearchRejectEvent {
+    /// The requested technology
+    #[getset(get_copy = "pub")]
+    tech: tech::TypeId,
+    /// The reason for rejection
+    #[getset(get_copy = "pub")]
+    reason: Rejection,
+}
+
+#[codegen::system]
+#[read_component(cargo::Storage)]
+#[write_component(cargo::NextStorageSize)]
+#[read_component(Skills)]
+fn research(
+    world: &mut SubWorld,
+    #[resource] research: &mut Research,
+    #[resource] def: &GameDefinition,
+    #[subscriber] requests: impl Iterator<Item = ResearchRequestEvent>,
+    #[publisher] research_pub: impl FnMut(ResearchEvent),
+    #[publisher] reject_pub: impl FnMut(ResearchRejectEvent),
+) {
+    use legion::IntoQuery;
+
+    for request in requests {
+        let mut cargo = BTreeMap::new();
+        for (storage, size) in <(&cargo::Storage, &cargo::NextStorageSize)>::query().iter(world) {
+            *cargo.entry(storage.cargo()).or_default() += size.size();
+        }
+
+        let mut skills = BTreeMap::<_, Skill>::new();
+        for inhabitant in <&Skills>::query().iter(world) {
+            for (ty, level) in inhabitant.iter() {
+                let max = skills.entry(ty).or_default();
+                if *max < level {
+                    *max = level;
+                }
+            }
+        }
+
+        let tech = match research.check(def, request.tech, &cargo, &skills) {
+            Ok(tech) => tech,
+            Err(reason) => {
+                reject_pub(ResearchRejectEvent {
+                    tech: request.tech,
+                    reason,
+                });
+                continue;
+            }
+        };
+
+        for cost in tech.costs() {
+            match *cost {
+                tech::Cost::Cargo { ty, mut size } => {
+                    for (storage, next) in
+                        <(&cargo::Storage, &mut cargo::NextStorageSize)>::query().iter_mut(world)
+                    {
+                        if storage.cargo() != ty {
+                            continue;
+                        }
+                        let taken = if next.size() < size {
+                            next.size()
+                        } else {
+                            size
+                        };
+                        *next.size_mut() -= taken;
+                        size -= taken;
+                    }
+                }
+                tech::Cost::Skill { .. } => {}
+                tech::Cost::Research(points) => research.points -= points,
+            }
+        }
+
+        research.unlocked.insert(request.tech);
+        research_pub(ResearchEvent { tech: request.tech });
+    }
+}
+
+/// Initializes ECS
+pub fn setup_ecs(setup: SetupEcs) -> SetupEcs {
+    setup.resource(Research::default()).uses(research_setup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::def::{building, vehicle};
+    use crate::testing;
+
+    #[test]
+    fn require_any_unlocking_tech() {
+        let mut def = GameDefinition::default();
+        let car = tech::Unlock::Vehicle(vehicle::TypeId(0));
+        let first = testing::tech(&mut def, Vec::new(), Vec::new(), vec![car]);
+        let second = testing::tech(&mut def, Vec::new(), Vec::new(), vec![car]);
+
+        let mut research = Research::default();
+        assert!(research.is_unlocked(&def, tech::Unlock::Building(building::TypeId(0))));
+        let locked = research
+            .require(&def, car)
+            .expect_err("No tech is researched");
+        assert_eq!(locked.techs(), &vec![first, second]);
+
+        research.unlocked.insert(second);
+        assert!(research.require(&def, car).is_ok());
+    }
+
+    #[test]
+    fn check_prerequisites_and_costs() {
+        let mut def = GameDefinition::default();
+        let ore = def::cargo::TypeId(0);
+        let skill = def::skill::TypeId(0);
+        let base = testing::tech(&mut def, Vec::new(), Vec::new(), Vec::new());
+        let advanced = testing::tech(
+            &mut def,
+            vec![base],
+            vec![
+                tech::Cost::Cargo {
+                    ty: ore,
+                    size: CargoSize(10.),
+                },
+                tech::Cost::Skill {
+                    ty: skill,
+                    level: Skill(5.),
+                },
+                tech::Cost::Research(ResearchPoint(100.)),
+            ],
+            Vec::new(),
+        );
+
+        let check = |research: &Research,
+                     id,
+                     cargo: &BTreeMap<def::cargo::TypeId, CargoSize>,
+                     skills: &BTreeMap<def::skill::TypeId, Skill>| {
+            research.check(&def, id, cargo, skills).map(|_| ())
+        };
+
+        let mut research = Research::default();
+        let mut cargo = BTreeMap::new();
+        let mut skills = BTreeMap::new();
+        assert_eq!(
+            check(&research, tech::TypeId(2), &cargo, &skills),
+            Err(Rejection::NoSuchTech)
+        );
+        assert_eq!(
+            check(&research, advanced, &cargo, &skills),
+            Err(Rejection::MissingPrerequisite(base))
+        );
+        assert_eq!(check(&research, base, &cargo, &skills), Ok(()));
+
+        research.unlocked.insert(base);
+        assert_eq!(
+            check(&research, base, &cargo, &skills),
+            Err(Rejection::AlreadyResearched)
+        );
+        assert_eq!(
+            check(&research, advanced, &cargo, &skills),
+            Err(Rejection::InsufficientCargo(ore))
+        );
+
+        cargo.insert(ore, CargoSize(10.));
+        skills.insert(skill, Skill(4.));
+        assert_eq!(
+            check(&research, advanced, &cargo, &skills),
+            Err(Rejection::InsufficientSkill(skill))
+        );
+
+        skills.insert(skill, Skill(5.));
+        research.add_points(ResearchPoint(60.));
+        assert_eq!(
+            check(&research, advanced, &cargo, &skills),
+            Err(Rejection::InsufficientResearch)
+        );
+
+        research.add_points(ResearchPoint(40.));
+        assert_eq!(research.points(), ResearchPoint(100.));
+        assert_eq!(check(&research, advanced, &cargo, &skills), Ok(()));
+    }
+}
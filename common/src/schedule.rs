@@ -0,0 +1,479 @@
+//! Vehicle routes defined by the player.
+//!
+//! A [`Route`] is a named list of terminals,
+//! each with a [rule][Rule] for loading or unloading some cargo types.
+//! Vehicles [scheduled][Scheduled] on a route stop at its terminals in order.
+//! At each stop, they first unload the cargo types with the [`Unload`][Rule::Unload] rule
+//! into the storage of the terminal,
+//! then load the cargo types with the [`Load`][Rule::Load] rule
+//! up to the [capacity][crate::def::vehicle::Type::capacity] of the vehicle.
+//!
+//! Routes are defined, removed and assigned to vehicles through request events,
+//! which are rejected with a [`RouteRejectEvent`] if invalid.
+//! Only vehicles of types [unlocked][crate::def::tech::Unlock::Vehicle] by research
+//! can be assigned to routes.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use derive_new::new;
+use legion::world::SubWorld;
+use legion::Entity;
+use serde::{Deserialize, Serialize};
+
+use crate::cargo;
+use crate::construction::Site;
+use crate::def::{self, building, tech, GameDefinition};
+use crate::graph::{Graph, NodeBuilding, NodeId};
+use crate::inhabitant::Inventory;
+use crate::logistics::Carrier;
+use crate::research::{Locked, Research};
+use crate::units::CargoSize;
+use crate::vehicle::{StopEvent, Vehicle, VehicleId};
+use crate::SetupEcs;
+
+/// The cargo handling rule of a cargo type at a stop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Rule {
+    /// Load the cargo from the terminal storage.
+    Load,
+    /// Unload the cargo into the terminal storage.
+    Unload,
+}
+
+/// A stop of a route.
+#[derive(Debug, Clone, new, getset::CopyGetters, getset::Getters, Serialize, Deserialize)]
+pub struct RouteStop {
+    /// The terminal node
+    #[getset(get_copy = "pub")]
+    node: NodeId,
+    /// The rules for cargo types handled at this stop
+    #[getset(get = "pub")]
+    rules: BTreeMap<def::cargo::TypeId, Rule>,
+}
+
+/// A list of stops visited cyclically by scheduled vehicles.
+#[derive(Debug, Clone, getset::Getters)]
+pub struct Route {
+    /// The stops of the route
+    #[getset(get = "pub")]
+    stops: Vec<RouteStop>,
+}
+
+impl Route {
+    /// The nodes of the stops in order.
+    fn nodes(&self) -> Vec<NodeId> {
+        self.stops.iter().map(|stop| stop.node).collect()
+    }
+}
+
+/// A resource storing the routes defined by the player.
+#[derive(Debug, Default)]
+pub struct Routes {
+    routes: BTreeMap<String, Route>,
+}
+
+impl Routes {
+    /// Returns the route with the given name.
+    pub fn get(&self, name: &str) -> Option<&Route> {
+        self.routes.get(name)
+    }
+
+    /// Iterates over the routes with their names.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Route)> + '_ {
+        self.routes
+            .iter()
+            .map(|(name, route)| (name.as_str(), route))
+    }
+}
+
+/// A component attached to vehicles following a route.
+#[derive(Debug, Clone, new, getset::Getters)]
+pub struct Scheduled {
+    /// The name of the route
+    #[getset(get = "pub")]
+    route: String,
+}
+
+/// Requests to define a route, replacing any route with the same name.
+#[derive(Debug, new, getset::Getters)]
+pub struct DefineRouteRequestEvent {
+    /// The name of the route
+    #[getset(get = "pub")]
+    name: String,
+    /// The stops of the route
+    #[getset(get = "pub")]
+    stops: Vec<RouteStop>,
+}
+
+/// Requests to remove a route.
+///
+/// Vehicles scheduled on the route stop at their current node.
+#[derive(Debug, new, getset::Getters)]
+pub struct RemoveRouteRequestEvent {
+    /// The name of the route
+    #[getset(get = "pub")]
+    name: String,
+}
+
+/// Requests to schedule a vehicle on a route.
+///
+/// The vehicle is no longer a logistics [carrier][Carrier].
+#[derive(Debug, new, getset::CopyGetters, getset::Getters)]
+pub struct AssignRouteRequestEvent {
+    /// The vehicle
+    #[getset(get_copy = "pub")]
+    vehicle: VehicleId,
+    /// The name of the route
+    #[getset(get = "pub")]
+    route: String,
+}
+
+/// Indicates that a route request has been rejected.
+#[derive(Debug, new, getset::Getters)]
+pub struct RouteRejectEvent {
+    /// The reason for rejection
+    #[getset(get = "pub")]
+    reason: RouteRejection,
+}
+
+/// The reason for rejecting a route request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RouteRejection {
+    /// The defined route has no stops.
+    EmptyRoute(String),
+    /// A stop of the defined route is not an operational terminal.
+    NotTerminal {
+        /// The name of the route
+        route: String,
+        /// The node of the stop
+        node: NodeId,
+    },
+    /// No route has the given name.
+    UnknownRoute(String),
+    /// No vehicle has the given ID.
+    NotVehicle(VehicleId),
+    /// The vehicle type has not been unlocked by research.
+    Locked(Locked),
+}
+
+/// Checks whether the stops of a route are valid.
+pub(crate) fn check_stops(
+    world: &SubWorld,
+    def: &GameDefinition,
+    name: &str,
+    stops: &[RouteStop],
+) -> Result<(), RouteRejection> {
+    use legion::IntoQuery;
+
+    if stops.is_empty() {
+        return Err(RouteRejection::EmptyRoute(name.to_string()));
+    }
+    let terminals: BTreeSet<NodeId> = <(&NodeId, &NodeBuilding, Option<&Site>)>::query()
+        .iter(world)
+        .filter(|(_, building, site)| {
+            site.is_none()
+                && def
+                    .get_building(building.building())
+                    .features()
+                    .iter()
+                    .any(|feature| matches!(feature, building::ExtraFeature::RailTerminal(_)))
+        })
+        .map(|(&node, _, _)| node)
+        .collect();
+    match stops.iter().find(|stop| !terminals.contains(&stop.node)) {
+        Some(stop) => Err(RouteRejection::NotTerminal {
+            route: name.to_string(),
+            node: stop.node,
+        }),
+        None => Ok(()),
+    }
+}
+
+#[codegen::system]
+#[read_component(NodeId)]
+#[read_component(NodeBuilding)]
+#[read_component(Site)]
+#[read_component(Scheduled)]
+#[read_component(Carrier)]
+#[read_component(Inventory)]
+#[read_component(VehicleId)]
+#[write_component(Vehicle)]
+fn configure(
+    world: &mut SubWorld,
+    cmd_buf: &mut legion::systems::CommandBuffer,
+    #[resource] def: &GameDefinition,
+    #[resource] research: &Research,
+    #[resource] routes: &mut Routes,
+    #[subscriber] define_sub: impl Iterator<Item = DefineRouteRequestEvent>,
+    #[subscriber] remove_sub: impl Iterator<Item = RemoveRouteRequestEvent>,
+    #[subscriber] assign_sub: impl Iterator<Item = AssignRouteRequestEvent>,
+    #[publisher] reject_pub: impl FnMut(RouteRejectEvent),
+) {
+    use legion::{EntityStore, IntoQuery};
+
+    for request in define_sub {
+        if let Err(reason) = check_stops(world, def, &request.name, &request.stops) {
+            reject_pub(RouteRejectEvent { reason });
+            continue;
+        }
+
+        let route = Route {
+            stops: request.stops.clone(),
+        };
+        for (scheduled, vehicle) in <(&Scheduled, &mut Vehicle)>::query().iter_mut(world) {
+            if scheduled.route == request.name {
+                vehicle.set_stops(route.nodes());
+            }
+        }
+        routes.routes.insert(request.name.clone(), route);
+    }
+
+    for request in remove_sub {
+        if routes.routes.remove(&request.name).is_none() {
+            reject_pub(RouteRejectEvent {
+                reason: RouteRejection::UnknownRoute(request.name.clone()),
+            });
+            continue;
+        }
+        for (&entity, scheduled, vehicle) in
+            <(Entity, &Scheduled, &mut Vehicle)>::query().iter_mut(world)
+        {
+            if scheduled.route == request.name {
+                let node = vehicle.node();
+                vehicle.set_stops(vec![node]);
+                cmd_buf.remove_component::<Scheduled>(entity);
+            }
+        }
+    }
+
+    for request in assign_sub {
+        let route = match routes.get(&request.route) {
+            Some(route) => route,
+            None => {
+                reject_pub(RouteRejectEvent {
+                    reason: RouteRejection::UnknownRoute(request.route.clone()),
+                });
+                continue;
+            }
+        };
+        let vehicle = <(Entity, &VehicleId, &Vehicle)>::query()
+            .iter(world)
+            .find(|&(_, &id, _)| id == request.vehicle)
+            .map(|(&entity, _, _)| entity);
+        let vehicle = match vehicle {
+            Some(vehicle) => vehicle,
+            None => {
+                reject_pub(RouteRejectEvent {
+                    reason: RouteRejection::NotVehicle(request.vehicle),
+                });
+                continue;
+            }
+        };
+        let mut entry = world
+            .entry_mut(vehicle)
+            .expect("Vehicle entity does not exist");
+        let ty = entry
+            .get_component::<Vehicle>()
+            .expect("Vehicle entity does not have Vehicle")
+            .ty();
+        if let Err(locked) = research.require(def, tech::Unlock::Vehicle(ty)) {
+            reject_pub(RouteRejectEvent {
+                reason: RouteRejection::Locked(locked),
+            });
+            continue;
+        }
+
+        // cargo loaded as a carrier is kept in the hold
+        let mut hold = entry
+            .get_component::<Inventory>()
+            .ok()
+            .cloned()
+            .unwrap_or_default();
+        if let Ok(carrier) = entry.get_component::<Carrier>() {
+            if let Some((ty, size)) = carrier.load() {
+                hold.add(ty, size);
+            }
+            cmd_buf.remove_component::<Carrier>(vehicle);
+        }
+        entry
+            .get_component_mut::<Vehicle>()
+            .expect("Vehicle entity does not have Vehicle")
+            .set_stops(route.nodes());
+        cmd_buf.add_component(vehicle, hold);
+        cmd_buf.add_component(vehicle, Scheduled::new(request.route.clone()));
+    }
+}
+
+#[codegen::system]
+#[read_component(Scheduled)]
+#[read_component(NodeBuilding)]
+#[read_component(cargo::Storage)]
+#[write_component(cargo::StorageList)]
+#[write_component(cargo::NextStorageSize)]
+#[read_component(Vehicle)]
+#[write_component(Inventory)]
+fn exchange(
+    world: &mut SubWorld,
+    cmd_buf: &mut legion::systems::CommandBuffer,
+    #[resource] def: &GameDefinition,
+    #[resource] graph: &Graph,
+    #[resource] routes: &Routes,
+    #[subscriber] stop_sub: impl Iterator<Item = StopEvent>,
+) {
+    use legion::EntityStore;
+
+    let mut delivery = cargo::Delivery::default();
+
+    for stop in stop_sub {
+        let (route, index, capacity, mut hold) = match world.entry_ref(stop.vehicle()) {
+            Ok(entry) => match (
+                entry.get_component::<Scheduled>(),
+                entry.get_component::<Vehicle>(),
+                entry.get_component::<Inventory>(),
+            ) {
+                (Ok(scheduled), Ok(vehicle), Ok(hold)) => {
+                    // the stop that the vehicle has just arrived at
+                    let len = vehicle.stops().len();
+                    let index = (vehicle.next_stop() + len - 1) % len;
+                    let capacity = def.get_vehicle(vehicle.ty()).capacity();
+                    (scheduled.route().clone(), index, capacity, hold.clone())
+                }
+                _ => continue,
+            },
+            Err(_) => continue,
+        };
+        let rules = match routes.get(&route).and_then(|route| route.stops.get(index)) {
+            Some(route_stop) if route_stop.node == stop.node() => &route_stop.rules,
+            _ => continue,
+        };
+        let node = match graph.get_node(stop.node()) {
+            Some(node) => node,
+            None => continue,
+        };
+
+        unload(rules, &mut hold, |ty, size| {
+            delivery.store(world, def, node, ty, size)
+        });
+        load(rules, &mut hold, capacity, |ty, size| {
+            cargo::take(world, node, ty, size)
+        });
+
+        let mut entry = world
+            .entry_mut(stop.vehicle())
+            .expect("Vehicle entity does not exist");
+        *entry
+            .get_component_mut::<Inventory>()
+            .expect("Vehicle entity does not have Inventory") = hold;
+    }
+
+    delivery.finish(world, cmd_buf);
+}
+
+/// Unloads the cargo types with the [`Unload`][Rule::Unload] rule from `hold`.
+///
+/// `store` stores up to the given size of a cargo type in the terminal
+/// and returns the amount actually stored.
+fn unload(
+    rules: &BTreeMap<def::cargo::TypeId, Rule>,
+    hold: &mut Inventory,
+    mut store: impl FnMut(def::cargo::TypeId, CargoSize) -> CargoSize,
+) {
+    for (&ty, &rule) in rules {
+        if rule == Rule::Unload {
+            let stored = store(ty, hold.get(ty));
+            hold.take(ty, stored);
+        }
+    }
+}
+
+/// Loads the cargo types with the [`Load`][Rule::Load] rule into `hold`
+/// up to `capacity` in total.
+///
+/// `take` takes up to the given size of a cargo type from the terminal
+/// and returns the amount actually taken.
+fn load(
+    rules: &BTreeMap<def::cargo::TypeId, Rule>,
+    hold: &mut Inventory,
+    capacity: CargoSize,
+    mut take: impl FnMut(def::cargo::TypeId, CargoSize) -> CargoSize,
+) {
+    let mut free = hold.iter().fold(capacity, |free, (_, size)| free - size);
+    for (&ty, &rule) in rules {
+        if rule == Rule::Load && free > CargoSize::default() {
+            let taken = take(ty, free);
+            hold.add(ty, taken);
+            free -= taken;
+        }
+    }
+}
+
+/// Initializes ECS
+pub fn setup_ecs(setup: SetupEcs) -> SetupEcs {
+    setup
+        .resource(Routes::default())
+        .uses(configure_setup)
+        .uses(exchange_setup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unload_then_load() {
+        let ore = def::cargo::TypeId(0);
+        let steel = def::cargo::TypeId(1);
+        let food = def::cargo::TypeId(2);
+        let rules = vec![(ore, Rule::Unload), (steel, Rule::Load), (food, Rule::Load)]
+            .into_iter()
+            .collect();
+        let mut terminal: BTreeMap<def::cargo::TypeId, (CargoSize, CargoSize)> = vec![
+            (ore, (CargoSize(0.), CargoSize(30.))),
+            (steel, (CargoSize(40.), CargoSize(100.))),
+            (food, (CargoSize(50.), CargoSize(100.))),
+        ]
+        .into_iter()
+        .collect();
+
+        let mut hold = Inventory::default();
+        hold.add(ore, CargoSize(50.));
+        hold.add(food, CargoSize(10.));
+
+        unload(&rules, &mut hold, |ty, size| {
+            let (stored, capacity) = terminal.get_mut(&ty).expect("Terminal stores all types");
+            let space = *capacity - *stored;
+            let given = if space < size { space } else { size };
+            *stored += given;
+            given
+        });
+        // the terminal only has space for 30 ore
+        assert_eq!(hold.get(ore), CargoSize(20.));
+        assert_eq!(terminal[&ore].0, CargoSize(30.));
+
+        load(&rules, &mut hold, CargoSize(100.), |ty, size| {
+            let (stored, _) = terminal.get_mut(&ty).expect("Terminal stores all types");
+            let taken = if *stored < size { *stored } else { size };
+            *stored -= taken;
+            taken
+        });
+        // 70 of free space, filled with all 40 steel, then 30 food
+        assert_eq!(hold.get(steel), CargoSize(40.));
+        assert_eq!(hold.get(food), CargoSize(40.));
+        assert_eq!(hold.get(ore), CargoSize(20.));
+        assert_eq!(terminal[&steel].0, CargoSize(0.));
+        assert_eq!(terminal[&food].0, CargoSize(20.));
+    }
+
+    #[test]
+    fn load_into_full_hold() {
+        let ore = def::cargo::TypeId(0);
+        let rules = vec![(ore, Rule::Load)].into_iter().collect();
+        let mut hold = Inventory::default();
+        hold.add(ore, CargoSize(100.));
+
+        load(&rules, &mut hold, CargoSize(100.), |_, _| {
+            panic!("Full vehicles do not take cargo")
+        });
+        assert_eq!(hold.get(ore), CargoSize(100.));
+    }
+}
@@ -0,0 +1,409 @@
+//! Police and prisons.
+//!
+//! [Police] officers look for [outlaws][Happiness::is_outlaw] through the corridors,
+//! moving by one node every simulation tick.
+//! An outlaw sharing a node with the officer chasing them is arrested
+//! and escorted to the nearest building with the [`Prison`][building::ExtraFeature::Prison] feature
+//! that still has free capacity.
+//! [Prisoners][Prisoner] stay in the prison until their happiness is positive.
+//! Prisoners are released where they are if their escort dies or leaves the police.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use derive_new::new;
+use legion::world::SubWorld;
+use legion::Entity;
+
+use crate::clock::SimulationEvent;
+use crate::construction::Site;
+use crate::def::{building, GameDefinition};
+use crate::graph::{self, EdgeId, NodeBuilding, NodeId};
+use crate::happiness::Happiness;
+use crate::inhabitant::{DeathEvent, Destination, Location};
+use crate::vehicle::Passenger;
+use crate::SetupEcs;
+
+/// A component attached to inhabitants assigned as police officers.
+#[derive(Debug, Clone, Copy, Default, getset::CopyGetters)]
+pub struct Police {
+    /// The current duty of the officer
+    #[getset(get_copy = "pub")]
+    duty: Duty,
+}
+
+/// The duty of a police officer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Duty {
+    /// The officer is waiting for an outlaw to chase.
+    Patrol,
+    /// The officer is moving towards an outlaw.
+    Chase {
+        /// The chased outlaw
+        outlaw: Entity,
+    },
+    /// The officer is escorting an arrested outlaw to a prison.
+    Escort {
+        /// The arrested outlaw
+        prisoner: Entity,
+        /// The node of the prison
+        prison: NodeId,
+    },
+}
+
+impl Default for Duty {
+    fn default() -> Self {
+        Self::Patrol
+    }
+}
+
+/// A component attached to arrested outlaws.
+#[derive(Debug, Clone, Copy, new, getset::CopyGetters)]
+pub struct Prisoner {
+    /// The node entity of the prison
+    #[getset(get_copy = "pub")]
+    prison: Entity,
+}
+
+/// Returns the number of prisoners a building type can hold.
+fn capacity(building: &building::Type) -> u32 {
+    building
+        .features()
+        .iter()
+        .map(|feature| match *feature {
+            building::ExtraFeature::Prison(capacity) => capacity,
+            _ => 0,
+        })
+        .sum()
+}
+
+#[codegen::system]
+#[read_component(EdgeId)]
+#[read_component(NodeId)]
+#[read_component(NodeBuilding)]
+#[read_component(Site)]
+#[read_component(Happiness)]
+#[read_component(Prisoner)]
+#[write_component(Location)]
+#[write_component(Police)]
+fn patrol(
+    world: &mut SubWorld,
+    cmd_buf: &mut legion::systems::CommandBuffer,
+    #[resource] def: &GameDefinition,
+    #[subscriber] sim_sub: impl Iterator<Item = SimulationEvent>,
+    #[subscriber] death_sub: impl Iterator<Item = DeathEvent>,
+) {
+    use legion::{EntityStore, IntoQuery};
+
+    let mut dead = BTreeSet::new();
+    for death in death_sub {
+        let duty = <&Police>::query()
+            .get(world, death.inhabitant())
+            .ok()
+            .map(|police| police.duty);
+        if let Some(Duty::Escort { prisoner, .. }) = duty {
+            cmd_buf.remove_component::<Prisoner>(prisoner);
+        }
+        dead.insert(death.inhabitant());
+    }
+
+    if sim_sub.next().is_none() {
+        return;
+    }
+
+    let adjacency = graph::adjacency(<&EdgeId>::query().iter(world));
+
+    let mut locations = BTreeMap::new();
+    let mut outlaws = BTreeSet::new();
+    let mut inmates = BTreeMap::<Entity, u32>::new();
+    for (&entity, location, happiness, prisoner) in
+        <(Entity, &Location, Option<&Happiness>, Option<&Prisoner>)>::query().iter(world)
+    {
+        if dead.contains(&entity) {
+            continue;
+        }
+        locations.insert(entity, location.node());
+        if let Some(prisoner) = prisoner {
+            *inmates.entry(prisoner.prison).or_default() += 1;
+        } else if matches!(happiness, Some(happiness) if happiness.is_outlaw()) {
+            outlaws.insert(entity);
+        }
+    }
+
+    // prisons with free capacity, indexed by node
+    let mut prisons = BTreeMap::new();
+    for (&entity, &node, building, site) in
+        <(Entity, &NodeId, &NodeBuilding, Option<&Site>)>::query().iter(world)
+    {
+        if site.is_some() {
+            continue;
+        }
+        let capacity = capacity(def.get_building(building.building()));
+        let free = capacity.saturating_sub(inmates.get(&entity).copied().unwrap_or(0));
+        if free > 0 {
+            prisons.insert(node, (entity, free));
+        }
+    }
+
+    let officers: Vec<(Entity, Duty)> = <(Entity, &Police)>::query()
+        .filter(legion::component::<Location>())
+        .iter(world)
+        .filter(|(entity, _)| !dead.contains(entity))
+        .map(|(&entity, police)| (entity, police.duty))
+        .collect();
+    // outlaws already chased by another officer
+    let mut chased: BTreeSet<Entity> = officers
+        .iter()
+        .filter_map(|&(_, duty)| match duty {
+            Duty::Chase { outlaw } if outlaws.contains(&outlaw) => Some(outlaw),
+            _ => None,
+        })
+        .collect();
+
+    let mut moves = Vec::new();
+    for (officer, mut duty) in officers {
+        let mut node = *locations
+            .get(&officer)
+            .expect("Police entity does not have Location");
+
+        match duty {
+            Duty::Chase { outlaw } if !outlaws.contains(&outlaw) => duty = Duty::Patrol,
+            Duty::Escort { prisoner, .. } if !locations.contains_key(&prisoner) => {
+                duty = Duty::Patrol;
+            }
+            _ => {}
+        }
+
+        if duty == Duty::Patrol {
+            let targets: BTreeMap<NodeId, Entity> = outlaws
+                .iter()
+                .filter(|&&outlaw| outlaw != officer && !chased.contains(&outlaw))
+                .filter_map(|&outlaw| locations.get(&outlaw).map(|&node| (node, outlaw)))
+                .collect();
+            let path = graph::route(&adjacency, node, |node| targets.contains_key(&node));
+            if let Some(path) = path {
+                let outlaw = *targets
+                    .get(path.last().unwrap_or(&node))
+                    .expect("Route ends at a target");
+                chased.insert(outlaw);
+                duty = Duty::Chase { outlaw };
+            }
+        }
+
+        match duty {
+            Duty::Patrol => {}
+            Duty::Chase { outlaw } => {
+                let target = *locations
+                    .get(&outlaw)
+                    .expect("Outlaw entity does not have Location");
+                let path = graph::route(&adjacency, node, |node| node == target);
+                match path.as_ref().and_then(|path| path.first()) {
+                    Some(&next) => node = next,
+                    None if path.is_none() => duty = Duty::Patrol, // the outlaw is unreachable
+                    None => {}
+                }
+
+                if node == target {
+                    if let Some((prison, prison_entity)) =
+                        reserve_prison(&adjacency, node, &mut prisons)
+                    {
+                        cmd_buf.add_component(outlaw, Prisoner::new(prison_entity));
+                        cmd_buf.remove_component::<Destination>(outlaw);
+                        cmd_buf.remove_component::<Passenger>(outlaw);
+                        outlaws.remove(&outlaw);
+                        duty = Duty::Escort {
+                            prisoner: outlaw,
+                            prison,
+                        };
+                    }
+                }
+            }
+            Duty::Escort { prisoner, prison } => {
+                match graph::route(&adjacency, node, |node| node == prison) {
+                    Some(path) => {
+                        if let Some(&next) = path.first() {
+                            node = next;
+                        }
+                        moves.push((prisoner, node));
+                        if node == prison {
+                            duty = Duty::Patrol;
+                        }
+                    }
+                    None => {
+                        // the prison is no longer reachable
+                        cmd_buf.remove_component::<Prisoner>(prisoner);
+                        duty = Duty::Patrol;
+                    }
+                }
+            }
+        }
+
+        moves.push((officer, node));
+        let mut entry = world
+            .entry_mut(officer)
+            .expect("Police entity does not exist");
+        entry
+            .get_component_mut::<Police>()
+            .expect("Police entity does not have Police")
+            .duty = duty;
+    }
+
+    for (entity, node) in moves {
+        let mut entry = world
+            .entry_mut(entity)
+            .expect("Inhabitant entity does not exist");
+        *entry
+            .get_component_mut::<Location>()
+            .expect("Inhabitant entity does not have Location") = Location::new(node);
+    }
+}
+
+#[codegen::system]
+#[read_component(NodeId)]
+#[read_component(Location)]
+#[read_component(Happiness)]
+#[read_component(Prisoner)]
+#[read_component(Police)]
+fn release(
+    world: &mut SubWorld,
+    cmd_buf: &mut legion::systems::CommandBuffer,
+    #[subscriber] sim_sub: impl Iterator<Item = SimulationEvent>,
+) {
+    use legion::{EntityStore, IntoQuery};
+
+    if sim_sub.next().is_none() {
+        return;
+    }
+
+    let escorted: BTreeSet<Entity> = <&Police>::query()
+        .iter(world)
+        .filter_map(|police| match police.duty {
+            Duty::Escort { prisoner, .. } => Some(prisoner),
+            _ => None,
+        })
+        .collect();
+
+    for (&entity, location, happiness, prisoner) in
+        <(Entity, &Location, &Happiness, &Prisoner)>::query().iter(world)
+    {
+        let prison = world
+            .entry_ref(prisoner.prison)
+            .ok()
+            .and_then(|entry| entry.get_component::<NodeId>().ok().copied());
+        if should_release(
+            prison,
+            location.node(),
+            happiness,
+            escorted.contains(&entity),
+        ) {
+            cmd_buf.remove_component::<Prisoner>(entity);
+        }
+    }
+}
+
+/// Reserves a place in the nearest prison with free capacity from `node`.
+///
+/// `prisons` maps the prison nodes to their entities and free capacity.
+/// Returns the node and entity of the prison.
+fn reserve_prison(
+    adjacency: &BTreeMap<NodeId, Vec<NodeId>>,
+    node: NodeId,
+    prisons: &mut BTreeMap<NodeId, (Entity, u32)>,
+) -> Option<(NodeId, Entity)> {
+    let path = graph::route(adjacency, node, |node| prisons.contains_key(&node))?;
+    let prison = path.last().copied().unwrap_or(node);
+    let (entity, free) = prisons.get_mut(&prison).expect("Route ends at a prison");
+    let entity = *entity;
+    *free -= 1;
+    if *free == 0 {
+        prisons.remove(&prison);
+    }
+    Some((prison, entity))
+}
+
+/// Whether a prisoner at `location` should be released.
+///
+/// `prison` is the node of the prison, or `None` if it has been demolished.
+fn should_release(
+    prison: Option<NodeId>,
+    location: NodeId,
+    happiness: &Happiness,
+    escorted: bool,
+) -> bool {
+    match prison {
+        // the prison has been demolished
+        None => true,
+        Some(prison) if prison == location => happiness.value().0 > 0.,
+        // the escort has died or left the police before reaching the prison
+        Some(_) => !escorted,
+    }
+}
+
+/// Initializes ECS
+pub fn setup_ecs(setup: SetupEcs) -> SetupEcs {
+    setup.uses(patrol_setup).uses(release_setup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units;
+
+    #[test]
+    fn reserve_nearest_free_prison() {
+        let nodes: Vec<NodeId> = (0..4).map(NodeId::new).collect();
+        let edges = vec![
+            EdgeId::new(nodes[0], nodes[1]),
+            EdgeId::new(nodes[1], nodes[2]),
+            EdgeId::new(nodes[2], nodes[3]),
+        ];
+        let adjacency = graph::adjacency(&edges);
+
+        let mut world = legion::World::default();
+        let near = world.push(((),));
+        let far = world.push(((),));
+        let mut prisons = vec![(nodes[1], (near, 1)), (nodes[3], (far, 2))]
+            .into_iter()
+            .collect();
+
+        assert_eq!(
+            reserve_prison(&adjacency, nodes[0], &mut prisons),
+            Some((nodes[1], near))
+        );
+        assert_eq!(
+            reserve_prison(&adjacency, nodes[0], &mut prisons),
+            Some((nodes[3], far))
+        );
+        assert_eq!(
+            reserve_prison(&adjacency, nodes[3], &mut prisons),
+            Some((nodes[3], far))
+        );
+        assert!(prisons.is_empty());
+        assert_eq!(reserve_prison(&adjacency, nodes[0], &mut prisons), None);
+    }
+
+    #[test]
+    fn release_conditions() {
+        let prison = NodeId::new(0);
+        let outside = NodeId::new(1);
+        let happy = Happiness::new(units::Happiness(1.));
+        let unhappy = Happiness::new(units::Happiness(-1.));
+
+        assert!(should_release(Some(prison), prison, &happy, false));
+        assert!(!should_release(Some(prison), prison, &unhappy, false));
+        assert!(!should_release(Some(prison), outside, &happy, true));
+        assert!(should_release(Some(prison), outside, &unhappy, false));
+        assert!(should_release(None, outside, &unhappy, true));
+    }
+
+    #[test]
+    fn count_prison_capacity() {
+        let mut def = GameDefinition::default();
+        let prison = crate::testing::building(
+            &mut def,
+            Vec::new(),
+            crate::testing::storage(0., 0., 0.),
+            vec![building::ExtraFeature::Prison(4)],
+        );
+        assert_eq!(capacity(def.get_building(prison)), 4);
+    }
+}
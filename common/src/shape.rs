@@ -43,7 +43,7 @@ impl Shape {
 }
 
 /// A unit shape variant
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Unit {
     /// A unit cube `[-1, 1]^3`
     Cube,
@@ -72,85 +72,41 @@ impl Unit {
     /// If it does, returns the smallest weight `w` (`0 <= w <= 1`)
     /// at which `start * (1 - w) + end * w` is within this shape.
     pub fn between(&self, start: Point, end: Point) -> Option<f64> {
-        match self {
-            Self::Cube => {
-                let dir = end - start;
+        let dir = end - start;
 
-                let mut min_w = None;
+        let range = match self {
+            Self::Cube => {
+                // Intersect the segment with the slabs `-1 <= x, y, z <= 1`.
+                let mut range = 0. ..=1.;
                 for dim in 0..3 {
                     #[allow(clippy::indexing_slicing)]
-                    for &target in &[-1., 1.] {
-                        let w = (target - start[dim]) / dir[dim];
-                        if (0. ..=1.).contains(&w) {
-                            let point = start + dir * w;
-                            let inside = (0..3)
-                                .filter(|&other| other != dim)
-                                .all(|other| (-1. ..=1.).contains(&point[other]));
-                            if inside {
-                                min_w = Some(match min_w {
-                                    Some(prev) if prev < w => prev,
-                                    _ => w,
-                                });
-                            }
-                        }
-                    }
+                    let slab = line_slab(start[dim], dir[dim], -1., 1.)?;
+                    range = intersect_ranges(range, slab)?;
                 }
-
-                min_w
+                range
             }
             Self::Sphere => {
-                if self.contains(start) {
-                    return Some(0.);
-                }
-                if self.contains(end) {
-                    return Some(1.); // FIXME: This is not the closest point!
-                }
-
-                let dir = end - start;
-
-                // Neither endpoint is within the sphere.
-                // If the sphere contains part of the segment, the closest point of the line
-                // containing the segment from the sphere center must also be within the segment.
-                let w = (Point::origin() - start).dot(&dir) / dir.norm_squared();
-                let closest = start + dir * w;
-                self.contains(closest).then(|| w)
+                // Solve |start + dir * w|^2 = 1
+                let range = line_quadric(
+                    dir.norm_squared(),
+                    start.coords.dot(&dir) * 2.,
+                    start.coords.norm_squared() - 1.,
+                )?;
+                intersect_ranges(0. ..=1., range)?
             }
             Self::Cylinder => {
-                let delta = end - start;
-                let d2 = delta.norm_squared();
-                let discrim = d2 - (start.x * end.y - start.y * end.x).powi(2);
-
-                if discrim > 0. {
-                    let dr = discrim.sqrt();
-                    let base = start.x * delta.x + start.y * delta.y;
-                    let w1 = (-base - dr) / d2;
-                    let w2 = (-base + dr) / d2;
-
-                    let zw0 = -start.z / delta.z;
-                    let zw1 = (1. - start.z) / delta.z;
-
-                    fn intersect_ranges(
-                        a: RangeInclusive<f64>,
-                        b: RangeInclusive<f64>,
-                    ) -> Option<RangeInclusive<f64>> {
-                        if a.end() < b.start() || b.end() < a.start() {
-                            return None;
-                        }
-                        (a.end() >= b.start() && b.end() >= a.start()).then(|| {
-                            f64::max(*a.start(), *b.start())..=f64::min(*a.end(), *b.end())
-                        })
-                    }
-
-                    let range = intersect_ranges(
-                        intersect_ranges(0. ..=1., w1..=w2)?,
-                        f64::min(zw0, zw1)..=f64::max(zw0, zw1),
-                    )?;
-                    Some(*range.start())
-                } else {
-                    None
-                }
+                // Solve |start.xy + dir.xy * w|^2 = 1
+                let range = line_quadric(
+                    dir.x.powi(2) + dir.y.powi(2),
+                    (start.x * dir.x + start.y * dir.y) * 2.,
+                    start.x.powi(2) + start.y.powi(2) - 1.,
+                )?;
+                let range = intersect_ranges(0. ..=1., range)?;
+                intersect_ranges(range, line_slab(start.z, dir.z, 0., 1.)?)?
             }
-        }
+        };
+
+        Some(*range.start())
     }
 
     /// Computes the axis-aligned bounding box under the given transformation matrix
@@ -165,15 +121,16 @@ impl Unit {
                 type Storage = nalgebra::storage::Owned<f64, dim::U4, dim::U8>;
                 type Points = nalgebra::Matrix<f64, dim::U4, dim::U8, Storage>;
 
-                fn p01() -> impl Iterator<Item = f64> {
-                    [0., 1.].iter().copied()
+                fn corners() -> impl Iterator<Item = f64> {
+                    [-1., 1.].iter().copied()
                 }
                 fn xyz(x: f64, y: f64, z: f64) -> impl Iterator<Item = f64> {
                     let vec: SmallVec<[f64; 4]> = smallvec![x, y, z, 1.];
                     vec.into_iter()
                 }
-                let iter = p01()
-                    .flat_map(|x| p01().flat_map(move |y| p01().flat_map(move |z| xyz(x, y, z))));
+                let iter = corners().flat_map(|x| {
+                    corners().flat_map(move |y| corners().flat_map(move |z| xyz(x, y, z)))
+                });
                 let mut points = Points::from_iterator(iter);
                 points = transform * points;
 
@@ -275,6 +232,40 @@ impl Unit {
     }
 }
 
+/// Intersects two closed ranges, returning `None` if they are disjoint.
+fn intersect_ranges(a: RangeInclusive<f64>, b: RangeInclusive<f64>) -> Option<RangeInclusive<f64>> {
+    let start = f64::max(*a.start(), *b.start());
+    let end = f64::min(*a.end(), *b.end());
+    (start <= end).then(|| start..=end)
+}
+
+/// Computes the range of `w` such that `min <= start + dir * w <= max`.
+fn line_slab(start: f64, dir: f64, min: f64, max: f64) -> Option<RangeInclusive<f64>> {
+    if dir.abs() < 1e-10 {
+        return (min..=max)
+            .contains(&start)
+            .then(|| f64::NEG_INFINITY..=f64::INFINITY);
+    }
+
+    let w1 = (min - start) / dir;
+    let w2 = (max - start) / dir;
+    Some(f64::min(w1, w2)..=f64::max(w1, w2))
+}
+
+/// Computes the range of `w` such that `a * w^2 + b * w + c <= 0`, where `a >= 0`.
+fn line_quadric(a: f64, b: f64, c: f64) -> Option<RangeInclusive<f64>> {
+    if a < 1e-10 {
+        return (c <= 0.).then(|| f64::NEG_INFINITY..=f64::INFINITY);
+    }
+
+    let discrim = b.powi(2) - a * c * 4.;
+    if discrim < 0. {
+        return None;
+    }
+    let sqrt = discrim.sqrt();
+    Some((-b - sqrt) / (a * 2.)..=(-b + sqrt) / (a * 2.))
+}
+
 #[cfg(test)]
 mod tests {
     use std::f64::consts::{PI, SQRT_2};
@@ -303,6 +294,21 @@ mod tests {
         assert_pt(bb.1, range.end);
     }
 
+    #[test]
+    pub fn cube_bb() {
+        assert_bb(
+            Unit::Cube,
+            Matrix::identity(),
+            [-1., -1., -1.]..[1., 1., 1.],
+        );
+        assert_bb(
+            Unit::Cube,
+            Matrix::new_nonuniform_scaling(&Vector::new(0.5, 2., 5.))
+                .append_translation(&Vector::new(1., 1., 1.)),
+            [0.5, -1., -4.]..[1.5, 3., 6.],
+        );
+    }
+
     #[test]
     pub fn sphere_bb() {
         assert_bb(
@@ -395,6 +401,36 @@ mod tests {
         assert_between!((0., 0., 0.5)..(1., 0., 1.5) => Some(0., 1e-6));
         assert_between!((1., 0., 0.5)..(0., 0., 1.5) => Some(0., 1e-6));
     }
+
+    #[test]
+    pub fn sphere_between() {
+        let between = |start: [f64; 3], end: [f64; 3]| {
+            Unit::Sphere.between(Point::from(start), Point::from(end))
+        };
+
+        let w = between([-2., 0., 0.], [2., 0., 0.]).expect("Segment passes through center");
+        assert!((w - 0.25).abs() < 1e-6, "got {}", w);
+        let w = between([0., 0., 0.], [2., 0., 0.]).expect("Segment starts inside");
+        assert!(w.abs() < 1e-6, "got {}", w);
+        let w = between([0., 0., 3.], [0., 0., 0.5]).expect("Segment ends inside");
+        assert!((w - 0.8).abs() < 1e-6, "got {}", w);
+        assert_eq!(between([-2., 1.5, 0.], [2., 1.5, 0.]), None);
+        assert_eq!(between([3., 0., 0.], [2., 0., 0.]), None);
+    }
+
+    #[test]
+    pub fn cube_between() {
+        let between = |start: [f64; 3], end: [f64; 3]| {
+            Unit::Cube.between(Point::from(start), Point::from(end))
+        };
+
+        let w = between([3., 0.5, 0.5], [-3., 0.5, 0.5]).expect("Segment crosses cube");
+        assert!((w - 1. / 3.).abs() < 1e-6, "got {}", w);
+        let w = between([0., 0., 0.], [3., 0., 0.]).expect("Segment starts inside");
+        assert!(w.abs() < 1e-6, "got {}", w);
+        assert_eq!(between([3., 1.5, 0.], [-3., 1.5, 0.]), None);
+        assert_eq!(between([3., 3., 0.], [2., -3., 0.]), None);
+    }
 }
 
 /// The texture of a rendered object
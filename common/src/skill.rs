@@ -0,0 +1,37 @@
+//! Skill levels of inhabitants.
+
+use std::collections::BTreeMap;
+
+use crate::def;
+use crate::units;
+
+/// A component storing the skill levels of an inhabitant.
+///
+/// Skills not stored are at level zero.
+#[derive(Debug, Clone, Default)]
+pub struct Skills {
+    levels: BTreeMap<def::skill::TypeId, units::Skill>,
+}
+
+impl Skills {
+    /// Returns the level of a skill.
+    pub fn get(&self, ty: def::skill::TypeId) -> units::Skill {
+        self.levels.get(&ty).copied().unwrap_or_default()
+    }
+
+    /// Sets the level of a skill.
+    pub fn set(&mut self, ty: def::skill::TypeId, level: units::Skill) {
+        self.levels.insert(ty, level);
+    }
+
+    /// Changes the level of a skill by `delta`.
+    pub fn add(&mut self, ty: def::skill::TypeId, delta: units::Skill) {
+        let level = self.levels.entry(ty).or_default();
+        *level += delta;
+    }
+
+    /// Iterates over the stored skill levels.
+    pub fn iter(&self) -> impl Iterator<Item = (def::skill::TypeId, units::Skill)> + '_ {
+        self.levels.iter().map(|(&ty, &level)| (ty, level))
+    }
+}
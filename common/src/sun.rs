@@ -1,46 +1,75 @@
 //! Calculates the sunlight level of each building
 
-use std::collections::{btree_map::Entry, BTreeMap};
+use std::collections::{BTreeMap, BTreeSet};
 use std::f64::consts::PI;
 
-use smallvec::SmallVec;
-
-use crate::clock::Clock;
-use crate::config;
+use crate::clock::{Clock, SimulationEvent};
+use crate::def::orbit::Orbit;
+use crate::def::GameDefinition;
 use crate::graph::*;
-use crate::shape::Shape;
-use crate::space::{Position, Vector};
+use crate::shape::{self, Shape};
+use crate::space::{Matrix, Position, Vector};
 use crate::units::Brightness;
-use crate::util::Finite;
+use crate::util::lerp;
 use crate::SetupEcs;
 use safety::Safety;
 
+pub mod shadow;
+pub use shadow::ShadowMap;
+
 /// The position of the sun
 #[derive(Default, getset::CopyGetters)]
 pub struct Sun {
-    /// Orientation of the sun, in radians from +x towards +y
+    /// Orbital angle of the sun, in radians from +x towards +y before tilting
     #[getset(get_copy = "pub")]
     yaw: f64,
+    /// Inclination of the orbital plane of the sun from the XY plane, in radians
+    #[getset(get_copy = "pub")]
+    tilt: f64,
 }
 
 impl Sun {
     /// Direction vector from any opaque object to the sun.
     pub fn direction(&self) -> Vector {
-        Vector::new(self.yaw().cos(), self.yaw().sin(), 0.)
+        orbit_direction(self.yaw(), self.tilt())
+    }
+
+    /// The current month as a fractional index in `0..month_count`.
+    pub fn month(&self, month_count: usize) -> f64 {
+        self.yaw() / PI / 2. * month_count.small_float::<f64>()
     }
 }
 
+/// Direction vector from any opaque object to the sun at the given orbital angle.
+///
+/// The sun orbits from +x towards +y in a plane rotated around the x-axis by `tilt`,
+/// so the sun is above the XY plane for half of the year and below it for the other half.
+pub fn orbit_direction(yaw: f64, tilt: f64) -> Vector {
+    Vector::new(yaw.cos(), yaw.sin() * tilt.cos(), yaw.sin() * tilt.sin())
+}
+
+/// Direction vector from any opaque object to the sun at the start of a month.
+pub fn month_direction(orbit: &Orbit, month: usize) -> Vector {
+    let yaw = PI * 2. / orbit.month_count().small_float::<f64>() * month.small_float::<f64>();
+    orbit_direction(yaw, orbit.axial_tilt())
+}
+
 #[codegen::system]
 fn move_sun(
     #[resource] sun: &mut Sun,
     #[resource] clock: &Clock,
-    #[resource] config: &config::Scalar,
+    #[resource] def: &GameDefinition,
 
     #[debug("Time", "Clock")] clock_debug: &codegen::DebugEntry,
     #[debug("Time", "Sun")] sun_debug: &codegen::DebugEntry,
 ) {
-    sun.yaw += config.sun_speed * clock.delta();
-    sun.yaw %= PI * 2.;
+    let orbit = def.orbit();
+    let year = orbit.year_length().value();
+    if year > 0 {
+        let phase = clock.now().since_epoch().value() % year;
+        sun.yaw = f64::from(phase) / f64::from(year) * PI * 2.;
+    }
+    sun.tilt = orbit.axial_tilt();
 
     codegen::update_debug!(clock_debug, "{}", clock.now().since_epoch().value());
     codegen::update_debug!(
@@ -52,134 +81,236 @@ fn move_sun(
     );
 }
 
-/// Number of partitions to compute shadow casting for
-pub const MONTH_COUNT: usize = 12;
-
 /// A component storing the lighting data for a node.
 #[derive(Debug, Default, getset::Getters)]
 pub struct LightStats {
     /// The brightness values in each month.
     ///
     /// The brightness value is the area receiving sunlight.
+    /// The number of values is [`Orbit::month_count`].
     #[getset(get = "pub")]
-    brightness: [Brightness; MONTH_COUNT],
+    brightness: Vec<Brightness>,
+}
+
+impl LightStats {
+    /// The current brightness of the node,
+    /// interpolated between the two months around the current sun position.
+    pub fn current(&self, sun: &Sun) -> Brightness {
+        let month_count = self.brightness.len();
+        if month_count == 0 {
+            return Brightness::default();
+        }
+        let month = sun.month(month_count);
+        let prev = self.month(month.floor().trunc_int::<usize>());
+        let next = self.month(month.ceil().trunc_int::<usize>());
+        Brightness(lerp(prev.0, next.0, month.fract()))
+    }
+
+    /// The brightness in a month, wrapping around the year.
+    fn month(&self, month: usize) -> Brightness {
+        self.brightness
+            .get(month % self.brightness.len().max(1))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// Identifies an object in the [`ShadowMap`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Occluder {
+    /// A node, which also receives sunlight
+    Node(NodeId),
+    /// A corridor between two nodes
+    Edge(NodeId, NodeId),
+}
+
+/// Tracks the occluders to be updated in the [`ShadowMap`].
+///
+/// Nodes and corridors are queued when they are added, moved or reshaped,
+/// so that the shadow map only recomputes the buckets around them.
+#[derive(Default)]
+pub struct ShadowQueue {
+    /// Occluders waiting to be set in the shadow map
+    pending: BTreeSet<Occluder>,
+    /// The size of each corridor
+    edges: BTreeMap<(NodeId, NodeId), EdgeSize>,
+    /// The corridors connected to each node
+    adjacency: BTreeMap<NodeId, BTreeSet<(NodeId, NodeId)>>,
+    /// Nodes removed from the shadow map whose entities are not deleted yet
+    removed: BTreeSet<NodeId>,
+}
+
+impl ShadowQueue {
+    /// Queues a node added or changed, along with the corridors connected to it.
+    fn node_changed(&mut self, node: NodeId) {
+        if self.removed.contains(&node) {
+            return;
+        }
+        self.pending.insert(Occluder::Node(node));
+        for &(from, to) in self.adjacency.get(&node).into_iter().flatten() {
+            self.pending.insert(Occluder::Edge(from, to));
+        }
+    }
+
+    /// Queues an added corridor.
+    fn edge_added(&mut self, from: NodeId, to: NodeId, size: EdgeSize) {
+        self.edges.insert((from, to), size);
+        for &node in &[from, to] {
+            self.adjacency.entry(node).or_default().insert((from, to));
+        }
+        self.pending.insert(Occluder::Edge(from, to));
+    }
+
+    /// Removes a corridor from the shadow map.
+    fn edge_removed(&mut self, shadows: &mut ShadowMap<Occluder>, from: NodeId, to: NodeId) {
+        self.edges.remove(&(from, to));
+        for node in &[from, to] {
+            if let Some(edges) = self.adjacency.get_mut(node) {
+                edges.remove(&(from, to));
+            }
+        }
+        self.pending.remove(&Occluder::Edge(from, to));
+        shadows.remove(Occluder::Edge(from, to));
+    }
+
+    /// Removes a node and the corridors connected to it from the shadow map.
+    fn node_removed(&mut self, shadows: &mut ShadowMap<Occluder>, node: NodeId) {
+        for (from, to) in self.adjacency.remove(&node).unwrap_or_default() {
+            self.edge_removed(shadows, from, to);
+        }
+        self.pending.remove(&Occluder::Node(node));
+        shadows.remove(Occluder::Node(node));
+        self.removed.insert(node);
+    }
+
+    /// Sets the pending occluders in the shadow map.
+    ///
+    /// `geometry` returns the position, unit shape and transformation matrix of a node,
+    /// or `None` if the node entity is not created yet,
+    /// in which case the occluders depending on it remain pending.
+    fn flush(
+        &mut self,
+        shadows: &mut ShadowMap<Occluder>,
+        geometry: impl Fn(NodeId) -> Option<(Position, shape::Unit, Matrix)>,
+    ) {
+        let edges = &self.edges;
+        self.pending.retain(|&occluder| match occluder {
+            Occluder::Node(node) => match geometry(node) {
+                Some((_, unit, transform)) => {
+                    shadows.set(occluder, unit, transform, true);
+                    false
+                }
+                None => true,
+            },
+            Occluder::Edge(from, to) => {
+                let size = match edges.get(&(from, to)) {
+                    Some(size) => size,
+                    None => return false,
+                };
+                match (geometry(from), geometry(to)) {
+                    (Some((from, _, _)), Some((to, _, _))) => {
+                        if from != to {
+                            let transform = edge_tf_between(from, to, size, true);
+                            shadows.set(occluder, shape::Unit::Cylinder, transform, false);
+                        }
+                        false
+                    }
+                    _ => true,
+                }
+            }
+        });
+    }
 }
 
 #[codegen::system]
 #[write_component(LightStats)]
+#[read_component(NodeId)]
 #[read_component(Position)]
 #[read_component(Shape)]
 fn shadow_cast(
     world: &mut legion::world::SubWorld,
-    #[state(true)] first: &mut bool,
-    #[subscriber] node_additions: impl Iterator<Item = NodeAddEvent>,
-    #[subscriber] node_post_removals: impl Iterator<Item = PostNodeRemoveEvent>,
+    #[resource] shadows: &mut ShadowMap<Occluder>,
+    #[resource] queue: &mut ShadowQueue,
+    #[resource] graph: &Graph,
+    #[resource] def: &GameDefinition,
+    #[subscriber] node_add_sub: impl Iterator<Item = NodeAddEvent>,
+    #[subscriber] node_remove_sub: impl Iterator<Item = NodeRemoveEvent>,
+    #[subscriber] edge_add_sub: impl Iterator<Item = EdgeAddEvent>,
+    #[subscriber] edge_remove_sub: impl Iterator<Item = EdgeRemoveEvent>,
+    #[subscriber] sim_sub: impl Iterator<Item = SimulationEvent>,
+    #[debug("Light", "Shadow updates")] update_debug: &codegen::DebugEntry,
 ) {
-    // we must drain the whole iterator even though we just want to know if there is at least one
-    // item!
-    let has_change = node_additions.count() > 0 && node_post_removals.count() > 0;
+    use legion::{maybe_changed, EntityStore, IntoQuery};
 
-    if !has_change && !*first {
+    for event in node_add_sub {
+        queue.node_changed(event.node());
+    }
+    for event in edge_add_sub {
+        queue.edge_added(event.edge().from(), event.edge().to(), event.size());
+    }
+    for event in edge_remove_sub {
+        queue.edge_removed(shadows, event.edge().from(), event.edge().to());
+    }
+    for event in node_remove_sub {
+        queue.node_removed(shadows, event.node());
+    }
+
+    if sim_sub.next().is_none() {
         return;
     }
-    *first = false;
 
-    #[allow(clippy::indexing_slicing)]
-    for month in 0..MONTH_COUNT {
-        use legion::IntoQuery;
-        let mut query = <(&mut LightStats, &Position, &Shape)>::query();
+    // moved and reshaped nodes are detected by the change tracking of legion
+    for &node in <&NodeId>::query()
+        .filter(maybe_changed::<Position>() | maybe_changed::<Shape>())
+        .iter(world)
+    {
+        queue.node_changed(node);
+    }
+    queue.removed.retain(|&node| graph.get_node(node).is_some());
 
-        struct Marker<'t> {
-            light: &'t mut Brightness,
-            min: [Finite; 2],
-            max: [Finite; 2],
-            priority: Finite,
-        }
+    let orbit = def.orbit();
+    let directions: Vec<Vector> = (0..orbit.month_count())
+        .map(|month| month_direction(orbit, month))
+        .collect();
+    if shadows.directions().ne(directions.iter().copied()) {
+        // the orbit has changed, so all occluders need to be recomputed.
+        queue.pending.extend(shadows.keys());
+        *shadows = ShadowMap::new(
+            directions,
+            shadow::DEFAULT_BUCKET_SIZE,
+            shadow::DEFAULT_SAMPLES,
+        );
+    }
 
-        let mut markers = Vec::new();
-
-        for (stats, &position, shape) in query.iter_mut(world) {
-            // Sun rotates from +x towards +y, normal to +z
-            let yaw: f64 = PI * 2. / MONTH_COUNT.small_float::<f64>() * month.small_float::<f64>();
-
-            // rot is the rotation matrix from the real coordinates to the time when yaw=0
-            let rot = nalgebra::Rotation3::from_axis_angle(&Vector::z_axis(), -yaw)
-                .matrix()
-                .to_homogeneous();
-            let trans = shape.transform(position);
-            let (min, max) = shape.unit().bb_under(rot * trans);
-
-            let priority = Finite::new(max.x);
-            let light = stats
-                .brightness
-                .get_mut(month)
-                .expect("month < MONTH_COUNT");
-            *light = Brightness(0.);
-
-            let marker = Marker {
-                light,
-                min: [Finite::new(min.y), Finite::new(min.z)],
-                max: [Finite::new(max.y), Finite::new(max.z)],
-                priority,
-            };
-            markers.push(marker);
-        }
+    queue.flush(shadows, |node| {
+        let entry = world.entry_ref(graph.get_node(node)?).ok()?;
+        let position = *entry.get_component::<Position>().ok()?;
+        let shape = entry.get_component::<Shape>().ok()?;
+        Some((position, shape.unit(), shape.transform(position)))
+    });
 
-        let cuts: SmallVec<[Vec<Finite>; 2]> = (0_usize..2)
-            .map(|axis| {
-                let mut vec: Vec<_> = markers
-                    .iter()
-                    .map(|marker| marker.min[axis])
-                    .chain(markers.iter().map(|marker| marker.max[axis]))
-                    .collect();
-                vec.sort_unstable();
-                vec
-            })
-            .collect();
-
-        // If grids[(i, j)] == k, markers[k] is the highest marker in the grid
-        // from (cuts[0][i], cuts[1][j]) to (cuts[0][i + 1], cuts[1][j + 1])
-        let mut grids = BTreeMap::<(usize, usize), usize>::new();
-        for (marker_index, marker) in markers.iter().enumerate() {
-            let min_grid_index: SmallVec<[usize; 2]> = (0_usize..2)
-                .map(|axis| {
-                    cuts[axis]
-                        .binary_search(&marker.min[axis])
-                        .expect("Cut was inserted to Vec")
-                })
-                .collect();
-            let max_grid_index: SmallVec<[usize; 2]> = (0_usize..2)
-                .map(|axis| {
-                    cuts[axis]
-                        .binary_search(&marker.max[axis])
-                        .expect("Cut was inserted to Vec")
-                })
-                .collect();
-
-            for i in min_grid_index[0]..max_grid_index[0] {
-                for j in min_grid_index[1]..max_grid_index[1] {
-                    let key = (i, j);
-                    match grids.entry(key) {
-                        Entry::Vacant(entry) => {
-                            entry.insert(marker_index);
-                        }
-                        Entry::Occupied(mut entry) => {
-                            if markers[*entry.get()].priority < marker.priority {
-                                entry.insert(marker_index);
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        log::debug!("Split objects into {} grids", grids.len());
-
-        for ((i, j), marker_index) in grids {
-            let len0 = cuts[0][i + 1].value() - cuts[0][i].value();
-            let len1 = cuts[1][j + 1].value() - cuts[1][j].value();
-            let area = Brightness(len0 * len1);
-            let light = &mut *markers[marker_index].light;
-            *light += area;
+    let updated = shadows.update();
+    codegen::update_debug!(update_debug, "{}", updated.len());
+
+    for occluder in updated {
+        let node = match occluder {
+            Occluder::Node(node) => node,
+            Occluder::Edge(..) => continue,
+        };
+        let brightness = match shadows.brightness(occluder) {
+            Some(brightness) => brightness,
+            None => continue,
+        };
+        let mut entry = match graph
+            .get_node(node)
+            .and_then(|entity| world.entry_mut(entity).ok())
+        {
+            Some(entry) => entry,
+            None => continue,
+        };
+        if let Ok(stats) = entry.get_component_mut::<LightStats>() {
+            stats.brightness.clear();
+            stats.brightness.extend_from_slice(brightness);
         }
     }
 }
@@ -188,3 +319,92 @@ fn shadow_cast(
 pub fn setup_ecs(setup: SetupEcs) -> SetupEcs {
     setup.uses(move_sun_setup).uses(shadow_cast_setup)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::{month_direction, LightStats, Occluder, ShadowMap, ShadowQueue, Sun};
+    use crate::def::orbit::Orbit;
+    use crate::graph::{EdgeSize, NodeId};
+    use crate::shape::Unit;
+    use crate::space::{Matrix, Position};
+    use crate::units::Brightness;
+
+    #[test]
+    pub fn current_brightness() {
+        let stats = LightStats {
+            brightness: vec![
+                Brightness(0.),
+                Brightness(2.),
+                Brightness(4.),
+                Brightness(6.),
+            ],
+        };
+
+        let sun = Sun {
+            yaw: PI * 0.75, // halfway between months 1 and 2
+            tilt: 0.,
+        };
+        assert!((stats.current(&sun).0 - 3.).abs() < 1e-6);
+
+        let sun = Sun {
+            yaw: PI * 1.75, // halfway between month 3 and month 0 of the next year
+            tilt: 0.,
+        };
+        assert!((stats.current(&sun).0 - 3.).abs() < 1e-6);
+
+        let empty = LightStats::default();
+        assert!(empty.current(&sun).0.abs() < 1e-6);
+    }
+
+    #[test]
+    fn queued_occluders() {
+        let orbit = Orbit::default();
+        let directions = (0..orbit.month_count()).map(|month| month_direction(&orbit, month));
+        let mut shadows = ShadowMap::new(directions, 4., 4);
+        let mut queue = ShadowQueue::default();
+
+        let nodes: Vec<NodeId> = (0..3).map(NodeId::new).collect();
+        let geometry = |node: NodeId| {
+            // the third node entity is not created yet
+            let index = (0_u32..2).find(|&index| NodeId::new(index) == node)?;
+            let position = Position::new(f64::from(index) * 10., 0., 0.);
+            let transform = Matrix::identity().append_translation(&position.vector());
+            Some((position, Unit::Cube, transform))
+        };
+        let keys = |shadows: &ShadowMap<Occluder>| shadows.keys().collect::<Vec<_>>();
+
+        for &node in &nodes {
+            queue.node_changed(node);
+        }
+        queue.edge_added(nodes[0], nodes[1], EdgeSize::new(1.));
+        queue.edge_added(nodes[1], nodes[2], EdgeSize::new(1.));
+        queue.flush(&mut shadows, geometry);
+        assert_eq!(
+            keys(&shadows),
+            vec![
+                Occluder::Node(nodes[0]),
+                Occluder::Node(nodes[1]),
+                Occluder::Edge(nodes[0], nodes[1]),
+            ]
+        );
+        assert_eq!(queue.pending.len(), 2);
+        assert_eq!(shadows.update().len(), 2);
+
+        // nothing is recomputed until a node changes
+        queue.flush(&mut shadows, geometry);
+        assert!(shadows.update().is_empty());
+
+        // removing a node removes its corridors and ignores later changes
+        queue.node_removed(&mut shadows, nodes[1]);
+        queue.node_changed(nodes[1]);
+        queue.flush(&mut shadows, geometry);
+        assert_eq!(keys(&shadows), vec![Occluder::Node(nodes[0])]);
+        assert_eq!(
+            queue.pending.iter().copied().collect::<Vec<_>>(),
+            vec![Occluder::Node(nodes[2])]
+        );
+        assert!(shadows.brightness(Occluder::Node(nodes[0])).is_some());
+    }
+}
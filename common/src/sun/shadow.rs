@@ -0,0 +1,457 @@
+//! Incremental shadow casting.
+//!
+//! For each month, every occluder is projected onto the plane normal to the sun direction.
+//! The projected bounding boxes are indexed in a uniform bucket grid,
+//! so that a change to an occluder only requires recomputing
+//! the receivers whose projections overlap with the old or new projection of the occluder.
+//!
+//! The brightness of a receiver is estimated by casting rays from the sun
+//! through a fixed grid of samples over its projected bounding box.
+//! Rays are tested against the exact unit shapes rather than their bounding boxes.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::f64::consts::PI;
+
+use smallvec::SmallVec;
+
+use crate::shape::Unit;
+use crate::space::{Matrix, Point, Vector};
+use crate::units::Brightness;
+use safety::Safety;
+
+/// The default side length of each bucket in the spatial index.
+pub const DEFAULT_BUCKET_SIZE: f64 = 4.;
+
+/// The default number of samples along each axis of a receiver projection.
+pub const DEFAULT_SAMPLES: usize = 16;
+
+/// An object that casts shadows.
+#[derive(Debug, Clone, Copy)]
+struct Occluder {
+    /// The unit shape of the object.
+    unit: Unit,
+    /// The transformation matrix from the unit shape to the real coordinates.
+    transform: Matrix,
+    /// The transformation matrix from the real coordinates to the unit shape.
+    inv_transform: Matrix,
+    /// Whether the brightness of this object should be computed.
+    receiver: bool,
+}
+
+/// The bounding box of an occluder projected in the coordinates of a month.
+///
+/// The x-axis points towards the sun.
+#[derive(Debug, Clone, Copy)]
+struct Bounds {
+    min: Point,
+    max: Point,
+}
+
+impl Bounds {
+    /// Checks whether the projections onto the yz-plane overlap.
+    fn overlaps(&self, other: &Self) -> bool {
+        self.min.y <= other.max.y
+            && other.min.y <= self.max.y
+            && self.min.z <= other.max.z
+            && other.min.z <= self.max.z
+    }
+
+    /// Checks whether the projection onto the yz-plane contains the point `(y, z)`.
+    fn contains(&self, y: f64, z: f64) -> bool {
+        (self.min.y..=self.max.y).contains(&y) && (self.min.z..=self.max.z).contains(&z)
+    }
+}
+
+/// Shadow casting data for a single month.
+struct Month<K> {
+    /// The direction vector towards the sun.
+    direction: Vector,
+    /// The rotation matrix from the real coordinates to the month coordinates,
+    /// where the sun is in the +x direction.
+    projection: Matrix,
+    /// Projected bounding boxes of each occluder.
+    bounds: BTreeMap<K, Bounds>,
+    /// Occluders overlapping with each bucket.
+    buckets: BTreeMap<(i64, i64), SmallVec<[K; 4]>>,
+    /// Receivers pending brightness recomputation.
+    dirty: BTreeSet<K>,
+}
+
+impl<K: Copy + Ord> Month<K> {
+    fn new(direction: Vector) -> Self {
+        let rotation = nalgebra::Rotation3::rotation_between(&direction, &Vector::x())
+            .unwrap_or_else(|| nalgebra::Rotation3::from_axis_angle(&Vector::z_axis(), PI));
+        Self {
+            direction,
+            projection: rotation.to_homogeneous(),
+            bounds: BTreeMap::new(),
+            buckets: BTreeMap::new(),
+            dirty: BTreeSet::new(),
+        }
+    }
+}
+
+/// Incrementally maintains the brightness of receivers among a set of occluders.
+pub struct ShadowMap<K> {
+    months: Vec<Month<K>>,
+    occluders: BTreeMap<K, Occluder>,
+    brightness: BTreeMap<K, Vec<Brightness>>,
+    bucket_size: f64,
+    samples: usize,
+}
+
+impl<K: Copy + Ord> ShadowMap<K> {
+    /// Creates an empty shadow map.
+    ///
+    /// `directions` are the vectors towards the sun in each month.
+    pub fn new(
+        directions: impl IntoIterator<Item = Vector>,
+        bucket_size: f64,
+        samples: usize,
+    ) -> Self {
+        Self {
+            months: directions.into_iter().map(Month::new).collect(),
+            occluders: BTreeMap::new(),
+            brightness: BTreeMap::new(),
+            bucket_size,
+            samples,
+        }
+    }
+
+    /// The number of months computed
+    pub fn month_count(&self) -> usize {
+        self.months.len()
+    }
+
+    /// Iterates over the directions towards the sun in each month.
+    pub fn directions(&self) -> impl Iterator<Item = Vector> + '_ {
+        self.months.iter().map(|month| month.direction)
+    }
+
+    /// Iterates over the keys of all occluders.
+    pub fn keys(&self) -> impl Iterator<Item = K> + '_ {
+        self.occluders.keys().copied()
+    }
+
+    /// The brightness of a receiver in each month.
+    ///
+    /// The values are only up to date after [`ShadowMap::update`] is called.
+    pub fn brightness(&self, key: K) -> Option<&[Brightness]> {
+        self.brightness.get(&key).map(Vec::as_slice)
+    }
+
+    /// Adds or moves an occluder.
+    ///
+    /// `transform` is the transformation matrix from the unit shape to the real coordinates.
+    /// If `receiver` is true, the brightness of the occluder is also computed.
+    ///
+    /// This is a no-op if the occluder is unchanged.
+    pub fn set(&mut self, key: K, unit: Unit, transform: Matrix, receiver: bool) {
+        if let Some(prev) = self.occluders.get(&key) {
+            if prev.unit == unit && prev.transform == transform && prev.receiver == receiver {
+                return;
+            }
+        }
+
+        let inv_transform = match transform.try_inverse() {
+            Some(inv) if transform.iter().all(|f| f.is_finite()) => inv,
+            _ => {
+                // degenerate shapes do not cast shadows
+                self.remove(key);
+                return;
+            }
+        };
+
+        self.remove(key);
+
+        self.occluders.insert(
+            key,
+            Occluder {
+                unit,
+                transform,
+                inv_transform,
+                receiver,
+            },
+        );
+        if receiver {
+            self.brightness
+                .insert(key, vec![Brightness(0.); self.months.len()]);
+        }
+
+        for month in &mut self.months {
+            let (min, max) = unit.bb_under(month.projection * transform);
+            let bounds = Bounds { min, max };
+            month.bounds.insert(key, bounds);
+            for bucket in buckets(bounds, self.bucket_size) {
+                month.buckets.entry(bucket).or_default().push(key);
+            }
+            mark_dirty(month, bounds, self.bucket_size, &self.occluders);
+            if receiver {
+                month.dirty.insert(key);
+            }
+        }
+    }
+
+    /// Removes an occluder.
+    ///
+    /// This is a no-op if the occluder does not exist.
+    pub fn remove(&mut self, key: K) {
+        if self.occluders.remove(&key).is_none() {
+            return;
+        }
+        self.brightness.remove(&key);
+
+        for month in &mut self.months {
+            month.dirty.remove(&key);
+            let bounds = match month.bounds.remove(&key) {
+                Some(bounds) => bounds,
+                None => continue,
+            };
+            for bucket in buckets(bounds, self.bucket_size) {
+                if let Some(keys) = month.buckets.get_mut(&bucket) {
+                    keys.retain(|&mut other| other != key);
+                    if keys.is_empty() {
+                        month.buckets.remove(&bucket);
+                    }
+                }
+            }
+            mark_dirty(month, bounds, self.bucket_size, &self.occluders);
+        }
+    }
+
+    /// Recomputes the brightness of receivers affected by changes since the last update.
+    ///
+    /// Returns the keys of the receivers with recomputed brightness.
+    pub fn update(&mut self) -> BTreeSet<K> {
+        let mut updated = BTreeSet::new();
+
+        for (month_index, month) in self.months.iter_mut().enumerate() {
+            let dirty = std::mem::take(&mut month.dirty);
+            for key in dirty {
+                let value =
+                    compute_brightness(month, key, self.bucket_size, self.samples, &self.occluders);
+                if let Some(slot) = self
+                    .brightness
+                    .get_mut(&key)
+                    .and_then(|vec| vec.get_mut(month_index))
+                {
+                    *slot = value;
+                    updated.insert(key);
+                }
+            }
+        }
+
+        updated
+    }
+}
+
+impl<K: Copy + Ord> Default for ShadowMap<K> {
+    fn default() -> Self {
+        Self::new(Vec::new(), DEFAULT_BUCKET_SIZE, DEFAULT_SAMPLES)
+    }
+}
+
+/// Iterates over the buckets overlapping with the projection of `bounds`.
+fn buckets(bounds: Bounds, bucket_size: f64) -> impl Iterator<Item = (i64, i64)> {
+    let index = |f: f64| (f / bucket_size).floor().trunc_int::<i64>();
+    let (min_y, max_y) = (index(bounds.min.y), index(bounds.max.y));
+    let (min_z, max_z) = (index(bounds.min.z), index(bounds.max.z));
+    (min_y..=max_y).flat_map(move |y| (min_z..=max_z).map(move |z| (y, z)))
+}
+
+/// Collects the occluders that may overlap with the projection of `bounds`.
+fn candidates<K: Copy + Ord>(month: &Month<K>, bounds: Bounds, bucket_size: f64) -> BTreeSet<K> {
+    buckets(bounds, bucket_size)
+        .filter_map(|bucket| month.buckets.get(&bucket))
+        .flatten()
+        .copied()
+        .filter(|key| {
+            month
+                .bounds
+                .get(key)
+                .map_or(false, |other| other.overlaps(&bounds))
+        })
+        .collect()
+}
+
+/// Flags the receivers overlapping with `bounds` as dirty.
+fn mark_dirty<K: Copy + Ord>(
+    month: &mut Month<K>,
+    bounds: Bounds,
+    bucket_size: f64,
+    occluders: &BTreeMap<K, Occluder>,
+) {
+    for key in candidates(month, bounds, bucket_size) {
+        if occluders
+            .get(&key)
+            .map_or(false, |occluder| occluder.receiver)
+        {
+            month.dirty.insert(key);
+        }
+    }
+}
+
+/// Computes the x-coordinate at which the ray from the sun through `(y, z)` hits the occluder.
+fn ray_hit(
+    occluder: &Occluder,
+    projection: &Matrix,
+    bounds: Bounds,
+    y: f64,
+    z: f64,
+) -> Option<f64> {
+    let start_x = bounds.max.x + 1.;
+    let end_x = bounds.min.x - 1.;
+
+    // transforms from the month coordinates to the unit shape
+    let tf = occluder.inv_transform * projection.transpose();
+    let start = tf.transform_point(&Point::new(start_x, y, z));
+    let end = tf.transform_point(&Point::new(end_x, y, z));
+
+    let weight = occluder.unit.between(start, end)?;
+    Some(start_x + (end_x - start_x) * weight)
+}
+
+/// Computes the brightness of a receiver in a month.
+fn compute_brightness<K: Copy + Ord>(
+    month: &Month<K>,
+    key: K,
+    bucket_size: f64,
+    samples: usize,
+    occluders: &BTreeMap<K, Occluder>,
+) -> Brightness {
+    let (receiver, bounds) = match (occluders.get(&key), month.bounds.get(&key)) {
+        (Some(receiver), Some(&bounds)) => (receiver, bounds),
+        _ => return Brightness(0.),
+    };
+
+    let others: Vec<_> = candidates(month, bounds, bucket_size)
+        .into_iter()
+        .filter(|&other| other != key)
+        .filter_map(|other| {
+            let occluder = occluders.get(&other)?;
+            let other_bounds = *month.bounds.get(&other)?;
+            // only occluders partly closer to the sun than the receiver matter
+            (other_bounds.max.x > bounds.min.x).then(|| (occluder, other_bounds))
+        })
+        .collect();
+
+    let size_y = bounds.max.y - bounds.min.y;
+    let size_z = bounds.max.z - bounds.min.z;
+    let step_y = size_y / samples.small_float::<f64>();
+    let step_z = size_z / samples.small_float::<f64>();
+
+    let mut lit = 0_usize;
+    for i in 0..samples {
+        let y = bounds.min.y + step_y * (i.small_float::<f64>() + 0.5);
+        for j in 0..samples {
+            let z = bounds.min.z + step_z * (j.small_float::<f64>() + 0.5);
+
+            let depth = match ray_hit(receiver, &month.projection, bounds, y, z) {
+                Some(depth) => depth,
+                None => continue,
+            };
+
+            let occluded = others.iter().any(|&(occluder, other_bounds)| {
+                other_bounds.max.x > depth
+                    && other_bounds.contains(y, z)
+                    && ray_hit(occluder, &month.projection, other_bounds, y, z)
+                        .map_or(false, |other_depth| other_depth > depth)
+            });
+            if !occluded {
+                lit += 1;
+            }
+        }
+    }
+
+    Brightness(step_y * step_z * lit.small_float::<f64>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShadowMap;
+    use crate::shape::Unit;
+    use crate::space::{Matrix, Vector};
+
+    fn cube(x: f64, y: f64, z: f64) -> Matrix {
+        Matrix::new_translation(&Vector::new(x, y, z))
+    }
+
+    fn assert_brightness(map: &ShadowMap<u32>, key: u32, expect: f64) {
+        let value = map.brightness(key).expect("Receiver exists")[0].0;
+        assert!(
+            (value - expect).abs() < 1e-6,
+            "brightness of {} should be {}, got {}",
+            key,
+            expect,
+            value
+        );
+    }
+
+    #[test]
+    pub fn occlusion() {
+        let mut map = ShadowMap::new(vec![Vector::x()], 4., 16);
+
+        map.set(1, Unit::Cube, cube(0., 0., 0.), true);
+        map.update();
+        assert_brightness(&map, 1, 4.);
+
+        // adding a node alone must trigger a recompute
+        map.set(2, Unit::Cube, cube(5., 1., 0.), true);
+        map.update();
+        assert_brightness(&map, 1, 2.);
+        assert_brightness(&map, 2, 4.);
+
+        // moving the occluder away restores the brightness
+        map.set(2, Unit::Cube, cube(5., 10., 0.), true);
+        map.update();
+        assert_brightness(&map, 1, 4.);
+
+        // non-receivers still cast shadows
+        map.set(3, Unit::Cube, cube(5., 0., 0.), false);
+        map.update();
+        assert_brightness(&map, 1, 0.);
+        assert!(map.brightness(3).is_none());
+
+        map.remove(3);
+        map.update();
+        assert_brightness(&map, 1, 4.);
+    }
+
+    #[test]
+    pub fn exact_shapes() {
+        let mut map = ShadowMap::new(vec![Vector::x()], 4., 64);
+
+        map.set(1, Unit::Cube, cube(0., 0., 0.), true);
+        // the sphere only covers a disc of area pi, not its bounding square
+        map.set(2, Unit::Sphere, cube(5., 0., 0.), false);
+        map.update();
+
+        let value = map.brightness(1).expect("Receiver exists")[0].0;
+        assert!(
+            (value - (4. - std::f64::consts::PI)).abs() < 0.05,
+            "got {}",
+            value
+        );
+    }
+
+    #[test]
+    pub fn incremental() {
+        let mut map = ShadowMap::new(vec![Vector::x()], 4., 4);
+
+        for i in 0..100 {
+            map.set(i, Unit::Cube, cube(0., f64::from(i) * 10., 0.), true);
+        }
+        assert_eq!(map.update().len(), 100);
+
+        // only the receivers near the changed occluder are recomputed
+        map.set(1000, Unit::Cube, cube(5., 500., 0.), false);
+        let updated = map.update();
+        assert_eq!(updated.into_iter().collect::<Vec<_>>(), vec![50]);
+        assert_brightness(&map, 50, 0.);
+        assert_brightness(&map, 49, 4.);
+
+        // unchanged occluders are no-op
+        map.set(1000, Unit::Cube, cube(5., 500., 0.), false);
+        assert!(map.update().is_empty());
+    }
+}
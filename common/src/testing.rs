@@ -0,0 +1,130 @@
+//! Minimal game definitions for unit tests.
+//!
+//! Tests of reactions, construction, research and commands all need a [`GameDefinition`]
+//! with a few reaction, building or technology types.
+//! The builders of these types take dozens of lines irrelevant to each test,
+//! so the fixtures are shared here rather than repeated in every module.
+
+use arcstr::{format, literal};
+use smallvec::SmallVec;
+
+use crate::def::{building, reaction, tech, GameDefinition};
+use crate::space::Matrix;
+use crate::time::Time;
+use crate::units;
+
+/// Adds a reaction type, creating a reaction category if there is none.
+pub(crate) fn reaction(
+    def: &mut GameDefinition,
+    catalysts: Vec<reaction::Catalyst>,
+    puts: Vec<reaction::Put>,
+) -> reaction::TypeId {
+    if def.reaction_cats().is_empty() {
+        def.add_reaction_category(
+            reaction::Category::builder()
+                .id(literal!("test"))
+                .title(literal!(""))
+                .description(literal!(""))
+                .build(),
+        );
+    }
+    let id = format!("test:reaction{}", def.reaction().len());
+    def.add_reaction(
+        reaction::Type::builder()
+            .id(id)
+            .name(literal!(""))
+            .description(literal!(""))
+            .category(reaction::CategoryId(0))
+            .catalysts(catalysts.into_iter().collect())
+            .puts(puts.into_iter().collect::<SmallVec<_>>())
+            .build(),
+    )
+}
+
+/// Adds a building type, creating a building category if there is none.
+pub(crate) fn building(
+    def: &mut GameDefinition,
+    reactions: Vec<(reaction::TypeId, building::ReactionPolicy)>,
+    storage: building::Storage,
+    features: Vec<building::ExtraFeature>,
+) -> building::TypeId {
+    if def.building_cats().is_empty() {
+        def.add_building_category(
+            building::Category::builder()
+                .id(literal!("test"))
+                .title(literal!(""))
+                .description(literal!(""))
+                .build(),
+        );
+    }
+    let id = format!("test:building{}", def.building().len());
+    def.add_building(
+        building::Type::builder()
+            .id(id)
+            .name(literal!(""))
+            .summary(literal!(""))
+            .description(literal!(""))
+            .category(building::CategoryId(0))
+            .shape(
+                building::Shape::builder()
+                    .transform(Matrix::identity())
+                    .texture_src(literal!(""))
+                    .texture_name(literal!(""))
+                    .build(),
+            )
+            .reactions(reactions)
+            .hitpoint(units::Hitpoint(100.))
+            .storage(storage)
+            .construction(
+                building::Construction::builder()
+                    .cargo(Vec::new())
+                    .energy(units::ElectricEnergy(0.))
+                    .time(Time(100))
+                    .skill(crate::def::skill::TypeId(0))
+                    .levels(units::Skill(0.)..units::Skill(1.))
+                    .multipliers(multipliers(1., 1.))
+                    .build(),
+            )
+            .features(features)
+            .build(),
+    )
+}
+
+/// Adds a technology type.
+pub(crate) fn tech(
+    def: &mut GameDefinition,
+    prerequisites: Vec<tech::TypeId>,
+    costs: Vec<tech::Cost>,
+    unlocks: Vec<tech::Unlock>,
+) -> tech::TypeId {
+    let id = format!("test:tech{}", def.tech().len());
+    def.add_tech(
+        tech::Type::builder()
+            .id(id)
+            .name(literal!(""))
+            .description(literal!(""))
+            .prerequisites(prerequisites.into_iter().collect())
+            .costs(costs.into_iter().collect())
+            .unlocks(unlocks.into_iter().collect())
+            .build(),
+    )
+}
+
+/// A storage with the given cargo, liquid and gas capacities.
+pub(crate) fn storage(cargo: f64, liquid: f64, gas: f64) -> building::Storage {
+    building::Storage::builder()
+        .cargo(units::CargoSize(cargo))
+        .liquid(units::LiquidVolume(liquid))
+        .gas(units::GasVolume(gas))
+        .build()
+}
+
+/// Multipliers interpolating from `min` to `max`, held outside the lerp endpoints.
+pub(crate) fn multipliers(min: f64, max: f64) -> reaction::Multipliers {
+    reaction::Multipliers::builder()
+        .underflow(min)
+        .min(min)
+        .max(max)
+        .overflow(max)
+        .build()
+}
@@ -0,0 +1,126 @@
+//! Heat and temperature of nodes.
+//!
+//! Reactions emit or absorb [heat][crate::def::reaction::Put::Heat],
+//! which changes the temperature of the node.
+//! Cooling reactions absorb heat by consuming coolant
+//! from the [liquid storage][crate::liquid::LiquidPool] of the node,
+//! which [flows in through corridors][crate::liquid] from adjacent nodes.
+//! Nodes gradually dissipate heat towards the ambient temperature.
+//! Nodes above the overheat temperature lose hitpoints
+//! and are destroyed when their hitpoints are depleted.
+
+use derive_new::new;
+use legion::world::SubWorld;
+
+use crate::clock::{SimulationEvent, SIMULATION_PERIOD};
+use crate::config;
+use crate::graph::{NodeHitpoint, NodeId, NodeRemoveEvent};
+use crate::units::{Heat, Hitpoint, Temperature};
+use crate::SetupEcs;
+
+/// A component storing the heat of a node.
+#[derive(Debug, Clone, Copy, Default, new, getset::CopyGetters)]
+pub struct Thermal {
+    /// The heat above the ambient temperature
+    #[getset(get_copy = "pub")]
+    heat: Heat,
+}
+
+impl Thermal {
+    /// The current temperature of the node.
+    pub fn temperature(&self, config: &config::Scalar) -> Temperature {
+        Temperature(config.ambient_temperature + self.heat.0 / config.heat_capacity)
+    }
+
+    /// Adds heat to the node. Negative values remove heat.
+    pub fn add_heat(&mut self, heat: Heat) {
+        self.heat += heat;
+    }
+}
+
+#[codegen::system]
+#[read_component(NodeId)]
+#[write_component(Thermal)]
+#[write_component(NodeHitpoint)]
+fn dissipate(
+    world: &mut SubWorld,
+    #[resource] config: &config::Scalar,
+    #[subscriber] sim_sub: impl Iterator<Item = SimulationEvent>,
+    #[publisher] node_remove_pub: impl FnMut(NodeRemoveEvent),
+) {
+    use legion::IntoQuery;
+
+    if sim_sub.next().is_none() {
+        return;
+    }
+
+    let secs = SIMULATION_PERIOD.as_secs();
+    for (&node, thermal, hitpoint) in
+        <(&NodeId, &mut Thermal, &mut NodeHitpoint)>::query().iter_mut(world)
+    {
+        if cool(config, secs, thermal, hitpoint) {
+            node_remove_pub(NodeRemoveEvent::new(node));
+        }
+    }
+}
+
+/// Dissipates heat from a node and applies overheat damage over `secs` seconds.
+///
+/// Returns whether the node is destroyed by the damage.
+fn cool(
+    config: &config::Scalar,
+    secs: f64,
+    thermal: &mut Thermal,
+    hitpoint: &mut NodeHitpoint,
+) -> bool {
+    // active cooling is done by reactions consuming coolant from the node,
+    // so only passive dissipation towards the ambient temperature is simulated here
+    thermal.heat *= (1. - config.heat_dissipation * secs).max(0.);
+
+    let excess = thermal.temperature(config).0 - config.overheat_temperature;
+    if excess > 0. && hitpoint.hitpoint().0 > 0. {
+        let damage = Hitpoint(excess * config.overheat_damage * secs);
+        return hitpoint.damage(damage);
+    }
+    false
+}
+
+/// Initializes ECS
+pub fn setup_ecs(setup: SetupEcs) -> SetupEcs {
+    setup.uses(dissipate_setup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heat_and_overheat() {
+        let config = config::Scalar::default();
+        let mut thermal = Thermal::default();
+        let mut hitpoint = NodeHitpoint::new(Hitpoint(100.));
+        assert!((thermal.temperature(&config).0 - config.ambient_temperature).abs() < 1e-9);
+
+        // heat below the overheat temperature only dissipates
+        let safe = config.overheat_temperature - config.ambient_temperature;
+        thermal.add_heat(Heat(safe * config.heat_capacity * 0.5));
+        let before = thermal.heat();
+        assert!(!cool(&config, 1., &mut thermal, &mut hitpoint));
+        assert!(thermal.heat() < before);
+        assert!(thermal.heat().0 > 0.);
+        assert_eq!(hitpoint.hitpoint(), Hitpoint(100.));
+
+        // heat accumulates above the overheat temperature and damages the node
+        thermal.add_heat(Heat(safe * config.heat_capacity * 10.));
+        assert!(thermal.temperature(&config).0 > config.overheat_temperature);
+        let mut destroyed = false;
+        for _ in 0..10000 {
+            if cool(&config, 1., &mut thermal, &mut hitpoint) {
+                destroyed = true;
+                break;
+            }
+        }
+        assert!(destroyed);
+        assert!(hitpoint.hitpoint().0 <= 0.);
+    }
+}
@@ -0,0 +1,364 @@
+//! Vehicles carrying passengers between terminals.
+//!
+//! Each vehicle drives through the corridors along a cyclic list of stops
+//! at the [speed][crate::def::vehicle::Type::speed] of its type.
+//! At stops with the [`RailTerminal`][building::ExtraFeature::RailTerminal] feature,
+//! inhabitants travelling to a [`Destination`] board the vehicle
+//! if riding to one of the upcoming terminals and walking from there
+//! is faster than walking all the way.
+//! They alight at that terminal and continue walking.
+//!
+//! Rails, terminal forces and driver skills are not simulated yet.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+
+use derive_new::new;
+use legion::world::SubWorld;
+use legion::Entity;
+use serde::{Deserialize, Serialize};
+
+use crate::clock::{SimulationEvent, SIMULATION_PERIOD};
+use crate::construction::Site;
+use crate::def::{building, vehicle, GameDefinition};
+use crate::graph::{self, EdgeId, NodeBuilding, NodeId};
+use crate::inhabitant::{Destination, Location};
+use crate::space::Position;
+use crate::SetupEcs;
+use safety::Safety;
+
+/// Component storing an identifier for a vehicle
+///
+/// Unlike the entity, the identifier is stable across clients and replays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, new, Serialize, Deserialize)]
+pub struct VehicleId {
+    inner: u32,
+}
+
+/// A component attached to vehicle entities.
+#[derive(Debug, Clone, getset::CopyGetters, getset::Getters)]
+pub struct Vehicle {
+    /// The vehicle type
+    #[getset(get_copy = "pub")]
+    ty: vehicle::TypeId,
+    /// The nodes to stop at, visited cyclically
+    #[getset(get = "pub")]
+    stops: Vec<NodeId>,
+    /// The index of the next stop in `stops`
+    #[getset(get_copy = "pub")]
+    next_stop: usize,
+    /// The node the vehicle is in or has just left
+    #[getset(get_copy = "pub")]
+    node: NodeId,
+    /// The distance travelled from `node` towards the next node, in metres
+    #[getset(get_copy = "pub")]
+    progress: f64,
+}
+
+impl Vehicle {
+    /// Creates a vehicle parked at the first stop.
+    ///
+    /// # Panics
+    /// Panics if `stops` is empty.
+    pub fn new(ty: vehicle::TypeId, stops: Vec<NodeId>) -> Self {
+        let node = *stops.first().expect("Vehicle route must not be empty");
+        Self {
+            ty,
+            stops,
+            next_stop: 0,
+            node,
+            progress: 0.,
+        }
+    }
+
+    /// Replaces the stops of the vehicle, starting from the first one.
+    ///
+    /// # Panics
+    /// Panics if `stops` is empty.
+    pub fn set_stops(&mut self, stops: Vec<NodeId>) {
+        assert!(!stops.is_empty(), "Vehicle route must not be empty");
+        self.stops = stops;
+        self.next_stop = 0;
+    }
+
+    /// Lists the stops within the next cycle of the route
+    /// with the number of seconds required to reach each of them.
+    ///
+    /// The list stops at the first unreachable stop.
+    fn itinerary(
+        &self,
+        adjacency: &BTreeMap<NodeId, Vec<NodeId>>,
+        positions: &BTreeMap<NodeId, Position>,
+        speed: f64,
+    ) -> Vec<(NodeId, f64)> {
+        let mut itinerary = Vec::new();
+        let mut node = self.node;
+        let mut secs = -self.progress / speed;
+        let stops = self.stops.iter().cycle().skip(self.next_stop);
+        for &stop in stops.take(self.stops.len()) {
+            let path = match graph::route(adjacency, node, |node| node == stop) {
+                Some(path) => path,
+                None => break,
+            };
+            for next in path {
+                secs += distance(positions, node, next) / speed;
+                node = next;
+            }
+            itinerary.push((stop, secs));
+        }
+        itinerary
+    }
+}
+
+/// Return type of [`create_components`].
+pub type VehicleComponents = (VehicleId, Vehicle);
+
+/// Creates the components for a vehicle parked at the first stop.
+///
+/// # Panics
+/// Panics if `stops` is empty.
+pub fn create_components(ty: vehicle::TypeId, stops: Vec<NodeId>) -> VehicleComponents {
+    (VehicleId::new(rand::random()), Vehicle::new(ty, stops))
+}
+
+/// A component attached to inhabitants carried by a vehicle.
+#[derive(Debug, Clone, Copy, new, getset::CopyGetters)]
+pub struct Passenger {
+    /// The vehicle entity
+    #[getset(get_copy = "pub")]
+    vehicle: Entity,
+    /// The stop to alight at
+    #[getset(get_copy = "pub")]
+    alight: NodeId,
+}
+
+/// Indicates that a vehicle has stopped at one of its stops.
+#[derive(Debug, new, getset::CopyGetters)]
+pub struct StopEvent {
+    /// The vehicle entity
+    #[getset(get_copy = "pub")]
+    vehicle: Entity,
+    /// The node of the stop
+    #[getset(get_copy = "pub")]
+    node: NodeId,
+}
+
+/// Returns the distance between two nodes in metres.
+fn distance(positions: &BTreeMap<NodeId, Position>, from: NodeId, to: NodeId) -> f64 {
+    match (positions.get(&from), positions.get(&to)) {
+        (Some(&from), Some(&to)) => (to - from).norm(),
+        _ => 0.,
+    }
+}
+
+#[codegen::system]
+#[read_component(EdgeId)]
+#[read_component(NodeId)]
+#[read_component(NodeBuilding)]
+#[read_component(Site)]
+#[read_component(Position)]
+#[read_component(Destination)]
+#[read_component(Passenger)]
+#[write_component(Vehicle)]
+#[write_component(Location)]
+fn drive(
+    world: &mut SubWorld,
+    cmd_buf: &mut legion::systems::CommandBuffer,
+    #[resource] def: &GameDefinition,
+    #[subscriber] sim_sub: impl Iterator<Item = SimulationEvent>,
+    #[publisher] stop_pub: impl FnMut(StopEvent),
+) {
+    use legion::{EntityStore, IntoQuery};
+
+    if sim_sub.next().is_none() {
+        return;
+    }
+
+    let secs = SIMULATION_PERIOD.as_secs();
+    let adjacency = graph::adjacency(<&EdgeId>::query().iter(world));
+    let mut positions = BTreeMap::new();
+    let mut terminals = BTreeSet::new();
+    for (&node, &position, building, site) in
+        <(&NodeId, &Position, &NodeBuilding, Option<&Site>)>::query().iter(world)
+    {
+        positions.insert(node, position);
+        let terminal = def
+            .get_building(building.building())
+            .features()
+            .iter()
+            .any(|feature| matches!(feature, building::ExtraFeature::RailTerminal(_)));
+        if terminal && site.is_none() {
+            terminals.insert(node);
+        }
+    }
+
+    let mut passengers = BTreeMap::<Entity, Vec<(Entity, NodeId)>>::new();
+    for (&entity, passenger) in <(Entity, &Passenger)>::query().iter(world) {
+        passengers
+            .entry(passenger.vehicle)
+            .or_default()
+            .push((entity, passenger.alight));
+    }
+
+    // inhabitants in terminals who may board a vehicle, with their destinations
+    let mut waiting = BTreeMap::<NodeId, Vec<(Entity, NodeId)>>::new();
+    for (&entity, location, destination) in <(Entity, &Location, &Destination)>::query()
+        .filter(!legion::component::<Passenger>())
+        .iter(world)
+    {
+        if terminals.contains(&location.node()) {
+            waiting
+                .entry(location.node())
+                .or_default()
+                .push((entity, destination.node()));
+        }
+    }
+
+    // the number of corridors to walk from each node to each destination
+    let mut walks = BTreeMap::<NodeId, BTreeMap<NodeId, usize>>::new();
+    let mut moves = Vec::new();
+    for (&entity, vehicle) in <(Entity, &mut Vehicle)>::query().iter_mut(world) {
+        let ty = def.get_vehicle(vehicle.ty);
+        let speed = ty.speed().0;
+        let mut budget = speed * secs;
+        let mut arrival = None;
+        while budget > 0. {
+            let stop = match vehicle.stops.get(vehicle.next_stop) {
+                Some(&stop) => stop,
+                None => break,
+            };
+            if vehicle.node == stop && vehicle.progress == 0. {
+                arrival = Some(stop);
+                vehicle.next_stop = (vehicle.next_stop + 1) % vehicle.stops.len();
+                break;
+            }
+            let next = match graph::route(&adjacency, vehicle.node, |node| node == stop) {
+                Some(path) => match path.first() {
+                    Some(&next) => next,
+                    None => break,
+                },
+                None => break, // the next stop is unreachable
+            };
+            let length = distance(&positions, vehicle.node, next);
+            if vehicle.progress + budget >= length {
+                budget -= length - vehicle.progress;
+                vehicle.node = next;
+                vehicle.progress = 0.;
+            } else {
+                vehicle.progress += budget;
+                budget = 0.;
+            }
+        }
+
+        let riding = passengers.entry(entity).or_default();
+        let stop = match arrival {
+            Some(stop) => {
+                stop_pub(StopEvent {
+                    vehicle: entity,
+                    node: stop,
+                });
+                stop
+            }
+            None => {
+                moves.extend(
+                    riding
+                        .iter()
+                        .map(|&(passenger, _)| (passenger, vehicle.node)),
+                );
+                continue;
+            }
+        };
+
+        riding.retain(|&(passenger, alight)| {
+            moves.push((passenger, stop));
+            if alight == stop {
+                cmd_buf.remove_component::<Passenger>(passenger);
+                false
+            } else {
+                true
+            }
+        });
+
+        let mut free = ty
+            .passengers()
+            .saturating_sub(u32::try_from(riding.len()).unwrap_or(u32::MAX));
+        let queue = match waiting.get_mut(&stop) {
+            Some(queue) if terminals.contains(&stop) && free > 0 => queue,
+            _ => continue,
+        };
+        let itinerary = vehicle.itinerary(&adjacency, &positions, speed);
+        queue.retain(|&(inhabitant, destination)| {
+            if free == 0 {
+                return true;
+            }
+            let walk = walks
+                .entry(destination)
+                .or_insert_with(|| graph::distances(&adjacency, destination));
+            let walk_secs = |node: NodeId| {
+                walk.get(&node)
+                    .map(|&count| count.small_float::<f64>() * secs)
+            };
+            let direct = match walk_secs(stop) {
+                Some(direct) => direct,
+                None => return true,
+            };
+            let best = itinerary
+                .iter()
+                .filter(|&&(node, _)| node != stop && terminals.contains(&node))
+                .filter_map(|&(node, ride)| walk_secs(node).map(|walk| (node, ride + walk)))
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+            match best {
+                Some((alight, total)) if total < direct => {
+                    cmd_buf.add_component(inhabitant, Passenger::new(entity, alight));
+                    free -= 1;
+                    false
+                }
+                _ => true,
+            }
+        });
+    }
+
+    for (inhabitant, node) in moves {
+        if let Ok(mut entry) = world.entry_mut(inhabitant) {
+            if let Ok(location) = entry.get_component_mut::<Location>() {
+                *location = Location::new(node);
+            }
+        }
+    }
+}
+
+/// Initializes ECS
+pub fn setup_ecs(setup: SetupEcs) -> SetupEcs {
+    setup.uses(drive_setup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn itinerary_times() {
+        let nodes: Vec<NodeId> = (0..3).map(NodeId::new).collect();
+        let edges = vec![
+            EdgeId::new(nodes[0], nodes[1]),
+            EdgeId::new(nodes[1], nodes[2]),
+        ];
+        let adjacency = graph::adjacency(&edges);
+        let positions: BTreeMap<NodeId, Position> = vec![
+            (nodes[0], Position::new(0., 0., 0.)),
+            (nodes[1], Position::new(10., 0., 0.)),
+            (nodes[2], Position::new(30., 0., 0.)),
+        ]
+        .into_iter()
+        .collect();
+
+        let mut vehicle = Vehicle::new(vehicle::TypeId(0), vec![nodes[0], nodes[2]]);
+        vehicle.next_stop = 1;
+        vehicle.progress = 5.;
+        assert_eq!(
+            vehicle.itinerary(&adjacency, &positions, 10.),
+            vec![(nodes[2], 2.5), (nodes[0], 5.5)]
+        );
+    }
+}
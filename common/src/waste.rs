@@ -0,0 +1,209 @@
+//! Accumulation of waste products in nodes.
+//!
+//! Cargo, liquid and gas types flagged as waste in the game definition
+//! are tracked in each node.
+//! When the storage of a waste type in a node is full,
+//! the inhabitants in the node lose happiness until the waste is removed or recycled.
+//! Since liquid and gas storages are shared by all types in a node,
+//! a liquid or gas waste type is full when it is present in a full storage.
+
+use legion::world::SubWorld;
+use smallvec::SmallVec;
+
+use crate::cargo;
+use crate::clock::SimulationEvent;
+use crate::config;
+use crate::def::{self, GameDefinition};
+use crate::gas::GasPool;
+use crate::graph::NodeId;
+use crate::liquid::LiquidPool;
+use crate::units::{self, CargoSize, GasVolume, LiquidVolume};
+use crate::SetupEcs;
+use safety::Safety;
+
+/// A waste type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waste {
+    /// A waste cargo type
+    Cargo(def::cargo::TypeId),
+    /// A waste liquid type
+    Liquid(def::liquid::TypeId),
+    /// A waste gas type
+    Gas(def::gas::TypeId),
+}
+
+/// A component storing the waste types whose storage is full in a node.
+#[derive(Debug, Clone, Default, getset::Getters, getset::CopyGetters)]
+pub struct Overflow {
+    /// The waste types with full storage
+    #[getset(get = "pub")]
+    full: SmallVec<[Waste; 1]>,
+    /// The happiness lost by each inhabitant in the node per second
+    #[getset(get_copy = "pub")]
+    penalty: units::Happiness,
+}
+
+/// Lists the waste types with full storage in a node.
+///
+/// `cargo` contains the type, size and capacity of each cargo storage.
+/// Liquid and gas waste types are full when they are present in a full pool.
+fn full_wastes(
+    def: &GameDefinition,
+    cargo: impl IntoIterator<Item = (def::cargo::TypeId, CargoSize, CargoSize)>,
+    liquid: &LiquidPool,
+    gas: &GasPool,
+) -> SmallVec<[Waste; 1]> {
+    let mut full = SmallVec::new();
+    for (ty, size, capacity) in cargo {
+        if def.get_cargo(ty).waste() && size >= capacity {
+            full.push(Waste::Cargo(ty));
+        }
+    }
+    if liquid.total() >= liquid.capacity() {
+        for (ty, level) in liquid.iter() {
+            if def.get_liquid(ty).waste() && level > LiquidVolume::default() {
+                full.push(Waste::Liquid(ty));
+            }
+        }
+    }
+    if gas.total() >= gas.capacity() {
+        for (ty, level) in gas.iter() {
+            if def.get_gas(ty).waste() && level > GasVolume::default() {
+                full.push(Waste::Gas(ty));
+            }
+        }
+    }
+    full
+}
+
+#[codegen::system]
+#[read_component(NodeId)]
+#[read_component(cargo::StorageList)]
+#[read_component(cargo::Storage)]
+#[read_component(cargo::NextStorageSize)]
+#[read_component(LiquidPool)]
+#[read_component(GasPool)]
+#[write_component(Overflow)]
+fn track_waste(
+    world: &mut SubWorld,
+    #[resource] def: &GameDefinition,
+    #[resource] config: &config::Scalar,
+    #[subscriber] sim_sub: impl Iterator<Item = SimulationEvent>,
+) {
+    use legion::{Entity, EntityStore, IntoQuery};
+
+    if sim_sub.next().is_none() {
+        return;
+    }
+
+    let mut updates = Vec::new();
+    for (&entity, list, liquid, gas) in
+        <(Entity, Option<&cargo::StorageList>, &LiquidPool, &GasPool)>::query()
+            .filter(legion::component::<NodeId>())
+            .iter(world)
+    {
+        let storages = list.into_iter().flat_map(|list| list.storages());
+        let cargo = storages.map(|&(ty, storage)| {
+            let entry = world
+                .entry_ref(storage)
+                .expect("Storage entity does not exist");
+            let capacity = entry
+                .get_component::<cargo::Storage>()
+                .expect("Storage entity does not have Storage")
+                .capacity();
+            let size = entry
+                .get_component::<cargo::NextStorageSize>()
+                .expect("Storage entity does not have NextStorageSize")
+                .size();
+            (ty, size, capacity)
+        });
+        updates.push((entity, full_wastes(def, cargo, liquid, gas)));
+    }
+
+    for (entity, full) in updates {
+        let mut entry = world.entry_mut(entity).expect("Node entity does not exist");
+        let overflow = entry
+            .get_component_mut::<Overflow>()
+            .expect("Node entity does not have Overflow");
+        overflow.penalty = units::Happiness(config.waste_penalty * full.len().small_float::<f64>());
+        overflow.full = full;
+    }
+}
+
+/// Initializes ECS
+pub fn setup_ecs(setup: SetupEcs) -> SetupEcs {
+    setup.uses(track_waste_setup)
+}
+
+#[cfg(test)]
+mod tests {
+    use arcstr::literal;
+
+    use super::*;
+
+    #[test]
+    fn detect_full_waste() {
+        let mut def = GameDefinition::default();
+        let category = def.add_cargo_category(
+            def::cargo::Category::builder()
+                .id(literal!("test"))
+                .title(literal!(""))
+                .description(literal!(""))
+                .build(),
+        );
+        let cargo = |id, waste| {
+            def::cargo::Type::builder()
+                .id(id)
+                .name(literal!(""))
+                .summary(literal!(""))
+                .description(literal!(""))
+                .category(category)
+                .texture(literal!(""))
+                .waste(waste)
+                .build()
+        };
+        let food = def.add_cargo(cargo(literal!("test:food"), false));
+        let sediment = def.add_cargo(cargo(literal!("test:sediment"), true));
+        let liquid = |id, waste| {
+            def::liquid::Type::builder()
+                .id(id)
+                .name(literal!(""))
+                .summary(literal!(""))
+                .description(literal!(""))
+                .viscosity(units::LiquidViscosity(1.))
+                .texture(literal!(""))
+                .waste(waste)
+                .build()
+        };
+        let water = def.add_liquid(liquid(literal!("test:water"), false));
+        let urine = def.add_liquid(liquid(literal!("test:urine"), true));
+        let carbon_dioxide = def.add_gas(
+            def::gas::Type::builder()
+                .id(literal!("test:carbon_dioxide"))
+                .name(literal!(""))
+                .summary(literal!(""))
+                .description(literal!(""))
+                .texture(literal!(""))
+                .waste(true)
+                .build(),
+        );
+
+        let mut liquids = LiquidPool::new(LiquidVolume(100.));
+        liquids.add(water, LiquidVolume(60.));
+        liquids.add(urine, LiquidVolume(30.));
+        let mut gases = GasPool::new(GasVolume(100.));
+        gases.add(carbon_dioxide, GasVolume(100.));
+        let storages = vec![
+            (food, CargoSize(100.), CargoSize(100.)),
+            (sediment, CargoSize(50.), CargoSize(100.)),
+        ];
+        let full = full_wastes(&def, storages, &liquids, &gases);
+        assert_eq!(&full[..], &[Waste::Gas(carbon_dioxide)]);
+
+        liquids.add(water, LiquidVolume(10.));
+        gases.add(carbon_dioxide, GasVolume(-1.));
+        let storages = vec![(sediment, CargoSize(100.), CargoSize(100.))];
+        let full = full_wastes(&def, storages, &liquids, &gases);
+        assert_eq!(&full[..], &[Waste::Cargo(sediment), Waste::Liquid(urine)]);
+    }
+}
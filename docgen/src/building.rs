@@ -103,6 +103,50 @@ fn write_building(
         building.storage().liquid()
     )?;
     writeln!(&mut fh, "| Gas storage | {} |", building.storage().gas())?;
+    if building.storage().electricity().0 > 0. {
+        writeln!(
+            &mut fh,
+            "| [Energy storage](../../electricity#energy-storage) | {} |",
+            building.storage().electricity()
+        )?;
+        writeln!(
+            &mut fh,
+            "| Charge rate | {} |",
+            building.storage().charge_rate()
+        )?;
+    }
+    writeln!(&mut fh)?;
+
+    let construction = building.construction();
+    writeln!(&mut fh, "## Construction")?;
+    writeln!(&mut fh, "| Cost | Amount |")?;
+    writeln!(&mut fh, "| :-: | :-: |")?;
+    for &(ty, size) in construction.cargo() {
+        writeln!(
+            &mut fh,
+            "| [{}](../../cargo/{}) | {} |",
+            def.get_cargo(ty).name(),
+            def.get_cargo(ty).name().to_kebab_case(),
+            size,
+        )?;
+    }
+    writeln!(&mut fh, "| Electricity | {} |", construction.energy())?;
+    writeln!(
+        &mut fh,
+        "| Build time | {} s |",
+        construction.time().as_secs()
+    )?;
+    writeln!(&mut fh)?;
+    writeln!(
+        &mut fh,
+        "Each worker with [{}](../../skill/{}) between {} and {} works at {}x to {}x speed.",
+        def.get_skill(construction.skill()).name(),
+        def.get_skill(construction.skill()).name().to_kebab_case(),
+        construction.levels().start,
+        construction.levels().end,
+        construction.multipliers().min(),
+        construction.multipliers().max(),
+    )?;
     writeln!(&mut fh)?;
 
     if !building.features().is_empty() || building.reactions().is_empty() {
@@ -128,12 +172,8 @@ fn write_building(
             &mut fh,
             "| {} | {} | {} |",
             if policy.configurable() { "Yes" } else { "No" },
-            match policy.on_underflow() {
-                building::FlowPolicy::ReduceRate => "Reduce rate",
-            },
-            match policy.on_overflow() {
-                building::FlowPolicy::ReduceRate => "Reduce rate",
-            },
+            flow_policy_text(policy.on_underflow()),
+            flow_policy_text(policy.on_overflow()),
         )?;
         writeln!(&mut fh)?;
     }
@@ -141,6 +181,18 @@ fn write_building(
     Ok(file)
 }
 
+fn flow_policy_text(policy: building::FlowPolicy) -> String {
+    match policy {
+        building::FlowPolicy::ReduceRate => "Reduce rate".to_string(),
+        building::FlowPolicy::Halt => "Halt".to_string(),
+        building::FlowPolicy::Vent => "Vent to space".to_string(),
+        building::FlowPolicy::Dump => "Dump into node".to_string(),
+        building::FlowPolicy::Priority(priority) => {
+            format!("Reduce rate (priority {})", priority)
+        }
+    }
+}
+
 fn write_feature(mut fh: impl Write, feature: &building::ExtraFeature) -> Result<()> {
     match feature {
         building::ExtraFeature::Core => {
@@ -168,6 +220,51 @@ fn write_feature(mut fh: impl Write, feature: &building::ExtraFeature) -> Result
             )?;
             writeln!(&mut fh)?;
         }
+        building::ExtraFeature::Education(seats) => {
+            writeln!(&mut fh, "### Education ({} students)", seats)?;
+            writeln!(
+                &mut fh,
+                "This building provides {} seats for [students](../../skill).",
+                seats
+            )?;
+            writeln!(
+                &mut fh,
+                "Idle inhabitants are enrolled automatically when seats are available."
+            )?;
+            writeln!(
+                &mut fh,
+                "Students receive the skill changes of the reactions in this building."
+            )?;
+            writeln!(&mut fh)?;
+        }
+        building::ExtraFeature::Prison(capacity) => {
+            writeln!(&mut fh, "### Prison ({} inmates)", capacity)?;
+            writeln!(
+                &mut fh,
+                "This building holds up to {} [outlaws](../../happiness) arrested by police.",
+                capacity
+            )?;
+            writeln!(
+                &mut fh,
+                "Prisoners stay in this building until their happiness is positive."
+            )?;
+            writeln!(&mut fh)?;
+        }
+        building::ExtraFeature::Entertainment { capacity, rate } => {
+            writeln!(&mut fh, "### Entertainment ({} visitors)", capacity)?;
+            writeln!(
+                &mut fh,
+                "Up to {} off-duty inhabitants can visit this building at the same time.",
+                capacity
+            )?;
+            writeln!(
+                &mut fh,
+                "Each visitor gains {} [happiness](../../happiness) per second.",
+                rate.0
+            )?;
+            writeln!(&mut fh, "Outlaws do not visit this building.")?;
+            writeln!(&mut fh)?;
+        }
         building::ExtraFeature::RailTerminal(force) => {
             writeln!(&mut fh, "### Rail terminal")?;
             writeln!(&mut fh, "Vehicles in adjacent [corridors](../../corridor#vehicles) are powered by an extra {}.", force)?;
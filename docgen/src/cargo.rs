@@ -90,6 +90,39 @@ fn write_cargo(
     writeln!(&mut fh, "> {}", cargo.summary())?;
     writeln!(&mut fh)?;
     writeln!(&mut fh, "{}", cargo.description())?;
+    writeln!(&mut fh)?;
+    if cargo.waste() {
+        writeln!(
+            &mut fh,
+            "This is a [waste product](../../waste). Storing too much of it reduces happiness."
+        )?;
+        writeln!(&mut fh)?;
+    }
+    match cargo.bottle() {
+        Some(cargo::Bottle::Liquid { ty, volume }) => {
+            let liquid = def.get_liquid(ty);
+            writeln!(
+                &mut fh,
+                "Each dm\u{b3} of this bottle stores {} of [{}](../../liquid/{}).",
+                volume,
+                liquid.name(),
+                liquid.name().to_kebab_case()
+            )?;
+            writeln!(&mut fh)?;
+        }
+        Some(cargo::Bottle::Gas { ty, volume }) => {
+            let gas = def.get_gas(ty);
+            writeln!(
+                &mut fh,
+                "Each dm\u{b3} of this bottle stores {} of [{}](../../gas/{}).",
+                volume,
+                gas.name(),
+                gas.name().to_kebab_case()
+            )?;
+            writeln!(&mut fh)?;
+        }
+        None => {}
+    }
 
     fn is_cargo_range(range: &reaction::CatalystRange, cargo_id: usize) -> bool {
         match range {
@@ -0,0 +1,99 @@
+use std::fs;
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use heck::KebabCase;
+
+use super::opts;
+use traffloat_types::def::{building, reaction, GameDefinition};
+use traffloat_types::units;
+
+pub fn gen_economy(opts: &opts::Opts, def: &GameDefinition) -> Result<()> {
+    let mut fh = fs::File::create(opts.root_dir.join("docs/happiness-economy.md"))
+        .context("Could not create happiness-economy.md")?;
+    writeln!(&mut fh, "{}", include_str!("happiness-economy.md"))?;
+
+    writeln!(&mut fh, "## Entertainment")?;
+    writeln!(&mut fh, "| Building | Visitors | Happiness per second |")?;
+    writeln!(&mut fh, "| :-: | :-: | :-: |")?;
+    for building in def.building() {
+        for feature in building.features() {
+            if let building::ExtraFeature::Entertainment { capacity, rate } = feature {
+                writeln!(
+                    &mut fh,
+                    "| [{}](../building/{}) | {} | {} |",
+                    building.name(),
+                    building.name().to_kebab_case(),
+                    capacity,
+                    rate.0,
+                )?;
+            }
+        }
+    }
+    writeln!(&mut fh)?;
+
+    writeln!(&mut fh, "## Reactions")?;
+    writeln!(
+        &mut fh,
+        "| Mechanism | Base happiness change per second | Buildings |"
+    )?;
+    writeln!(&mut fh, "| :-: | :-: | :-: |")?;
+    for (reaction_id, reaction) in def.reaction().iter().enumerate() {
+        let happiness: units::Happiness = reaction
+            .puts()
+            .iter()
+            .filter_map(|put| match put {
+                reaction::Put::Happiness { base } => Some(base.0),
+                _ => None,
+            })
+            .sum();
+        if happiness.0 != 0. {
+            let buildings = def
+                .building()
+                .iter()
+                .filter(|building| {
+                    building
+                        .reactions()
+                        .iter()
+                        .any(|(id, _)| id.0 == reaction_id)
+                })
+                .map(|building| {
+                    format!(
+                        "[{}](../building/{})",
+                        building.name(),
+                        building.name().to_kebab_case()
+                    )
+                })
+                .collect::<Vec<String>>();
+
+            writeln!(
+                &mut fh,
+                "| {} | {} | {} |",
+                reaction.name(),
+                happiness,
+                buildings.join(", "),
+            )?;
+        }
+    }
+    writeln!(&mut fh)?;
+
+    writeln!(&mut fh, "## Crimes")?;
+    writeln!(
+        &mut fh,
+        "| Crime | Happiness of offender | Happiness change |"
+    )?;
+    writeln!(&mut fh, "| :-: | :-: | :-: |")?;
+    for crime in def.crime() {
+        let range = crime.trigger_happiness_range();
+        writeln!(
+            &mut fh,
+            "| {} | {} to {} | {} |",
+            crime.name(),
+            range.start,
+            range.end,
+            crime.happiness_change(),
+        )?;
+    }
+
+    Ok(())
+}
@@ -19,6 +19,23 @@ pub fn gen_electricity(
         .context("Could not create electricity.md")?;
     writeln!(&mut fh, "{}", include_str!("electricity.md"))?;
 
+    writeln!(&mut fh, "| Building | Energy storage | Charge rate |")?;
+    writeln!(&mut fh, "| :-: | :-: | :-: |")?;
+    for building in def.building() {
+        let storage = building.storage();
+        if storage.electricity().0 > 0. {
+            writeln!(
+                &mut fh,
+                "| [{}](../building/{}) | {} | {} |",
+                building.name(),
+                building.name().to_kebab_case(),
+                storage.electricity(),
+                storage.charge_rate(),
+            )?;
+        }
+    }
+    writeln!(&mut fh)?;
+
     writeln!(&mut fh, "## Electricity generation")?;
     writeln!(
         &mut fh,
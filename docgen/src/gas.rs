@@ -82,6 +82,32 @@ fn write_gas(
     writeln!(&mut fh, "> {}", gas.summary())?;
     writeln!(&mut fh)?;
     writeln!(&mut fh, "{}", gas.description())?;
+    writeln!(&mut fh)?;
+    if gas.waste() {
+        writeln!(
+            &mut fh,
+            "This is a [waste product](../../waste). Storing too much of it reduces happiness."
+        )?;
+        writeln!(&mut fh)?;
+    }
+    match gas.respiration() {
+        gas::Respiration::Inert => {}
+        gas::Respiration::Inhaled => {
+            writeln!(
+                &mut fh,
+                "Inhabitants [breathe](../../population#breathing) this gas. \
+                They lose health when their node runs out of it."
+            )?;
+            writeln!(&mut fh)?;
+        }
+        gas::Respiration::Exhaled => {
+            writeln!(
+                &mut fh,
+                "Inhabitants [exhale](../../population#breathing) this gas."
+            )?;
+            writeln!(&mut fh)?;
+        }
+    }
 
     fn is_gas_range(range: &reaction::CatalystRange, gas_id: usize) -> bool {
         match range {
@@ -0,0 +1,61 @@
+use std::fs;
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use heck::KebabCase;
+
+use super::opts;
+use traffloat_types::def::{reaction, GameDefinition};
+use traffloat_types::units;
+
+pub fn gen_heat(opts: &opts::Opts, def: &GameDefinition) -> Result<()> {
+    let mut fh =
+        fs::File::create(opts.root_dir.join("docs/heat.md")).context("Could not create heat.md")?;
+    writeln!(&mut fh, "{}", include_str!("heat.md"))?;
+
+    writeln!(&mut fh, "## Heat by mechanism")?;
+    writeln!(
+        &mut fh,
+        "| Mechanism | Base heat emission per second | Buildings |"
+    )?;
+    writeln!(&mut fh, "| :-: | :-: | :-: |")?;
+    for (reaction_id, reaction) in def.reaction().iter().enumerate() {
+        let heat: units::Heat = reaction
+            .puts()
+            .iter()
+            .filter_map(|put| match put {
+                reaction::Put::Heat { base } => Some(base.0),
+                _ => None,
+            })
+            .sum();
+        if heat.0 != 0. {
+            let buildings = def
+                .building()
+                .iter()
+                .filter(|building| {
+                    building
+                        .reactions()
+                        .iter()
+                        .any(|(id, _)| id.0 == reaction_id)
+                })
+                .map(|building| {
+                    format!(
+                        "[{}](../building/{})",
+                        building.name(),
+                        building.name().to_kebab_case()
+                    )
+                })
+                .collect::<Vec<String>>();
+
+            writeln!(
+                &mut fh,
+                "| {} | {} | {} |",
+                reaction.name(),
+                heat,
+                buildings.join(", "),
+            )?;
+        }
+    }
+
+    Ok(())
+}
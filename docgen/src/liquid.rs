@@ -83,6 +83,13 @@ fn write_liquid(
     writeln!(&mut fh)?;
     writeln!(&mut fh, "{}", liquid.description())?;
     writeln!(&mut fh)?;
+    if liquid.waste() {
+        writeln!(
+            &mut fh,
+            "This is a [waste product](../../waste). Storing too much of it reduces happiness."
+        )?;
+        writeln!(&mut fh)?;
+    }
 
     fn is_liquid_range(range: &reaction::CatalystRange, liquid_id: usize) -> bool {
         match range {
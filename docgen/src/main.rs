@@ -3,23 +3,42 @@
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use structopt::StructOpt;
+use traffloat_types::def::layer::{Layer, ModInfo};
+use traffloat_types::def::schema::{Definition, Format};
+use traffloat_types::def::validate::{Severity, Texture};
+use traffloat_types::def::GameDefinition;
 
 mod assets;
 mod building;
 mod cargo;
+mod economy;
 mod electricity;
 mod gas;
+mod heat;
 mod liquid;
 mod manifest;
 mod opts;
 mod reaction;
 mod skill;
 mod vehicle;
+mod waste;
 
 fn main() -> Result<()> {
     let opts = opts::Opts::from_args();
+
+    let def = match &opts.definition {
+        Some(path) => load_definition(path)?,
+        None => traffloat_vanilla::get().0,
+    };
+    let def = load_mods(&opts, def)?;
+
+    validate(&opts, &def)?;
+    if opts.validate_only {
+        return Ok(());
+    }
+
     fs::create_dir_all(&opts.root_dir).context("Could not create --root-dir")?;
     let root_dir = opts
         .root_dir
@@ -35,7 +54,13 @@ fn main() -> Result<()> {
         Ok(stripped.to_path_buf())
     };
 
-    let (def, _, _) = traffloat_vanilla::get();
+    for path in &opts.export {
+        let format = path_format(path)?;
+        let text = format
+            .write(&Definition::export(&def))
+            .with_context(|| format!("Exporting definition to {}", path.display()))?;
+        fs::write(path, text).with_context(|| format!("Writing {}", path.display()))?;
+    }
 
     let buildings_index = building::gen_buildings(&opts, &mut assets, relativize, &def)
         .context("Generating building guide")?;
@@ -51,6 +76,9 @@ fn main() -> Result<()> {
         .context("Generating vehicle guide")?;
     electricity::gen_electricity(&opts, &mut assets, relativize, &def)
         .context("Generating electricity guide")?;
+    heat::gen_heat(&opts, &def).context("Generating heat guide")?;
+    waste::gen_waste(&opts, &def).context("Generating waste guide")?;
+    economy::gen_economy(&opts, &def).context("Generating happiness economy guide")?;
 
     {
         let docs_dir = opts.root_dir.join("docs");
@@ -84,6 +112,7 @@ fn main() -> Result<()> {
                 manifest::Nav::Path(PathBuf::from("population.md")),
                 manifest::Nav::Path(PathBuf::from("housing.md")),
                 manifest::Nav::Path(PathBuf::from("happiness.md")),
+                manifest::Nav::Path(PathBuf::from("happiness-economy.md")),
                 manifest::Nav::Index {
                     title: String::from("Skill"),
                     items: skills_index,
@@ -103,6 +132,8 @@ fn main() -> Result<()> {
             items: liquids_index,
         },
         manifest::Nav::Path(PathBuf::from("electricity.md")),
+        manifest::Nav::Path(PathBuf::from("heat.md")),
+        manifest::Nav::Path(PathBuf::from("waste.md")),
         manifest::Nav::Index {
             title: String::from("Vehicle"),
             items: vehicles_index,
@@ -156,3 +187,87 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+fn path_format(path: &Path) -> Result<Format> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(Format::from_extension)
+        .with_context(|| format!("Unknown definition format for {}", path.display()))
+}
+
+fn load_definition(path: &Path) -> Result<GameDefinition> {
+    let format = path_format(path)?;
+    let text = fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+    let def = format
+        .parse(&text)
+        .and_then(Definition::resolve)
+        .with_context(|| format!("Loading definition from {}", path.display()))?;
+    Ok(def)
+}
+
+fn validate(opts: &opts::Opts, def: &GameDefinition) -> Result<()> {
+    let textures_dir = opts.client_dir.join("textures");
+    let mut diagnostics = def.validate();
+    diagnostics.extend(def.validate_textures(|texture| match texture {
+        Texture::Icon(name) => textures_dir.join(name).with_extension("svg").is_file(),
+        Texture::Building(name) => textures_dir.join(name).is_dir(),
+    }));
+
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+    let errors = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .count();
+    if errors > 0 {
+        bail!("Game definition has {} errors", errors);
+    }
+    Ok(())
+}
+
+fn load_mods(opts: &opts::Opts, def: GameDefinition) -> Result<GameDefinition> {
+    if opts.mods.is_empty() {
+        return Ok(def);
+    }
+
+    let layers = opts
+        .mods
+        .iter()
+        .map(|path| {
+            let text =
+                fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+            let layer: Layer = path_format(path)?
+                .parse(&text)
+                .with_context(|| format!("Parsing mod {}", path.display()))?;
+            Ok(layer)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let base = match &opts.definition {
+        Some(path) => ModInfo {
+            id: path
+                .file_stem()
+                .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned())
+                .into(),
+            version: Default::default(),
+            after: Vec::new(),
+        },
+        None => traffloat_vanilla::info(),
+    };
+
+    match def.layered(base, layers) {
+        Ok((def, manifest)) => {
+            for info in manifest.mods() {
+                eprintln!("Loaded mod {} {}", info.id, info.version);
+            }
+            Ok(def)
+        }
+        Err(conflicts) => {
+            for conflict in &conflicts {
+                eprintln!("{}", conflict);
+            }
+            bail!("{} conflicts among mods", conflicts.len())
+        }
+    }
+}
@@ -10,4 +10,17 @@ pub struct Opts {
     pub client_dir: PathBuf,
     #[structopt(long)]
     pub site_url: Option<String>,
+    /// Load the game definition from this file instead of using the vanilla definition.
+    #[structopt(long, parse(from_os_str))]
+    pub definition: Option<PathBuf>,
+    /// Stack these mod files on top of the game definition.
+    #[structopt(long = "mod", parse(from_os_str))]
+    pub mods: Vec<PathBuf>,
+    /// Export the game definition to these files.
+    /// The format is inferred from the file extension.
+    #[structopt(long, parse(from_os_str))]
+    pub export: Vec<PathBuf>,
+    /// Only validate the game definition without generating the guide.
+    #[structopt(long)]
+    pub validate_only: bool,
 }
@@ -67,6 +67,13 @@ pub fn write_reaction(
                         levels.start, levels.end
                     )?;
                 }
+                reaction::CatalystRange::Heat { levels } => {
+                    write!(
+                        &mut fh,
+                        "| [Temperature](../../heat) | {} | {} ",
+                        levels.start, levels.end
+                    )?;
+                }
                 reaction::CatalystRange::Skill { ty, levels } => {
                     write!(
                         &mut fh,
@@ -139,6 +146,9 @@ pub fn write_reaction(
                             base.0 * mul,
                         )?;
                     }
+                    reaction::Put::Heat { base } => {
+                        writeln!(&mut fh, "| [Heat](../../heat) | {} |", base.0 * mul)?;
+                    }
                     reaction::Put::Happiness { base } => {
                         writeln!(
                             &mut fh,
@@ -155,6 +165,9 @@ pub fn write_reaction(
                             base.0 * mul,
                         )?;
                     }
+                    reaction::Put::Research { base } => {
+                        writeln!(&mut fh, "| Research points | {} |", base.0 * mul)?;
+                    }
                 }
             }
             writeln!(&mut fh)?;
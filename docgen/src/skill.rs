@@ -58,6 +58,22 @@ fn write_skill(
     writeln!(&mut fh, "# {}", skill.name())?;
     writeln!(&mut fh, "{}", skill.description())?;
     writeln!(&mut fh)?;
+    match skill.kind() {
+        skill::Kind::Trained => writeln!(
+            &mut fh,
+            "This skill [grows slowly](../../population#aging) with time, \
+            faster for younger inhabitants."
+        )?,
+        skill::Kind::Age => writeln!(
+            &mut fh,
+            "This skill is the [age](../../population#aging) of the inhabitant in years."
+        )?,
+        skill::Kind::Health => writeln!(
+            &mut fh,
+            "The inhabitant [dies](../../population#death) when this skill drops to zero."
+        )?,
+    }
+    writeln!(&mut fh)?;
 
     let reactions = def
         .reaction()
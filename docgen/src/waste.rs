@@ -0,0 +1,93 @@
+use std::fs;
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use heck::KebabCase;
+
+use super::opts;
+use traffloat_types::def::{reaction, GameDefinition};
+
+pub fn gen_waste(opts: &opts::Opts, def: &GameDefinition) -> Result<()> {
+    let mut fh = fs::File::create(opts.root_dir.join("docs/waste.md"))
+        .context("Could not create waste.md")?;
+    writeln!(&mut fh, "{}", include_str!("waste.md"))?;
+
+    writeln!(&mut fh, "## Waste cycles")?;
+    writeln!(&mut fh, "| Waste | Produced by | Recycled by |")?;
+    writeln!(&mut fh, "| :-: | :-: | :-: |")?;
+
+    for (id, cargo) in def.cargo().iter().enumerate() {
+        if cargo.waste() {
+            write_cycle(
+                &mut fh,
+                def,
+                &format!(
+                    "[{}](../cargo/{})",
+                    cargo.name(),
+                    cargo.name().to_kebab_case()
+                ),
+                |put| matches!(put, reaction::Put::Cargo { ty, .. } if ty.0 == id),
+            )?;
+        }
+    }
+    for (id, liquid) in def.liquid().iter().enumerate() {
+        if liquid.waste() {
+            write_cycle(
+                &mut fh,
+                def,
+                &format!(
+                    "[{}](../liquid/{})",
+                    liquid.name(),
+                    liquid.name().to_kebab_case()
+                ),
+                |put| matches!(put, reaction::Put::Liquid { ty, .. } if ty.0 == id),
+            )?;
+        }
+    }
+    for (id, gas) in def.gas().iter().enumerate() {
+        if gas.waste() {
+            write_cycle(
+                &mut fh,
+                def,
+                &format!("[{}](../gas/{})", gas.name(), gas.name().to_kebab_case()),
+                |put| matches!(put, reaction::Put::Gas { ty, .. } if ty.0 == id),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_cycle(
+    mut fh: impl Write,
+    def: &GameDefinition,
+    name: &str,
+    matches: impl Fn(&reaction::Put) -> bool,
+) -> Result<()> {
+    let reactions = |output: bool| {
+        let names: Vec<String> = def
+            .reaction()
+            .iter()
+            .filter(|reaction| {
+                reaction
+                    .puts()
+                    .iter()
+                    .any(|put| matches(put) && put.is_output() == output)
+            })
+            .map(|reaction| reaction.name().to_string())
+            .collect();
+        if names.is_empty() {
+            String::from("None")
+        } else {
+            names.join(", ")
+        }
+    };
+    writeln!(
+        &mut fh,
+        "| {} | {} | {} |",
+        name,
+        reactions(true),
+        reactions(false)
+    )?;
+    Ok(())
+}
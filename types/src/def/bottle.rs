@@ -0,0 +1,346 @@
+//! Generated bottle cargo types for liquids and gases.
+//!
+//! Liquids and gases can be packed into bottles,
+//! which are transported as cargo by vehicles
+//! between buildings not connected by pipes.
+//! [`GameDefinition::add_bottles`] generates a bottle cargo type for each liquid and gas type,
+//! together with the reactions that fill and empty them.
+
+use arcstr::{format, ArcStr};
+use smallvec::smallvec;
+use typed_builder::TypedBuilder;
+
+use super::{cargo, gas, liquid, reaction, GameDefinition};
+use crate::time::Rate;
+use crate::units;
+
+/// Parameters for generating bottle types.
+#[derive(Debug, Clone, TypedBuilder, getset::CopyGetters, getset::Getters)]
+pub struct Options {
+    /// The category of the generated cargo types.
+    #[getset(get_copy = "pub")]
+    cargo_category: cargo::CategoryId,
+    /// The category of the generated reactions.
+    #[getset(get_copy = "pub")]
+    reaction_category: reaction::CategoryId,
+    /// The amount of liquid stored in 1 dm\u{b3} of liquid bottles.
+    #[getset(get_copy = "pub")]
+    liquid_volume: units::LiquidVolume,
+    /// The amount of gas stored in 1 dm\u{b3} of gas bottles.
+    #[getset(get_copy = "pub")]
+    gas_volume: units::GasVolume,
+    /// The rate of bottles filled or emptied by the generated reactions.
+    #[getset(get_copy = "pub")]
+    rate: Rate<units::CargoSize>,
+    /// Description of the generated liquid bottles.
+    #[getset(get = "pub")]
+    liquid_description: ArcStr,
+    /// Description of the generated gas bottles.
+    #[getset(get = "pub")]
+    gas_description: ArcStr,
+}
+
+/// The types generated for a bottled liquid or gas.
+#[derive(Debug, Clone, Copy, getset::CopyGetters)]
+pub struct Bottled {
+    /// The bottle cargo type.
+    #[getset(get_copy = "pub")]
+    cargo: cargo::TypeId,
+    /// The reaction filling the bottle.
+    #[getset(get_copy = "pub")]
+    bottling: reaction::TypeId,
+    /// The reaction emptying the bottle.
+    #[getset(get_copy = "pub")]
+    unbottling: reaction::TypeId,
+}
+
+/// IDs of the generated bottle types.
+#[derive(Debug, Clone, Default, getset::Getters)]
+pub struct Ids {
+    /// The bottles of each liquid type, indexed by [`liquid::TypeId`].
+    #[getset(get = "pub")]
+    liquid: Vec<Bottled>,
+    /// The bottles of each gas type, indexed by [`gas::TypeId`].
+    #[getset(get = "pub")]
+    gas: Vec<Bottled>,
+}
+
+impl Ids {
+    /// Iterates over all generated bottling and unbottling reactions.
+    pub fn reactions(&self) -> impl Iterator<Item = reaction::TypeId> + '_ {
+        self.liquid
+            .iter()
+            .chain(&self.gas)
+            .flat_map(|bottled| vec![bottled.bottling, bottled.unbottling])
+    }
+}
+
+impl GameDefinition {
+    /// Generates bottle cargo types for every liquid and gas type,
+    /// together with their bottling and unbottling reactions.
+    ///
+    /// The string identifier of the bottle of `vanilla:water` is `vanilla:water_liquid_bottle`,
+    /// and its texture name is `<liquid texture>-liquid-bottle`.
+    /// Gas bottles are named similarly.
+    pub fn add_bottles(&mut self, options: &Options) -> Ids {
+        let mut ids = Ids::default();
+
+        for (index, liquid) in self.liquid.clone().into_iter().enumerate() {
+            let ty = liquid::TypeId(index);
+            let volume = options.liquid_volume;
+            let cargo = self.add_cargo(
+                cargo::Type::builder()
+                    .id(format!("{}_liquid_bottle", liquid.id()))
+                    .name(format!("Liquid bottle ({})", liquid.name()))
+                    .summary(format!("Stores a small amount of {}", liquid.name()))
+                    .description(options.liquid_description.clone())
+                    .texture(format!("{}-liquid-bottle", liquid.texture()))
+                    .category(options.cargo_category)
+                    .bottle(Some(cargo::Bottle::Liquid { ty, volume }))
+                    .build(),
+            );
+            let fluid = Rate(units::LiquidVolume(options.rate.0 .0 * volume.0));
+            let catalyst = reaction::CatalystRange::Liquid {
+                ty,
+                levels: units::LiquidVolume(0.)..fluid.0,
+            };
+            let (bottling, unbottling) = self.add_bottle_reactions(
+                options,
+                liquid.id(),
+                liquid.name(),
+                "liquid",
+                cargo,
+                catalyst,
+                |sign| reaction::Put::Liquid {
+                    ty,
+                    base: Rate(fluid.0 * sign),
+                },
+            );
+            ids.liquid.push(Bottled {
+                cargo,
+                bottling,
+                unbottling,
+            });
+        }
+
+        for (index, gas) in self.gas.clone().into_iter().enumerate() {
+            let ty = gas::TypeId(index);
+            let volume = options.gas_volume;
+            let cargo = self.add_cargo(
+                cargo::Type::builder()
+                    .id(format!("{}_gas_bottle", gas.id()))
+                    .name(format!("Gas bottle ({})", gas.name()))
+                    .summary(format!("Stores a small amount of {}", gas.name()))
+                    .description(options.gas_description.clone())
+                    .texture(format!("{}-gas-bottle", gas.texture()))
+                    .category(options.cargo_category)
+                    .bottle(Some(cargo::Bottle::Gas { ty, volume }))
+                    .build(),
+            );
+            let fluid = Rate(units::GasVolume(options.rate.0 .0 * volume.0));
+            let catalyst = reaction::CatalystRange::Gas {
+                ty,
+                levels: units::GasVolume(0.)..fluid.0,
+            };
+            let (bottling, unbottling) = self.add_bottle_reactions(
+                options,
+                gas.id(),
+                gas.name(),
+                "gas",
+                cargo,
+                catalyst,
+                |sign| reaction::Put::Gas {
+                    ty,
+                    base: Rate(fluid.0 * sign),
+                },
+            );
+            ids.gas.push(Bottled {
+                cargo,
+                bottling,
+                unbottling,
+            });
+        }
+
+        ids
+    }
+
+    /// Adds the bottling and unbottling reactions of a bottle type.
+    ///
+    /// `catalyst` is the fluid level required for bottling,
+    /// and `fluid` creates the fluid put with the given sign.
+    #[allow(clippy::too_many_arguments)]
+    fn add_bottle_reactions(
+        &mut self,
+        options: &Options,
+        id: &str,
+        name: &str,
+        kind: &str,
+        cargo: cargo::TypeId,
+        catalyst: reaction::CatalystRange,
+        fluid: impl Fn(f64) -> reaction::Put,
+    ) -> (reaction::TypeId, reaction::TypeId) {
+        let bottle = |sign: f64| reaction::Put::Cargo {
+            ty: cargo,
+            base: Rate(options.rate.0 * sign),
+        };
+
+        let bottling = self.add_reaction(
+            reaction::Type::builder()
+                .id(format!("{}_{}_bottling", id, kind))
+                .name(format!("Bottling ({})", name))
+                .description(format!("Fills {} into bottles.", name))
+                .category(options.reaction_category)
+                .catalysts(smallvec![reaction::Catalyst::builder()
+                    .range(catalyst)
+                    .multipliers(
+                        reaction::Multipliers::builder()
+                            .underflow(0.)
+                            .min(0.)
+                            .max(1.)
+                            .overflow(1.)
+                            .build()
+                    )
+                    .build()])
+                .puts(smallvec![fluid(-1.), bottle(1.)])
+                .build(),
+        );
+        let unbottling = self.add_reaction(
+            reaction::Type::builder()
+                .id(format!("{}_{}_unbottling", id, kind))
+                .name(format!("Unbottling ({})", name))
+                .description(format!("Releases {} from bottles.", name))
+                .category(options.reaction_category)
+                .catalysts(smallvec![])
+                .puts(smallvec![bottle(-1.), fluid(1.)])
+                .build(),
+        );
+        (bottling, unbottling)
+    }
+
+    /// Returns the bottle cargo type of a liquid type.
+    pub fn liquid_bottle(
+        &self,
+        ty: liquid::TypeId,
+    ) -> Option<(cargo::TypeId, units::LiquidVolume)> {
+        self.cargo
+            .iter()
+            .enumerate()
+            .find_map(|(index, cargo)| match cargo.bottle() {
+                Some(cargo::Bottle::Liquid {
+                    ty: bottled,
+                    volume,
+                }) if bottled == ty => Some((cargo::TypeId(index), volume)),
+                _ => None,
+            })
+    }
+
+    /// Returns the bottle cargo type of a gas type.
+    pub fn gas_bottle(&self, ty: gas::TypeId) -> Option<(cargo::TypeId, units::GasVolume)> {
+        self.cargo
+            .iter()
+            .enumerate()
+            .find_map(|(index, cargo)| match cargo.bottle() {
+                Some(cargo::Bottle::Gas {
+                    ty: bottled,
+                    volume,
+                }) if bottled == ty => Some((cargo::TypeId(index), volume)),
+                _ => None,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generate_bottles() {
+        let mut def = GameDefinition::default();
+        let oxygen = def.add_gas(
+            gas::Type::builder()
+                .id(arcstr::literal!("test:oxygen"))
+                .name(arcstr::literal!("Oxygen"))
+                .summary(arcstr::literal!(""))
+                .description(arcstr::literal!(""))
+                .texture(arcstr::literal!("oxygen"))
+                .build(),
+        );
+        let water = def.add_liquid(
+            liquid::Type::builder()
+                .id(arcstr::literal!("test:water"))
+                .name(arcstr::literal!("Water"))
+                .summary(arcstr::literal!(""))
+                .description(arcstr::literal!(""))
+                .viscosity(units::LiquidViscosity(1.))
+                .texture(arcstr::literal!("water"))
+                .build(),
+        );
+        let cargo_category = def.add_cargo_category(
+            cargo::Category::builder()
+                .id(arcstr::literal!("test:container"))
+                .title(arcstr::literal!("Container"))
+                .description(arcstr::literal!(""))
+                .build(),
+        );
+        let reaction_category = def.add_reaction_category(
+            reaction::Category::builder()
+                .id(arcstr::literal!("test:bottling"))
+                .title(arcstr::literal!("Bottling"))
+                .description(arcstr::literal!(""))
+                .build(),
+        );
+
+        let ids = def.add_bottles(
+            &Options::builder()
+                .cargo_category(cargo_category)
+                .reaction_category(reaction_category)
+                .liquid_volume(units::LiquidVolume(2.))
+                .gas_volume(units::GasVolume(5.))
+                .rate(Rate(units::CargoSize(1.)))
+                .liquid_description(arcstr::literal!(""))
+                .gas_description(arcstr::literal!(""))
+                .build(),
+        );
+
+        let bottled = ids.liquid()[0];
+        assert_eq!(
+            def.find_cargo("test:water_liquid_bottle"),
+            Some(bottled.cargo())
+        );
+        assert_eq!(
+            def.liquid_bottle(water),
+            Some((bottled.cargo(), units::LiquidVolume(2.)))
+        );
+        let unbottling = def.get_reaction(bottled.unbottling());
+        assert_eq!(unbottling.id(), "test:water_liquid_unbottling");
+        match unbottling.puts()[1] {
+            reaction::Put::Liquid { ty, base } => {
+                assert_eq!(ty, water);
+                assert_eq!(base, Rate(units::LiquidVolume(2.)));
+            }
+            _ => panic!("Unbottling should produce liquid second"),
+        }
+
+        let bottled = ids.gas()[0];
+        assert_eq!(
+            def.find_cargo("test:oxygen_gas_bottle"),
+            Some(bottled.cargo())
+        );
+        assert_eq!(
+            def.gas_bottle(oxygen),
+            Some((bottled.cargo(), units::GasVolume(5.)))
+        );
+        assert_eq!(ids.reactions().count(), 4);
+
+        let bottling = def.get_reaction(bottled.bottling());
+        assert_eq!(bottling.id(), "test:oxygen_gas_bottling");
+        match bottling.puts()[0] {
+            reaction::Put::Gas { ty, base } => {
+                assert_eq!(ty, oxygen);
+                assert_eq!(base, Rate(units::GasVolume(-5.)));
+            }
+            _ => panic!("Bottling should consume gas first"),
+        }
+        assert!(def.validate().is_empty());
+    }
+}
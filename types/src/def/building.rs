@@ -1,19 +1,26 @@
 //! Building definitions
 
 use arcstr::ArcStr;
+use serde::{Deserialize, Serialize};
 use typed_builder::TypedBuilder;
 
-use super::reaction;
+use std::ops::Range;
+
+use super::{cargo, reaction, skill};
 use crate::space::Matrix;
+use crate::time::{Rate, Time};
 use crate::units;
 
 /// Identifies a building category
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct TypeId(pub usize);
 
 /// A type of building.
 #[derive(TypedBuilder, getset::CopyGetters, getset::Getters)]
 pub struct Type {
+    /// String identifier of the building type.
+    #[getset(get = "pub")]
+    id: ArcStr,
     /// Name of the building type.
     #[getset(get = "pub")]
     name: ArcStr,
@@ -41,6 +48,9 @@ pub struct Type {
     /// Storage provided by a building
     #[getset(get = "pub")]
     storage: Storage,
+    /// Resources required to construct the building.
+    #[getset(get = "pub")]
+    construction: Construction,
     /// Extra features associated with the building.
     #[getset(get = "pub")]
     features: Vec<ExtraFeature>,
@@ -61,8 +71,9 @@ pub struct Shape {
 }
 
 /// Reaction behaviour specific to this building.
-#[derive(TypedBuilder, getset::CopyGetters)]
+#[derive(Clone, Copy, Default, TypedBuilder, getset::CopyGetters, Serialize, Deserialize)]
 #[builder(field_defaults(default))]
+#[serde(default)]
 pub struct ReactionPolicy {
     /// Whethre the reaction rate can be configured by the players.
     #[get_copy = "pub"]
@@ -76,10 +87,38 @@ pub struct ReactionPolicy {
 }
 
 /// behaviour when inputs underflow or outputs overflow.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
 pub enum FlowPolicy {
     /// Reduce the rate of reaction such that the input/output capacity is just enough.
     ReduceRate,
+    /// Stop the reaction entirely until the inputs/outputs can sustain the full rate.
+    Halt,
+    /// Discard overflowing liquid or gas outputs to space.
+    ///
+    /// Overflowing cargo outputs and underflowing inputs reduce the rate instead.
+    Vent,
+    /// Spill overflowing cargo outputs into the node,
+    /// reducing the happiness of inhabitants in the node.
+    ///
+    /// Overflowing liquid or gas outputs and underflowing inputs reduce the rate instead.
+    Dump,
+    /// Reduce the rate like [`FlowPolicy::ReduceRate`],
+    /// but reactions with higher priority in the same building
+    /// take shared inputs and output capacity first.
+    Priority(u32),
+}
+
+impl FlowPolicy {
+    /// The priority of the reaction among reactions in the same building.
+    ///
+    /// Reactions without [`FlowPolicy::Priority`] have priority 0.
+    pub fn priority(self) -> u32 {
+        match self {
+            Self::Priority(priority) => priority,
+            _ => 0,
+        }
+    }
 }
 
 impl Default for FlowPolicy {
@@ -92,7 +131,7 @@ impl Default for FlowPolicy {
 ///
 /// This storage is also used as a buffer for liquid and gas transfer.
 /// The storage size is the maximum amount of liquid and gas that
-#[derive(TypedBuilder, getset::CopyGetters)]
+#[derive(Clone, Copy, TypedBuilder, getset::CopyGetters, Serialize, Deserialize)]
 pub struct Storage {
     /// Cargo storage provided
     #[getset(get_copy = "pub")]
@@ -103,15 +142,65 @@ pub struct Storage {
     /// Gas storage provided
     #[getset(get_copy = "pub")]
     gas: units::GasVolume,
+    /// Electric energy storage provided
+    #[getset(get_copy = "pub")]
+    #[builder(default)]
+    #[serde(default)]
+    electricity: units::ElectricEnergy,
+    /// Maximum power at which the electric energy storage is charged or discharged
+    #[getset(get_copy = "pub")]
+    #[builder(default)]
+    #[serde(default)]
+    charge_rate: units::ElectricPower,
+}
+
+/// Resources required to construct a building.
+///
+/// A construction site consumes the cargo and energy delivered to it,
+/// and advances with the work of assigned workers.
+#[derive(Clone, TypedBuilder, getset::CopyGetters, getset::Getters)]
+pub struct Construction {
+    /// Cargo consumed by the construction site.
+    #[getset(get = "pub")]
+    cargo: Vec<(cargo::TypeId, units::CargoSize)>,
+    /// Electric energy consumed by the construction site.
+    #[getset(get_copy = "pub")]
+    energy: units::ElectricEnergy,
+    /// Time to complete construction with a single worker at a multiplier of 1.
+    #[getset(get_copy = "pub")]
+    time: Time,
+    /// The skill of workers that speeds up construction.
+    #[getset(get_copy = "pub")]
+    skill: skill::TypeId,
+    /// The skill level range of varying work speed multipliers.
+    #[getset(get = "pub")]
+    levels: Range<units::Skill>,
+    /// The multipliers applied on the work speed of each worker.
+    #[getset(get_copy = "pub")]
+    multipliers: reaction::Multipliers,
 }
 
 /// Extra features of a building (in addition to reactions)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
 pub enum ExtraFeature {
     /// The building is a core and must not be destroyed.
     Core,
     /// The building provides housing capacity, and inhabitants can be assigned to it.
     ProvidesHousing(u32),
+    /// The building provides seats for students,
+    /// who receive the skill changes of the reactions in the building.
+    Education(u32),
+    /// The building holds up to this number of prisoners,
+    /// who stay in the building until their happiness is positive.
+    Prison(u32),
+    /// Off-duty inhabitants visit the building to restore happiness.
+    Entertainment {
+        /// The maximum number of visitors at the same time.
+        capacity: u32,
+        /// The happiness gained by each visitor.
+        rate: Rate<units::Happiness>,
+    },
     /// The building provides driving force for vehicles on adjacent rails.
     RailTerminal(units::RailForce),
     /// The building provides pumping force for adjacent liquid pipes.
@@ -139,12 +228,15 @@ pub enum ExtraFeature {
 }
 
 /// Identifies a building category
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct CategoryId(pub usize);
 
 /// A category of building.
-#[derive(TypedBuilder, getset::Getters)]
+#[derive(Clone, TypedBuilder, getset::Getters, Serialize, Deserialize)]
 pub struct Category {
+    /// String identifier of the building category.
+    #[getset(get = "pub")]
+    id: ArcStr,
     /// Title of the building category.
     #[getset(get = "pub")]
     title: ArcStr,
@@ -1,15 +1,22 @@
 //! Cargo definitions.
 
 use arcstr::ArcStr;
+use serde::{Deserialize, Serialize};
 use typed_builder::TypedBuilder;
 
+use super::{gas, liquid};
+use crate::units;
+
 /// Identifies a cargo category
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct TypeId(pub usize);
 
 /// A type of cargo.
 #[derive(TypedBuilder, getset::CopyGetters, getset::Getters)]
 pub struct Type {
+    /// String identifier of the cargo type.
+    #[getset(get = "pub")]
+    id: ArcStr,
     /// Name of the cargo type.
     #[getset(get = "pub")]
     name: ArcStr,
@@ -25,15 +32,50 @@ pub struct Type {
     /// Name of the texture.
     #[getset(get = "pub")]
     texture: ArcStr,
+    /// Whether the cargo type is a waste product.
+    ///
+    /// Nodes with full waste storage penalize their inhabitants.
+    #[getset(get_copy = "pub")]
+    #[builder(default)]
+    waste: bool,
+    /// The fluid stored in the cargo type if it is a bottle.
+    ///
+    /// Bottle types are usually generated by
+    /// [`GameDefinition::add_bottles`][super::GameDefinition::add_bottles].
+    #[getset(get_copy = "pub")]
+    #[builder(default)]
+    bottle: Option<Bottle>,
+}
+
+/// The fluid stored in a bottle cargo type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bottle {
+    /// The cargo stores a liquid.
+    Liquid {
+        /// The liquid type stored
+        ty: liquid::TypeId,
+        /// The amount of liquid stored in 1 dm\u{b3} of the cargo
+        volume: units::LiquidVolume,
+    },
+    /// The cargo stores a gas.
+    Gas {
+        /// The gas type stored
+        ty: gas::TypeId,
+        /// The amount of gas stored in 1 dm\u{b3} of the cargo
+        volume: units::GasVolume,
+    },
 }
 
 /// Identifies a cargo category
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct CategoryId(pub usize);
 
 /// A category of cargo.
-#[derive(TypedBuilder, getset::Getters)]
+#[derive(Clone, TypedBuilder, getset::Getters, Serialize, Deserialize)]
 pub struct Category {
+    /// String identifier of the cargo category.
+    #[getset(get = "pub")]
+    id: ArcStr,
     /// Title of the cargo category.
     #[getset(get = "pub")]
     title: ArcStr,
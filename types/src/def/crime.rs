@@ -9,6 +9,10 @@ use typed_builder::TypedBuilder;
 use crate::def::skill;
 use crate::units;
 
+/// Identifies a crime type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TypeId(pub usize);
+
 /// Consequence of a crime.
 #[derive(Clone, Copy)]
 pub enum Action {
@@ -44,6 +48,9 @@ pub enum InhabitantCriterion {
 /// A type of crime customized for the game definition.
 #[derive(TypedBuilder, getset::Getters, getset::CopyGetters)]
 pub struct Type {
+    /// String identifier of the crime type.
+    #[getset(get = "pub")]
+    id: ArcStr,
     /// Name of the crime.
     #[getset(get = "pub")]
     name: ArcStr,
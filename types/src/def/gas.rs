@@ -1,15 +1,19 @@
 //! Gas definitions.
 
 use arcstr::ArcStr;
+use serde::{Deserialize, Serialize};
 use typed_builder::TypedBuilder;
 
 /// Identifies a gas category
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct TypeId(pub usize);
 
 /// A type of gas.
-#[derive(Clone, TypedBuilder, getset::CopyGetters, getset::Getters)]
+#[derive(Clone, TypedBuilder, getset::CopyGetters, getset::Getters, Serialize, Deserialize)]
 pub struct Type {
+    /// String identifier of the gas type.
+    #[getset(get = "pub")]
+    id: ArcStr,
     /// Name of the gas type.
     #[getset(get = "pub")]
     name: ArcStr,
@@ -22,4 +26,35 @@ pub struct Type {
     /// Name of the texture.
     #[getset(get = "pub")]
     texture: ArcStr,
+    /// Whether the gas type is a waste product.
+    ///
+    /// Nodes with full waste storage penalize their inhabitants.
+    #[getset(get_copy = "pub")]
+    #[builder(default)]
+    #[serde(default)]
+    waste: bool,
+    /// The role of the gas type in the breathing of inhabitants.
+    #[getset(get_copy = "pub")]
+    #[builder(default)]
+    #[serde(default)]
+    respiration: Respiration,
+}
+
+/// The role of a gas type in the breathing of inhabitants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Respiration {
+    /// The gas is not involved in breathing.
+    Inert,
+    /// The gas is consumed by inhabitants.
+    ///
+    /// Inhabitants lose health when the node they are in does not have enough of it.
+    Inhaled,
+    /// The gas is produced by inhabitants.
+    Exhaled,
+}
+
+impl Default for Respiration {
+    fn default() -> Self {
+        Self::Inert
+    }
 }
@@ -0,0 +1,702 @@
+//! Stacking mods on top of a game definition.
+//!
+//! A [`Layer`] adds new types, patches fields of existing types and removes types,
+//! all referring to types by their string keys.
+//! Layers are applied in a deterministic load order:
+//! each layer is loaded after the layers listed in its [`ModInfo::after`],
+//! and independent layers are loaded in the lexicographical order of their IDs.
+//!
+//! The resulting [`Manifest`] lists the active mods in load order,
+//! and should be recorded in saves so that the same stack can be loaded again.
+//!
+//! This module requires the `schema` feature.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use arcstr::ArcStr;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::schema::{self, Definition};
+use super::{building, cargo, gas, liquid, reaction, skill, GameDefinition};
+
+/// Identifies a mod and the mods it depends on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModInfo {
+    /// The mod ID, which is also the namespace of the keys added by the mod.
+    pub id: ArcStr,
+    /// The version of the mod.
+    pub version: ArcStr,
+    /// The mods that must be loaded before this mod.
+    #[serde(default)]
+    pub after: Vec<ArcStr>,
+}
+
+/// The active mods of a game in load order, including the base definition.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, getset::Getters)]
+pub struct Manifest {
+    /// The active mods.
+    #[getset(get = "pub")]
+    mods: Vec<ModInfo>,
+}
+
+/// A mod file.
+#[derive(Serialize, Deserialize)]
+pub struct Layer {
+    /// Information about the mod.
+    #[serde(rename = "mod")]
+    pub info: ModInfo,
+    /// New types added by the mod.
+    ///
+    /// The orbit of the sun cannot be changed by mods, so `add.orbit` is ignored.
+    #[serde(default)]
+    pub add: Definition,
+    /// Field changes to existing types.
+    #[serde(default)]
+    pub patch: Vec<Patch>,
+    /// Existing types removed by the mod.
+    #[serde(default)]
+    pub remove: Vec<Removal>,
+}
+
+/// Changes some fields of an existing type.
+///
+/// Nested tables are merged recursively; all other values replace the original value.
+#[derive(Serialize, Deserialize)]
+pub struct Patch {
+    /// The kind of the patched type.
+    pub kind: Kind,
+    /// The key of the patched type.
+    pub id: ArcStr,
+    /// The new field values.
+    #[serde(flatten)]
+    pub fields: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Removes an existing type.
+#[derive(Serialize, Deserialize)]
+pub struct Removal {
+    /// The kind of the removed type.
+    pub kind: Kind,
+    /// The key of the removed type.
+    pub id: ArcStr,
+}
+
+/// A conflict found when stacking layers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Conflict {
+    /// Two layers have the same mod ID.
+    DuplicateMod(ArcStr),
+    /// A layer depends on a mod that is not active.
+    MissingDependency {
+        /// The dependent mod.
+        mod_id: ArcStr,
+        /// The missing mod.
+        dependency: ArcStr,
+    },
+    /// The listed mods depend on each other cyclically.
+    DependencyCycle(Vec<ArcStr>),
+    /// A layer adds a key outside its own namespace.
+    ForeignNamespace {
+        /// The mod adding the key.
+        mod_id: ArcStr,
+        /// The kind of the added type.
+        kind: Kind,
+        /// The added key.
+        key: ArcStr,
+    },
+    /// A layer adds a key that already exists.
+    DuplicateKey {
+        /// The mod adding the key.
+        mod_id: ArcStr,
+        /// The kind of the added type.
+        kind: Kind,
+        /// The duplicated key.
+        key: ArcStr,
+    },
+    /// A layer patches or removes a key that does not exist.
+    MissingKey {
+        /// The mod referring to the key.
+        mod_id: ArcStr,
+        /// The kind of type referred to.
+        kind: Kind,
+        /// The missing key.
+        key: ArcStr,
+    },
+    /// A patch cannot be applied to the type.
+    InvalidPatch {
+        /// The mod with the patch.
+        mod_id: ArcStr,
+        /// The kind of the patched type.
+        kind: Kind,
+        /// The key of the patched type.
+        key: ArcStr,
+        /// Describes the error.
+        message: String,
+    },
+    /// Two mods that do not depend on each other modify the same type or field.
+    Overlap {
+        /// The mod loaded earlier.
+        first: ArcStr,
+        /// The mod loaded later.
+        second: ArcStr,
+        /// The kind of the modified type.
+        kind: Kind,
+        /// The key of the modified type.
+        key: ArcStr,
+        /// The modified field, or `None` if the type is added or removed.
+        field: Option<String>,
+    },
+    /// The stacked definition cannot be resolved.
+    Resolve(schema::Error),
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DuplicateMod(id) => write!(f, "Mod {:?} is loaded more than once", id),
+            Self::MissingDependency { mod_id, dependency } => {
+                write!(f, "Mod {:?} requires missing mod {:?}", mod_id, dependency)
+            }
+            Self::DependencyCycle(mods) => write!(f, "Mods {:?} depend on each other", mods),
+            Self::ForeignNamespace { mod_id, kind, key } => write!(
+                f,
+                "Mod {:?} adds {} {:?} outside its namespace",
+                mod_id, kind, key
+            ),
+            Self::DuplicateKey { mod_id, kind, key } => {
+                write!(f, "Mod {:?} adds existing {} {:?}", mod_id, kind, key)
+            }
+            Self::MissingKey { mod_id, kind, key } => {
+                write!(f, "Mod {:?} refers to undefined {} {:?}", mod_id, kind, key)
+            }
+            Self::InvalidPatch {
+                mod_id,
+                kind,
+                key,
+                message,
+            } => write!(
+                f,
+                "Mod {:?} cannot patch {} {:?}: {}",
+                mod_id, kind, key, message
+            ),
+            Self::Overlap {
+                first,
+                second,
+                kind,
+                key,
+                field,
+            } => {
+                write!(
+                    f,
+                    "Mods {:?} and {:?} both modify {} {:?}",
+                    first, second, kind, key
+                )?;
+                if let Some(field) = field {
+                    write!(f, " field {:?}", field)?;
+                }
+                Ok(())
+            }
+            Self::Resolve(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Conflict {}
+
+/// A type in a [`Definition`] that can be modified by layers.
+trait Entry: Serialize + DeserializeOwned {
+    fn key(&self) -> &ArcStr;
+}
+
+macro_rules! kinds {
+    ($($variant:ident($field:ident, $name:literal, $ty:ty, |$entry:ident| $key:expr);)*) => {
+        /// A kind of type in a game definition.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+        #[serde(rename_all = "snake_case")]
+        #[allow(missing_docs)]
+        pub enum Kind {
+            $($variant,)*
+        }
+
+        impl fmt::Display for Kind {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                let name = match self {
+                    $(Self::$variant => $name,)*
+                };
+                write!(f, "{}", name)
+            }
+        }
+
+        $(
+            impl Entry for $ty {
+                fn key(&self) -> &ArcStr {
+                    let $entry = self;
+                    $key
+                }
+            }
+        )*
+
+        impl Stack {
+            fn add_all(&mut self, mod_id: &ArcStr, add: Definition) {
+                $(
+                    self.add(Kind::$variant, mod_id, add.$field, |def| &mut def.$field);
+                )*
+            }
+
+            fn apply_patch(&mut self, mod_id: &ArcStr, patch: Patch) {
+                match patch.kind {
+                    $(
+                        Kind::$variant => self.patch(mod_id, patch, |def| &mut def.$field),
+                    )*
+                }
+            }
+
+            fn apply_removal(&mut self, mod_id: &ArcStr, removal: Removal) {
+                match removal.kind {
+                    $(
+                        Kind::$variant => self.remove(mod_id, removal, |def| &mut def.$field),
+                    )*
+                }
+            }
+        }
+    };
+}
+
+kinds! {
+    CargoCategory(cargo_category, "cargo category", cargo::Category, |ty| ty.id());
+    Cargo(cargo, "cargo type", schema::Cargo, |ty| &ty.id);
+    Liquid(liquid, "liquid type", liquid::Type, |ty| ty.id());
+    Gas(gas, "gas type", gas::Type, |ty| ty.id());
+    Skill(skill, "skill type", skill::Type, |ty| ty.id());
+    Vehicle(vehicle, "vehicle type", schema::Vehicle, |ty| &ty.id);
+    ReactionCategory(reaction_category, "reaction category", reaction::Category, |ty| ty.id());
+    Reaction(reaction, "reaction type", schema::Reaction, |ty| &ty.id);
+    BuildingCategory(building_category, "building category", building::Category, |ty| ty.id());
+    Building(building, "building type", schema::Building, |ty| &ty.id);
+    Crime(crime, "crime type", schema::Crime, |ty| &ty.id);
+    Tech(tech, "technology", schema::Tech, |ty| &ty.id);
+}
+
+/// The definition being stacked, along with the mods that modified each part of it.
+struct Stack {
+    def: Definition,
+    base: ArcStr,
+    /// The transitive dependencies of each mod.
+    deps: BTreeMap<ArcStr, BTreeSet<ArcStr>>,
+    /// The last mod that added, patched or removed each type or field.
+    owners: BTreeMap<(Kind, ArcStr, Option<String>), ArcStr>,
+    conflicts: Vec<Conflict>,
+}
+
+impl Stack {
+    /// Records that `mod_id` modifies a type or field,
+    /// reporting an overlap if the previous modifier is not a dependency.
+    fn claim(&mut self, mod_id: &ArcStr, kind: Kind, key: &ArcStr, field: Option<String>) {
+        let entry_owner = self.owners.get(&(kind, key.clone(), None)).cloned();
+        let owner = match &field {
+            Some(field) => self
+                .owners
+                .get(&(kind, key.clone(), Some(field.clone())))
+                .cloned()
+                .or(entry_owner),
+            None => entry_owner,
+        };
+
+        if let Some(owner) = owner {
+            let depends = self
+                .deps
+                .get(mod_id)
+                .map_or(false, |deps| deps.contains(&owner));
+            if owner != *mod_id && owner != self.base && !depends {
+                self.conflicts.push(Conflict::Overlap {
+                    first: owner,
+                    second: mod_id.clone(),
+                    kind,
+                    key: key.clone(),
+                    field: field.clone(),
+                });
+            }
+        }
+
+        self.owners
+            .insert((kind, key.clone(), field), mod_id.clone());
+    }
+
+    fn add<T: Entry>(
+        &mut self,
+        kind: Kind,
+        mod_id: &ArcStr,
+        entries: Vec<T>,
+        list: impl Fn(&mut Definition) -> &mut Vec<T>,
+    ) {
+        for entry in entries {
+            let key = entry.key().clone();
+            if *mod_id != self.base && key.split(':').next() != Some(mod_id.as_str()) {
+                self.conflicts.push(Conflict::ForeignNamespace {
+                    mod_id: mod_id.clone(),
+                    kind,
+                    key: key.clone(),
+                });
+            }
+            if list(&mut self.def).iter().any(|other| *other.key() == key) {
+                self.conflicts.push(Conflict::DuplicateKey {
+                    mod_id: mod_id.clone(),
+                    kind,
+                    key,
+                });
+                continue;
+            }
+            self.claim(mod_id, kind, &key, None);
+            list(&mut self.def).push(entry);
+        }
+    }
+
+    fn patch<T: Entry>(
+        &mut self,
+        mod_id: &ArcStr,
+        patch: Patch,
+        list: impl Fn(&mut Definition) -> &mut Vec<T>,
+    ) {
+        let invalid = |message: String| Conflict::InvalidPatch {
+            mod_id: mod_id.clone(),
+            kind: patch.kind,
+            key: patch.id.clone(),
+            message,
+        };
+
+        let entry = match list(&mut self.def)
+            .iter_mut()
+            .find(|entry| *entry.key() == patch.id)
+        {
+            Some(entry) => entry,
+            None => {
+                self.conflicts.push(Conflict::MissingKey {
+                    mod_id: mod_id.clone(),
+                    kind: patch.kind,
+                    key: patch.id.clone(),
+                });
+                return;
+            }
+        };
+
+        let mut value = match serde_json::to_value(&*entry) {
+            Ok(serde_json::Value::Object(value)) => value,
+            Ok(_) => unreachable!("Definition types are serialized as maps"),
+            Err(err) => {
+                self.conflicts.push(invalid(err.to_string()));
+                return;
+            }
+        };
+        for (field, patch_value) in &patch.fields {
+            match value.get_mut(field) {
+                Some(original) => merge(original, patch_value.clone()),
+                None => {
+                    self.conflicts
+                        .push(invalid(format!("Unknown field {:?}", field)));
+                    return;
+                }
+            }
+        }
+        match serde_json::from_value(serde_json::Value::Object(value)) {
+            Ok(patched) => *entry = patched,
+            Err(err) => {
+                self.conflicts.push(invalid(err.to_string()));
+                return;
+            }
+        }
+
+        for field in patch.fields.keys() {
+            self.claim(mod_id, patch.kind, &patch.id, Some(field.clone()));
+        }
+    }
+
+    fn remove<T: Entry>(
+        &mut self,
+        mod_id: &ArcStr,
+        removal: Removal,
+        list: impl Fn(&mut Definition) -> &mut Vec<T>,
+    ) {
+        let entries = list(&mut self.def);
+        let before = entries.len();
+        entries.retain(|entry| *entry.key() != removal.id);
+        if entries.len() == before {
+            self.conflicts.push(Conflict::MissingKey {
+                mod_id: mod_id.clone(),
+                kind: removal.kind,
+                key: removal.id,
+            });
+            return;
+        }
+
+        let patchers: Vec<_> = self
+            .owners
+            .range((removal.kind, removal.id.clone(), None)..)
+            .take_while(|((kind, key, _), _)| *kind == removal.kind && *key == removal.id)
+            .map(|((_, _, field), _)| field.clone())
+            .collect();
+        for field in patchers {
+            self.claim(mod_id, removal.kind, &removal.id, field);
+        }
+        self.claim(mod_id, removal.kind, &removal.id, None);
+    }
+}
+
+/// Merges `patch` into `value`.
+///
+/// Maps are merged recursively; other values are replaced.
+fn merge(value: &mut serde_json::Value, patch: serde_json::Value) {
+    match (value, patch) {
+        (serde_json::Value::Object(value), serde_json::Value::Object(patch)) => {
+            for (field, patch_value) in patch {
+                match value.get_mut(&field) {
+                    Some(original) => merge(original, patch_value),
+                    None => {
+                        value.insert(field, patch_value);
+                    }
+                }
+            }
+        }
+        (value, patch) => *value = patch,
+    }
+}
+
+/// Sorts the layers in load order.
+///
+/// Returns the sorted layers and the transitive dependencies of each mod.
+fn load_order(
+    base: &ModInfo,
+    layers: Vec<Layer>,
+    conflicts: &mut Vec<Conflict>,
+) -> (Vec<Layer>, BTreeMap<ArcStr, BTreeSet<ArcStr>>) {
+    let mut pending = BTreeMap::new();
+    for layer in layers {
+        let id = layer.info.id.clone();
+        if id == base.id || pending.contains_key(&id) {
+            conflicts.push(Conflict::DuplicateMod(id));
+            continue;
+        }
+        pending.insert(id, layer);
+    }
+
+    let mut deps = BTreeMap::new();
+    deps.insert(base.id.clone(), BTreeSet::new());
+
+    for layer in pending.values() {
+        for dep in &layer.info.after {
+            if *dep != base.id && !pending.contains_key(dep) {
+                conflicts.push(Conflict::MissingDependency {
+                    mod_id: layer.info.id.clone(),
+                    dependency: dep.clone(),
+                });
+            }
+        }
+    }
+
+    let mut sorted = Vec::new();
+    loop {
+        let ready = pending
+            .iter()
+            .find(|(_, layer)| {
+                layer
+                    .info
+                    .after
+                    .iter()
+                    .all(|dep| deps.contains_key(dep) || !pending.contains_key(dep))
+            })
+            .map(|(id, _)| id.clone());
+        let id = match ready {
+            Some(id) => id,
+            None => break,
+        };
+        let layer = pending.remove(&id).expect("id was found in pending");
+
+        let mut closure = BTreeSet::new();
+        for dep in &layer.info.after {
+            if let Some(dep_closure) = deps.get(dep) {
+                closure.insert(dep.clone());
+                closure.extend(dep_closure.iter().cloned());
+            }
+        }
+        deps.insert(id, closure);
+        sorted.push(layer);
+    }
+
+    if !pending.is_empty() {
+        conflicts.push(Conflict::DependencyCycle(pending.into_keys().collect()));
+    }
+
+    (sorted, deps)
+}
+
+impl GameDefinition {
+    /// Stacks mod layers on top of this definition.
+    ///
+    /// `base` identifies this definition in the returned manifest.
+    /// All conflicts are returned if any layer cannot be applied cleanly.
+    pub fn layered(
+        &self,
+        base: ModInfo,
+        layers: Vec<Layer>,
+    ) -> Result<(GameDefinition, Manifest), Vec<Conflict>> {
+        let mut conflicts = Vec::new();
+        let (layers, deps) = load_order(&base, layers, &mut conflicts);
+
+        let mut stack = Stack {
+            def: Definition::export(self),
+            base: base.id.clone(),
+            deps,
+            owners: BTreeMap::new(),
+            conflicts,
+        };
+        let mut mods = vec![base];
+
+        for layer in layers {
+            let mod_id = layer.info.id.clone();
+            stack.add_all(&mod_id, layer.add);
+            for patch in layer.patch {
+                stack.apply_patch(&mod_id, patch);
+            }
+            for removal in layer.remove {
+                stack.apply_removal(&mod_id, removal);
+            }
+            mods.push(layer.info);
+        }
+
+        let mut conflicts = stack.conflicts;
+        match stack.def.resolve() {
+            Ok(def) if conflicts.is_empty() => Ok((def, Manifest { mods })),
+            Ok(_) => Err(conflicts),
+            Err(err) => {
+                conflicts.push(Conflict::Resolve(err));
+                Err(conflicts)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arcstr::literal;
+
+    use super::{Conflict, Kind, Layer, ModInfo};
+    use crate::def::schema::Format;
+    use crate::def::{skill, GameDefinition};
+
+    fn base() -> GameDefinition {
+        let mut def = GameDefinition::default();
+        for &(key, name) in &[("base:driving", "Driving"), ("base:cooking", "Cooking")] {
+            def.add_skill(
+                skill::Type::builder()
+                    .id(key.into())
+                    .name(name.into())
+                    .description(literal!(""))
+                    .build(),
+            );
+        }
+        def
+    }
+
+    fn base_info() -> ModInfo {
+        ModInfo {
+            id: literal!("base"),
+            version: literal!("1.0.0"),
+            after: Vec::new(),
+        }
+    }
+
+    fn layer(toml: &str) -> Layer {
+        Format::Toml.parse(toml).expect("Invalid test layer")
+    }
+
+    #[test]
+    fn stack() {
+        let rename = layer(
+            r#"
+            [mod]
+            id = "rename"
+            version = "0.1.0"
+
+            [[patch]]
+            kind = "skill"
+            id = "base:driving"
+            name = "Piloting"
+            "#,
+        );
+        let extend = layer(
+            r#"
+            [mod]
+            id = "extend"
+            version = "0.2.0"
+            after = ["rename"]
+
+            [[add.skill]]
+            id = "extend:farming"
+            name = "Farming"
+            description = ""
+
+            [[patch]]
+            kind = "skill"
+            id = "base:driving"
+            name = "Flying"
+
+            [[remove]]
+            kind = "skill"
+            id = "base:cooking"
+            "#,
+        );
+
+        let (def, manifest) = base()
+            .layered(base_info(), vec![extend, rename])
+            .expect("Layers should not conflict");
+        let ids: Vec<_> = manifest
+            .mods()
+            .iter()
+            .map(|info| info.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["base", "rename", "extend"]);
+
+        let names: Vec<_> = def
+            .skill()
+            .iter()
+            .map(|skill| skill.name().as_str())
+            .collect();
+        assert_eq!(names, vec!["Flying", "Farming"]);
+        assert!(def.find_skill("base:cooking").is_none());
+    }
+
+    #[test]
+    fn overlap() {
+        let patch = |id: &str, name: &str| {
+            layer(&format!(
+                r#"
+                [mod]
+                id = "{}"
+                version = "1.0.0"
+
+                [[patch]]
+                kind = "skill"
+                id = "base:driving"
+                name = "{}"
+                "#,
+                id, name
+            ))
+        };
+
+        let conflicts = match base().layered(base_info(), vec![patch("b", "B"), patch("a", "A")]) {
+            Ok(_) => panic!("Independent patches to the same field should conflict"),
+            Err(conflicts) => conflicts,
+        };
+        assert_eq!(
+            conflicts,
+            vec![Conflict::Overlap {
+                first: literal!("a"),
+                second: literal!("b"),
+                kind: Kind::Skill,
+                key: literal!("base:driving"),
+                field: Some(String::from("name")),
+            }]
+        );
+    }
+}
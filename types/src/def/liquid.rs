@@ -1,17 +1,21 @@
 //! Liquid definitions.
 
 use arcstr::ArcStr;
+use serde::{Deserialize, Serialize};
 use typed_builder::TypedBuilder;
 
 use crate::units;
 
 /// Identifies a liquid category
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct TypeId(pub usize);
 
 /// A type of liquid.
-#[derive(Clone, TypedBuilder, getset::Getters)]
+#[derive(Clone, TypedBuilder, getset::CopyGetters, getset::Getters, Serialize, Deserialize)]
 pub struct Type {
+    /// String identifier of the liquid type.
+    #[getset(get = "pub")]
+    id: ArcStr,
     /// Name of the liquid type.
     #[getset(get = "pub")]
     name: ArcStr,
@@ -27,4 +31,11 @@ pub struct Type {
     /// Name of the texture.
     #[getset(get = "pub")]
     texture: ArcStr,
+    /// Whether the liquid type is a waste product.
+    ///
+    /// Nodes with full waste storage penalize their inhabitants.
+    #[getset(get_copy = "pub")]
+    #[builder(default)]
+    #[serde(default)]
+    waste: bool,
 }
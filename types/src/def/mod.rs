@@ -1,102 +1,275 @@
 //! Defines the mechanism of a game.
+//!
+//! Each type in the definition has a positional ID, which is only valid within the same
+//! definition, and a stable string key in the form `namespace:name` (e.g. `vanilla:core`).
+//! Use the string key to refer to types across different processes.
+//! Adding two types of the same kind with the same key is a programming error.
 
+use std::collections::{btree_map, BTreeMap};
+
+use arcstr::ArcStr;
+
+pub mod bottle;
 pub mod building;
 pub mod cargo;
 pub mod crime;
 pub mod gas;
+#[cfg(feature = "schema")]
+pub mod layer;
 pub mod liquid;
+pub mod orbit;
 pub mod reaction;
+pub mod schema;
 pub mod skill;
+pub mod tech;
+pub mod validate;
 pub mod vehicle;
 
 /// Game mechanism definition.
-#[derive(Default, getset::Getters, getset::MutGetters)]
+#[derive(Default, getset::Getters)]
 pub struct GameDefinition {
     /// Cargo types.
-    #[getset(get = "pub", get_mut = "pub")]
+    #[getset(get = "pub")]
     cargo: Vec<cargo::Type>,
     /// Cargo categories.
-    #[getset(get = "pub", get_mut = "pub")]
+    #[getset(get = "pub")]
     cargo_cats: Vec<cargo::Category>,
     /// Liquid types.
-    #[getset(get = "pub", get_mut = "pub")]
+    #[getset(get = "pub")]
     liquid: Vec<liquid::Type>,
     /// Gas types.
-    #[getset(get = "pub", get_mut = "pub")]
+    #[getset(get = "pub")]
     gas: Vec<gas::Type>,
     /// Skill types.
-    #[getset(get = "pub", get_mut = "pub")]
+    #[getset(get = "pub")]
     skill: Vec<skill::Type>,
     /// Vehicle types.
-    #[getset(get = "pub", get_mut = "pub")]
+    #[getset(get = "pub")]
     vehicle: Vec<vehicle::Type>,
     /// Reaction types.
-    #[getset(get = "pub", get_mut = "pub")]
+    #[getset(get = "pub")]
     reaction: Vec<reaction::Type>,
     /// Reaction categories.
-    #[getset(get = "pub", get_mut = "pub")]
+    #[getset(get = "pub")]
     reaction_cats: Vec<reaction::Category>,
     /// Building types.
-    #[getset(get = "pub", get_mut = "pub")]
+    #[getset(get = "pub")]
     building: Vec<building::Type>,
     /// Building categories.
-    #[getset(get = "pub", get_mut = "pub")]
+    #[getset(get = "pub")]
     building_cats: Vec<building::Category>,
     /// List of possible crimes.
-    #[getset(get = "pub", get_mut = "pub")]
+    #[getset(get = "pub")]
     crime: Vec<crime::Type>,
+    /// Technologies.
+    #[getset(get = "pub")]
+    tech: Vec<tech::Type>,
+    /// The orbit of the sun.
+    #[getset(get = "pub")]
+    orbit: orbit::Orbit,
+    /// Positional IDs indexed by string keys.
+    keys: Keys,
+}
+
+/// Positional IDs of each kind of type indexed by string keys.
+#[derive(Default)]
+struct Keys {
+    cargo: BTreeMap<ArcStr, usize>,
+    cargo_cats: BTreeMap<ArcStr, usize>,
+    liquid: BTreeMap<ArcStr, usize>,
+    gas: BTreeMap<ArcStr, usize>,
+    skill: BTreeMap<ArcStr, usize>,
+    vehicle: BTreeMap<ArcStr, usize>,
+    reaction: BTreeMap<ArcStr, usize>,
+    reaction_cats: BTreeMap<ArcStr, usize>,
+    building: BTreeMap<ArcStr, usize>,
+    building_cats: BTreeMap<ArcStr, usize>,
+    crime: BTreeMap<ArcStr, usize>,
+    tech: BTreeMap<ArcStr, usize>,
+}
+
+/// Indexes the next pushed type by its key.
+///
+/// # Panics
+/// Panics if the key is already used by another type of the same kind.
+fn index_next(keys: &mut BTreeMap<ArcStr, usize>, kind: &str, key: &ArcStr, id: usize) -> usize {
+    match keys.entry(key.clone()) {
+        btree_map::Entry::Vacant(entry) => {
+            entry.insert(id);
+        }
+        btree_map::Entry::Occupied(_) => panic!("Duplicate {} key {:?}", kind, key),
+    }
+    id
 }
 
 impl GameDefinition {
     /// Adds a cargo category.
+    ///
+    /// # Panics
+    /// Panics if the key is already used by another cargo category.
     pub fn add_cargo_category(&mut self, cat: cargo::Category) -> cargo::CategoryId {
+        let id = index_next(
+            &mut self.keys.cargo_cats,
+            "cargo category",
+            cat.id(),
+            self.cargo_cats.len(),
+        );
         self.cargo_cats.push(cat);
-        cargo::CategoryId(self.cargo_cats.len() - 1)
+        cargo::CategoryId(id)
     }
     /// Adds a cargo type.
+    ///
+    /// # Panics
+    /// Panics if the key is already used by another cargo type.
     pub fn add_cargo(&mut self, cargo: cargo::Type) -> cargo::TypeId {
+        let id = index_next(
+            &mut self.keys.cargo,
+            "cargo type",
+            cargo.id(),
+            self.cargo.len(),
+        );
         self.cargo.push(cargo);
-        cargo::TypeId(self.cargo.len() - 1)
+        cargo::TypeId(id)
     }
     /// Adds a liquid type.
+    ///
+    /// # Panics
+    /// Panics if the key is already used by another liquid type.
     pub fn add_liquid(&mut self, liquid: liquid::Type) -> liquid::TypeId {
+        let id = index_next(
+            &mut self.keys.liquid,
+            "liquid type",
+            liquid.id(),
+            self.liquid.len(),
+        );
         self.liquid.push(liquid);
-        liquid::TypeId(self.liquid.len() - 1)
+        liquid::TypeId(id)
     }
     /// Adds a gas type.
+    ///
+    /// # Panics
+    /// Panics if the key is already used by another gas type.
     pub fn add_gas(&mut self, gas: gas::Type) -> gas::TypeId {
+        let id = index_next(&mut self.keys.gas, "gas type", gas.id(), self.gas.len());
         self.gas.push(gas);
-        gas::TypeId(self.gas.len() - 1)
+        gas::TypeId(id)
     }
     /// Adds a skill type.
+    ///
+    /// # Panics
+    /// Panics if the key is already used by another skill type.
     pub fn add_skill(&mut self, skill: skill::Type) -> skill::TypeId {
+        let id = index_next(
+            &mut self.keys.skill,
+            "skill type",
+            skill.id(),
+            self.skill.len(),
+        );
         self.skill.push(skill);
-        skill::TypeId(self.skill.len() - 1)
+        skill::TypeId(id)
     }
     /// Adds a vehicle type.
+    ///
+    /// # Panics
+    /// Panics if the key is already used by another vehicle type.
     pub fn add_vehicle(&mut self, vehicle: vehicle::Type) -> vehicle::TypeId {
+        let id = index_next(
+            &mut self.keys.vehicle,
+            "vehicle type",
+            vehicle.id(),
+            self.vehicle.len(),
+        );
         self.vehicle.push(vehicle);
-        vehicle::TypeId(self.vehicle.len() - 1)
+        vehicle::TypeId(id)
     }
     /// Adds a reaction category.
+    ///
+    /// # Panics
+    /// Panics if the key is already used by another reaction category.
     pub fn add_reaction_category(&mut self, cat: reaction::Category) -> reaction::CategoryId {
+        let id = index_next(
+            &mut self.keys.reaction_cats,
+            "reaction category",
+            cat.id(),
+            self.reaction_cats.len(),
+        );
         self.reaction_cats.push(cat);
-        reaction::CategoryId(self.reaction_cats.len() - 1)
+        reaction::CategoryId(id)
     }
     /// Adds a reaction type.
+    ///
+    /// # Panics
+    /// Panics if the key is already used by another reaction type.
     pub fn add_reaction(&mut self, reaction: reaction::Type) -> reaction::TypeId {
+        let id = index_next(
+            &mut self.keys.reaction,
+            "reaction type",
+            reaction.id(),
+            self.reaction.len(),
+        );
         self.reaction.push(reaction);
-        reaction::TypeId(self.reaction.len() - 1)
+        reaction::TypeId(id)
     }
     /// Adds a building category.
+    ///
+    /// # Panics
+    /// Panics if the key is already used by another building category.
     pub fn add_building_category(&mut self, cat: building::Category) -> building::CategoryId {
+        let id = index_next(
+            &mut self.keys.building_cats,
+            "building category",
+            cat.id(),
+            self.building_cats.len(),
+        );
         self.building_cats.push(cat);
-        building::CategoryId(self.building_cats.len() - 1)
+        building::CategoryId(id)
     }
     /// Adds a building type.
+    ///
+    /// # Panics
+    /// Panics if the key is already used by another building type.
     pub fn add_building(&mut self, building: building::Type) -> building::TypeId {
+        let id = index_next(
+            &mut self.keys.building,
+            "building type",
+            building.id(),
+            self.building.len(),
+        );
         self.building.push(building);
-        building::TypeId(self.building.len() - 1)
+        building::TypeId(id)
+    }
+    /// Adds a crime type.
+    ///
+    /// # Panics
+    /// Panics if the key is already used by another crime type.
+    pub fn add_crime(&mut self, crime: crime::Type) -> crime::TypeId {
+        let id = index_next(
+            &mut self.keys.crime,
+            "crime type",
+            crime.id(),
+            self.crime.len(),
+        );
+        self.crime.push(crime);
+        crime::TypeId(id)
+    }
+    /// Adds a technology.
+    ///
+    /// # Panics
+    /// Panics if the key is already used by another technology.
+    pub fn add_tech(&mut self, tech: tech::Type) -> tech::TypeId {
+        let id = index_next(
+            &mut self.keys.tech,
+            "technology",
+            tech.id(),
+            self.tech.len(),
+        );
+        self.tech.push(tech);
+        tech::TypeId(id)
+    }
+
+    /// Sets the orbit of the sun.
+    pub fn set_orbit(&mut self, orbit: orbit::Orbit) {
+        self.orbit = orbit;
     }
 
     /// Returns the cargo with the specified ID
@@ -106,6 +279,15 @@ impl GameDefinition {
     pub fn get_cargo(&self, id: cargo::TypeId) -> &cargo::Type {
         self.cargo.get(id.0).expect("Cargo ID is out of bounds")
     }
+    /// Returns the cargo category with the specified ID
+    ///
+    /// # Panics
+    /// Panics if the category ID is undefined
+    pub fn get_cargo_category(&self, id: cargo::CategoryId) -> &cargo::Category {
+        self.cargo_cats
+            .get(id.0)
+            .expect("Cargo category ID is out of bounds")
+    }
     /// Returns the liquid with the specified ID
     ///
     /// # Panics
@@ -127,6 +309,13 @@ impl GameDefinition {
     pub fn get_skill(&self, id: skill::TypeId) -> &skill::Type {
         self.skill.get(id.0).expect("Skill ID is out of bounds")
     }
+    /// Returns the vehicle with the specified ID
+    ///
+    /// # Panics
+    /// Panics if the type ID is undefined
+    pub fn get_vehicle(&self, id: vehicle::TypeId) -> &vehicle::Type {
+        self.vehicle.get(id.0).expect("Vehicle ID is out of bounds")
+    }
     /// Returns the reaction with the specified ID
     ///
     /// # Panics
@@ -136,6 +325,15 @@ impl GameDefinition {
             .get(id.0)
             .expect("Reaction ID is out of bounds")
     }
+    /// Returns the reaction category with the specified ID
+    ///
+    /// # Panics
+    /// Panics if the category ID is undefined
+    pub fn get_reaction_category(&self, id: reaction::CategoryId) -> &reaction::Category {
+        self.reaction_cats
+            .get(id.0)
+            .expect("Reaction category ID is out of bounds")
+    }
     /// Returns the building with the specified ID
     ///
     /// # Panics
@@ -145,4 +343,128 @@ impl GameDefinition {
             .get(id.0)
             .expect("Building ID is out of bounds")
     }
+    /// Returns the building category with the specified ID
+    ///
+    /// # Panics
+    /// Panics if the category ID is undefined
+    pub fn get_building_category(&self, id: building::CategoryId) -> &building::Category {
+        self.building_cats
+            .get(id.0)
+            .expect("Building category ID is out of bounds")
+    }
+    /// Returns the crime with the specified ID
+    ///
+    /// # Panics
+    /// Panics if the type ID is undefined
+    pub fn get_crime(&self, id: crime::TypeId) -> &crime::Type {
+        self.crime.get(id.0).expect("Crime ID is out of bounds")
+    }
+    /// Returns the technology with the specified ID
+    ///
+    /// # Panics
+    /// Panics if the type ID is undefined
+    pub fn get_tech(&self, id: tech::TypeId) -> &tech::Type {
+        self.tech.get(id.0).expect("Tech ID is out of bounds")
+    }
+
+    /// Returns the ID of the cargo type with the specified key
+    pub fn find_cargo(&self, key: &str) -> Option<cargo::TypeId> {
+        self.keys.cargo.get(key).copied().map(cargo::TypeId)
+    }
+    /// Returns the ID of the cargo category with the specified key
+    pub fn find_cargo_category(&self, key: &str) -> Option<cargo::CategoryId> {
+        self.keys
+            .cargo_cats
+            .get(key)
+            .copied()
+            .map(cargo::CategoryId)
+    }
+    /// Returns the ID of the liquid type with the specified key
+    pub fn find_liquid(&self, key: &str) -> Option<liquid::TypeId> {
+        self.keys.liquid.get(key).copied().map(liquid::TypeId)
+    }
+    /// Returns the ID of the gas type with the specified key
+    pub fn find_gas(&self, key: &str) -> Option<gas::TypeId> {
+        self.keys.gas.get(key).copied().map(gas::TypeId)
+    }
+    /// Returns the ID of the skill type with the specified key
+    pub fn find_skill(&self, key: &str) -> Option<skill::TypeId> {
+        self.keys.skill.get(key).copied().map(skill::TypeId)
+    }
+    /// Returns the ID of the first skill type of the specified kind
+    pub fn find_skill_kind(&self, kind: skill::Kind) -> Option<skill::TypeId> {
+        self.skill
+            .iter()
+            .position(|skill| skill.kind() == kind)
+            .map(skill::TypeId)
+    }
+    /// Returns the ID of the vehicle type with the specified key
+    pub fn find_vehicle(&self, key: &str) -> Option<vehicle::TypeId> {
+        self.keys.vehicle.get(key).copied().map(vehicle::TypeId)
+    }
+    /// Returns the ID of the reaction type with the specified key
+    pub fn find_reaction(&self, key: &str) -> Option<reaction::TypeId> {
+        self.keys.reaction.get(key).copied().map(reaction::TypeId)
+    }
+    /// Returns the ID of the reaction category with the specified key
+    pub fn find_reaction_category(&self, key: &str) -> Option<reaction::CategoryId> {
+        self.keys
+            .reaction_cats
+            .get(key)
+            .copied()
+            .map(reaction::CategoryId)
+    }
+    /// Returns the ID of the building type with the specified key
+    pub fn find_building(&self, key: &str) -> Option<building::TypeId> {
+        self.keys.building.get(key).copied().map(building::TypeId)
+    }
+    /// Returns the ID of the building category with the specified key
+    pub fn find_building_category(&self, key: &str) -> Option<building::CategoryId> {
+        self.keys
+            .building_cats
+            .get(key)
+            .copied()
+            .map(building::CategoryId)
+    }
+    /// Returns the ID of the crime type with the specified key
+    pub fn find_crime(&self, key: &str) -> Option<crime::TypeId> {
+        self.keys.crime.get(key).copied().map(crime::TypeId)
+    }
+    /// Returns the ID of the technology with the specified key
+    pub fn find_tech(&self, key: &str) -> Option<tech::TypeId> {
+        self.keys.tech.get(key).copied().map(tech::TypeId)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arcstr::{literal, ArcStr};
+
+    use super::{skill, GameDefinition};
+
+    fn skill(key: &'static str) -> skill::Type {
+        skill::Type::builder()
+            .id(ArcStr::from(key))
+            .name(literal!(""))
+            .description(literal!(""))
+            .build()
+    }
+
+    #[test]
+    fn find_by_key() {
+        let mut def = GameDefinition::default();
+        let a = def.add_skill(skill("test:a"));
+        let b = def.add_skill(skill("test:b"));
+        assert_eq!(def.find_skill("test:a"), Some(a));
+        assert_eq!(def.find_skill("test:b"), Some(b));
+        assert_eq!(def.find_skill("test:c"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Duplicate skill type key")]
+    fn duplicate_key() {
+        let mut def = GameDefinition::default();
+        def.add_skill(skill("test:a"));
+        def.add_skill(skill("test:a"));
+    }
 }
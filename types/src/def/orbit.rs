@@ -0,0 +1,34 @@
+//! Orbit definitions.
+
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+use crate::time::Time;
+
+/// The orbit of the sun around the colony.
+#[derive(Debug, Clone, TypedBuilder, getset::CopyGetters, Serialize, Deserialize)]
+pub struct Orbit {
+    /// The time for the sun to complete one orbit.
+    #[getset(get_copy = "pub")]
+    year_length: Time,
+    /// The number of partitions of a year to compute shadow casting for.
+    ///
+    /// Must be positive.
+    #[getset(get_copy = "pub")]
+    month_count: usize,
+    /// The inclination of the orbital plane of the sun from the XY plane, in radians.
+    #[getset(get_copy = "pub")]
+    axial_tilt: f64,
+}
+
+impl Default for Orbit {
+    fn default() -> Self {
+        Self {
+            year_length: Time(30000), // 5 minutes = 1 year
+            month_count: 12,
+            axial_tilt: PI / 8.,
+        }
+    }
+}
@@ -3,6 +3,7 @@
 use std::ops::Range;
 
 use arcstr::ArcStr;
+use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
 use typed_builder::TypedBuilder;
 
@@ -11,12 +12,15 @@ use crate::time::Rate;
 use crate::units;
 
 /// Identifies a reaction category
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct TypeId(pub usize);
 
 /// A type of reaction.
 #[derive(TypedBuilder, getset::CopyGetters, getset::Getters)]
 pub struct Type {
+    /// String identifier of the reaction type.
+    #[getset(get = "pub")]
+    id: ArcStr,
     /// Name of the reaction type.
     #[getset(get = "pub")]
     name: ArcStr,
@@ -79,6 +83,11 @@ pub enum CatalystRange {
         /// Min and max levels of light catalyst
         levels: Range<units::Brightness>,
     },
+    /// Temperature of the building
+    Heat {
+        /// Min and max temperature of heat catalyst
+        levels: Range<units::Temperature>,
+    },
     /// Existence of skilled operators
     ///
     /// Only the most skilled operator is counted as a catalyst.
@@ -91,7 +100,7 @@ pub enum CatalystRange {
 }
 
 /// The multipliers associated with a catalyst.
-#[derive(Clone, Copy, TypedBuilder, getset::CopyGetters)]
+#[derive(Clone, Copy, TypedBuilder, getset::CopyGetters, Serialize, Deserialize)]
 pub struct Multipliers {
     /// Multiplier to the reaction rate when the catalyst is in deficiency.
     #[getset(get_copy = "pub")]
@@ -107,6 +116,57 @@ pub struct Multipliers {
     overflow: f64,
 }
 
+impl Multipliers {
+    /// Computes the multiplier for a catalyst level.
+    ///
+    /// Levels within `levels` are linearly interpolated between `min` and `max`.
+    pub fn get<U: units::Unit>(self, levels: &Range<U>, level: U) -> f64 {
+        match Zone::of(levels, level) {
+            Zone::Underflow => self.underflow,
+            Zone::Min => self.min,
+            Zone::Lerp => {
+                let (start, end) = (levels.start.value(), levels.end.value());
+                self.min + (self.max - self.min) * (level.value() - start) / (end - start)
+            }
+            Zone::Max => self.max,
+            Zone::Overflow => self.overflow,
+        }
+    }
+}
+
+/// The position of a catalyst level relative to its lerp endpoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Zone {
+    /// The level is below the min endpoint.
+    Underflow,
+    /// The level is exactly at the min endpoint.
+    Min,
+    /// The level is strictly between the endpoints.
+    Lerp,
+    /// The level is exactly at the max endpoint.
+    Max,
+    /// The level is above the max endpoint.
+    Overflow,
+}
+
+impl Zone {
+    /// Classifies a catalyst level.
+    pub fn of<U: units::Unit>(levels: &Range<U>, level: U) -> Self {
+        let (start, end, level) = (levels.start.value(), levels.end.value(), level.value());
+        if level < start {
+            Self::Underflow
+        } else if level > end {
+            Self::Overflow
+        } else if level >= end {
+            Self::Max
+        } else if level <= start {
+            Self::Min
+        } else {
+            Self::Lerp
+        }
+    }
+}
+
 /// The inputs and outputs of a reaction.
 pub enum Put {
     /// Consumption or production of cargo
@@ -135,6 +195,11 @@ pub enum Put {
         /// Base (unmultiplied) rate of electricity consumed/generated
         base: Rate<units::ElectricPower>,
     },
+    /// Emission or absorption of heat
+    Heat {
+        /// Base (unmultiplied) rate of heat absorbed/emitted
+        base: Rate<units::Heat>,
+    },
     /// Change in happiness
     Happiness {
         /// Base (unmultiplied) rate of happiness change
@@ -150,6 +215,11 @@ pub enum Put {
         /// Base (unmultiplied) rate of gas consumed/produced
         base: Rate<units::Skill>,
     },
+    /// Production of research points
+    Research {
+        /// Base (unmultiplied) rate of research points consumed/produced
+        base: Rate<units::ResearchPoint>,
+    },
 }
 
 impl Put {
@@ -160,8 +230,10 @@ impl Put {
             Self::Liquid { base, .. } => base.0.value(),
             Self::Gas { base, .. } => base.0.value(),
             Self::Electricity { base, .. } => base.0.value(),
+            Self::Heat { base } => base.0.value(),
             Self::Happiness { base, .. } => base.0.value(),
             Self::Skill { base, .. } => base.0.value(),
+            Self::Research { base } => base.0.value(),
         }
     }
 
@@ -177,12 +249,15 @@ impl Put {
 }
 
 /// Identifies a reaction category
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct CategoryId(pub usize);
 
 /// A category of reaction.
-#[derive(TypedBuilder, getset::Getters)]
+#[derive(Clone, TypedBuilder, getset::Getters, Serialize, Deserialize)]
 pub struct Category {
+    /// String identifier of the reaction category.
+    #[getset(get = "pub")]
+    id: ArcStr,
     /// Title of the reaction category.
     #[getset(get = "pub")]
     title: ArcStr,
@@ -0,0 +1,1213 @@
+//! File format of game definitions.
+//!
+//! A [`Definition`] is the serializable counterpart of a [`GameDefinition`].
+//! Instead of positional type IDs, types refer to each other using their string identifiers,
+//! so that definitions can be written by hand in TOML, YAML or JSON.
+//!
+//! Parsing and writing files in these formats with `Format` requires the `schema` feature.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+
+use arcstr::ArcStr;
+#[cfg(feature = "schema")]
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    building, cargo, crime, gas, liquid, orbit, reaction, skill, tech, vehicle, GameDefinition,
+};
+use crate::space::Matrix;
+use crate::time::{Rate, Time};
+use crate::units;
+
+/// A complete game definition in the file format.
+///
+/// The order of entries in each list determines the order of the loaded types.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Definition {
+    /// Cargo categories.
+    pub cargo_category: Vec<cargo::Category>,
+    /// Cargo types.
+    pub cargo: Vec<Cargo>,
+    /// Liquid types.
+    pub liquid: Vec<liquid::Type>,
+    /// Gas types.
+    pub gas: Vec<gas::Type>,
+    /// Skill types.
+    pub skill: Vec<skill::Type>,
+    /// Vehicle types.
+    pub vehicle: Vec<Vehicle>,
+    /// Reaction categories.
+    pub reaction_category: Vec<reaction::Category>,
+    /// Reaction types.
+    pub reaction: Vec<Reaction>,
+    /// Building categories.
+    pub building_category: Vec<building::Category>,
+    /// Building types.
+    pub building: Vec<Building>,
+    /// Crime types.
+    pub crime: Vec<Crime>,
+    /// Technologies.
+    pub tech: Vec<Tech>,
+    /// The orbit of the sun.
+    pub orbit: orbit::Orbit,
+}
+
+/// File format of [`cargo::Type`].
+#[derive(Serialize, Deserialize)]
+pub struct Cargo {
+    /// String identifier of the cargo type.
+    pub id: ArcStr,
+    /// Name of the cargo type.
+    pub name: ArcStr,
+    /// Short summary of the cargo type.
+    pub summary: ArcStr,
+    /// Long description of the cargo type.
+    pub description: ArcStr,
+    /// Identifier of the cargo category.
+    pub category: ArcStr,
+    /// Name of the texture.
+    pub texture: ArcStr,
+    /// Whether the cargo type is a waste product.
+    #[serde(default)]
+    pub waste: bool,
+    /// The fluid stored in the cargo type if it is a bottle.
+    #[serde(default)]
+    pub bottle: Option<Bottle>,
+}
+
+/// File format of [`cargo::Bottle`].
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[allow(missing_docs)]
+pub enum Bottle {
+    Liquid {
+        ty: ArcStr,
+        volume: units::LiquidVolume,
+    },
+    Gas {
+        ty: ArcStr,
+        volume: units::GasVolume,
+    },
+}
+
+/// File format of [`vehicle::Type`].
+#[derive(Serialize, Deserialize)]
+pub struct Vehicle {
+    /// String identifier of the vehicle type.
+    pub id: ArcStr,
+    /// Name of the vehicle type.
+    pub name: ArcStr,
+    /// Long description of the vehicle type.
+    pub description: ArcStr,
+    /// Base speed of the vehicle.
+    pub speed: units::VehicleSpeed,
+    /// The amount of cargo that the vehicle can carry.
+    pub capacity: units::CargoSize,
+    /// The number of non-driver inhabitants carried by the vehicle.
+    pub passengers: u32,
+    /// The skill required to operate this vehicle.
+    pub skill: VehicleSkill,
+    /// Name of the texture.
+    pub texture: ArcStr,
+}
+
+/// File format of [`vehicle::Skill`].
+#[derive(Serialize, Deserialize)]
+pub struct VehicleSkill {
+    /// Identifier of the skill type.
+    pub skill: ArcStr,
+    /// The skill level range of varying speed multipliers.
+    pub levels: Range<units::Skill>,
+    /// The multipliers applied on the driving speed.
+    pub multipliers: reaction::Multipliers,
+}
+
+/// File format of [`reaction::Type`].
+#[derive(Serialize, Deserialize)]
+pub struct Reaction {
+    /// String identifier of the reaction type.
+    pub id: ArcStr,
+    /// Name of the reaction type.
+    pub name: ArcStr,
+    /// Description of the reaction type.
+    pub description: ArcStr,
+    /// Identifier of the reaction category.
+    pub category: ArcStr,
+    /// Catalysts for the reaction.
+    #[serde(default)]
+    pub catalysts: Vec<Catalyst>,
+    /// Inputs and outputs for the reaction.
+    #[serde(default)]
+    pub puts: Vec<Put>,
+}
+
+/// File format of [`reaction::Catalyst`].
+#[derive(Serialize, Deserialize)]
+pub struct Catalyst {
+    /// The lerp endpoints of the catalyst.
+    pub range: CatalystRange,
+    /// The multipliers associated with the catalyst.
+    pub multipliers: reaction::Multipliers,
+}
+
+/// File format of [`reaction::CatalystRange`].
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[allow(missing_docs)]
+pub enum CatalystRange {
+    Cargo {
+        ty: ArcStr,
+        levels: Range<units::CargoSize>,
+    },
+    Liquid {
+        ty: ArcStr,
+        levels: Range<units::LiquidVolume>,
+    },
+    Gas {
+        ty: ArcStr,
+        levels: Range<units::GasVolume>,
+    },
+    Electricity {
+        levels: Range<units::ElectricPower>,
+    },
+    Light {
+        levels: Range<units::Brightness>,
+    },
+    Heat {
+        levels: Range<units::Temperature>,
+    },
+    Skill {
+        ty: ArcStr,
+        levels: Range<units::Skill>,
+    },
+}
+
+/// File format of [`reaction::Put`].
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[allow(missing_docs)]
+pub enum Put {
+    Cargo {
+        ty: ArcStr,
+        base: Rate<units::CargoSize>,
+    },
+    Liquid {
+        ty: ArcStr,
+        base: Rate<units::LiquidVolume>,
+    },
+    Gas {
+        ty: ArcStr,
+        base: Rate<units::GasVolume>,
+    },
+    Electricity {
+        base: Rate<units::ElectricPower>,
+    },
+    Heat {
+        base: Rate<units::Heat>,
+    },
+    Happiness {
+        base: Rate<units::Happiness>,
+    },
+    Skill {
+        ty: ArcStr,
+        base: Rate<units::Skill>,
+    },
+    Research {
+        base: Rate<units::ResearchPoint>,
+    },
+}
+
+/// File format of [`building::Type`].
+#[derive(Serialize, Deserialize)]
+pub struct Building {
+    /// String identifier of the building type.
+    pub id: ArcStr,
+    /// Name of the building type.
+    pub name: ArcStr,
+    /// Short summary of the building type.
+    pub summary: ArcStr,
+    /// Long description of the building type.
+    pub description: ArcStr,
+    /// Identifier of the building category.
+    pub category: ArcStr,
+    /// Shape of the building.
+    pub shape: Shape,
+    /// Reactions associated with the building.
+    #[serde(default)]
+    pub reactions: Vec<BuildingReaction>,
+    /// Maximum hitpoint of a building.
+    pub hitpoint: units::Hitpoint,
+    /// Storage provided by a building
+    pub storage: building::Storage,
+    /// Resources required to construct the building.
+    pub construction: Construction,
+    /// Extra features associated with the building.
+    #[serde(default)]
+    pub features: Vec<building::ExtraFeature>,
+}
+
+/// File format of [`building::Shape`].
+#[derive(Serialize, Deserialize)]
+pub struct Shape {
+    /// Rows of the transformation matrix from the unit cube to this shape.
+    pub transform: [[f64; 4]; 4],
+    /// The texture source path of the building.
+    pub texture_src: ArcStr,
+    /// The texture name of the building.
+    pub texture_name: ArcStr,
+}
+
+/// File format of [`building::Construction`].
+#[derive(Serialize, Deserialize)]
+pub struct Construction {
+    /// Cargo consumed by the construction site.
+    #[serde(default)]
+    pub cargo: Vec<ConstructionCargo>,
+    /// Electric energy consumed by the construction site.
+    #[serde(default)]
+    pub energy: units::ElectricEnergy,
+    /// Time to complete construction with a single worker at a multiplier of 1.
+    pub time: Time,
+    /// Identifier of the skill that speeds up construction.
+    pub skill: ArcStr,
+    /// The skill level range of varying work speed multipliers.
+    pub levels: Range<units::Skill>,
+    /// The multipliers applied on the work speed of each worker.
+    pub multipliers: reaction::Multipliers,
+}
+
+/// Cargo consumed by a construction site.
+#[derive(Serialize, Deserialize)]
+pub struct ConstructionCargo {
+    /// Identifier of the cargo type.
+    pub ty: ArcStr,
+    /// Amount of cargo consumed.
+    pub size: units::CargoSize,
+}
+
+/// A reaction associated with a building.
+#[derive(Serialize, Deserialize)]
+pub struct BuildingReaction {
+    /// Identifier of the reaction type.
+    pub reaction: ArcStr,
+    /// Reaction behaviour specific to this building.
+    #[serde(default)]
+    pub policy: building::ReactionPolicy,
+}
+
+/// File format of [`crime::Type`].
+#[derive(Serialize, Deserialize)]
+pub struct Crime {
+    /// String identifier of the crime type.
+    pub id: ArcStr,
+    /// Name of the crime.
+    pub name: ArcStr,
+    /// Description of the crime.
+    pub description: ArcStr,
+    /// The actual consequence of the crime.
+    pub action: CrimeAction,
+    /// The happiness range at which this crime may happen.
+    pub trigger_happiness_range: Range<units::Happiness>,
+    /// The base (unmultiplied) probability per second that an inhabitant starts to commit this
+    /// crime.
+    pub probability: f64,
+    /// The change in happiness after committing this crime.
+    pub happiness_change: units::Happiness,
+    /// The change in skill levels after committing this crime.
+    #[serde(default)]
+    pub skill_change: Vec<SkillChange>,
+}
+
+/// File format of [`crime::Action`].
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+#[allow(missing_docs)]
+pub enum CrimeAction {
+    InhabitantTheft(units::CargoSize),
+    VehicleTheft(units::CargoSize),
+    NodeTheft(units::CargoSize),
+    Antagonize(InhabitantCriterion),
+    Arson,
+    Murder(InhabitantCriterion),
+}
+
+/// File format of [`crime::InhabitantCriterion`].
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+#[allow(missing_docs)]
+pub enum InhabitantCriterion {
+    HighestSkill(ArcStr),
+}
+
+/// A change in the level of a skill.
+#[derive(Serialize, Deserialize)]
+pub struct SkillChange {
+    /// Identifier of the skill type.
+    pub skill: ArcStr,
+    /// The change in skill level.
+    pub change: units::Skill,
+}
+
+/// File format of [`tech::Type`].
+#[derive(Serialize, Deserialize)]
+pub struct Tech {
+    /// String identifier of the technology.
+    pub id: ArcStr,
+    /// Name of the technology.
+    pub name: ArcStr,
+    /// Description of the technology.
+    pub description: ArcStr,
+    /// Identifiers of technologies that must be researched first.
+    #[serde(default)]
+    pub prerequisites: Vec<ArcStr>,
+    /// The requirements to research this technology.
+    #[serde(default)]
+    pub costs: Vec<TechCost>,
+    /// The content unlocked by this technology.
+    #[serde(default)]
+    pub unlocks: Vec<TechUnlock>,
+}
+
+/// File format of [`tech::Cost`].
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[allow(missing_docs)]
+pub enum TechCost {
+    Cargo { ty: ArcStr, size: units::CargoSize },
+    Skill { ty: ArcStr, level: units::Skill },
+    Research { points: units::ResearchPoint },
+}
+
+/// File format of [`tech::Unlock`].
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+#[allow(missing_docs)]
+pub enum TechUnlock {
+    Building(ArcStr),
+    Reaction(ArcStr),
+    Vehicle(ArcStr),
+}
+
+/// A serialization format for definition files.
+#[cfg(feature = "schema")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Tom's Obvious Minimal Language
+    Toml,
+    /// YAML Ain't Markup Language
+    Yaml,
+    /// JavaScript Object Notation
+    Json,
+}
+
+#[cfg(feature = "schema")]
+impl Format {
+    /// Infers the format from a file extension.
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext {
+            "toml" => Some(Self::Toml),
+            "yml" | "yaml" => Some(Self::Yaml),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    /// Parses a definition or mod file.
+    pub fn parse<T: DeserializeOwned>(self, text: &str) -> Result<T, Error> {
+        Ok(match self {
+            Self::Toml => toml::from_str(text).map_err(|err| Error::Syntax(err.to_string()))?,
+            Self::Yaml => {
+                serde_yaml::from_str(text).map_err(|err| Error::Syntax(err.to_string()))?
+            }
+            Self::Json => {
+                serde_json::from_str(text).map_err(|err| Error::Syntax(err.to_string()))?
+            }
+        })
+    }
+
+    /// Writes a definition or mod file.
+    pub fn write<T: Serialize>(self, def: &T) -> Result<String, Error> {
+        Ok(match self {
+            Self::Toml => {
+                // Converting to a `toml::Value` first moves tables after plain values.
+                let value =
+                    toml::Value::try_from(def).map_err(|err| Error::Syntax(err.to_string()))?;
+                toml::to_string(&value).map_err(|err| Error::Syntax(err.to_string()))?
+            }
+            Self::Yaml => {
+                serde_yaml::to_string(def).map_err(|err| Error::Syntax(err.to_string()))?
+            }
+            Self::Json => {
+                serde_json::to_string_pretty(def).map_err(|err| Error::Syntax(err.to_string()))?
+            }
+        })
+    }
+}
+
+/// An error in a definition file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// The file cannot be parsed or written in the requested format.
+    Syntax(String),
+    /// Two types of the same kind have the same identifier.
+    DuplicateId {
+        /// The kind of type.
+        kind: &'static str,
+        /// The duplicated identifier.
+        id: ArcStr,
+    },
+    /// A type refers to an undefined identifier.
+    UnknownId {
+        /// The kind of type referred to.
+        kind: &'static str,
+        /// The undefined identifier.
+        id: ArcStr,
+        /// The identifier of the type containing the reference.
+        referrer: ArcStr,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Syntax(err) => write!(f, "Syntax error: {}", err),
+            Self::DuplicateId { kind, id } => write!(f, "Duplicate {} identifier {:?}", kind, id),
+            Self::UnknownId { kind, id, referrer } => write!(
+                f,
+                "{:?} refers to an undefined {} identifier {:?}",
+                referrer, kind, id
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Maps string identifiers of a kind of type to their positions.
+struct Index {
+    kind: &'static str,
+    map: BTreeMap<ArcStr, usize>,
+}
+
+impl Index {
+    fn new<'t>(kind: &'static str, ids: impl Iterator<Item = &'t ArcStr>) -> Result<Self, Error> {
+        let mut map = BTreeMap::new();
+        for (i, id) in ids.enumerate() {
+            if map.insert(id.clone(), i).is_some() {
+                return Err(Error::DuplicateId {
+                    kind,
+                    id: id.clone(),
+                });
+            }
+        }
+        Ok(Self { kind, map })
+    }
+
+    fn get(&self, id: &ArcStr, referrer: &ArcStr) -> Result<usize, Error> {
+        self.map.get(id).copied().ok_or_else(|| Error::UnknownId {
+            kind: self.kind,
+            id: id.clone(),
+            referrer: referrer.clone(),
+        })
+    }
+}
+
+impl Definition {
+    /// Resolves the string identifiers and builds a [`GameDefinition`].
+    pub fn resolve(self) -> Result<GameDefinition, Error> {
+        let cargo_cats = Index::new("cargo category", self.cargo_category.iter().map(|t| t.id()))?;
+        let cargos = Index::new("cargo", self.cargo.iter().map(|t| &t.id))?;
+        let liquids = Index::new("liquid", self.liquid.iter().map(|t| t.id()))?;
+        let gases = Index::new("gas", self.gas.iter().map(|t| t.id()))?;
+        let skills = Index::new("skill", self.skill.iter().map(|t| t.id()))?;
+        let vehicles = Index::new("vehicle", self.vehicle.iter().map(|t| &t.id))?;
+        let reaction_cats = Index::new(
+            "reaction category",
+            self.reaction_category.iter().map(|t| t.id()),
+        )?;
+        let reactions = Index::new("reaction", self.reaction.iter().map(|t| &t.id))?;
+        let building_cats = Index::new(
+            "building category",
+            self.building_category.iter().map(|t| t.id()),
+        )?;
+        let buildings = Index::new("building", self.building.iter().map(|t| &t.id))?;
+        Index::new("crime", self.crime.iter().map(|t| &t.id))?;
+        let techs = Index::new("tech", self.tech.iter().map(|t| &t.id))?;
+
+        let mut def = GameDefinition::default();
+
+        for cat in self.cargo_category {
+            def.add_cargo_category(cat);
+        }
+        for cargo in self.cargo {
+            let category = cargo::CategoryId(cargo_cats.get(&cargo.category, &cargo.id)?);
+            let bottle = match cargo.bottle {
+                Some(Bottle::Liquid { ref ty, volume }) => Some(cargo::Bottle::Liquid {
+                    ty: liquid::TypeId(liquids.get(ty, &cargo.id)?),
+                    volume,
+                }),
+                Some(Bottle::Gas { ref ty, volume }) => Some(cargo::Bottle::Gas {
+                    ty: gas::TypeId(gases.get(ty, &cargo.id)?),
+                    volume,
+                }),
+                None => None,
+            };
+            def.add_cargo(
+                cargo::Type::builder()
+                    .id(cargo.id)
+                    .name(cargo.name)
+                    .summary(cargo.summary)
+                    .description(cargo.description)
+                    .category(category)
+                    .texture(cargo.texture)
+                    .waste(cargo.waste)
+                    .bottle(bottle)
+                    .build(),
+            );
+        }
+        for liquid in self.liquid {
+            def.add_liquid(liquid);
+        }
+        for gas in self.gas {
+            def.add_gas(gas);
+        }
+        for skill in self.skill {
+            def.add_skill(skill);
+        }
+        for vehicle in self.vehicle {
+            let skill = skill::TypeId(skills.get(&vehicle.skill.skill, &vehicle.id)?);
+            def.add_vehicle(
+                vehicle::Type::builder()
+                    .id(vehicle.id)
+                    .name(vehicle.name)
+                    .description(vehicle.description)
+                    .speed(vehicle.speed)
+                    .capacity(vehicle.capacity)
+                    .passengers(vehicle.passengers)
+                    .skill(
+                        vehicle::Skill::builder()
+                            .skill(skill)
+                            .levels(vehicle.skill.levels)
+                            .multipliers(vehicle.skill.multipliers)
+                            .build(),
+                    )
+                    .texture(vehicle.texture)
+                    .build(),
+            );
+        }
+        for cat in self.reaction_category {
+            def.add_reaction_category(cat);
+        }
+        for reaction in self.reaction {
+            let id = &reaction.id;
+            let category = reaction::CategoryId(reaction_cats.get(&reaction.category, id)?);
+            let catalysts = reaction
+                .catalysts
+                .iter()
+                .map(|catalyst| {
+                    let range = match &catalyst.range {
+                        CatalystRange::Cargo { ty, levels } => reaction::CatalystRange::Cargo {
+                            ty: cargo::TypeId(cargos.get(ty, id)?),
+                            levels: levels.clone(),
+                        },
+                        CatalystRange::Liquid { ty, levels } => reaction::CatalystRange::Liquid {
+                            ty: liquid::TypeId(liquids.get(ty, id)?),
+                            levels: levels.clone(),
+                        },
+                        CatalystRange::Gas { ty, levels } => reaction::CatalystRange::Gas {
+                            ty: gas::TypeId(gases.get(ty, id)?),
+                            levels: levels.clone(),
+                        },
+                        CatalystRange::Electricity { levels } => {
+                            reaction::CatalystRange::Electricity {
+                                levels: levels.clone(),
+                            }
+                        }
+                        CatalystRange::Light { levels } => reaction::CatalystRange::Light {
+                            levels: levels.clone(),
+                        },
+                        CatalystRange::Heat { levels } => reaction::CatalystRange::Heat {
+                            levels: levels.clone(),
+                        },
+                        CatalystRange::Skill { ty, levels } => reaction::CatalystRange::Skill {
+                            ty: skill::TypeId(skills.get(ty, id)?),
+                            levels: levels.clone(),
+                        },
+                    };
+                    Ok(reaction::Catalyst::builder()
+                        .range(range)
+                        .multipliers(catalyst.multipliers)
+                        .build())
+                })
+                .collect::<Result<_, Error>>()?;
+            let puts = reaction
+                .puts
+                .iter()
+                .map(|put| {
+                    Ok(match *put {
+                        Put::Cargo { ref ty, base } => reaction::Put::Cargo {
+                            ty: cargo::TypeId(cargos.get(ty, id)?),
+                            base,
+                        },
+                        Put::Liquid { ref ty, base } => reaction::Put::Liquid {
+                            ty: liquid::TypeId(liquids.get(ty, id)?),
+                            base,
+                        },
+                        Put::Gas { ref ty, base } => reaction::Put::Gas {
+                            ty: gas::TypeId(gases.get(ty, id)?),
+                            base,
+                        },
+                        Put::Electricity { base } => reaction::Put::Electricity { base },
+                        Put::Heat { base } => reaction::Put::Heat { base },
+                        Put::Happiness { base } => reaction::Put::Happiness { base },
+                        Put::Skill { ref ty, base } => reaction::Put::Skill {
+                            ty: skill::TypeId(skills.get(ty, id)?),
+                            base,
+                        },
+                        Put::Research { base } => reaction::Put::Research { base },
+                    })
+                })
+                .collect::<Result<_, Error>>()?;
+
+            def.add_reaction(
+                reaction::Type::builder()
+                    .id(reaction.id.clone())
+                    .name(reaction.name)
+                    .description(reaction.description)
+                    .category(category)
+                    .catalysts(catalysts)
+                    .puts(puts)
+                    .build(),
+            );
+        }
+        for cat in self.building_category {
+            def.add_building_category(cat);
+        }
+        for building in self.building {
+            let id = &building.id;
+            let category = building::CategoryId(building_cats.get(&building.category, id)?);
+            let rows = building.shape.transform;
+            #[allow(clippy::indexing_slicing)] // i, j < 4
+            let transform = Matrix::from_fn(|i, j| rows[i][j]);
+            let building_reactions = building
+                .reactions
+                .iter()
+                .map(|entry| {
+                    Ok((
+                        reaction::TypeId(reactions.get(&entry.reaction, id)?),
+                        entry.policy,
+                    ))
+                })
+                .collect::<Result<_, Error>>()?;
+            let construction = &building.construction;
+            let construction_cargo = construction
+                .cargo
+                .iter()
+                .map(|entry| Ok((cargo::TypeId(cargos.get(&entry.ty, id)?), entry.size)))
+                .collect::<Result<_, Error>>()?;
+            let construction = building::Construction::builder()
+                .cargo(construction_cargo)
+                .energy(construction.energy)
+                .time(construction.time)
+                .skill(skill::TypeId(skills.get(&construction.skill, id)?))
+                .levels(construction.levels.clone())
+                .multipliers(construction.multipliers)
+                .build();
+
+            def.add_building(
+                building::Type::builder()
+                    .id(building.id.clone())
+                    .name(building.name)
+                    .summary(building.summary)
+                    .description(building.description)
+                    .category(category)
+                    .shape(
+                        building::Shape::builder()
+                            .transform(transform)
+                            .texture_src(building.shape.texture_src)
+                            .texture_name(building.shape.texture_name)
+                            .build(),
+                    )
+                    .reactions(building_reactions)
+                    .hitpoint(building.hitpoint)
+                    .storage(building.storage)
+                    .construction(construction)
+                    .features(building.features)
+                    .build(),
+            );
+        }
+        for crime in self.crime {
+            let id = &crime.id;
+            let criterion = |criterion: &InhabitantCriterion| {
+                Ok(match criterion {
+                    InhabitantCriterion::HighestSkill(skill) => {
+                        crime::InhabitantCriterion::HighestSkill(skill::TypeId(
+                            skills.get(skill, id)?,
+                        ))
+                    }
+                })
+            };
+            let action = match &crime.action {
+                &CrimeAction::InhabitantTheft(size) => crime::Action::InhabitantTheft(size),
+                &CrimeAction::VehicleTheft(size) => crime::Action::VehicleTheft(size),
+                &CrimeAction::NodeTheft(size) => crime::Action::NodeTheft(size),
+                CrimeAction::Antagonize(c) => crime::Action::Antagonize(criterion(c)?),
+                CrimeAction::Arson => crime::Action::Arson,
+                CrimeAction::Murder(c) => crime::Action::Murder(criterion(c)?),
+            };
+            let skill_change = crime
+                .skill_change
+                .iter()
+                .map(|change| Ok((skill::TypeId(skills.get(&change.skill, id)?), change.change)))
+                .collect::<Result<_, Error>>()?;
+
+            def.add_crime(
+                crime::Type::builder()
+                    .id(crime.id.clone())
+                    .name(crime.name)
+                    .description(crime.description)
+                    .action(action)
+                    .trigger_happiness_range(crime.trigger_happiness_range)
+                    .probability(crime.probability)
+                    .happiness_change(crime.happiness_change)
+                    .skill_change(skill_change)
+                    .build(),
+            );
+        }
+
+        for tech in self.tech {
+            let id = &tech.id;
+            let prerequisites = tech
+                .prerequisites
+                .iter()
+                .map(|prereq| Ok(tech::TypeId(techs.get(prereq, id)?)))
+                .collect::<Result<_, Error>>()?;
+            let costs = tech
+                .costs
+                .iter()
+                .map(|cost| {
+                    Ok(match *cost {
+                        TechCost::Cargo { ref ty, size } => tech::Cost::Cargo {
+                            ty: cargo::TypeId(cargos.get(ty, id)?),
+                            size,
+                        },
+                        TechCost::Skill { ref ty, level } => tech::Cost::Skill {
+                            ty: skill::TypeId(skills.get(ty, id)?),
+                            level,
+                        },
+                        TechCost::Research { points } => tech::Cost::Research(points),
+                    })
+                })
+                .collect::<Result<_, Error>>()?;
+            let unlocks = tech
+                .unlocks
+                .iter()
+                .map(|unlock| {
+                    Ok(match unlock {
+                        TechUnlock::Building(building) => {
+                            tech::Unlock::Building(building::TypeId(buildings.get(building, id)?))
+                        }
+                        TechUnlock::Reaction(reaction) => {
+                            tech::Unlock::Reaction(reaction::TypeId(reactions.get(reaction, id)?))
+                        }
+                        TechUnlock::Vehicle(vehicle) => {
+                            tech::Unlock::Vehicle(vehicle::TypeId(vehicles.get(vehicle, id)?))
+                        }
+                    })
+                })
+                .collect::<Result<_, Error>>()?;
+
+            def.add_tech(
+                tech::Type::builder()
+                    .id(tech.id.clone())
+                    .name(tech.name)
+                    .description(tech.description)
+                    .prerequisites(prerequisites)
+                    .costs(costs)
+                    .unlocks(unlocks)
+                    .build(),
+            );
+        }
+
+        def.set_orbit(self.orbit);
+
+        Ok(def)
+    }
+
+    /// Exports a [`GameDefinition`] into the file format.
+    pub fn export(def: &GameDefinition) -> Self {
+        let cargo_id = |id: cargo::TypeId| def.get_cargo(id).id().clone();
+        let liquid_id = |id: liquid::TypeId| def.get_liquid(id).id().clone();
+        let gas_id = |id: gas::TypeId| def.get_gas(id).id().clone();
+        let skill_id = |id: skill::TypeId| def.get_skill(id).id().clone();
+
+        Self {
+            cargo_category: def.cargo_cats().clone(),
+            cargo: def
+                .cargo()
+                .iter()
+                .map(|cargo| Cargo {
+                    id: cargo.id().clone(),
+                    name: cargo.name().clone(),
+                    summary: cargo.summary().clone(),
+                    description: cargo.description().clone(),
+                    category: def.get_cargo_category(cargo.category()).id().clone(),
+                    texture: cargo.texture().clone(),
+                    waste: cargo.waste(),
+                    bottle: cargo.bottle().map(|bottle| match bottle {
+                        cargo::Bottle::Liquid { ty, volume } => Bottle::Liquid {
+                            ty: liquid_id(ty),
+                            volume,
+                        },
+                        cargo::Bottle::Gas { ty, volume } => Bottle::Gas {
+                            ty: gas_id(ty),
+                            volume,
+                        },
+                    }),
+                })
+                .collect(),
+            liquid: def.liquid().clone(),
+            gas: def.gas().clone(),
+            skill: def.skill().clone(),
+            vehicle: def
+                .vehicle()
+                .iter()
+                .map(|vehicle| Vehicle {
+                    id: vehicle.id().clone(),
+                    name: vehicle.name().clone(),
+                    description: vehicle.description().clone(),
+                    speed: vehicle.speed(),
+                    capacity: vehicle.capacity(),
+                    passengers: vehicle.passengers(),
+                    skill: VehicleSkill {
+                        skill: skill_id(vehicle.skill().skill()),
+                        levels: vehicle.skill().levels().clone(),
+                        multipliers: vehicle.skill().multipliers(),
+                    },
+                    texture: vehicle.texture().clone(),
+                })
+                .collect(),
+            reaction_category: def.reaction_cats().clone(),
+            reaction: def
+                .reaction()
+                .iter()
+                .map(|reaction| Reaction {
+                    id: reaction.id().clone(),
+                    name: reaction.name().clone(),
+                    description: reaction.description().clone(),
+                    category: def.get_reaction_category(reaction.category()).id().clone(),
+                    catalysts: reaction
+                        .catalysts()
+                        .iter()
+                        .map(|catalyst| Catalyst {
+                            range: match catalyst.range() {
+                                reaction::CatalystRange::Cargo { ty, levels } => {
+                                    CatalystRange::Cargo {
+                                        ty: cargo_id(*ty),
+                                        levels: levels.clone(),
+                                    }
+                                }
+                                reaction::CatalystRange::Liquid { ty, levels } => {
+                                    CatalystRange::Liquid {
+                                        ty: liquid_id(*ty),
+                                        levels: levels.clone(),
+                                    }
+                                }
+                                reaction::CatalystRange::Gas { ty, levels } => CatalystRange::Gas {
+                                    ty: gas_id(*ty),
+                                    levels: levels.clone(),
+                                },
+                                reaction::CatalystRange::Electricity { levels } => {
+                                    CatalystRange::Electricity {
+                                        levels: levels.clone(),
+                                    }
+                                }
+                                reaction::CatalystRange::Light { levels } => CatalystRange::Light {
+                                    levels: levels.clone(),
+                                },
+                                reaction::CatalystRange::Heat { levels } => CatalystRange::Heat {
+                                    levels: levels.clone(),
+                                },
+                                reaction::CatalystRange::Skill { ty, levels } => {
+                                    CatalystRange::Skill {
+                                        ty: skill_id(*ty),
+                                        levels: levels.clone(),
+                                    }
+                                }
+                            },
+                            multipliers: catalyst.multipliers(),
+                        })
+                        .collect(),
+                    puts: reaction
+                        .puts()
+                        .iter()
+                        .map(|put| match *put {
+                            reaction::Put::Cargo { ty, base } => Put::Cargo {
+                                ty: cargo_id(ty),
+                                base,
+                            },
+                            reaction::Put::Liquid { ty, base } => Put::Liquid {
+                                ty: liquid_id(ty),
+                                base,
+                            },
+                            reaction::Put::Gas { ty, base } => Put::Gas {
+                                ty: gas_id(ty),
+                                base,
+                            },
+                            reaction::Put::Electricity { base } => Put::Electricity { base },
+                            reaction::Put::Heat { base } => Put::Heat { base },
+                            reaction::Put::Happiness { base } => Put::Happiness { base },
+                            reaction::Put::Skill { ty, base } => Put::Skill {
+                                ty: skill_id(ty),
+                                base,
+                            },
+                            reaction::Put::Research { base } => Put::Research { base },
+                        })
+                        .collect(),
+                })
+                .collect(),
+            building_category: def.building_cats().clone(),
+            building: def
+                .building()
+                .iter()
+                .map(|building| {
+                    let mut transform = [[0.; 4]; 4];
+                    for (row, source) in transform
+                        .iter_mut()
+                        .zip(building.shape().transform().row_iter())
+                    {
+                        for (cell, &value) in row.iter_mut().zip(source.iter()) {
+                            *cell = value;
+                        }
+                    }
+                    Building {
+                        id: building.id().clone(),
+                        name: building.name().clone(),
+                        summary: building.summary().clone(),
+                        description: building.description().clone(),
+                        category: def.get_building_category(building.category()).id().clone(),
+                        shape: Shape {
+                            transform,
+                            texture_src: building.shape().texture_src().clone(),
+                            texture_name: building.shape().texture_name().clone(),
+                        },
+                        reactions: building
+                            .reactions()
+                            .iter()
+                            .map(|&(reaction, policy)| BuildingReaction {
+                                reaction: def.get_reaction(reaction).id().clone(),
+                                policy,
+                            })
+                            .collect(),
+                        hitpoint: *building.hitpoint(),
+                        storage: *building.storage(),
+                        construction: Construction {
+                            cargo: building
+                                .construction()
+                                .cargo()
+                                .iter()
+                                .map(|&(ty, size)| ConstructionCargo {
+                                    ty: cargo_id(ty),
+                                    size,
+                                })
+                                .collect(),
+                            energy: building.construction().energy(),
+                            time: building.construction().time(),
+                            skill: skill_id(building.construction().skill()),
+                            levels: building.construction().levels().clone(),
+                            multipliers: building.construction().multipliers(),
+                        },
+                        features: building.features().clone(),
+                    }
+                })
+                .collect(),
+            crime: def
+                .crime()
+                .iter()
+                .map(|crime| {
+                    let criterion = |criterion: crime::InhabitantCriterion| match criterion {
+                        crime::InhabitantCriterion::HighestSkill(skill) => {
+                            InhabitantCriterion::HighestSkill(skill_id(skill))
+                        }
+                    };
+                    Crime {
+                        id: crime.id().clone(),
+                        name: crime.name().clone(),
+                        description: crime.description().clone(),
+                        action: match *crime.action() {
+                            crime::Action::InhabitantTheft(size) => {
+                                CrimeAction::InhabitantTheft(size)
+                            }
+                            crime::Action::VehicleTheft(size) => CrimeAction::VehicleTheft(size),
+                            crime::Action::NodeTheft(size) => CrimeAction::NodeTheft(size),
+                            crime::Action::Antagonize(c) => CrimeAction::Antagonize(criterion(c)),
+                            crime::Action::Arson => CrimeAction::Arson,
+                            crime::Action::Murder(c) => CrimeAction::Murder(criterion(c)),
+                        },
+                        trigger_happiness_range: crime.trigger_happiness_range().clone(),
+                        probability: crime.probability(),
+                        happiness_change: crime.happiness_change(),
+                        skill_change: crime
+                            .skill_change()
+                            .iter()
+                            .map(|&(skill, change)| SkillChange {
+                                skill: skill_id(skill),
+                                change,
+                            })
+                            .collect(),
+                    }
+                })
+                .collect(),
+            tech: def
+                .tech()
+                .iter()
+                .map(|tech| Tech {
+                    id: tech.id().clone(),
+                    name: tech.name().clone(),
+                    description: tech.description().clone(),
+                    prerequisites: tech
+                        .prerequisites()
+                        .iter()
+                        .map(|&prereq| def.get_tech(prereq).id().clone())
+                        .collect(),
+                    costs: tech
+                        .costs()
+                        .iter()
+                        .map(|cost| match *cost {
+                            tech::Cost::Cargo { ty, size } => TechCost::Cargo {
+                                ty: cargo_id(ty),
+                                size,
+                            },
+                            tech::Cost::Skill { ty, level } => TechCost::Skill {
+                                ty: skill_id(ty),
+                                level,
+                            },
+                            tech::Cost::Research(points) => TechCost::Research { points },
+                        })
+                        .collect(),
+                    unlocks: tech
+                        .unlocks()
+                        .iter()
+                        .map(|unlock| match *unlock {
+                            tech::Unlock::Building(building) => {
+                                TechUnlock::Building(def.get_building(building).id().clone())
+                            }
+                            tech::Unlock::Reaction(reaction) => {
+                                TechUnlock::Reaction(def.get_reaction(reaction).id().clone())
+                            }
+                            tech::Unlock::Vehicle(vehicle) => {
+                                TechUnlock::Vehicle(def.get_vehicle(vehicle).id().clone())
+                            }
+                        })
+                        .collect(),
+                })
+                .collect(),
+            orbit: def.orbit().clone(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "schema"))]
+mod tests {
+    use super::{Definition, Error, Format};
+
+    const CARGO: &str = r#"
+[[cargo_category]]
+id = "test:food"
+title = "Food"
+description = ""
+
+[[cargo]]
+id = "test:bread"
+name = "Bread"
+summary = ""
+description = ""
+category = "test:food"
+texture = "bread"
+
+[[cargo]]
+id = "test:waste"
+name = "Waste"
+summary = ""
+description = ""
+category = "test:food"
+texture = "waste"
+waste = true
+
+[orbit]
+year_length = 60000
+month_count = 6
+axial_tilt = 0.5
+"#;
+
+    #[test]
+    fn resolve_all_formats() {
+        let def = Format::Toml
+            .parse::<Definition>(CARGO)
+            .and_then(Definition::resolve)
+            .expect("Cannot load definition");
+        let bread = def.find_cargo("test:bread").expect("test:bread is defined");
+        let waste = def.find_cargo("test:waste").expect("test:waste is defined");
+        assert_eq!(def.get_cargo(bread).name(), "Bread");
+        assert!(!def.get_cargo(bread).waste());
+        assert!(def.get_cargo(waste).waste());
+        assert_eq!(def.orbit().month_count(), 6);
+
+        for &format in &[Format::Toml, Format::Yaml, Format::Json] {
+            let text = format
+                .write(&Definition::export(&def))
+                .expect("Cannot write definition");
+            let loaded = format
+                .parse::<Definition>(&text)
+                .and_then(Definition::resolve)
+                .expect("Cannot load written definition");
+            assert_eq!(loaded.find_cargo("test:waste"), Some(waste), "{:?}", format);
+            assert_eq!(loaded.orbit().year_length().value(), 60000, "{:?}", format);
+            assert_eq!(
+                loaded.find_cargo_category("test:food"),
+                def.find_cargo_category("test:food"),
+                "{:?}",
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn resolve_errors() {
+        let syntax = Format::Json.parse::<Definition>(CARGO);
+        assert!(matches!(syntax, Err(Error::Syntax(_))));
+
+        let duplicate = CARGO.replace("test:waste", "test:bread");
+        let err = Format::Toml
+            .parse::<Definition>(&duplicate)
+            .and_then(Definition::resolve)
+            .err()
+            .expect("test:bread is duplicated");
+        assert_eq!(
+            err,
+            Error::DuplicateId {
+                kind: "cargo",
+                id: "test:bread".into(),
+            }
+        );
+
+        let unknown = CARGO.replace("category = \"test:food\"", "category = \"test:drink\"");
+        let err = Format::Toml
+            .parse::<Definition>(&unknown)
+            .and_then(Definition::resolve)
+            .err()
+            .expect("test:drink is undefined");
+        assert_eq!(
+            err,
+            Error::UnknownId {
+                kind: "cargo category",
+                id: "test:drink".into(),
+                referrer: "test:bread".into(),
+            }
+        );
+    }
+}
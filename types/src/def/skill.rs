@@ -1,19 +1,49 @@
 //! Skill definitions.
 
 use arcstr::ArcStr;
+use serde::{Deserialize, Serialize};
 use typed_builder::TypedBuilder;
 
 /// Identifies a cargo category
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct TypeId(pub usize);
 
 /// A type of skill.
-#[derive(TypedBuilder, getset::CopyGetters, getset::Getters)]
+#[derive(Clone, TypedBuilder, getset::CopyGetters, getset::Getters, Serialize, Deserialize)]
 pub struct Type {
+    /// String identifier of the skill type.
+    #[getset(get = "pub")]
+    id: ArcStr,
     /// Name of the skill type.
     #[getset(get = "pub")]
     name: ArcStr,
     /// Long description of the skill type.
     #[getset(get = "pub")]
     description: ArcStr,
+    /// The role of the skill in the lifecycle of inhabitants.
+    #[getset(get_copy = "pub")]
+    #[builder(default)]
+    #[serde(default)]
+    kind: Kind,
+}
+
+/// The role of a skill type in the lifecycle of inhabitants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Kind {
+    /// The skill is trained by schools and grows slowly with age.
+    Trained,
+    /// The skill is the age of the inhabitant in years.
+    ///
+    /// Inhabitants die when they reach the maximum age.
+    Age,
+    /// The skill is the health of the inhabitant.
+    ///
+    /// Inhabitants die when their health drops to zero.
+    Health,
+}
+
+impl Default for Kind {
+    fn default() -> Self {
+        Self::Trained
+    }
 }
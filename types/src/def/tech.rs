@@ -0,0 +1,73 @@
+//! Technology definitions.
+
+use arcstr::ArcStr;
+use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
+use typed_builder::TypedBuilder;
+
+use crate::def::{building, cargo, reaction, skill, vehicle};
+use crate::units;
+
+/// Identifies a technology
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct TypeId(pub usize);
+
+/// A technology that unlocks other content.
+///
+/// Buildings, reactions and vehicles unlocked by any technology
+/// are unavailable until one of such technologies is researched.
+#[derive(TypedBuilder, getset::Getters)]
+pub struct Type {
+    /// String identifier of the technology.
+    #[getset(get = "pub")]
+    id: ArcStr,
+    /// Name of the technology.
+    #[getset(get = "pub")]
+    name: ArcStr,
+    /// Description of the technology.
+    #[getset(get = "pub")]
+    description: ArcStr,
+    /// Technologies that must be researched before this technology.
+    #[getset(get = "pub")]
+    prerequisites: SmallVec<[TypeId; 2]>,
+    /// The requirements to research this technology.
+    #[getset(get = "pub")]
+    costs: SmallVec<[Cost; 2]>,
+    /// The content unlocked by this technology.
+    #[getset(get = "pub")]
+    unlocks: SmallVec<[Unlock; 2]>,
+}
+
+/// A requirement to research a technology.
+#[derive(Debug, Clone, Copy)]
+pub enum Cost {
+    /// Consumes cargo from the colony storage.
+    Cargo {
+        /// Type of cargo consumed
+        ty: cargo::TypeId,
+        /// Amount of cargo consumed
+        size: units::CargoSize,
+    },
+    /// Requires an inhabitant with at least the skill level.
+    ///
+    /// The skill is not consumed.
+    Skill {
+        /// Type of skill required
+        ty: skill::TypeId,
+        /// Minimum skill level required
+        level: units::Skill,
+    },
+    /// Consumes research points accumulated by the colony.
+    Research(units::ResearchPoint),
+}
+
+/// Content unlocked by a technology.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unlock {
+    /// Allows constructing a building type.
+    Building(building::TypeId),
+    /// Allows enabling a reaction in buildings.
+    Reaction(reaction::TypeId),
+    /// Allows producing a vehicle type.
+    Vehicle(vehicle::TypeId),
+}
@@ -0,0 +1,710 @@
+//! Consistency checks for game definitions.
+//!
+//! [`GameDefinition::get_cargo`] and similar methods panic when the ID is out of bounds,
+//! so a definition should be validated before it is used in a game.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::ops::Range;
+
+use arcstr::{literal, ArcStr};
+
+use super::{cargo, reaction, tech, GameDefinition};
+
+/// The severity of a [`Diagnostic`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The definition is usable, but probably not intended.
+    Warning,
+    /// The definition would cause a panic or undefined behaviour in game.
+    Error,
+}
+
+/// Identifies the type that a [`Diagnostic`] is about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subject {
+    /// The kind of type, e.g. `"building"`.
+    pub kind: &'static str,
+    /// The string identifier of the type.
+    pub id: ArcStr,
+}
+
+impl fmt::Display for Subject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {:?}", self.kind, self.id)
+    }
+}
+
+/// A problem found in a game definition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// The type refers to an ID that is out of bounds.
+    UndefinedReference {
+        /// The kind of type referred to.
+        kind: &'static str,
+        /// The positional ID referred to.
+        index: usize,
+    },
+    /// A range has a start not less than its end.
+    EmptyRange {
+        /// The field containing the range.
+        field: &'static str,
+    },
+    /// A quantity that must be positive is zero.
+    NotPositive {
+        /// The field containing the quantity.
+        field: &'static str,
+    },
+    /// The string identifier is not in the form `namespace:name`.
+    MissingNamespace,
+    /// The technology directly or indirectly requires itself.
+    PrerequisiteCycle,
+    /// Another type of the same kind has the same display name.
+    DuplicateName {
+        /// The duplicated name.
+        name: ArcStr,
+    },
+    /// The texture does not exist.
+    MissingTexture {
+        /// The texture name.
+        texture: ArcStr,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UndefinedReference { kind, index } => {
+                write!(f, "refers to undefined {} #{}", kind, index)
+            }
+            Self::EmptyRange { field } => write!(f, "{} is an empty range", field),
+            Self::NotPositive { field } => write!(f, "{} is not positive", field),
+            Self::MissingNamespace => write!(f, "identifier has no namespace"),
+            Self::PrerequisiteCycle => write!(f, "requires itself as a prerequisite"),
+            Self::DuplicateName { name } => write!(f, "name {:?} is used more than once", name),
+            Self::MissingTexture { texture } => write!(f, "texture {:?} does not exist", texture),
+        }
+    }
+}
+
+/// A problem found in a game definition, along with its location.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The severity of the problem.
+    pub severity: Severity,
+    /// The type with the problem.
+    pub subject: Subject,
+    /// The problem.
+    pub problem: Problem,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}: {} {}", severity, self.subject, self.problem)
+    }
+}
+
+/// A texture referenced by a game definition.
+#[derive(Debug, Clone, Copy)]
+pub enum Texture<'t> {
+    /// A single-image icon, used for cargo, liquids, gases and vehicles.
+    Icon(&'t str),
+    /// A directory of images for each face of a building.
+    Building(&'t str),
+}
+
+/// Collects diagnostics for a game definition.
+struct Validator {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator {
+    fn push(&mut self, severity: Severity, kind: &'static str, id: &ArcStr, problem: Problem) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            subject: Subject {
+                kind,
+                id: id.clone(),
+            },
+            problem,
+        });
+    }
+
+    fn reference(
+        &mut self,
+        kind: &'static str,
+        id: &ArcStr,
+        target: &'static str,
+        index: usize,
+        len: usize,
+    ) {
+        if index >= len {
+            self.push(
+                Severity::Error,
+                kind,
+                id,
+                Problem::UndefinedReference {
+                    kind: target,
+                    index,
+                },
+            );
+        }
+    }
+
+    fn range<T: PartialOrd>(
+        &mut self,
+        severity: Severity,
+        kind: &'static str,
+        id: &ArcStr,
+        field: &'static str,
+        range: &Range<T>,
+    ) {
+        if range.start >= range.end {
+            self.push(severity, kind, id, Problem::EmptyRange { field });
+        }
+    }
+
+    fn unique<'t>(
+        &mut self,
+        kind: &'static str,
+        types: impl Iterator<Item = (&'t ArcStr, &'t ArcStr)>,
+    ) {
+        let mut names = BTreeSet::new();
+        for (id, name) in types {
+            if !id.contains(':') {
+                self.push(Severity::Warning, kind, id, Problem::MissingNamespace);
+            }
+            if !names.insert(name) {
+                self.push(
+                    Severity::Warning,
+                    kind,
+                    id,
+                    Problem::DuplicateName { name: name.clone() },
+                );
+            }
+        }
+    }
+}
+
+impl GameDefinition {
+    /// Checks the definition for dangling references, empty ranges and duplicates.
+    ///
+    /// Returns an empty list if no problems are found.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut v = Validator {
+            diagnostics: Vec::new(),
+        };
+
+        v.unique(
+            "cargo category",
+            self.cargo_cats().iter().map(|cat| (cat.id(), cat.title())),
+        );
+        v.unique(
+            "cargo",
+            self.cargo().iter().map(|cargo| (cargo.id(), cargo.name())),
+        );
+        v.unique(
+            "liquid",
+            self.liquid()
+                .iter()
+                .map(|liquid| (liquid.id(), liquid.name())),
+        );
+        v.unique("gas", self.gas().iter().map(|gas| (gas.id(), gas.name())));
+        v.unique(
+            "skill",
+            self.skill().iter().map(|skill| (skill.id(), skill.name())),
+        );
+        v.unique(
+            "vehicle",
+            self.vehicle()
+                .iter()
+                .map(|vehicle| (vehicle.id(), vehicle.name())),
+        );
+        v.unique(
+            "reaction category",
+            self.reaction_cats()
+                .iter()
+                .map(|cat| (cat.id(), cat.title())),
+        );
+        v.unique(
+            "reaction",
+            self.reaction()
+                .iter()
+                .map(|reaction| (reaction.id(), reaction.name())),
+        );
+        v.unique(
+            "building category",
+            self.building_cats()
+                .iter()
+                .map(|cat| (cat.id(), cat.title())),
+        );
+        v.unique(
+            "building",
+            self.building()
+                .iter()
+                .map(|building| (building.id(), building.name())),
+        );
+        v.unique(
+            "crime",
+            self.crime().iter().map(|crime| (crime.id(), crime.name())),
+        );
+        v.unique(
+            "tech",
+            self.tech().iter().map(|tech| (tech.id(), tech.name())),
+        );
+
+        for cargo in self.cargo() {
+            v.reference(
+                "cargo",
+                cargo.id(),
+                "cargo category",
+                cargo.category().0,
+                self.cargo_cats().len(),
+            );
+            match cargo.bottle() {
+                Some(cargo::Bottle::Liquid { ty, .. }) => {
+                    v.reference("cargo", cargo.id(), "liquid", ty.0, self.liquid().len());
+                }
+                Some(cargo::Bottle::Gas { ty, .. }) => {
+                    v.reference("cargo", cargo.id(), "gas", ty.0, self.gas().len());
+                }
+                None => {}
+            }
+        }
+
+        for vehicle in self.vehicle() {
+            let id = vehicle.id();
+            let skill = vehicle.skill();
+            v.reference("vehicle", id, "skill", skill.skill().0, self.skill().len());
+            v.range(
+                Severity::Error,
+                "vehicle",
+                id,
+                "skill levels",
+                skill.levels(),
+            );
+        }
+
+        for reaction in self.reaction() {
+            let id = reaction.id();
+            v.reference(
+                "reaction",
+                id,
+                "reaction category",
+                reaction.category().0,
+                self.reaction_cats().len(),
+            );
+            for catalyst in reaction.catalysts() {
+                match catalyst.range() {
+                    reaction::CatalystRange::Cargo { ty, levels } => {
+                        v.reference("reaction", id, "cargo", ty.0, self.cargo().len());
+                        v.range(Severity::Error, "reaction", id, "catalyst levels", levels);
+                    }
+                    reaction::CatalystRange::Liquid { ty, levels } => {
+                        v.reference("reaction", id, "liquid", ty.0, self.liquid().len());
+                        v.range(Severity::Error, "reaction", id, "catalyst levels", levels);
+                    }
+                    reaction::CatalystRange::Gas { ty, levels } => {
+                        v.reference("reaction", id, "gas", ty.0, self.gas().len());
+                        v.range(Severity::Error, "reaction", id, "catalyst levels", levels);
+                    }
+                    reaction::CatalystRange::Electricity { levels } => {
+                        v.range(Severity::Error, "reaction", id, "catalyst levels", levels);
+                    }
+                    reaction::CatalystRange::Light { levels } => {
+                        v.range(Severity::Error, "reaction", id, "catalyst levels", levels);
+                    }
+                    reaction::CatalystRange::Heat { levels } => {
+                        v.range(Severity::Error, "reaction", id, "catalyst levels", levels);
+                    }
+                    reaction::CatalystRange::Skill { ty, levels } => {
+                        v.reference("reaction", id, "skill", ty.0, self.skill().len());
+                        v.range(Severity::Error, "reaction", id, "catalyst levels", levels);
+                    }
+                }
+            }
+            for put in reaction.puts() {
+                match *put {
+                    reaction::Put::Cargo { ty, .. } => {
+                        v.reference("reaction", id, "cargo", ty.0, self.cargo().len());
+                    }
+                    reaction::Put::Liquid { ty, .. } => {
+                        v.reference("reaction", id, "liquid", ty.0, self.liquid().len());
+                    }
+                    reaction::Put::Gas { ty, .. } => {
+                        v.reference("reaction", id, "gas", ty.0, self.gas().len());
+                    }
+                    reaction::Put::Skill { ty, .. } => {
+                        v.reference("reaction", id, "skill", ty.0, self.skill().len());
+                    }
+                    reaction::Put::Electricity { .. }
+                    | reaction::Put::Heat { .. }
+                    | reaction::Put::Happiness { .. }
+                    | reaction::Put::Research { .. } => {}
+                }
+            }
+        }
+
+        for building in self.building() {
+            let id = building.id();
+            v.reference(
+                "building",
+                id,
+                "building category",
+                building.category().0,
+                self.building_cats().len(),
+            );
+            for &(reaction, _) in building.reactions() {
+                v.reference(
+                    "building",
+                    id,
+                    "reaction",
+                    reaction.0,
+                    self.reaction().len(),
+                );
+            }
+            let construction = building.construction();
+            for &(cargo, _) in construction.cargo() {
+                v.reference("building", id, "cargo", cargo.0, self.cargo().len());
+            }
+            v.reference(
+                "building",
+                id,
+                "skill",
+                construction.skill().0,
+                self.skill().len(),
+            );
+            v.range(
+                Severity::Error,
+                "building",
+                id,
+                "construction skill levels",
+                construction.levels(),
+            );
+        }
+
+        for crime in self.crime() {
+            let id = crime.id();
+            match *crime.action() {
+                super::crime::Action::Antagonize(criterion)
+                | super::crime::Action::Murder(criterion) => match criterion {
+                    super::crime::InhabitantCriterion::HighestSkill(skill) => {
+                        v.reference("crime", id, "skill", skill.0, self.skill().len());
+                    }
+                },
+                _ => {}
+            }
+            for &(skill, _) in crime.skill_change() {
+                v.reference("crime", id, "skill", skill.0, self.skill().len());
+            }
+            v.range(
+                Severity::Warning,
+                "crime",
+                id,
+                "trigger happiness range",
+                crime.trigger_happiness_range(),
+            );
+        }
+
+        for tech in self.tech() {
+            let id = tech.id();
+            for prereq in tech.prerequisites() {
+                v.reference("tech", id, "tech", prereq.0, self.tech().len());
+            }
+            for cost in tech.costs() {
+                match *cost {
+                    tech::Cost::Cargo { ty, .. } => {
+                        v.reference("tech", id, "cargo", ty.0, self.cargo().len());
+                    }
+                    tech::Cost::Skill { ty, .. } => {
+                        v.reference("tech", id, "skill", ty.0, self.skill().len());
+                    }
+                    tech::Cost::Research(_) => {}
+                }
+            }
+            for unlock in tech.unlocks() {
+                match *unlock {
+                    tech::Unlock::Building(ty) => {
+                        v.reference("tech", id, "building", ty.0, self.building().len());
+                    }
+                    tech::Unlock::Reaction(ty) => {
+                        v.reference("tech", id, "reaction", ty.0, self.reaction().len());
+                    }
+                    tech::Unlock::Vehicle(ty) => {
+                        v.reference("tech", id, "vehicle", ty.0, self.vehicle().len());
+                    }
+                }
+            }
+        }
+        for cycle in tech_cycles(self) {
+            let id = self.get_tech(cycle).id();
+            v.push(Severity::Error, "tech", id, Problem::PrerequisiteCycle);
+        }
+
+        let orbit = self.orbit();
+        if orbit.month_count() == 0 {
+            let problem = Problem::NotPositive {
+                field: "month count",
+            };
+            v.push(Severity::Error, "orbit", &literal!("sun"), problem);
+        }
+        if orbit.year_length().value() == 0 {
+            let problem = Problem::NotPositive {
+                field: "year length",
+            };
+            v.push(Severity::Error, "orbit", &literal!("sun"), problem);
+        }
+
+        v.diagnostics
+    }
+
+    /// Checks that all textures referenced by the definition exist.
+    ///
+    /// `exists` is called for each referenced texture
+    /// and should return whether the texture is available.
+    pub fn validate_textures(&self, exists: impl Fn(Texture<'_>) -> bool) -> Vec<Diagnostic> {
+        let mut v = Validator {
+            diagnostics: Vec::new(),
+        };
+
+        let mut check = |kind, id: &ArcStr, texture: Texture<'_>, name: &ArcStr| {
+            if !exists(texture) {
+                v.push(
+                    Severity::Error,
+                    kind,
+                    id,
+                    Problem::MissingTexture {
+                        texture: name.clone(),
+                    },
+                );
+            }
+        };
+
+        for cargo in self.cargo() {
+            check(
+                "cargo",
+                cargo.id(),
+                Texture::Icon(cargo.texture()),
+                cargo.texture(),
+            );
+        }
+        for liquid in self.liquid() {
+            check(
+                "liquid",
+                liquid.id(),
+                Texture::Icon(liquid.texture()),
+                liquid.texture(),
+            );
+        }
+        for gas in self.gas() {
+            check("gas", gas.id(), Texture::Icon(gas.texture()), gas.texture());
+        }
+        for vehicle in self.vehicle() {
+            check(
+                "vehicle",
+                vehicle.id(),
+                Texture::Icon(vehicle.texture()),
+                vehicle.texture(),
+            );
+        }
+        for building in self.building() {
+            let name = building.shape().texture_name();
+            check("building", building.id(), Texture::Building(name), name);
+        }
+
+        v.diagnostics
+    }
+}
+
+/// Returns the technologies that directly or indirectly require themselves.
+fn tech_cycles(def: &GameDefinition) -> Vec<tech::TypeId> {
+    let techs = def.tech();
+    let mut cycles = Vec::new();
+    for (index, tech) in techs.iter().enumerate() {
+        let start = tech::TypeId(index);
+        let mut visited = BTreeSet::new();
+        let mut stack: Vec<_> = tech.prerequisites().to_vec();
+        while let Some(next) = stack.pop() {
+            if next == start {
+                cycles.push(start);
+                break;
+            }
+            if !visited.insert(next) {
+                continue;
+            }
+            if let Some(next) = techs.get(next.0) {
+                stack.extend(next.prerequisites().iter().copied());
+            }
+        }
+    }
+    cycles
+}
+
+#[cfg(test)]
+mod tests {
+    use arcstr::{literal, ArcStr};
+
+    use super::{Problem, Severity};
+    use crate::def::{building, cargo, orbit, reaction, skill, tech, GameDefinition};
+    use crate::space::Matrix;
+    use crate::time::Time;
+
+    #[test]
+    fn dangling_references() {
+        let mut def = GameDefinition::default();
+        def.add_cargo(
+            cargo::Type::builder()
+                .id(literal!("test:rock"))
+                .name(literal!("Rock"))
+                .summary(literal!(""))
+                .description(literal!(""))
+                .category(cargo::CategoryId(0))
+                .texture(literal!("rock"))
+                .build(),
+        );
+        def.add_skill(
+            skill::Type::builder()
+                .id(literal!("test:construction"))
+                .name(literal!("Construction"))
+                .description(literal!(""))
+                .build(),
+        );
+        def.add_building_category(
+            building::Category::builder()
+                .id(literal!("test:misc"))
+                .title(literal!("Misc"))
+                .description(literal!(""))
+                .build(),
+        );
+        for &id in &["test:hut", "test:shed"] {
+            def.add_building(
+                building::Type::builder()
+                    .id(ArcStr::from(id))
+                    .name(literal!("Hut"))
+                    .summary(literal!(""))
+                    .description(literal!(""))
+                    .category(building::CategoryId(0))
+                    .shape(
+                        building::Shape::builder()
+                            .transform(Matrix::identity())
+                            .texture_src(literal!(""))
+                            .texture_name(literal!("house"))
+                            .build(),
+                    )
+                    .reactions(Default::default())
+                    .hitpoint(100.0.into())
+                    .storage(
+                        building::Storage::builder()
+                            .cargo(0.0.into())
+                            .liquid(0.0.into())
+                            .gas(0.0.into())
+                            .build(),
+                    )
+                    .construction(
+                        building::Construction::builder()
+                            .cargo(vec![(cargo::TypeId(0), 10.0.into())])
+                            .energy(0.0.into())
+                            .time(Time(1000))
+                            .skill(skill::TypeId(0))
+                            .levels(0.0.into()..10.0.into())
+                            .multipliers(
+                                reaction::Multipliers::builder()
+                                    .underflow(1.)
+                                    .min(1.)
+                                    .max(2.)
+                                    .overflow(2.)
+                                    .build(),
+                            )
+                            .build(),
+                    )
+                    .features(Vec::new())
+                    .build(),
+            );
+        }
+
+        let diagnostics = def.validate();
+        let problems: Vec<_> = diagnostics
+            .iter()
+            .map(|diag| (diag.severity, diag.subject.kind, &diag.problem))
+            .collect();
+        assert_eq!(
+            problems,
+            vec![
+                (
+                    Severity::Warning,
+                    "building",
+                    &Problem::DuplicateName {
+                        name: literal!("Hut")
+                    },
+                ),
+                (
+                    Severity::Error,
+                    "cargo",
+                    &Problem::UndefinedReference {
+                        kind: "cargo category",
+                        index: 0,
+                    },
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn prerequisite_cycle() {
+        let mut def = GameDefinition::default();
+        for (key, prereq) in [("test:a", 1), ("test:b", 0), ("test:c", 0)] {
+            def.add_tech(
+                tech::Type::builder()
+                    .id(key.into())
+                    .name(key.into())
+                    .description(literal!(""))
+                    .prerequisites(std::iter::once(tech::TypeId(prereq)).collect())
+                    .costs(Default::default())
+                    .unlocks(Default::default())
+                    .build(),
+            );
+        }
+
+        let cycles: Vec<_> = def
+            .validate()
+            .into_iter()
+            .filter(|diag| diag.problem == Problem::PrerequisiteCycle)
+            .map(|diag| diag.subject.id)
+            .collect();
+        assert_eq!(cycles, vec!["test:a", "test:b"]);
+    }
+
+    #[test]
+    fn empty_orbit() {
+        let mut def = GameDefinition::default();
+        assert!(def.validate().is_empty());
+
+        def.set_orbit(
+            orbit::Orbit::builder()
+                .year_length(Time(0))
+                .month_count(0)
+                .axial_tilt(0.)
+                .build(),
+        );
+        let problems: Vec<_> = def
+            .validate()
+            .into_iter()
+            .map(|diag| diag.problem)
+            .collect();
+        assert_eq!(
+            problems,
+            vec![
+                Problem::NotPositive {
+                    field: "month count"
+                },
+                Problem::NotPositive {
+                    field: "year length"
+                },
+            ]
+        );
+    }
+}
@@ -9,12 +9,15 @@ use crate::def::{reaction, skill};
 use crate::units;
 
 /// Identifies a vehicle category
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct TypeId(pub usize);
 
 /// A type of vehicle.
 #[derive(Clone, TypedBuilder, getset::Getters, getset::CopyGetters)]
 pub struct Type {
+    /// String identifier of the vehicle type.
+    #[getset(get = "pub")]
+    id: ArcStr,
     /// Name of the vehicle type.
     #[getset(get = "pub")]
     name: ArcStr,
@@ -2,11 +2,13 @@
 
 use std::ops::{Add, AddAssign, Mul, Sub, SubAssign};
 
+use serde::{Deserialize, Serialize};
+
 units! {
     /// Internal trait just because declarative macros are stupid.
     _TimeTrait(Clone + Copy);
 
-    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)] u32:
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)] u32:
 
     /// Synchronized time span.
     ///
@@ -84,13 +86,13 @@ impl SubAssign<Time> for Instant {
 /// The rate of change.
 ///
 /// The inner value is the amount of change over one second.
-#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct Rate<T>(pub T);
 
 impl<T: Mul<f64, Output = T>> std::ops::Mul<Time> for Rate<T> {
     type Output = T;
 
     fn mul(self, time: Time) -> T {
-        self.0 * (time.value() as f64)
+        self.0 * time.as_secs()
     }
 }
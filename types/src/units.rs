@@ -1,10 +1,12 @@
 //! Defines common units
 
+use serde::{Deserialize, Serialize};
+
 units! {
     /// A common unit type
     Unit(std::fmt::Debug + Clone + Copy + Default + PartialEq + PartialOrd);
 
-    #[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)] f64:
+    #[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Serialize, Deserialize)] f64:
 
     /// An amount of liquid.
     LiquidVolume("{} L");
@@ -27,6 +29,12 @@ units! {
     /// Orthogonal area of a node receiving sunlight.
     Brightness("{} m\u{b2}");
 
+    /// Thermal energy.
+    Heat("{} kJ");
+
+    /// Temperature of a node.
+    Temperature("{} K");
+
     /// Skill level of an inhabitant.
     Skill("{} SP");
 
@@ -47,4 +55,7 @@ units! {
 
     /// Hitpoint of a building.
     Hitpoint("{} HP");
+
+    /// Progress of technology research.
+    ResearchPoint("{} RP");
 }
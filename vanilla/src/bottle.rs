@@ -0,0 +1,42 @@
+//! Vanilla bottle definitions.
+//!
+//! Bottle cargo types and their reactions are generated for every liquid and gas type.
+
+use arcstr::literal;
+
+use traffloat_types::def::{bottle, reaction, GameDefinition};
+use traffloat_types::time::Rate;
+use traffloat_types::units;
+
+/// Populates a [`GameDefinition`] with bottles of all liquid and gas types.
+pub fn populate(def: &mut GameDefinition, cargo: &super::cargo::Ids) -> bottle::Ids {
+    let bottling = def.add_reaction_category(
+        reaction::Category::builder()
+            .id(key!(bottling))
+            .title(literal!("Bottling"))
+            .description(literal!(
+                "Packing liquids and gases into cargo bottles and releasing them."
+            ))
+            .build(),
+    );
+
+    def.add_bottles(
+        &bottle::Options::builder()
+            .cargo_category(cargo.container)
+            .reaction_category(bottling)
+            .liquid_volume(units::LiquidVolume(10.))
+            .gas_volume(units::GasVolume(10.))
+            .rate(Rate(units::CargoSize(10.)))
+            .liquid_description(literal!(
+                "Produced in liquid bottlers and centrifuges, liquid bottles can be used to \
+                transfer a small amount of liquid to factories \
+                as a replacement of constructing dedicated pipes through corridors."
+            ))
+            .gas_description(literal!(
+                "Produced in gas bottlers and centrifuges, gas bottles can be used to \
+                transfer a small amount of gas to factories \
+                as a replacement of diffusing gas slowly through corridors."
+            ))
+            .build(),
+    )
+}
@@ -28,6 +28,11 @@
 //!     Each reaction is in the format `reaction_name { ... }`,
 //!     where `...` are building-specific options on the reaction.
 //!     See [`building::ReactionPolicy`] for possible options.
+//!     The variants of [`building::FlowPolicy`] can be used without qualification,
+//!     e.g. `on_overflow: Dump` or `on_underflow: Priority(1)`.
+//! - `bottling` (optional): Write `bottling: true,` after `reactions`
+//!     to additionally support the generated [bottling reactions][super::bottle]
+//!     of all liquids and gases.
 //! - `hitpoint`: The full hitpoints of the building type.
 //! - `storage`: The maximum amount of cargo, liquid and gas stored in the building,
 //!     in the format `{cargo: 1000., liquid: 2000., gas: 3000.,}`.
@@ -41,6 +46,16 @@
 //!         otherwise the building would be the bottleneck for transfer.
 //!     - The gas storage is used to buffer oxygen that inhabitants breathe.
 //!         If the gas storage is too small, inhabitants without oxygen bottles may suffocate.
+//!     - Buildings that store electric energy additionally specify
+//!         `electricity: 5000., charge_rate: 100.,` after `gas`,
+//!         which are the energy capacity and the maximum charge/discharge power.
+//! - `construction`: The resources required to construct the building,
+//!     in the format `{cargo: [aluminium: 100.], energy: 1000., time: 30,}`.
+//!     - `cargo` is the list of cargo consumed by the construction site,
+//!         each in the format `cargo_name: size`.
+//!     - `energy` is the electric energy consumed by the construction site.
+//!     - `time` is the number of seconds to complete construction with a single unskilled worker.
+//!         Workers with higher construction skill work up to twice as fast.
 //! - `features`: A list of extra features (in addition to reactions) supported by the building,
 //!     separated by comma and surrounded by `[]`.
 //!     See [`building::ExtraFeature`] for possible options.
@@ -61,8 +76,8 @@
 use arcstr::literal;
 
 use crate::VANILLA_TEXTURE;
-use traffloat_types::def::{building, GameDefinition};
-use traffloat_types::{space, units};
+use traffloat_types::def::{bottle, building, reaction, GameDefinition};
+use traffloat_types::{space, time, units};
 
 macro_rules! buildings {
     (
@@ -89,11 +104,21 @@ macro_rules! buildings {
                         },
                     )*
                 ],
+                $(bottling: $bottling:literal,)?
                 hitpoint: $hitpoint:literal,
                 storage: {
                     cargo: $cargo_storage:literal,
                     liquid: $liquid_storage:literal,
                     gas: $gas_storage:literal,
+                    $(
+                        electricity: $electricity_storage:literal,
+                        charge_rate: $charge_rate:literal,
+                    )?
+                },
+                construction: {
+                    cargo: [$($construction_cargo:ident: $construction_size:literal),* $(,)?],
+                    energy: $construction_energy:literal,
+                    time: $construction_time:literal,
                 },
                 features: [$($features:expr),* $(,)?],
             })*
@@ -109,10 +134,17 @@ macro_rules! buildings {
         }
 
         /// Populates a [`GameDefinition`] with building definition.
-        pub fn populate(def: &mut GameDefinition, reactions: &super::reaction::Ids) -> Ids {
+        pub fn populate(
+            def: &mut GameDefinition,
+            cargo: &super::cargo::Ids,
+            skill: &super::skill::Ids,
+            reactions: &super::reaction::Ids,
+            bottles: &bottle::Ids,
+        ) -> Ids {
             $(
                 let $category_ident = def.add_building_category(
                     building::Category::builder()
+                        .id(key!($category_ident))
                         .title(literal!($category))
                         .description(literal!($category_description))
                         .build()
@@ -120,6 +152,7 @@ macro_rules! buildings {
                 $(
                     let $ident = def.add_building(
                         building::Type::builder()
+                            .id(key!($ident))
                             .name(literal!($name))
                             .summary(literal!($summary))
                             .description(literal!($description))
@@ -137,23 +170,64 @@ macro_rules! buildings {
                                 .texture_name(literal!($texture))
                                 .build())
                             .category($category_ident)
-                            .reactions(vec![
+                            .reactions({
+                                #[allow(unused_mut)]
+                                let mut reactions = vec![
+                                    $(
+                                        (
+                                            reactions.$reaction_name,
+                                            {
+                                                #[allow(unused_imports)]
+                                                use building::FlowPolicy::*;
+                                                building::ReactionPolicy::builder()
+                                                    $(
+                                                        .$reaction_param($reaction_value)
+                                                    )*
+                                                    .build()
+                                            },
+                                        ),
+                                    )*
+                                ];
                                 $(
-                                    (
-                                        reactions.$reaction_name,
-                                        building::ReactionPolicy::builder()
-                                            $(
-                                                .$reaction_param($reaction_value)
-                                            )*
-                                            .build(),
-                                    ),
-                                )*
-                            ])
+                                    if $bottling {
+                                        reactions.extend(bottles.reactions().map(|reaction| {
+                                            (
+                                                reaction,
+                                                building::ReactionPolicy::builder()
+                                                    .configurable(true)
+                                                    .build(),
+                                            )
+                                        }));
+                                    }
+                                )?
+                                reactions
+                            })
                             .hitpoint($hitpoint.into())
                             .storage(building::Storage::builder()
                                 .cargo($cargo_storage.into())
                                 .liquid($liquid_storage.into())
                                 .gas($gas_storage.into())
+                                $(
+                                    .electricity($electricity_storage.into())
+                                    .charge_rate($charge_rate.into())
+                                )?
+                                .build())
+                            .construction(building::Construction::builder()
+                                .cargo(vec![
+                                    $(
+                                        (cargo.$construction_cargo, $construction_size.into()),
+                                    )*
+                                ])
+                                .energy($construction_energy.into())
+                                .time(time::Time($construction_time * 100))
+                                .skill(skill.construction)
+                                .levels(units::Skill(0.)..units::Skill(20.))
+                                .multipliers(reaction::Multipliers::builder()
+                                    .underflow(1.)
+                                    .min(1.)
+                                    .max(2.)
+                                    .overflow(2.)
+                                    .build())
                                 .build())
                             .features({
                                 #[allow(unused_imports)]
@@ -187,12 +261,21 @@ buildings! {
                 Destruction of the core ends the game.",
             cube: 1.,
             texture: "core",
-            reactions: [],
+            reactions: [
+                carbon_dioxide_scrubbing {},
+            ],
             hitpoint: 3000.,
             storage: {
                 cargo: 1000.,
                 liquid: 1000.,
                 gas: 1000.,
+                electricity: 100000.,
+                charge_rate: 200.,
+            },
+            construction: {
+                cargo: [aluminium: 2000.],
+                energy: 20000.,
+                time: 300,
             },
             features: [Core, ProvidesHousing(4)],
         }
@@ -209,6 +292,11 @@ buildings! {
                 liquid: 1000.,
                 gas: 1000.,
             },
+            construction: {
+                cargo: [aluminium: 100.],
+                energy: 1000.,
+                time: 30,
+            },
             features: [ProvidesHousing(3)],
         }
     }
@@ -221,12 +309,18 @@ buildings! {
             cube: 0.2,
             texture: "terminal",
             reactions: [],
+            bottling: true,
             hitpoint: 100.,
             storage: {
                 cargo: 1000.,
                 liquid: 1000.,
                 gas: 1000.,
             },
+            construction: {
+                cargo: [aluminium: 50.],
+                energy: 500.,
+                time: 10,
+            },
             features: [
                 RailTerminal(units::RailForce(100.)),
                 LiquidPump(units::PipeForce(100.)),
@@ -252,6 +346,13 @@ buildings! {
                 cargo: 1000.,
                 liquid: 1000.,
                 gas: 1000.,
+                electricity: 5000.,
+                charge_rate: 100.,
+            },
+            construction: {
+                cargo: [aluminium: 200.],
+                energy: 2000.,
+                time: 45,
             },
             features: [],
         }
@@ -273,6 +374,11 @@ buildings! {
                 liquid: 1000.,
                 gas: 1000.,
             },
+            construction: {
+                cargo: [aluminium: 500.],
+                energy: 3000.,
+                time: 60,
+            },
             features: [],
         }
     }
@@ -292,14 +398,22 @@ buildings! {
                 cube: 1.,
                 texture: "filtration-plant",
                 reactions: [
-                    asteroidal_water_filtration {configurable: true},
+                    asteroidal_water_filtration {configurable: true, on_overflow: Dump},
+                    liquid_cooling {configurable: true},
+                    urine_filtration {configurable: true},
                 ],
+                bottling: true,
                 hitpoint: 150.,
                 storage: {
                     cargo: 3000.,
                     liquid: 5000.,
                     gas: 1000.,
                 },
+                construction: {
+                    cargo: [aluminium: 300.],
+                    energy: 4000.,
+                    time: 60,
+                },
                 features: [],
         }
     }
@@ -320,11 +434,42 @@ buildings! {
                 liquid: 1000.,
                 gas: 1000.,
             },
-            features: [],
+            construction: {
+                cargo: [aluminium: 200.],
+                energy: 3000.,
+                time: 45,
+            },
+            features: [Education(10)],
         }
     }
 
     entertainment "Entertainment" ("Buildings to restore happiness") {
+        theater {
+            name: "Theater",
+            summary: "Performances for off-duty inhabitants",
+            description: "Inhabitants with low happiness visit the theater in their free time \
+                to restore happiness. Outlaws are not welcome.",
+            cube: 1.,
+            texture: "theater",
+            reactions: [],
+            hitpoint: 100.,
+            storage: {
+                cargo: 1000.,
+                liquid: 1000.,
+                gas: 1000.,
+            },
+            construction: {
+                cargo: [aluminium: 200.],
+                energy: 2000.,
+                time: 45,
+            },
+            features: [
+                Entertainment {
+                    capacity: 20,
+                    rate: time::Rate(units::Happiness(1.)),
+                },
+            ],
+        }
     }
 
     security "Security" ("Buildings related to crimes") {
@@ -344,7 +489,13 @@ buildings! {
                 liquid: 1000.,
                 gas: 1000.,
             },
+            construction: {
+                cargo: [aluminium: 400.],
+                energy: 5000.,
+                time: 90,
+            },
             features: [
+                Prison(50),
                 SecureExit {
                     min_happiness: 10f64.into(),
                     breach_probability: 0.001,
@@ -367,6 +518,11 @@ buildings! {
                 liquid: 1000.,
                 gas: 1000.,
             },
+            construction: {
+                cargo: [aluminium: 250.],
+                energy: 3000.,
+                time: 60,
+            },
             features: [
                 SecureEntry {
                     min_happiness: 10f64.into(),
@@ -1,6 +1,6 @@
 //! Vanilla cargo type definitions.
 
-use arcstr::{format, literal};
+use arcstr::literal;
 
 use traffloat_types::def::{cargo, GameDefinition};
 
@@ -13,6 +13,7 @@ macro_rules! cargos {
                 summary: $summary:literal,
                 description: $description:literal,
                 texture: $texture:literal,
+                $(waste: $waste:literal,)?
             })*
         })*
     ) => {
@@ -23,8 +24,8 @@ macro_rules! cargos {
                     pub $ident: cargo::TypeId,
                 )*
             )*
-            pub first_liquid: cargo::TypeId,
-            pub first_gas: cargo::TypeId,
+            /// The category of generated bottle cargo types
+            pub $container: cargo::CategoryId,
         }
 
         /// Populates a [`GameDefinition`] with cargo definition.
@@ -32,6 +33,7 @@ macro_rules! cargos {
             $(
                 let $category_ident = def.add_cargo_category(
                     cargo::Category::builder()
+                        .id(key!($category_ident))
                         .title(literal!($category))
                         .description(literal!($category_description))
                         .build()
@@ -39,54 +41,23 @@ macro_rules! cargos {
                 $(
                     let $ident = def.add_cargo(
                         cargo::Type::builder()
+                            .id(key!($ident))
                             .name(literal!($name))
                             .summary(literal!($summary))
                             .description(literal!($description))
                             .texture(literal!($texture))
+                            $(.waste($waste))?
                             .category($category_ident)
                             .build()
                     );
                 )*
             )*
 
-            let first_liquid = cargo::TypeId(def.cargo().len());
-            let liquids = def.liquid().to_vec();
-            for liquid in liquids {
-                def.add_cargo(
-                    cargo::Type::builder()
-                        .name(format!("Liquid bottle ({})", liquid.name()))
-                        .summary(format!("Stores a small amount of {}", liquid.name()))
-                        .description(literal!("Produced in liquid bottlers and centrifuges, liquid bottles can be used to \
-                            transfer a small amount of liquid to factories \
-                            as a replacement of constructing dedicated pipes through corridors."))
-                        .texture(format!("{}-liquid-bottle", liquid.texture()))
-                        .category($container)
-                        .build()
-                );
-            }
-
-            let first_gas = cargo::TypeId(def.cargo().len());
-            let gases = def.gas().to_vec();
-            for gas in gases {
-                def.add_cargo(
-                    cargo::Type::builder()
-                        .name(format!("Gas bottle ({})", gas.name()))
-                        .summary(format!("Stores a small amount of {}", gas.name()))
-                        .description(literal!("Produced in gas bottlers and centrifuges, gas bottles can be used to \
-                            transfer a small amount of gas to factories \
-                            as a replacement of diffusing gas slowly through corridors."))
-                        .texture(format!("{}-gas-bottle", gas.texture()))
-                        .category($container)
-                        .build()
-                );
-            }
-
             Ids {
                 $(
                     $($ident,)*
                 )*
-                first_liquid,
-                first_gas,
+                $container,
             }
         }
     }
@@ -143,6 +114,7 @@ cargos! {
                 They cannot be used for anything, and should be ejected with junk launchers \
                 to avoid filling up storage space.",
             texture: "sediment",
+            waste: true,
         }
     }
 
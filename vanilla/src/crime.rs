@@ -9,7 +9,7 @@ use traffloat_types::def::{
 };
 
 macro_rules! crimes {
-    ($({
+    ($($ident:ident {
         name: $name:literal,
         description: $description:literal,
         action: $action:expr,
@@ -21,7 +21,8 @@ macro_rules! crimes {
         /// Populates the game definition with crime data.
         pub fn populate(def: &mut GameDefinition, skill: &super::skill::Ids) {
             $(
-                def.crime_mut().push(crime::Type::builder()
+                def.add_crime(crime::Type::builder()
+                    .id(key!($ident))
                     .name(literal!($name))
                     .description(literal!($description))
                     .action($action)
@@ -36,7 +37,7 @@ macro_rules! crimes {
 }
 
 crimes! {
-    {
+    pickpocket {
         name: "Pickpocket",
         description: "Steal cargo carried by other inhabitants",
         action: InhabitantTheft(5f64.into()),
@@ -45,7 +46,7 @@ crimes! {
         happiness_change: -5.,
         morality_change: -1f64,
     }
-    {
+    robbery {
         name: "Robbery",
         description: "Rob a building and remove the cargo stored inside",
         action: NodeTheft(5f64.into()),
@@ -54,7 +55,7 @@ crimes! {
         happiness_change: -20.,
         morality_change: -1.5,
     }
-    {
+    arson {
         name: "Arson",
         description: "Set a building on fire",
         action: Arson,
@@ -1,35 +1,40 @@
-use indvec::indvec;
-
-use traffloat::def::GameDefinition;
-use traffloat::graph;
+use traffloat::command::Command;
+use traffloat::graph::NodeId;
 use traffloat_types::space::{Matrix, Position, Vector};
 
-pub fn default_setup(
-    def: &GameDefinition,
-    building: &super::building::Ids,
-) -> (Vec<graph::NodeComponents>, Vec<(usize, usize, f64)>) {
-    indvec![
-        nodes = core = graph::create_node_components(
-            def,
-            building.core,
-            Position::new(1., 2., 3.),
-            Matrix::identity(),
-        ),
-        hut = graph::create_node_components(
-            def,
-            building.hut,
-            Position::new(1., -2., 3.),
-            Matrix::new_scaling(0.4),
-        ),
-        solar_panel = graph::create_node_components(
-            def,
-            building.solar_panel,
-            Position::new(-2., 0., 10.),
-            Matrix::new_nonuniform_scaling(&Vector::new(0.1, 0.5, 1.5)),
-        ),
-    ];
-
-    let edges = vec![(core, hut, 0.2), (core, solar_panel, 0.1)];
+pub fn default_setup(building: &super::building::Ids) -> Vec<Command> {
+    let core = NodeId::new(1);
+    let hut = NodeId::new(2);
+    let solar_panel = NodeId::new(3);
 
-    (nodes, edges)
+    vec![
+        Command::PlaceNode {
+            node: core,
+            building: building.core,
+            position: Position::new(1., 2., 3.),
+            rotation: Matrix::identity(),
+        },
+        Command::PlaceNode {
+            node: hut,
+            building: building.hut,
+            position: Position::new(1., -2., 3.),
+            rotation: Matrix::new_scaling(0.4),
+        },
+        Command::PlaceNode {
+            node: solar_panel,
+            building: building.solar_panel,
+            position: Position::new(-2., 0., 10.),
+            rotation: Matrix::new_nonuniform_scaling(&Vector::new(0.1, 0.5, 1.5)),
+        },
+        Command::BuildCorridor {
+            from: core,
+            to: hut,
+            radius: 0.2,
+        },
+        Command::BuildCorridor {
+            from: core,
+            to: solar_panel,
+            radius: 0.1,
+        },
+    ]
 }
@@ -11,6 +11,8 @@ macro_rules! gases {
             summary: $summary:literal,
             description: $description:literal,
             texture: $texture:literal,
+            $(waste: $waste:literal,)?
+            $(respiration: $respiration:ident,)?
         })*
     ) => {
         /// IDs assigned to the vanilla game definition.
@@ -25,10 +27,13 @@ macro_rules! gases {
             $(
                 let $ident = def.add_gas(
                     gas::Type::builder()
+                        .id(key!($ident))
                         .name(literal!($name))
                         .summary(literal!($summary))
                         .description(literal!($description))
                         .texture(literal!($texture))
+                        $(.waste($waste))?
+                        $(.respiration(gas::Respiration::$respiration))?
                         .build()
                 );
             )*
@@ -49,6 +54,7 @@ gases! {
             except for construction work, where \
             sufficient oxygen must be available in adjacent buildings.",
         texture: "oxygen",
+        respiration: Inhaled,
     }
 
     carbon_dioxide {
@@ -58,6 +64,8 @@ gases! {
             While high carbon dioxide level is not necessarily fatal, \
             they reduce the levels of other gases in the air.",
         texture: "carbon-dioxide",
+        waste: true,
+        respiration: Exhaled,
     }
 
     nitrogen {
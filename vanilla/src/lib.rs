@@ -1,8 +1,18 @@
 //! Vanilla data definition
 
-use traffloat::graph;
+use traffloat::command::Command;
+#[cfg(feature = "schema")]
+use traffloat_types::def::layer::ModInfo;
 use traffloat_types::def::GameDefinition;
 
+/// Creates the string key of a vanilla type.
+macro_rules! key {
+    ($ident:ident) => {
+        arcstr::literal!(concat!("vanilla:", stringify!($ident)))
+    };
+}
+
+pub mod bottle;
 pub mod building;
 pub mod cargo;
 pub mod crime;
@@ -11,27 +21,61 @@ pub mod gas;
 pub mod liquid;
 pub mod reaction;
 pub mod skill;
+pub mod tech;
 pub mod vehicle;
 
 pub const VANILLA_TEXTURE: &str = "textures-fancy.png"; // TODO allow customization of resolution
 
+/// Identifies the vanilla definition as the base layer of a mod stack.
+#[cfg(feature = "schema")]
+pub fn info() -> ModInfo {
+    ModInfo {
+        id: arcstr::literal!("vanilla"),
+        version: arcstr::literal!(env!("CARGO_PKG_VERSION")),
+        after: Vec::new(),
+    }
+}
+
 /// Define vanilla game rules
-pub fn get() -> (
-    GameDefinition,
-    Vec<graph::NodeComponents>,
-    Vec<(usize, usize, f64)>,
-) {
+///
+/// Also returns the commands placing the initial nodes and corridors.
+pub fn get() -> (GameDefinition, Vec<Command>) {
     let mut def = GameDefinition::default();
     let skill = skill::populate(&mut def);
-    let _vehicle = vehicle::populate(&mut def, &skill);
+    let vehicle = vehicle::populate(&mut def, &skill);
     let liquid = liquid::populate(&mut def);
     let gas = gas::populate(&mut def);
     let cargo = cargo::populate(&mut def);
     let reaction = reaction::populate(&mut def, &cargo, &liquid, &gas, &skill);
-    let building = building::populate(&mut def, &reaction);
+    let bottles = bottle::populate(&mut def, &cargo);
+    let building = building::populate(&mut def, &cargo, &skill, &reaction, &bottles);
     crime::populate(&mut def, &skill);
+    tech::populate(&mut def, &cargo, &building, &reaction, &vehicle);
+
+    let setup = default::default_setup(&building);
+
+    (def, setup)
+}
 
-    let (nodes, edges) = default::default_setup(&def, &building);
+#[cfg(test)]
+mod tests {
+    use traffloat_types::def::schema::{Definition, Format};
 
-    (def, nodes, edges)
+    #[test]
+    fn schema_roundtrip() {
+        let (def, _) = super::get();
+        for &format in &[Format::Toml, Format::Yaml, Format::Json] {
+            let text = format
+                .write(&Definition::export(&def))
+                .expect("Cannot write vanilla definition");
+            let loaded = format
+                .parse(&text)
+                .and_then(Definition::resolve)
+                .expect("Cannot load exported vanilla definition");
+            let reexported = format
+                .write(&Definition::export(&loaded))
+                .expect("Cannot write loaded definition");
+            assert_eq!(text, reexported, "{:?} roundtrip is not lossless", format);
+        }
+    }
 }
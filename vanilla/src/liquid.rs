@@ -12,6 +12,7 @@ macro_rules! liquids {
             description: $description:literal,
             viscosity: $viscosity:literal,
             texture: $texture:literal,
+            $(waste: $waste:literal,)?
         })*
     ) => {
         /// IDs assigned to the vanilla game definition.
@@ -26,11 +27,13 @@ macro_rules! liquids {
             $(
                 let $ident = def.add_liquid(
                     liquid::Type::builder()
+                        .id(key!($ident))
                         .name(literal!($name))
                         .summary(literal!($summary))
                         .description(literal!($description))
                         .viscosity($viscosity.into())
                         .texture(literal!($texture))
+                        $(.waste($waste))?
                         .build()
                 );
             )*
@@ -79,6 +82,7 @@ liquids! {
             recycle them by distillation into drinking water.",
         viscosity: 5.,
         texture: "urine",
+        waste: true,
     }
 
     coolant {
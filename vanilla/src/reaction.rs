@@ -49,6 +49,7 @@ macro_rules! reactions {
             $(
                 let $category_ident = def.add_reaction_category(
                     reaction::Category::builder()
+                        .id(key!($category_ident))
                         .title(literal!($category))
                         .description(literal!($category_description))
                         .build()
@@ -56,6 +57,7 @@ macro_rules! reactions {
                 $(
                     let $ident = def.add_reaction(
                         reaction::Type::builder()
+                            .id(key!($ident))
                             .name(literal!($name))
                             .description(literal!($description))
                             .catalysts(smallvec![
@@ -129,6 +131,10 @@ reactions! {
                     levels: 0. .. 20.,
                     multipliers: [1., 1., 2., 2.],
                 },
+                Heat {
+                    levels: 380. .. 450.,
+                    multipliers: [1., 1., 0., 0.],
+                },
             ],
             puts: [
                 Liquid {
@@ -147,6 +153,71 @@ reactions! {
                     ty: cargo.sediment,
                     rate: 10.,
                 },
+                Heat {
+                    rate: 50.,
+                },
+            ],
+        }
+        liquid_cooling {
+            name: "Liquid cooling",
+            description: "Circulates [coolant](../../liquid/coolant) to remove heat from the building.",
+            catalysts: [
+                Heat {
+                    levels: 300. .. 350.,
+                    multipliers: [0., 0., 1., 1.],
+                },
+            ],
+            puts: [
+                Liquid {
+                    ty: liquid.coolant,
+                    rate: -1.,
+                },
+                Heat {
+                    rate: -100.,
+                },
+            ],
+        }
+    }
+
+    recycling "Recycling" ("Convert waste products back into useful resources.") {
+        urine_filtration {
+            name: "Urine filtration",
+            description: "Recycles [urine](../../liquid/urine) into filtered water.",
+            catalysts: [],
+            puts: [
+                Liquid {
+                    ty: liquid.urine,
+                    rate: -20.,
+                },
+                Liquid {
+                    ty: liquid.filtered_water,
+                    rate: 18.,
+                },
+                Cargo {
+                    ty: cargo.sediment,
+                    rate: 1.,
+                },
+                Electricity {
+                    rate: -20.,
+                },
+            ],
+        }
+        carbon_dioxide_scrubbing {
+            name: "Carbon dioxide scrubbing",
+            description: "Recycles [carbon dioxide](../../gas/carbon-dioxide) into oxygen.",
+            catalysts: [],
+            puts: [
+                Gas {
+                    ty: gas.carbon_dioxide,
+                    rate: -5.,
+                },
+                Gas {
+                    ty: gas.oxygen,
+                    rate: 5.,
+                },
+                Electricity {
+                    rate: -30.,
+                },
             ],
         }
     }
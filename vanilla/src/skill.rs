@@ -9,6 +9,7 @@ macro_rules! skills {
         $($ident:ident {
             name: $name:literal,
             description: $description:literal,
+            $(kind: $kind:ident,)?
         })*
     ) => {
         /// IDs assigned to the vanilla game definition.
@@ -23,8 +24,10 @@ macro_rules! skills {
             $(
                 let $ident = def.add_skill(
                     skill::Type::builder()
+                        .id(key!($ident))
                         .name(literal!($name))
                         .description(literal!($description))
+                        $(.kind(skill::Kind::$kind))?
                         .build()
                 );
             )*
@@ -41,10 +44,12 @@ skills! {
         name: "Age",
         description: "Age of the inhabitant. Some jobs require operators of a specific age range. \
             A high age increases the probability of health decrease.",
+        kind: Age,
     }
     health {
         name: "Health",
         description: "Physical well-being of the inhabitant. The inhabitant dies when health drops to zero.",
+        kind: Health,
     }
     morality {
         name: "Morality",
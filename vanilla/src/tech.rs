@@ -0,0 +1,82 @@
+//! Vanilla technology definitions.
+
+use arcstr::literal;
+use smallvec::smallvec;
+
+use traffloat_types::def::tech::{self, Cost::*, Unlock::*};
+use traffloat_types::def::GameDefinition;
+
+macro_rules! techs {
+    (
+        $cargo:ident $building:ident $reaction:ident $vehicle:ident; // macro hygiene hack
+        $($ident:ident {
+            name: $name:literal,
+            description: $description:literal,
+            prerequisites: [$($prereq:ident),* $(,)?],
+            costs: [$($cost:expr),* $(,)?],
+            unlocks: [$($unlock:expr),* $(,)?],
+        })*
+    ) => {
+        /// IDs assigned to the vanilla game definition.
+        pub struct Ids {
+            $(
+                pub $ident: tech::TypeId,
+            )*
+        }
+
+        /// Populates a [`GameDefinition`] with technology definition.
+        pub fn populate(
+            def: &mut GameDefinition,
+            $cargo: &super::cargo::Ids,
+            $building: &super::building::Ids,
+            $reaction: &super::reaction::Ids,
+            $vehicle: &super::vehicle::Ids,
+        ) -> Ids {
+            $(
+                let $ident = def.add_tech(
+                    tech::Type::builder()
+                        .id(key!($ident))
+                        .name(literal!($name))
+                        .description(literal!($description))
+                        .prerequisites(smallvec![$($prereq),*])
+                        .costs(smallvec![$($cost),*])
+                        .unlocks(smallvec![$($unlock),*])
+                        .build()
+                );
+            )*
+
+            Ids {
+                $($ident,)*
+            }
+        }
+    }
+}
+
+techs! {
+    cargo building reaction vehicle;
+
+    driver_education {
+        name: "Driver education",
+        description: "Train inhabitants to drive vehicles more efficiently.",
+        prerequisites: [],
+        costs: [
+            Cargo { ty: cargo.aluminium, size: 100f64.into() },
+        ],
+        unlocks: [
+            Building(building.driving_school),
+            Reaction(reaction.driving_lesson),
+        ],
+    }
+
+    public_transport {
+        name: "Public transport",
+        description: "Buses carry inhabitants between terminals faster than walking.",
+        prerequisites: [driver_education],
+        costs: [
+            Cargo { ty: cargo.aluminium, size: 200f64.into() },
+        ],
+        unlocks: [
+            Vehicle(vehicle.bus),
+        ],
+    }
+}
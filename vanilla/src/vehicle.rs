@@ -40,6 +40,7 @@ macro_rules! vehicles {
             $(
                 let $ident = def.add_vehicle(
                     vehicle::Type::builder()
+                        .id(key!($ident))
                         .name(literal!($name))
                         .description(literal!($description))
                         .speed($speed.into())